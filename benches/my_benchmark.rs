@@ -4,7 +4,6 @@ use env_logger::Env;
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 
 use hierarchical_pathfinding::prelude::*;
-use log::warn;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Tile {
@@ -109,7 +108,7 @@ fn bench_create_pathcache(c: &mut Criterion) {
                     b.iter(|| {
                         PathCache::new(
                             (width, height),
-                            map.cost_fn(),
+                            &map.cost_fn(),
                             MooreNeighborhood::new(width, height), //
                             PathCacheConfig::with_chunk_size(chunk_size),
                         )
@@ -140,7 +139,7 @@ fn bench_create_pathcache(c: &mut Criterion) {
     let chunk_size = 32;
 
     #[cfg(target_os = "windows")]
-    warn!("For some reason, the Create PathCache/Large Random Map... benchmark runs significantly slower on Windows than when running the same code in it's own binary.");
+    log::warn!("For some reason, the Create PathCache/Large Random Map... benchmark runs significantly slower on Windows than when running the same code in it's own binary.");
     #[cfg(feature = "parallel")]
     {
         let id = format!(
@@ -151,7 +150,7 @@ fn bench_create_pathcache(c: &mut Criterion) {
             b.iter(|| {
                 PathCache::new(
                     (width, height),
-                    map.cost_fn(),
+                    &map.cost_fn(),
                     MooreNeighborhood::new(width, height),
                     PathCacheConfig::with_chunk_size(chunk_size),
                 )
@@ -185,7 +184,7 @@ fn bench_update_pathcache(c: &mut Criterion) {
     let chunk_size = 32;
     let pathcache = PathCache::new(
         (width, height),
-        map.cost_fn(),
+        &map.cost_fn(),
         MooreNeighborhood::new(width, height),
         PathCacheConfig::with_chunk_size(chunk_size),
     );
@@ -206,7 +205,7 @@ fn bench_update_pathcache(c: &mut Criterion) {
             // clone on every iteration, so we aren't updaing the same pathcache twice.
             b.iter_batched_ref(
                 || pathcache.clone(),
-                |cache| cache.tiles_changed(&changed, map.cost_fn()),
+                |cache| cache.tiles_changed(&changed, &map.cost_fn()),
                 BatchSize::SmallInput,
             )
         });
@@ -244,7 +243,7 @@ fn bench_get_path(c: &mut Criterion) {
         let chunk_size = 32;
         let pathcache = PathCache::new(
             (size, size),
-            map.cost_fn(),
+            &map.cost_fn(),
             neighborhood,
             PathCacheConfig::with_chunk_size(chunk_size),
         );
@@ -259,7 +258,7 @@ fn bench_get_path(c: &mut Criterion) {
             log::trace!("");
         }
         group.bench_function(&id, |b| {
-            b.iter(|| pathcache.find_path(start, goal, map.cost_fn()))
+            b.iter(|| pathcache.find_path(start, goal, &map.cost_fn()))
         });
 
         // a_star comparison
@@ -275,7 +274,7 @@ fn bench_get_path(c: &mut Criterion) {
         let map = Map::new_random(size, size);
         let pathcache = PathCache::new(
             (size, size),
-            map.cost_fn(),
+            &map.cost_fn(),
             neighborhood,
             PathCacheConfig::with_chunk_size(chunk_size),
         );
@@ -290,9 +289,77 @@ fn bench_get_path(c: &mut Criterion) {
             log::trace!("");
         }
         group.bench_function(&id, |b| {
-            b.iter(|| pathcache.find_path(start, goal, map.cost_fn()))
+            b.iter(|| pathcache.find_path(start, goal, &map.cost_fn()))
         });
 
+        // bidirectional search on the Node Graph
+        let pathcache = PathCache::new(
+            (size, size),
+            &map.cost_fn(),
+            neighborhood,
+            PathCacheConfig {
+                bidirectional: true,
+                ..PathCacheConfig::with_chunk_size(chunk_size)
+            },
+        );
+        let id = format!(
+            "Get Single Path Bidirectional, {} Random Map, Map Size: ({}, {}), Cache Size: {}",
+            name, size, size, chunk_size
+        );
+        group.bench_function(&id, |b| {
+            b.iter(|| pathcache.find_path(start, goal, &map.cost_fn()))
+        });
+
+        // unreachable goal: walls around the goal with a gap that is too narrow for 2x2 agents.
+        // Single Tiles still fit through, so only the search on the Node Graph can find out
+        // that there is no Path. The walls enclose whole Chunks, which the agent can enter
+        let mut walled = Map::new(size, size);
+        let radius = 2 * chunk_size;
+        let (left, top) = (goal.0 - radius, goal.1 - radius);
+        let (right, bottom) = (goal.0 + radius, goal.1 + radius);
+        for x in left..=right {
+            walled.set_cost(x, top, -1);
+            walled.set_cost(x, bottom, -1);
+        }
+        for y in top..=bottom {
+            walled.set_cost(left, y, -1);
+            walled.set_cost(right, y, -1);
+        }
+        for x in left - 1..=goal.0 {
+            walled.set_cost(x, goal.1, 1);
+        }
+        for bidirectional in [false, true] {
+            let pathcache = PathCache::new(
+                (size, size),
+                &walled.cost_fn(),
+                neighborhood,
+                PathCacheConfig {
+                    bidirectional,
+                    max_agent_size: 2,
+                    ..PathCacheConfig::with_chunk_size(chunk_size)
+                },
+            );
+            assert!(pathcache
+                .find_path(start, goal, &walled.cost_fn())
+                .unwrap()
+                .is_some());
+            assert!(pathcache
+                .find_path_with_size(start, goal, 2, &walled.cost_fn())
+                .unwrap()
+                .is_none());
+            let id = format!(
+                "Get Single Path{}, {} Walled Uniform Map, Unreachable Goal, Map Size: ({}, {}), Cache Size: {}",
+                if bidirectional { " Bidirectional" } else { "" },
+                name,
+                size,
+                size,
+                chunk_size
+            );
+            group.bench_function(&id, |b| {
+                b.iter(|| pathcache.find_path_with_size(start, goal, 2, &walled.cost_fn()))
+            });
+        }
+
         // a_star comparison
         let id = format!(
             "Get Single Path A*, {} Random Map, Map Size: ({}, {})",
@@ -304,11 +371,87 @@ fn bench_get_path(c: &mut Criterion) {
     }
 }
 
+fn bench_levels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Levels");
+    group.sample_size(10);
+
+    let (width, height) = (512, 512);
+    let mut map = Map::new_random(width, height);
+    let neighborhood = MooreNeighborhood::new(width, height);
+    let chunk_size = 16;
+    let (start, goal) = ((20, 30), (500, 400));
+    map.set_cost(start.0, start.1, 1);
+    map.set_cost(goal.0, goal.1, 1);
+
+    // a wall across the map, like in bench_update_pathcache
+    let mut walled = map.clone();
+    let mut changed = Vec::with_capacity(width);
+    for x in 0..width {
+        walled.set_cost(x, 8, -1);
+        changed.push((x, 8));
+    }
+
+    for levels in [1, 2, 3] {
+        let config = PathCacheConfig {
+            levels,
+            ..PathCacheConfig::with_chunk_size(chunk_size)
+        };
+
+        let id = format!(
+            "Create cache, Random Map, Levels: {}, Map Size: ({}, {}), Cache Size: {}",
+            levels, width, height, chunk_size
+        );
+        group.bench_function(&id, |b| {
+            b.iter(|| PathCache::new((width, height), &map.cost_fn(), neighborhood, config))
+        });
+
+        let pathcache = PathCache::new((width, height), &map.cost_fn(), neighborhood, config);
+        let id = format!(
+            "Get Single Path, Random Map, Levels: {}, Map Size: ({}, {}), Cache Size: {}",
+            levels, width, height, chunk_size
+        );
+        group.bench_function(&id, |b| {
+            b.iter(|| pathcache.find_path(start, goal, &map.cost_fn()))
+        });
+
+        // the backward direction follows the incoming edges on every level
+        let bidirectional = PathCache::new(
+            (width, height),
+            &map.cost_fn(),
+            neighborhood,
+            PathCacheConfig {
+                bidirectional: true,
+                ..config
+            },
+        );
+        let id = format!(
+            "Get Single Path Bidirectional, Random Map, Levels: {}, Map Size: ({}, {}), Cache Size: {}",
+            levels, width, height, chunk_size
+        );
+        group.bench_function(&id, |b| {
+            b.iter(|| bidirectional.find_path(start, goal, &map.cost_fn()))
+        });
+
+        let id = format!(
+            "Update cache, Random Map, Levels: {}, Map Size: ({}, {}), Cache Size: {}",
+            levels, width, height, chunk_size
+        );
+        group.bench_function(&id, |b| {
+            b.iter_batched_ref(
+                || pathcache.clone(),
+                |cache| cache.tiles_changed(&changed, &walled.cost_fn()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
 criterion_group!(
     benches,
     bench_create_pathcache,
     bench_update_pathcache,
-    bench_get_path
+    bench_get_path,
+    bench_levels
 );
 criterion_main!(benches);
 
@@ -0,0 +1,315 @@
+use crate::Point;
+
+/// A Grid with the walking cost of every Tile.
+///
+/// Every method of the [`PathCache`](crate::PathCache) that reads the Grid takes a CostGrid,
+/// like [`find_path`](crate::PathCache::find_path) or
+/// [`tiles_changed`](crate::PathCache::tiles_changed). This makes it possible to detect when a
+/// different version of the Grid is used than the one the PathCache was last updated with.
+///
+/// Every `Fn((usize, usize)) -> isize` is a CostGrid without any of the optional parts, so
+/// a reference to a cost function can be passed as well.
+///
+/// ## Examples
+/// ```
+/// # use hierarchical_pathfinding::prelude::*;
+/// struct Grid {
+///     width: usize,
+///     costs: Vec<isize>,
+///     generation: u64,
+/// }
+///
+/// impl Grid {
+///     fn set(&mut self, (x, y): (usize, usize), cost: isize) {
+///         self.costs[y * self.width + x] = cost;
+///         self.generation += 1;
+///     }
+/// }
+///
+/// impl CostGrid for Grid {
+///     fn cost(&self, (x, y): (usize, usize)) -> isize {
+///         self.costs[y * self.width + x]
+///     }
+///     fn row(&self, y: usize) -> Option<&[isize]> {
+///         Some(&self.costs[y * self.width..(y + 1) * self.width])
+///     }
+///     fn generation(&self) -> Option<u64> {
+///         Some(self.generation)
+///     }
+/// }
+///
+/// let mut grid = Grid { width: 5, costs: vec![1; 25], generation: 0 };
+/// let mut pathfinding = PathCache::new(
+///     (5, 5),
+///     &grid,
+///     ManhattanNeighborhood::new(5, 5),
+///     PathCacheConfig::with_chunk_size(3),
+/// );
+///
+/// let path = pathfinding.find_path((0, 0), (4, 4), &grid).unwrap();
+/// assert_eq!(path.unwrap().cost(), 8);
+///
+/// grid.set((2, 2), 10);
+/// // the PathCache wasn't told about the change yet
+/// assert!(pathfinding.find_path((0, 0), (4, 4), &grid).is_err());
+///
+/// pathfinding.tiles_changed(&[(2, 2)], &grid);
+/// assert!(pathfinding.find_path((0, 0), (4, 4), &grid).is_ok());
+/// ```
+pub trait CostGrid {
+    /// The cost of walking over the Tile at `pos`. Costs below 0 are solid Tiles.
+    fn cost(&self, pos: Point) -> isize;
+
+    /// The costs of all Tiles in row `y`, if they are stored next to each other.
+    ///
+    /// If this returns `Some`, [`new`](crate::PathCache::new) and
+    /// [`tiles_changed`](crate::PathCache::tiles_changed) copy the Chunks they scan with one
+    /// call per row of a Chunk instead of calling [`cost`](CostGrid::cost) for every Tile.
+    /// Searches only look at a few Tiles of each row and always use `cost`. The default
+    /// implementation returns `None`.
+    fn row(&self, y: usize) -> Option<&[isize]> {
+        let _ = y;
+        None
+    }
+
+    /// A counter that changes whenever the costs of the Grid change.
+    ///
+    /// The PathCache remembers the generation of the Grid it was created or last updated with,
+    /// and its queries return a [`GridMismatch`] error if they are called with a different
+    /// generation. The default implementation returns `None`, which disables that check.
+    fn generation(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether the steps on this Grid have their own [`move_cost`](CostGrid::move_cost).
+    ///
+    /// A PathCache remembers if the Grid it was created with has move costs, and all of its
+    /// methods return a [`GridMismatch`] error for a Grid that differs in that. The default
+    /// implementation returns `false`.
+    fn has_moves(&self) -> bool {
+        false
+    }
+
+    /// The cost of the step from `from` to its neighbor `to`, if
+    /// [`has_moves`](CostGrid::has_moves) returns `true`.
+    ///
+    /// The move cost replaces the cost of `from` for that step. Negative move costs mean that the
+    /// step is not allowed, even if `to` is walkable. This allows for conveyor belts, ledges or
+    /// one-way doors. Since a step and the step back can have different costs, the edges between
+    /// the Nodes of the PathCache are directed. The default implementation returns the cost of
+    /// `from`.
+    fn move_cost(&self, from: Point, to: Point) -> isize {
+        let _ = to;
+        self.cost(from)
+    }
+}
+
+impl<F: Fn(Point) -> isize> CostGrid for F {
+    fn cost(&self, pos: Point) -> isize {
+        self(pos)
+    }
+}
+
+/// A [`CostGrid`] where moves can cost more than the Tiles, or only be allowed in one
+/// direction.
+///
+/// `WithMoves(grid, get_move_cost)` has the costs of `grid`, and `get_move_cost(from, to)` is
+/// its [`move_cost`](CostGrid::move_cost).
+///
+/// ## Examples
+/// ```
+/// # use hierarchical_pathfinding::prelude::*;
+/// // a corridor with a conveyor belt in the middle that only moves to the right
+/// let (width, height) = (6, 1);
+/// let conveyor = |(x, _): (usize, usize)| x == 2 || x == 3;
+/// let grid = WithMoves(
+///     |_| 1,
+///     |from: (usize, usize), to: (usize, usize)| {
+///         if conveyor(from) && to.0 < from.0 {
+///             -1 // can't walk against the conveyor belt
+///         } else {
+///             1
+///         }
+///     },
+/// );
+/// let pathfinding = PathCache::new(
+///     (width, height),
+///     &grid,
+///     ManhattanNeighborhood::new(width, height),
+///     PathCacheConfig::with_chunk_size(2),
+/// );
+///
+/// let path = pathfinding.find_path((0, 0), (5, 0), &grid).unwrap();
+/// assert_eq!(path.unwrap().cost(), 5);
+///
+/// // there is no way back
+/// let path = pathfinding.find_path((5, 0), (0, 0), &grid).unwrap();
+/// assert!(path.is_none());
+///
+/// // the PathCache was created with move costs, so it needs them for every search
+/// assert_eq!(
+///     pathfinding.find_path((0, 0), (5, 0), &|_: (usize, usize)| 1).unwrap_err(),
+///     GridMismatch::Moves { expected: true },
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct WithMoves<G, M>(pub G, pub M);
+
+impl<G, M> std::fmt::Debug for WithMoves<G, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the cost functions are usually closures, which can't be printed
+        f.write_str("WithMoves(..)")
+    }
+}
+
+impl<G: CostGrid, M: Fn(Point, Point) -> isize> CostGrid for WithMoves<G, M> {
+    fn cost(&self, pos: Point) -> isize {
+        self.0.cost(pos)
+    }
+    fn row(&self, y: usize) -> Option<&[isize]> {
+        self.0.row(y)
+    }
+    fn generation(&self) -> Option<u64> {
+        self.0.generation()
+    }
+    fn has_moves(&self) -> bool {
+        true
+    }
+    fn move_cost(&self, from: Point, to: Point) -> isize {
+        (self.1)(from, to)
+    }
+}
+
+/// Turns a CostGrid into a cost function
+pub(crate) fn cost_fn<G: CostGrid + ?Sized>(grid: &G) -> impl '_ + Fn(Point) -> isize {
+    move |pos| grid.cost(pos)
+}
+
+/// A copy of the costs of some Chunks of a CostGrid, for scanning large parts of it.
+///
+/// The rows of the Chunks are read with [`CostGrid::row`], and Tiles outside of the copied
+/// Chunks fall back to [`CostGrid::cost`].
+pub(crate) struct ChunkRows<'a, G: ?Sized> {
+    grid: &'a G,
+    chunk_size: usize,
+    num_chunks_w: usize,
+    /// the width and the costs of every Chunk, row by row, or nothing if it wasn't copied
+    chunks: Vec<(usize, Vec<isize>)>,
+}
+
+impl<'a, G: CostGrid + ?Sized> ChunkRows<'a, G> {
+    /// Copies the Chunks at the positions in `chunks`, unless `grid` doesn't have slices for
+    /// their rows
+    pub fn new(
+        grid: &'a G,
+        (width, height): Point,
+        chunk_size: usize,
+        chunks: impl IntoIterator<Item = Point>,
+    ) -> ChunkRows<'a, G> {
+        let num_chunks_w = width.div_ceil(chunk_size);
+        let num_chunks_h = height.div_ceil(chunk_size);
+        let mut copies = vec![];
+        for (cx, cy) in chunks {
+            if copies.is_empty() {
+                copies.resize(num_chunks_w * num_chunks_h, (0, vec![]));
+            }
+            let copy = &mut copies[cy * num_chunks_w + cx];
+            if copy.0 > 0 {
+                continue;
+            }
+            let (left, top) = (cx * chunk_size, cy * chunk_size);
+            let right = (left + chunk_size).min(width);
+            let bottom = (top + chunk_size).min(height);
+            let mut costs = Vec::with_capacity((right - left) * (bottom - top));
+            for y in top..bottom {
+                match grid.row(y) {
+                    Some(row) => costs.extend_from_slice(&row[left..right]),
+                    // a Grid without slices has to be read Tile by Tile
+                    None => return ChunkRows::new(grid, (width, height), chunk_size, []),
+                }
+            }
+            *copy = (right - left, costs);
+        }
+        ChunkRows {
+            grid,
+            chunk_size,
+            num_chunks_w,
+            chunks: copies,
+        }
+    }
+
+    pub fn cost(&self, (x, y): Point) -> isize {
+        if !self.chunks.is_empty() {
+            let size = self.chunk_size;
+            let (width, costs) = &self.chunks[(y / size) * self.num_chunks_w + x / size];
+            if *width > 0 {
+                return costs[(y % size) * width + x % size];
+            }
+        }
+        self.grid.cost((x, y))
+    }
+}
+
+/// The error returned when a [`CostGrid`] doesn't match the Grid that the PathCache or Path was
+/// created with.
+///
+/// This means that the Grid changed without the PathCache being updated, that an old
+/// [`AbstractPath`](crate::internals::AbstractPath) is used with a newer Grid, or that the move
+/// costs are missing. In all cases the stored Paths may lead through Tiles that are now solid,
+/// or against one-way moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridMismatch {
+    /// The Grid has a different [`generation`](CostGrid::generation)
+    Generation {
+        /// The generation that was expected
+        expected: u64,
+        /// The generation of the Grid that was passed in
+        found: u64,
+    },
+    /// The Grid has [`move_cost`](CostGrid::move_cost)s and the PathCache was created without
+    /// them, or the other way around
+    Moves {
+        /// Whether the PathCache was created with move costs
+        expected: bool,
+    },
+}
+
+impl GridMismatch {
+    /// Checks that `grid` has the `expected` generation, with either of them being `None`
+    /// accepted, and move costs if `moves` is set.
+    pub(crate) fn check<G: CostGrid + ?Sized>(
+        expected: Option<u64>,
+        moves: bool,
+        grid: &G,
+    ) -> Result<(), GridMismatch> {
+        if grid.has_moves() != moves {
+            return Err(GridMismatch::Moves { expected: moves });
+        }
+        match (expected, grid.generation()) {
+            (Some(expected), Some(found)) if expected != found => {
+                Err(GridMismatch::Generation { expected, found })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for GridMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridMismatch::Generation { expected, found } => write!(
+                f,
+                "expected generation {} of the Grid, but found {}",
+                expected, found
+            ),
+            GridMismatch::Moves { expected: true } => {
+                write!(f, "expected a Grid with move costs")
+            }
+            GridMismatch::Moves { expected: false } => {
+                write!(f, "expected a Grid without move costs")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GridMismatch {}
@@ -3,78 +3,129 @@ use crate::neighbors::Neighborhood;
 
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
+use std::task::Poll;
 
+#[allow(unused)]
 pub fn a_star_search<N: Neighborhood>(
-    nodes: &NodeList,
+    graph: &impl Graph,
     start: NodeID,
     goal: NodeID,
     neighborhood: &N,
     size_hint: usize,
 ) -> Option<Path<NodeID>> {
-    if start == goal {
-        return Some(Path::from_slice(&[start, start], 0));
+    let mut search = AStarSearch::new(start, goal, size_hint);
+    let mut budget = usize::MAX;
+    match search.step(graph, neighborhood, &mut budget) {
+        Poll::Ready(path) => path,
+        Poll::Pending => panic!("Internal Error #1 in graph::a_star_search. Please report this"),
     }
-    let mut visited = NodeIDMap::with_capacity(size_hint);
-    let mut next = BinaryHeap::with_capacity(size_hint / 2);
-    next.push(HeuristicElement(start, 0, 0));
-    visited.insert(start, (0, start));
+}
+
+/// The state of an [`a_star_search`] that can be continued at a later point
+pub struct AStarSearch {
+    start: NodeID,
+    goal: NodeID,
+    visited: NodeIDMap<(Cost, NodeID)>,
+    next: BinaryHeap<HeuristicElement<NodeID>>,
+}
 
-    while let Some(HeuristicElement(current_id, current_cost, _)) = next.pop() {
-        if current_id == goal {
-            break;
+impl AStarSearch {
+    pub fn new(start: NodeID, goal: NodeID, size_hint: usize) -> AStarSearch {
+        let mut visited = NodeIDMap::with_capacity(size_hint);
+        let mut next = BinaryHeap::with_capacity(size_hint / 2);
+        next.push(HeuristicElement(start, 0, 0));
+        visited.insert(start, (0, start));
+        AStarSearch {
+            start,
+            goal,
+            visited,
+            next,
         }
-        match current_cost.cmp(&visited[&current_id].0) {
-            Ordering::Greater => continue,
-            Ordering::Equal => {}
-            Ordering::Less => panic!("Binary Heap failed"),
+    }
+
+    /// Visits Nodes until either the search is done or `budget` Nodes were visited.
+    ///
+    /// Every visited Node is subtracted from `budget`. Returns `Pending` if the search ran out
+    /// of budget.
+    pub fn step<N: Neighborhood>(
+        &mut self,
+        graph: &impl Graph,
+        neighborhood: &N,
+        budget: &mut usize,
+    ) -> Poll<Option<Path<NodeID>>> {
+        if self.start == self.goal {
+            return Poll::Ready(Some(Path::from_slice(&[self.start, self.start], 0)));
         }
+        let visited = &mut self.visited;
+
+        loop {
+            if *budget == 0 {
+                return Poll::Pending;
+            }
+            let HeuristicElement(current_id, current_cost, _) = match self.next.pop() {
+                Some(element) => element,
+                None => break,
+            };
+            if current_id == self.goal {
+                break;
+            }
+            match current_cost.cmp(&visited[&current_id].0) {
+                Ordering::Greater => continue,
+                Ordering::Equal => {}
+                Ordering::Less => panic!("Binary Heap failed"),
+            }
+            *budget -= 1;
 
-        let current = &nodes[current_id];
+            let current_pos = graph.pos(current_id);
+            let next = &mut self.next;
 
-        for (&other_id, path) in current.edges.iter() {
-            let other_cost = current_cost + path.cost();
-            let other = &nodes[other_id];
+            graph.for_each_edge(current_id, |other_id, cost| {
+                let other_cost = current_cost + cost;
 
-            let mut needs_visit = true;
-            if let Some((prev_cost, prev_id)) = visited.get_mut(&other_id) {
-                if *prev_cost > other_cost {
-                    *prev_cost = other_cost;
-                    *prev_id = current_id;
+                let mut needs_visit = true;
+                if let Some((prev_cost, prev_id)) = visited.get_mut(&other_id) {
+                    if *prev_cost > other_cost {
+                        *prev_cost = other_cost;
+                        *prev_id = current_id;
+                    } else {
+                        needs_visit = false;
+                    }
                 } else {
-                    needs_visit = false;
+                    visited.insert(other_id, (other_cost, current_id));
                 }
-            } else {
-                visited.insert(other_id, (other_cost, current_id));
-            }
 
-            if needs_visit {
-                let heuristic = neighborhood.heuristic(current.pos, other.pos);
-                next.push(HeuristicElement(
-                    other_id,
-                    other_cost,
-                    other_cost + heuristic,
-                ));
-            }
+                if needs_visit {
+                    let heuristic = neighborhood.heuristic(current_pos, graph.pos(other_id));
+                    next.push(HeuristicElement(
+                        other_id,
+                        other_cost,
+                        other_cost + heuristic,
+                    ));
+                }
+            });
         }
-    }
-
-    if !visited.contains_key(&goal) {
-        return None;
-    }
 
-    let steps = {
-        let mut steps = vec![];
-        let mut current = goal;
+        // make sure that further calls return the same result
+        self.next.clear();
 
-        while current != start {
-            steps.push(current);
-            let (_, prev) = visited[&current];
-            current = prev;
+        if !visited.contains_key(&self.goal) {
+            return Poll::Ready(None);
         }
-        steps.push(start);
-        steps.reverse();
-        steps
-    };
 
-    Some(Path::new(steps, visited[&goal].0))
+        let steps = {
+            let mut steps = vec![];
+            let mut current = self.goal;
+
+            while current != self.start {
+                steps.push(current);
+                let (_, prev) = visited[&current];
+                current = prev;
+            }
+            steps.push(self.start);
+            steps.reverse();
+            steps
+        };
+
+        Poll::Ready(Some(Path::new(steps, visited[&self.goal].0)))
+    }
 }
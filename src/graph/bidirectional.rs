@@ -0,0 +1,193 @@
+use super::*;
+use crate::neighbors::Neighborhood;
+
+use std::collections::BinaryHeap;
+use std::task::Poll;
+
+/// What both directions of a [`BidirectionalSearch`] know about a Node
+struct Visit {
+    /// the cost from the origin of each direction, or `Cost::MAX` if not reached yet
+    cost: [Cost; 2],
+    /// the previous Node on the way from the origin of each direction
+    prev: [NodeID; 2],
+    /// if either direction is done with the Node
+    closed: bool,
+}
+
+impl Visit {
+    fn new(id: NodeID) -> Visit {
+        Visit {
+            cost: [Cost::MAX; 2],
+            prev: [id; 2],
+            closed: false,
+        }
+    }
+}
+
+/// An A* search that runs from the start and the goal at once until the two directions meet,
+/// based on NBA* ("Yet another bidirectional algorithm for shortest paths", Pijls and Post).
+///
+/// The search from the goal follows the edges backwards, so one-way edges are only ever used
+/// in their direction. Every Node is expanded by at most one of the directions, and Nodes
+/// that can't be part of a Path cheaper than the best one so far are skipped without
+/// expanding them.
+pub struct BidirectionalSearch {
+    start: NodeID,
+    goal: NodeID,
+    visited: NodeIDMap<Visit>,
+    /// the Nodes to visit forward from `start` and backward from `goal`
+    next: [BinaryHeap<HeuristicElement<NodeID>>; 2],
+    /// the cost of the best Path so far and the Node where its two halves meet
+    best: Option<(Cost, NodeID)>,
+    /// if the heuristic of the Neighborhood can be used. Otherwise, both directions are
+    /// Dijkstra searches
+    use_heuristic: bool,
+}
+
+impl BidirectionalSearch {
+    pub fn new(
+        start: NodeID,
+        goal: NodeID,
+        use_heuristic: bool,
+        size_hint: usize,
+    ) -> BidirectionalSearch {
+        let mut visited = NodeIDMap::with_capacity(size_hint);
+        let mut next = [
+            BinaryHeap::with_capacity(size_hint / 4),
+            BinaryHeap::with_capacity(size_hint / 4),
+        ];
+        for (side, origin) in [start, goal].into_iter().enumerate() {
+            visited
+                .entry(origin)
+                .or_insert_with(|| Visit::new(origin))
+                .cost[side] = 0;
+            next[side].push(HeuristicElement(origin, 0, 0));
+        }
+        BidirectionalSearch {
+            start,
+            goal,
+            visited,
+            next,
+            best: None,
+            use_heuristic,
+        }
+    }
+
+    /// Visits Nodes until either the search is done or `budget` Nodes were visited.
+    ///
+    /// Every visited Node is subtracted from `budget`. Returns `Pending` if the search ran out
+    /// of budget.
+    pub fn step<N: Neighborhood>(
+        &mut self,
+        graph: &impl ReverseGraph,
+        neighborhood: &N,
+        budget: &mut usize,
+    ) -> Poll<Option<Path<NodeID>>> {
+        if self.start == self.goal {
+            return Poll::Ready(Some(Path::from_slice(&[self.start, self.start], 0)));
+        }
+        // the forward direction heads for the goal, the backward one for the start
+        let targets = [graph.pos(self.goal), graph.pos(self.start)];
+        let use_heuristic = self.use_heuristic;
+        let heuristic = |side: usize, pos: Point| {
+            if use_heuristic {
+                neighborhood.heuristic(pos, targets[side])
+            } else {
+                0
+            }
+        };
+        let visited = &mut self.visited;
+
+        loop {
+            if *budget == 0 {
+                return Poll::Pending;
+            }
+            let [forward, backward] = &mut self.next;
+            if forward.is_empty() || backward.is_empty() {
+                break;
+            }
+            // continue with the direction that has fewer Nodes to visit
+            let (side, next, other) = if forward.len() <= backward.len() {
+                (0, forward, &*backward)
+            } else {
+                (1, backward, &*forward)
+            };
+
+            let HeuristicElement(current_id, current_cost, estimate) = next.pop().unwrap();
+            let current = visited.get_mut(&current_id).unwrap();
+            if current_cost > current.cost[side] || current.closed {
+                continue;
+            }
+            current.closed = true;
+            *budget -= 1;
+
+            // a lower bound for the estimate of every Node that the other direction has left.
+            // Outdated entries can only make it lower
+            let lowest = other.peek().map_or(Cost::MAX, |element| element.2);
+            let current_pos = graph.pos(current_id);
+            let remaining = lowest.saturating_sub(heuristic(1 - side, current_pos));
+            let mut best = self.best.map_or(Cost::MAX, |(cost, _)| cost);
+            if estimate >= best || current_cost.saturating_add(remaining) >= best {
+                continue;
+            }
+
+            let best_path = &mut self.best;
+            let visit = |other_id: NodeID, cost: Cost| {
+                let other_cost = current_cost + cost;
+                let visit = visited
+                    .entry(other_id)
+                    .or_insert_with(|| Visit::new(other_id));
+                if visit.closed || visit.cost[side] <= other_cost {
+                    return;
+                }
+                visit.cost[side] = other_cost;
+                visit.prev[side] = current_id;
+
+                let rest = visit.cost[1 - side];
+                if rest != Cost::MAX && other_cost + rest < best {
+                    best = other_cost + rest;
+                    *best_path = Some((best, other_id));
+                }
+
+                let heuristic = heuristic(side, graph.pos(other_id));
+                next.push(HeuristicElement(
+                    other_id,
+                    other_cost,
+                    other_cost + heuristic,
+                ));
+            };
+            if side == 0 {
+                graph.for_each_edge(current_id, visit);
+            } else {
+                graph.for_each_incoming_edge(current_id, visit);
+            }
+        }
+
+        // make sure that further calls return the same result
+        for next in self.next.iter_mut() {
+            next.clear();
+        }
+
+        let (cost, meeting) = match self.best {
+            Some(best) => best,
+            None => return Poll::Ready(None),
+        };
+
+        let mut steps = vec![];
+        let mut current = meeting;
+        while current != self.start {
+            steps.push(current);
+            current = visited[&current].prev[0];
+        }
+        steps.push(self.start);
+        steps.reverse();
+
+        let mut current = meeting;
+        while current != self.goal {
+            current = visited[&current].prev[1];
+            steps.push(current);
+        }
+
+        Poll::Ready(Some(Path::new(steps, cost)))
+    }
+}
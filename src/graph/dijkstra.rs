@@ -4,7 +4,7 @@ use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
 pub fn dijkstra_search(
-    nodes: &NodeList,
+    graph: &impl Graph,
     start: NodeID,
     goals: &[NodeID],
     only_closest_goal: bool,
@@ -33,10 +33,8 @@ pub fn dijkstra_search(
             }
         }
 
-        let current = &nodes[current_id];
-
-        for (&other_id, path) in current.edges.iter() {
-            let other_cost = current_cost + path.cost();
+        graph.for_each_edge(current_id, |other_id, cost| {
+            let other_cost = current_cost + cost;
 
             let mut needs_visit = true;
             if let Some((prev_cost, prev_id)) = visited.get_mut(&other_id) {
@@ -53,7 +51,7 @@ pub fn dijkstra_search(
             if needs_visit {
                 next.push(Element(other_id, other_cost));
             }
-        }
+        });
     }
 
     let mut goal_data = NodeIDMap::with_capacity_and_hasher(goal_costs.len(), Default::default());
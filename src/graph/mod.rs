@@ -5,11 +5,44 @@ mod node;
 pub use node::Node;
 
 mod a_star;
+#[allow(unused)]
 pub use a_star::a_star_search;
+pub use a_star::AStarSearch;
+
+mod bidirectional;
+pub use bidirectional::BidirectionalSearch;
 
 mod dijkstra;
 pub use dijkstra::dijkstra_search;
 
 pub(crate) use crate::grid::{Element, HeuristicElement};
-pub(crate) use crate::path::Path;
-pub(crate) use crate::{NodeID, NodeIDMap, NodeIDSet};
+pub(crate) use crate::path::{Cost, Path};
+pub(crate) use crate::{NodeID, NodeIDMap, NodeIDSet, Point};
+
+/// A view of the Node Graph that can be traversed by the searches in this module.
+///
+/// This allows the same search to run on the plain [`NodeList`] or on a view that uses
+/// the higher abstraction levels of a PathCache.
+pub trait Graph {
+    /// The position of a Node on the Grid
+    fn pos(&self, id: NodeID) -> Point;
+    /// Calls `f` with the target and cost of every outgoing edge of a Node
+    fn for_each_edge(&self, id: NodeID, f: impl FnMut(NodeID, Cost));
+}
+
+/// A [`Graph`] that can also be traversed against the direction of its edges
+pub trait ReverseGraph: Graph {
+    /// Calls `f` with the source and cost of every incoming edge of a Node
+    fn for_each_incoming_edge(&self, id: NodeID, f: impl FnMut(NodeID, Cost));
+}
+
+impl Graph for NodeList {
+    fn pos(&self, id: NodeID) -> Point {
+        self[id].pos
+    }
+    fn for_each_edge(&self, id: NodeID, mut f: impl FnMut(NodeID, Cost)) {
+        for (&other_id, path) in self[id].edges.iter() {
+            f(other_id, path.cost());
+        }
+    }
+}
@@ -1,11 +1,24 @@
-use crate::{path::PathSegment, NodeID, NodeIDMap, Point};
+use crate::{path::PathSegment, NodeID, NodeIDMap, NodeIDSet, Point};
 
 #[derive(Clone, Debug)]
 pub struct Node {
     pub id: NodeID,
     pub pos: Point,
+    /// the cost of the Tile, multiplied by the `cost_scale` of the Neighborhood
     pub walk_cost: usize,
     pub edges: NodeIDMap<PathSegment>,
+    /// Alternatives to the Paths in `edges` for agents that don't fit through them, as
+    /// (clearance, Path) with increasing clearance. Only present if the Path in `edges` is
+    /// narrower than the Nodes on either end, in which case the list starts with that Path
+    pub wide_edges: NodeIDMap<Vec<(usize, PathSegment)>>,
+    /// Alternatives to the Paths in `edges` for agents that can't enter every terrain class, as
+    /// (required capabilities, Path) with increasing cost. Only present if the Path in `edges`
+    /// crosses terrain classes other than those of the Nodes on either end, in which case the
+    /// list starts with that Path
+    pub terrain_edges: NodeIDMap<Vec<(u32, PathSegment)>>,
+    /// The Nodes that have an edge to this Node. Only kept up to date if the NodeList is
+    /// directed, since every edge has a counterpart in `edges` otherwise
+    pub incoming: NodeIDSet,
 }
 
 impl Node {
@@ -15,6 +28,9 @@ impl Node {
             pos,
             walk_cost,
             edges: NodeIDMap::default(),
+            wide_edges: NodeIDMap::default(),
+            terrain_edges: NodeIDMap::default(),
+            incoming: NodeIDSet::default(),
         }
     }
 }
@@ -6,6 +6,8 @@ pub struct NodeList {
     nodes: Vec<Option<Node>>,
     pos_map: PointMap<NodeID>,
     next_id: usize,
+    /// if edges only go one way, instead of always having a reversed counterpart
+    directed: bool,
 }
 
 impl NodeList {
@@ -14,9 +16,63 @@ impl NodeList {
             nodes: Vec::new(),
             pos_map: PointMap::default(),
             next_id: 0,
+            directed: false,
         }
     }
 
+    /// Creates an empty NodeList whose edges only go one way. See [`Node::incoming`]
+    pub fn new_directed() -> Self {
+        Self {
+            directed: true,
+            ..Self::new()
+        }
+    }
+
+    /// Creates a NodeList from raw slots, where `None` marks the ID of a removed Node
+    pub fn from_slots(mut nodes: Vec<Option<Node>>, directed: bool) -> Self {
+        let pos_map = nodes
+            .iter()
+            .flatten()
+            .map(|node| (node.pos, node.id))
+            .collect();
+        if directed {
+            let edges = nodes
+                .iter()
+                .flatten()
+                .flat_map(|node| node.edges.keys().map(move |other| (node.id, *other)))
+                .collect::<Vec<_>>();
+            for (id, other_id) in edges {
+                if let Some(Some(other)) = nodes.get_mut(other_id as usize) {
+                    other.incoming.insert(id);
+                }
+            }
+        }
+        Self {
+            nodes,
+            pos_map,
+            next_id: 0,
+            directed,
+        }
+    }
+
+    /// If edges only go one way. See [`Node::incoming`]
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    /// Turns this NodeList into a directed one. The existing edges keep their counterparts
+    pub fn make_directed(&mut self) {
+        if !self.directed {
+            let nodes = std::mem::take(&mut self.nodes);
+            *self = Self::from_slots(nodes, true);
+        }
+    }
+
+    /// The raw slots of this NodeList. See [`from_slots`](NodeList::from_slots)
+    pub fn slots(&self) -> &[Option<Node>] {
+        &self.nodes
+    }
+
     #[allow(unused)]
     pub fn len(&self) -> usize {
         self.pos_map.len()
@@ -41,6 +97,11 @@ impl NodeList {
     }
 
     pub fn add_edge(&mut self, src: NodeID, target: NodeID, path: PathSegment) {
+        if self.directed {
+            self[target].incoming.insert(src);
+            self[src].edges.insert(target, path);
+            return;
+        }
         let src_cost = self[src].walk_cost;
 
         let target_node = &mut self[target];
@@ -54,11 +115,122 @@ impl NodeList {
         src_node.edges.insert(target, path);
     }
 
+    /// Adds an edge without a counterpart and without alternative Paths, even if the NodeList
+    /// isn't directed. In that case, the edge back has to be inserted as well
+    pub fn insert_edge(&mut self, src: NodeID, target: NodeID, path: PathSegment) {
+        if self.directed {
+            self[target].incoming.insert(src);
+        }
+        let src_node = &mut self[src];
+        src_node.wide_edges.remove(&target);
+        src_node.terrain_edges.remove(&target);
+        src_node.edges.insert(target, path);
+    }
+
+    /// Removes a single edge, without its counterpart
+    pub fn remove_edge(&mut self, src: NodeID, target: NodeID) {
+        let src_node = &mut self[src];
+        src_node.wide_edges.remove(&target);
+        src_node.terrain_edges.remove(&target);
+        if src_node.edges.remove(&target).is_some() && self.directed {
+            self[target].incoming.remove(&src);
+        }
+    }
+
+    /// Adds the alternative Paths for bigger agents to an edge. See [`Node::wide_edges`]
+    pub fn add_wide_edges(
+        &mut self,
+        src: NodeID,
+        target: NodeID,
+        paths: Vec<(usize, PathSegment)>,
+    ) {
+        if self.directed {
+            self[src].wide_edges.insert(target, paths);
+            return;
+        }
+        let src_cost = self[src].walk_cost;
+        let target_cost = self[target].walk_cost;
+
+        let other_paths = paths
+            .iter()
+            .map(|(clearance, path)| (*clearance, path.reversed(src_cost, target_cost)))
+            .collect();
+        self[target].wide_edges.insert(src, other_paths);
+        self[src].wide_edges.insert(target, paths);
+    }
+
+    /// Adds the alternative Paths for restricted agents to an edge. See [`Node::terrain_edges`]
+    pub fn add_terrain_edges(
+        &mut self,
+        src: NodeID,
+        target: NodeID,
+        paths: Vec<(u32, PathSegment)>,
+    ) {
+        if self.directed {
+            self[src].terrain_edges.insert(target, paths);
+            return;
+        }
+        let src_cost = self[src].walk_cost;
+        let target_cost = self[target].walk_cost;
+
+        let other_paths = paths
+            .iter()
+            .map(|(required, path)| (*required, path.reversed(src_cost, target_cost)))
+            .collect();
+        self[target].terrain_edges.insert(src, other_paths);
+        self[src].terrain_edges.insert(target, paths);
+    }
+
+    /// Removes all edges that start or end at a Node
+    pub fn clear_edges(&mut self, id: NodeID) {
+        let node = &mut self[id];
+        node.wide_edges.clear();
+        node.terrain_edges.clear();
+        let edges = std::mem::take(&mut node.edges);
+        let incoming = std::mem::take(&mut node.incoming);
+        if self.directed {
+            for other_id in edges.keys() {
+                self[*other_id].incoming.remove(&id);
+            }
+        }
+        // without incoming Nodes, every edge has a counterpart
+        let sources = if self.directed {
+            incoming
+        } else {
+            edges.into_keys().collect()
+        };
+        for other_id in sources {
+            let other = &mut self[other_id];
+            other.edges.remove(&id);
+            other.wide_edges.remove(&id);
+            other.terrain_edges.remove(&id);
+        }
+    }
+
+    /// Calls `f` with every Node that has an edge to a Node
+    pub fn for_each_incoming(&self, id: NodeID, mut f: impl FnMut(NodeID)) {
+        let node = &self[id];
+        if self.directed {
+            node.incoming.iter().copied().for_each(&mut f);
+        } else {
+            // every edge has a counterpart
+            node.edges.keys().copied().for_each(&mut f);
+        }
+    }
+
     #[track_caller]
     pub fn remove_node(&mut self, id: NodeID) {
         let node = self.nodes[id as usize].take().unwrap();
-        for (other_id, _) in node.edges {
-            self[other_id].edges.remove(&id);
+        if self.directed {
+            for other_id in node.edges.keys() {
+                self[*other_id].incoming.remove(&id);
+            }
+        }
+        for other_id in node.incoming.into_iter().chain(node.edges.into_keys()) {
+            let other = &mut self[other_id];
+            other.edges.remove(&id);
+            other.wide_edges.remove(&id);
+            other.terrain_edges.remove(&id);
         }
         self.pos_map.remove(&node.pos);
         self.next_id = self.next_id.min(id as usize);
@@ -107,6 +279,21 @@ impl NodeList {
                 .into_iter()
                 .map(|(other_id, path)| (map[&other_id], path))
                 .collect();
+            new_node.wide_edges = old_node
+                .wide_edges
+                .into_iter()
+                .map(|(other_id, paths)| (map[&other_id], paths))
+                .collect();
+            new_node.terrain_edges = old_node
+                .terrain_edges
+                .into_iter()
+                .map(|(other_id, paths)| (map[&other_id], paths))
+                .collect();
+            new_node.incoming = old_node
+                .incoming
+                .into_iter()
+                .map(|other_id| map[&other_id])
+                .collect();
         }
 
         ret
@@ -161,3 +348,64 @@ fn absorb() {
     assert_eq!(nodes.nodes[4].as_ref().unwrap().pos, (11, 11));
     assert_eq!(nodes.nodes[3].as_ref().unwrap().edges[&4].cost(), 10);
 }
+
+#[test]
+fn directed() {
+    let segment = |cost| PathSegment::new(super::Path::from_slice(&[], cost), true);
+    let mut nodes = NodeList::new_directed();
+    nodes.add_node((0, 0), 1);
+    nodes.add_node((1, 1), 1);
+    nodes.add_node((2, 2), 1);
+    nodes.add_edge(0, 1, segment(3));
+    nodes.add_edge(2, 1, segment(5));
+    nodes.add_edge(1, 2, segment(7));
+
+    // edges don't get a counterpart
+    assert!(!nodes[1].edges.contains_key(&0));
+    assert_eq!(nodes[2].edges[&1].cost(), 5);
+    assert_eq!(nodes[1].edges[&2].cost(), 7);
+
+    let mut incoming = vec![];
+    nodes.for_each_incoming(1, |id| incoming.push(id));
+    incoming.sort_unstable();
+    assert_eq!(incoming, vec![0, 2]);
+
+    let restored = NodeList::from_slots(nodes.slots().to_vec(), true);
+    for id in nodes.keys() {
+        assert_eq!(restored[id].incoming, nodes[id].incoming);
+    }
+
+    nodes.remove_node(1);
+    assert!(nodes[0].edges.is_empty());
+    assert!(nodes[2].edges.is_empty());
+    assert!(nodes[2].incoming.is_empty());
+
+    nodes.add_edge(0, 2, segment(1));
+    nodes.add_edge(2, 0, segment(1));
+    nodes.clear_edges(0);
+    assert!(nodes[2].incoming.is_empty());
+    assert!(nodes[2].edges.is_empty());
+}
+
+#[test]
+fn make_directed() {
+    let segment = |cost| PathSegment::new(super::Path::from_slice(&[], cost), true);
+    let mut nodes = NodeList::new();
+    nodes.add_node((0, 0), 1);
+    nodes.add_node((1, 1), 2);
+    nodes.add_node((2, 2), 3);
+    nodes.add_edge(0, 1, segment(3));
+
+    nodes.make_directed();
+    assert!(nodes.is_directed());
+    assert_eq!(nodes.id_at((1, 1)), Some(1));
+    assert_eq!(nodes[1].edges[&0].cost(), 4);
+    assert!(nodes[1].incoming.contains(&0));
+
+    nodes.insert_edge(2, 0, segment(5));
+    assert!(!nodes[0].edges.contains_key(&2));
+    assert!(nodes[0].incoming.contains(&2));
+    nodes.remove_edge(2, 0);
+    assert!(nodes[2].edges.is_empty());
+    assert!(!nodes[0].incoming.contains(&2));
+}
@@ -1,98 +1,205 @@
-use super::{HeuristicElement, Path};
+use super::{expand_lines, Cost, HeuristicElement, JumpPoints, Path, SearchOptions};
 use crate::{neighbors::Neighborhood, Point, PointMap};
 
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
+use std::task::Poll;
 
 pub fn a_star_search<N: Neighborhood>(
     neighborhood: &N,
-    mut valid: impl FnMut(Point) -> bool,
-    mut get_cost: impl FnMut(Point) -> isize,
+    valid: impl FnMut(Point) -> bool,
+    get_cost: impl FnMut(Point) -> isize,
     start: Point,
     goal: Point,
     size_hint: usize,
+    options: SearchOptions,
 ) -> Option<Path<Point>> {
-    if get_cost(start) < 0 {
-        return None;
+    let mut search = AStarSearch::new(start, goal, size_hint, options);
+    let mut budget = usize::MAX;
+    match search.step(neighborhood, valid, get_cost, &mut budget) {
+        Poll::Ready(path) => path,
+        Poll::Pending => panic!("Internal Error #1 in grid::a_star_search. Please report this"),
     }
-    if start == goal {
-        return Some(Path::from_slice(&[start, start], 0));
-    }
-    let mut visited = PointMap::with_capacity(size_hint);
-    let mut next = BinaryHeap::with_capacity(size_hint / 2);
-    next.push(HeuristicElement(start, 0, 0));
-    visited.insert(start, (0, start));
+}
 
-    let mut all_neighbors = vec![];
+/// The state of an [`a_star_search`] that can be continued at a later point
+pub struct AStarSearch {
+    start: Point,
+    goal: Point,
+    visited: PointMap<(usize, Point)>,
+    next: BinaryHeap<HeuristicElement<Point>>,
+    options: SearchOptions,
+}
 
-    while let Some(HeuristicElement(current_id, current_cost, _)) = next.pop() {
-        if current_id == goal {
-            break;
-        }
-        match current_cost.cmp(&visited[&current_id].0) {
-            Ordering::Greater => continue,
-            Ordering::Equal => {}
-            Ordering::Less => panic!("Binary Heap failed"),
+impl AStarSearch {
+    pub fn new(start: Point, goal: Point, size_hint: usize, options: SearchOptions) -> AStarSearch {
+        let mut visited = PointMap::with_capacity(size_hint);
+        let mut next = BinaryHeap::with_capacity(size_hint / 2);
+        next.push(HeuristicElement(start, 0, 0));
+        visited.insert(start, (0, start));
+        AStarSearch {
+            start,
+            goal,
+            visited,
+            next,
+            options,
         }
+    }
 
-        let delta_cost = get_cost(current_id);
-        if delta_cost < 0 {
-            continue;
+    /// Visits Tiles until either the search is done or `budget` Tiles were visited.
+    ///
+    /// Every visited Tile is subtracted from `budget`. Returns `Pending` if the search ran out
+    /// of budget.
+    pub fn step<N: Neighborhood>(
+        &mut self,
+        neighborhood: &N,
+        mut valid: impl FnMut(Point) -> bool,
+        mut get_cost: impl FnMut(Point) -> isize,
+        budget: &mut usize,
+    ) -> Poll<Option<Path<Point>>> {
+        if self.start == self.goal {
+            return Poll::Ready(if get_cost(self.start) < 0 {
+                // cannot start on a wall
+                None
+            } else {
+                Some(Path::from_slice(&[self.start, self.start], 0))
+            });
         }
-        let other_cost = current_cost + delta_cost as usize;
+        let (goal, visited) = (self.goal, &mut self.visited);
+        let jumps = if self.options.jump_points {
+            JumpPoints::new(neighborhood, goal)
+        } else {
+            None
+        };
 
-        all_neighbors.clear();
-        neighborhood.get_all_neighbors(current_id, &mut all_neighbors);
-        for &other_id in all_neighbors.iter() {
-            if !valid(other_id) {
-                continue;
+        let mut all_neighbors = vec![];
+
+        loop {
+            if *budget == 0 {
+                return Poll::Pending;
+            }
+            let HeuristicElement(current_id, current_cost, _) = match self.next.pop() {
+                Some(element) => element,
+                None => break,
+            };
+            if current_id == goal {
+                break;
+            }
+            match current_cost.cmp(&visited[&current_id].0) {
+                Ordering::Greater => continue,
+                Ordering::Equal => {}
+                Ordering::Less => panic!("Binary Heap failed"),
             }
-            if get_cost(other_id) < 0 && other_id != goal {
+            *budget -= 1;
+
+            let delta_cost = get_cost(current_id);
+            if delta_cost < 0 {
+                // also covers a solid start
                 continue;
             }
+            let delta_cost = delta_cost as Cost;
+            let (_, parent) = visited[&current_id];
+
+            all_neighbors.clear();
+            let jumped = match &jumps {
+                Some(jumps) if parent != current_id => jumps.successors(
+                    current_id,
+                    parent,
+                    &mut valid,
+                    &mut get_cost,
+                    &mut all_neighbors,
+                ),
+                _ => false,
+            };
+            if !jumped {
+                neighborhood.get_all_neighbors(current_id, &mut all_neighbors);
+            }
+            for &other_id in all_neighbors.iter() {
+                if !valid(other_id) {
+                    continue;
+                }
+                let other_tile_cost = get_cost(other_id);
+                if other_tile_cost < 0 && other_id != goal {
+                    continue;
+                }
+                if !jumped && !neighborhood.is_step_allowed(current_id, other_id, &mut get_cost) {
+                    continue;
+                }
+                let other_tile_cost = other_tile_cost.max(0) as Cost;
+                let other_cost = if jumped {
+                    // jumps go in a straight or diagonal line over Tiles of the same cost
+                    let (dx, dy) = (
+                        other_id.0 as isize - current_id.0 as isize,
+                        other_id.1 as isize - current_id.1 as isize,
+                    );
+                    let steps = dx.unsigned_abs().max(dy.unsigned_abs());
+                    let (dx, dy) = (dx.signum(), dy.signum());
+                    let first = (
+                        current_id.0.wrapping_add_signed(dx),
+                        current_id.1.wrapping_add_signed(dy),
+                    );
+                    let last = (
+                        other_id.0.wrapping_add_signed(-dx),
+                        other_id.1.wrapping_add_signed(-dy),
+                    );
+                    let uniform = neighborhood.step_cost(current_id, first, delta_cost, delta_cost);
+                    let last_step =
+                        neighborhood.step_cost(last, other_id, delta_cost, other_tile_cost);
+                    current_cost + uniform * (steps - 1) + last_step
+                } else {
+                    current_cost
+                        + neighborhood.step_cost(current_id, other_id, delta_cost, other_tile_cost)
+                };
 
-            let mut needs_visit = true;
-            if let Some((prev_cost, prev_id)) = visited.get_mut(&other_id) {
-                if *prev_cost > other_cost {
-                    *prev_cost = other_cost;
-                    *prev_id = current_id;
+                let mut needs_visit = true;
+                if let Some((prev_cost, prev_id)) = visited.get_mut(&other_id) {
+                    if *prev_cost > other_cost {
+                        *prev_cost = other_cost;
+                        *prev_id = current_id;
+                    } else {
+                        needs_visit = false;
+                    }
                 } else {
-                    needs_visit = false;
+                    visited.insert(other_id, (other_cost, current_id));
                 }
-            } else {
-                visited.insert(other_id, (other_cost, current_id));
-            }
 
-            if needs_visit {
-                let heuristic = neighborhood.heuristic(other_id, goal);
-                next.push(HeuristicElement(
-                    other_id,
-                    other_cost,
-                    other_cost + heuristic,
-                ));
+                if needs_visit {
+                    let heuristic = neighborhood.heuristic(other_id, goal);
+                    self.next.push(HeuristicElement(
+                        other_id,
+                        other_cost,
+                        other_cost + heuristic,
+                    ));
+                }
             }
         }
-    }
 
-    if !visited.contains_key(&goal) {
-        return None;
-    }
-
-    let steps = {
-        let mut steps = vec![];
-        let mut current = goal;
+        // make sure that further calls return the same result
+        self.next.clear();
 
-        while current != start {
-            steps.push(current);
-            let (_, prev) = visited[&current];
-            current = prev;
+        if !visited.contains_key(&goal) {
+            return Poll::Ready(None);
         }
-        steps.push(start);
-        steps.reverse();
-        steps
-    };
 
-    Some(Path::new(steps, visited[&goal].0))
+        let steps = {
+            let mut steps = vec![];
+            let mut current = goal;
+
+            while current != self.start {
+                steps.push(current);
+                let (_, prev) = visited[&current];
+                current = prev;
+            }
+            steps.push(self.start);
+            steps.reverse();
+            if jumps.is_some() {
+                steps = expand_lines(neighborhood, &steps);
+            }
+            steps
+        };
+
+        Poll::Ready(Some(Path::new(steps, visited[&goal].0)))
+    }
 }
 
 #[cfg(test)]
@@ -125,7 +232,15 @@ mod tests {
         let start = (0, 0);
         let goal = (2, 0);
 
-        let path = a_star_search(&neighborhood, |_| true, cost_fn(&grid), start, goal, 40);
+        let path = a_star_search(
+            &neighborhood,
+            |_| true,
+            cost_fn(&grid),
+            start,
+            goal,
+            40,
+            Default::default(),
+        );
 
         assert!(path.is_none());
     }
@@ -155,7 +270,15 @@ mod tests {
 
         let start = (0, 0);
         let goal = (4, 4);
-        let path = a_star_search(&neighborhood, |_| true, cost_fn(&grid), start, goal, 40);
+        let path = a_star_search(
+            &neighborhood,
+            |_| true,
+            cost_fn(&grid),
+            start,
+            goal,
+            40,
+            Default::default(),
+        );
 
         assert!(path.is_some());
         let path = path.unwrap();
@@ -1,4 +1,4 @@
-use super::{Element, Path};
+use super::{Cost, Element, Path};
 use crate::{neighbors::Neighborhood, Point, PointMap, PointSet};
 
 use std::cmp::Ordering;
@@ -45,7 +45,7 @@ pub fn dijkstra_search<N: Neighborhood>(
         if delta_cost < 0 {
             continue;
         }
-        let other_cost = current_cost + delta_cost as usize;
+        let delta_cost = delta_cost as Cost;
 
         all_neighbors.clear();
         neighborhood.get_all_neighbors(current_id, &mut all_neighbors);
@@ -53,9 +53,20 @@ pub fn dijkstra_search<N: Neighborhood>(
             if !valid(other_id) {
                 continue;
             }
-            if get_cost(other_id) < 0 && !remaining_goals.contains(&other_id) {
+            let other_tile_cost = get_cost(other_id);
+            if other_tile_cost < 0 && !remaining_goals.contains(&other_id) {
                 continue;
             }
+            if !neighborhood.is_step_allowed(current_id, other_id, &mut get_cost) {
+                continue;
+            }
+            let step_cost = neighborhood.step_cost(
+                current_id,
+                other_id,
+                delta_cost,
+                other_tile_cost.max(0) as Cost,
+            );
+            let other_cost = current_cost + step_cost;
 
             let mut needs_visit = true;
             if let Some((prev_cost, prev_id)) = visited.get_mut(&other_id) {
@@ -0,0 +1,308 @@
+use crate::{
+    neighbors::{GridSteps, Neighborhood},
+    Point,
+};
+
+type Dir = (isize, isize);
+
+/// [Jump Point Search](https://en.wikipedia.org/wiki/Jump_point_search) for areas of uniform cost.
+///
+/// A Tile is uniform if all open Tiles around it (including diagonals) have the same cost as
+/// itself. Within uniform areas, Paths that only differ in the order of their steps are
+/// symmetric, so only one of them is searched: the one that takes diagonal steps (or horizontal
+/// steps without diagonals) first. Instead of adding the next Tile in every direction, the
+/// search "jumps" in a straight line until it reaches a Tile where other Paths could branch off.
+///
+/// The jumps stop at any Tile that is not uniform, which includes the Tiles next to a change of
+/// cost and next to the goal. Those Tiles are expanded like in a regular A* search.
+pub struct JumpPoints {
+    steps: GridSteps,
+    goal: Point,
+}
+
+impl JumpPoints {
+    pub fn new<N: Neighborhood>(neighborhood: &N, goal: Point) -> Option<JumpPoints> {
+        let steps = neighborhood.grid_steps()?;
+        Some(JumpPoints { steps, goal })
+    }
+
+    /// Writes the Tiles that the search should continue at after reaching `current` from
+    /// `parent` into `target`.
+    ///
+    /// Returns `false` without writing anything if `current` is not uniform, in which case all
+    /// of its neighbors should be visited. Every returned Tile is in a straight or diagonal line
+    /// from `current`, and all the Tiles before it on that line have the cost of `current`.
+    pub fn successors(
+        &self,
+        current: Point,
+        parent: Point,
+        mut valid: impl FnMut(Point) -> bool,
+        mut get_cost: impl FnMut(Point) -> isize,
+        target: &mut Vec<Point>,
+    ) -> bool {
+        let mut tile = Tile {
+            jumps: self,
+            valid: &mut valid,
+            get_cost: &mut get_cost,
+        };
+        let cost = match tile.uniform(current) {
+            Some(cost) => cost,
+            None => return false,
+        };
+        let dir = (
+            (current.0 as isize - parent.0 as isize).signum(),
+            (current.1 as isize - parent.1 as isize).signum(),
+        );
+
+        let mut dirs = [dir; 5];
+        let mut num_dirs = 1;
+        let mut add = |d: Dir| {
+            dirs[num_dirs] = d;
+            num_dirs += 1;
+        };
+        let (dx, dy) = dir;
+        if self.steps.diagonals {
+            if dx != 0 && dy != 0 {
+                add((dx, 0));
+                add((0, dy));
+                if tile.forced(current, (-dx, 0), (-dx, dy)) {
+                    add((-dx, dy));
+                }
+                if tile.forced(current, (0, -dy), (dx, -dy)) {
+                    add((dx, -dy));
+                }
+            } else {
+                let (sx, sy) = (dy, dx); // perpendicular to dir
+                if tile.forced(current, (sx, sy), (sx + dx, sy + dy)) {
+                    add((sx + dx, sy + dy));
+                }
+                if tile.forced(current, (-sx, -sy), (dx - sx, dy - sy)) {
+                    add((dx - sx, dy - sy));
+                }
+            }
+        } else if dx != 0 {
+            add((0, 1));
+            add((0, -1));
+        } else {
+            for sx in [1, -1] {
+                if tile.forced(current, (sx, -dy), (sx, 0)) {
+                    add((sx, 0));
+                }
+            }
+        }
+
+        for &d in &dirs[..num_dirs] {
+            if let Some(next) = tile.jump(current, d, cost) {
+                target.push(next);
+            }
+        }
+        true
+    }
+
+    fn offset(&self, (x, y): Point, (dx, dy): Dir) -> Option<Point> {
+        let x = x.checked_add_signed(dx)?;
+        let y = y.checked_add_signed(dy)?;
+        (x < self.steps.size.0 && y < self.steps.size.1).then_some((x, y))
+    }
+}
+
+/// The cost function and limits of a search, bundled together for the jumps
+struct Tile<'a, V, C> {
+    jumps: &'a JumpPoints,
+    valid: &'a mut V,
+    get_cost: &'a mut C,
+}
+
+impl<V: FnMut(Point) -> bool, C: FnMut(Point) -> isize> Tile<'_, V, C> {
+    /// The cost of the Tile at `point` + `dir`, or `-1` if it can't be entered
+    fn cost(&mut self, point: Point, dir: Dir) -> isize {
+        match self.jumps.offset(point, dir) {
+            Some(p) if (self.valid)(p) => (self.get_cost)(p),
+            _ => -1,
+        }
+    }
+
+    /// The cost of `point`, if it is uniform
+    fn uniform(&mut self, point: Point) -> Option<isize> {
+        let cost = (self.get_cost)(point);
+        if cost < 0 {
+            return None;
+        }
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if self.jumps.offset(point, (dx, dy)) == Some(self.jumps.goal) {
+                    return None;
+                }
+                let other = self.cost(point, (dx, dy));
+                if other >= 0 && other != cost {
+                    return None;
+                }
+            }
+        }
+        Some(cost)
+    }
+
+    /// Checks if `point` + `open` is a forced neighbor because `point` + `blocked` is solid
+    fn forced(&mut self, point: Point, blocked: Dir, open: Dir) -> bool {
+        self.cost(point, blocked) < 0 && self.cost(point, open) >= 0
+    }
+
+    /// Moves from `start` in `dir` until reaching a Tile where the search has to branch off.
+    ///
+    /// `start` must be uniform with the given `cost`.
+    fn jump(&mut self, start: Point, dir: Dir, cost: isize) -> Option<Point> {
+        let (dx, dy) = dir;
+        let diagonals = self.jumps.steps.diagonals;
+        let mut current = start;
+        loop {
+            current = self.jumps.offset(current, dir)?;
+            if !(self.valid)(current) {
+                return None;
+            }
+            if current == self.jumps.goal {
+                return Some(current);
+            }
+            if self.uniform(current) != Some(cost) {
+                return ((self.get_cost)(current) >= 0).then_some(current);
+            }
+
+            if diagonals {
+                if dx != 0 && dy != 0 {
+                    if self.forced(current, (-dx, 0), (-dx, dy))
+                        || self.forced(current, (0, -dy), (dx, -dy))
+                        || self.jump(current, (dx, 0), cost).is_some()
+                        || self.jump(current, (0, dy), cost).is_some()
+                    {
+                        return Some(current);
+                    }
+                } else {
+                    let (sx, sy) = (dy, dx);
+                    if self.forced(current, (sx, sy), (sx + dx, sy + dy))
+                        || self.forced(current, (-sx, -sy), (dx - sx, dy - sy))
+                    {
+                        return Some(current);
+                    }
+                }
+            } else if dx != 0 {
+                if self.jump(current, (0, 1), cost).is_some()
+                    || self.jump(current, (0, -1), cost).is_some()
+                {
+                    return Some(current);
+                }
+            } else if self.forced(current, (1, -dy), (1, 0))
+                || self.forced(current, (-1, -dy), (-1, 0))
+            {
+                return Some(current);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{a_star_search, AStarSearch, SearchOptions};
+    use crate::{prelude::*, Point};
+    use std::task::Poll;
+
+    const JUMP_POINTS: SearchOptions = SearchOptions { jump_points: true };
+
+    // walls, a room with a single entrance and areas of different cost
+    #[rustfmt::skip]
+    const TILES: [&str; 8] = [
+        "..........",
+        "..####....",
+        "..#..#.22.",
+        "..#..#.22.",
+        ".....#....",
+        "######.##.",
+        "..........",
+        "....3.....",
+    ];
+
+    fn cost_fn((x, y): Point) -> isize {
+        match TILES[y].as_bytes()[x] {
+            b'#' => -1,
+            b'.' => 1,
+            c => (c - b'0') as isize,
+        }
+    }
+
+    const QUERIES: [(Point, Point); 6] = [
+        ((0, 0), (9, 0)),
+        ((0, 0), (0, 7)),
+        ((3, 2), (9, 7)),
+        ((8, 2), (0, 7)),
+        ((9, 7), (4, 3)),
+        ((0, 4), (0, 4)),
+    ];
+
+    /// Checks that the jumps find Paths that cost as much as the regular search, and returns
+    /// their costs
+    fn check<N: Neighborhood>(neighborhood: N) -> Vec<usize> {
+        let mut costs = vec![];
+        let mut neighbors = vec![];
+        for (start, goal) in QUERIES {
+            let expected = a_star_search(
+                &neighborhood,
+                |_| true,
+                cost_fn,
+                start,
+                goal,
+                40,
+                Default::default(),
+            );
+            let path = a_star_search(
+                &neighborhood,
+                |_| true,
+                cost_fn,
+                start,
+                goal,
+                40,
+                JUMP_POINTS,
+            );
+            let (path, expected) = (path.unwrap(), expected.unwrap());
+            assert_eq!(path.cost(), expected.cost(), "{:?} -> {:?}", start, goal);
+            costs.push(path.cost());
+            if start == goal {
+                continue;
+            }
+
+            let steps = path.iter().copied().collect::<Vec<_>>();
+            assert_eq!(steps.first(), Some(&start));
+            assert_eq!(steps.last(), Some(&goal));
+            let cost: isize = steps[..steps.len() - 1].iter().map(|p| cost_fn(*p)).sum();
+            assert_eq!(cost as usize, path.cost());
+            for step in steps.windows(2) {
+                neighbors.clear();
+                neighborhood.get_all_neighbors(step[0], &mut neighbors);
+                assert!(neighbors.contains(&step[1]));
+            }
+        }
+        costs
+    }
+
+    #[test]
+    fn same_cost() {
+        let (width, height) = (TILES[0].len(), TILES.len());
+        let costs = check(ManhattanNeighborhood::new(width, height));
+        assert_eq!(costs, [9, 19, 23, 15, 23, 0]);
+        let costs = check(MooreNeighborhood::new(width, height));
+        assert_eq!(costs, [9, 16, 17, 11, 17, 0]);
+    }
+
+    #[test]
+    fn fewer_visits() {
+        let size = 64;
+        let visits = |neighborhood: &MooreNeighborhood, options| {
+            let mut search = AStarSearch::new((0, 0), (size - 1, 40), 40, options);
+            let mut budget = usize::MAX;
+            let result = search.step(neighborhood, |_| true, |_| 1, &mut budget);
+            assert!(matches!(result, Poll::Ready(Some(_))));
+            usize::MAX - budget
+        };
+        let neighborhood = MooreNeighborhood::new(size, size);
+        let regular = visits(&neighborhood, Default::default());
+        let jump_points = visits(&neighborhood, JUMP_POINTS);
+        assert!(jump_points * 10 < regular, "{} vs {}", jump_points, regular);
+    }
+}
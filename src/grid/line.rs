@@ -0,0 +1,160 @@
+use super::Cost;
+use crate::{neighbors::Neighborhood, Point};
+
+/// Walks along the straight line from `start` to `end` and calls `f` with every Tile after
+/// `start`, until `f` returns `false`.
+///
+/// The line only takes steps that are allowed by the Neighborhood: diagonal steps are split
+/// into two straight steps if the Neighborhood doesn't allow them.
+///
+/// Returns `false` if `f` returned `false`.
+pub fn walk_line<N: Neighborhood>(
+    neighborhood: &N,
+    start: Point,
+    end: Point,
+    mut f: impl FnMut(Point) -> bool,
+) -> bool {
+    let (dx, dy) = (
+        end.0.abs_diff(start.0) as isize,
+        end.1.abs_diff(start.1) as isize,
+    );
+    let sx = if end.0 > start.0 { 1 } else { -1 };
+    let sy = if end.1 > start.1 { 1 } else { -1 };
+    let step = |p: Point, x: bool, y: bool| {
+        (
+            if x { (p.0 as isize + sx) as usize } else { p.0 },
+            if y { (p.1 as isize + sy) as usize } else { p.1 },
+        )
+    };
+
+    let mut neighbors = vec![];
+    let mut current = start;
+    let mut error = dx - dy;
+    while current != end {
+        let double = 2 * error;
+        let step_x = double > -dy;
+        let step_y = double < dx;
+        if step_x {
+            error -= dy;
+        }
+        if step_y {
+            error += dx;
+        }
+        if step_x && step_y {
+            let diagonal = step(current, true, true);
+            neighbors.clear();
+            neighborhood.get_all_neighbors(current, &mut neighbors);
+            if !neighbors.contains(&diagonal) {
+                // take the straight step along the longer axis first
+                let straight = step(current, dx >= dy, dx < dy);
+                if !f(straight) {
+                    return false;
+                }
+            }
+            current = diagonal;
+        } else {
+            current = step(current, step_x, step_y);
+        }
+        if !f(current) {
+            return false;
+        }
+    }
+    true
+}
+
+/// The cost of walking along the straight line from `start` to `end`, as calculated by
+/// [`walk_line`].
+///
+/// Like with any Path, this is the sum of the [`step_cost`](Neighborhood::step_cost)s, which
+/// includes the cost of every Tile except `end`. Returns `None` if any of those Tiles is solid,
+/// or if any step isn't [allowed](Neighborhood::is_step_allowed).
+pub fn line_cost<N: Neighborhood>(
+    neighborhood: &N,
+    start: Point,
+    end: Point,
+    mut get_cost: impl FnMut(Point) -> isize,
+) -> Option<Cost> {
+    let mut previous = (start, get_cost(start));
+    let mut cost = 0;
+    let clear = walk_line(neighborhood, start, end, |p| {
+        let (previous_pos, c) = previous;
+        if c < 0 || !neighborhood.is_step_allowed(previous_pos, p, &mut get_cost) {
+            return false;
+        }
+        let next = get_cost(p);
+        cost += neighborhood.step_cost(previous_pos, p, c as Cost, next.max(0) as Cost);
+        previous = (p, next);
+        true
+    });
+    clear.then_some(cost)
+}
+
+/// Connects consecutive `waypoints` with the Tiles of the straight lines between them.
+pub fn expand_lines<N: Neighborhood>(neighborhood: &N, waypoints: &[Point]) -> Vec<Point> {
+    let mut steps = Vec::with_capacity(waypoints.len());
+    steps.extend(waypoints.first());
+    for pair in waypoints.windows(2) {
+        walk_line(neighborhood, pair[0], pair[1], |p| {
+            steps.push(p);
+            true
+        });
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn line<N: Neighborhood>(neighborhood: &N, start: Point, end: Point) -> Vec<Point> {
+        let mut ret = vec![];
+        walk_line(neighborhood, start, end, |p| {
+            ret.push(p);
+            true
+        });
+        ret
+    }
+
+    #[test]
+    fn lines() {
+        let moore = MooreNeighborhood::new(10, 10);
+        assert_eq!(line(&moore, (0, 0), (3, 1)), [(1, 0), (2, 1), (3, 1)]);
+        assert_eq!(line(&moore, (3, 3), (0, 0)), [(2, 2), (1, 1), (0, 0)]);
+        assert_eq!(line(&moore, (2, 2), (2, 2)), []);
+
+        let manhattan = ManhattanNeighborhood::new(10, 10);
+        assert_eq!(
+            line(&manhattan, (0, 0), (3, 1)),
+            [(1, 0), (2, 0), (2, 1), (3, 1)]
+        );
+        assert_eq!(
+            line(&manhattan, (3, 3), (1, 1)),
+            [(2, 3), (2, 2), (1, 2), (1, 1)]
+        );
+
+        for end in [(9, 4), (0, 9), (5, 5), (7, 0)] {
+            for start in [(0, 0), (4, 6)] {
+                let mut prev = start;
+                let mut neighbors = vec![];
+                for p in line(&manhattan, start, end) {
+                    neighbors.clear();
+                    manhattan.get_all_neighbors(prev, &mut neighbors);
+                    assert!(neighbors.contains(&p));
+                    prev = p;
+                }
+                assert_eq!(prev, end);
+            }
+        }
+    }
+
+    #[test]
+    fn blocked() {
+        let moore = MooreNeighborhood::new(5, 5);
+        let wall = |(x, y): Point| if x == 2 && y < 4 { -1 } else { 1 };
+        assert_eq!(line_cost(&moore, (0, 0), (4, 0), wall), None);
+        assert_eq!(line_cost(&moore, (0, 4), (4, 4), wall), Some(4));
+        // the end may be solid
+        assert_eq!(line_cost(&moore, (0, 0), (2, 0), wall), Some(2));
+    }
+}
@@ -1,13 +1,28 @@
 mod a_star;
-pub use a_star::a_star_search;
+pub use a_star::{a_star_search, AStarSearch};
 
 mod dijkstra;
 pub use dijkstra::dijkstra_search;
 
+mod jump_points;
+use jump_points::JumpPoints;
+
+mod line;
+use line::expand_lines;
+pub use line::line_cost;
+
 pub use crate::path::{Cost, Path};
 
 use std::cmp::Ordering;
 
+/// Options for the searches in this module, see the options of the same name in
+/// [`PathCacheConfig`](crate::PathCacheConfig)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// only used by [`a_star_search`]
+    pub jump_points: bool,
+}
+
 #[derive(PartialEq, Eq)]
 pub struct HeuristicElement<Id>(pub Id, pub Cost, pub Cost);
 impl<Id: Eq> PartialOrd for HeuristicElement<Id> {
@@ -93,14 +93,15 @@
 //!
 //! let mut pathfinding = PathCache::new(
 //!     (width, height),   // the size of the Grid
-//!     |(x, y)| cost_map[grid[y][x]],   // get the cost for walking over a Tile
+//!     &|(x, y): (usize, usize)| cost_map[grid[y][x]],   // get the cost for walking over a Tile
 //!     ManhattanNeighborhood::new(width, height),   // the Neighborhood
 //!     PathCacheConfig::with_chunk_size(3),   // config
 //! );
 //! ```
 //! The [`PathCache`] never takes the actual Grid, to allow for any storage format to be used
-//! (`Array`, `Vec`, `HashMap`, `kd-tree`, ...). Instead, it takes a callback function that
-//! indicates, how "expensive" walking across a Tile is (negative numbers for solid obstacles).
+//! (`Array`, `Vec`, `HashMap`, `kd-tree`, ...). Instead, it takes a [`CostGrid`], usually a
+//! callback function that indicates, how "expensive" walking across a Tile is (negative numbers
+//! for solid obstacles).
 //!
 //! Unfortunately, it is necessary to provide this function to every method of PathCache, since
 //! storing it would make the Grid immutable. See also [Updating the PathCache](#updating-the-pathcache).
@@ -129,7 +130,7 @@
 //!     (width, height), // the size of the Grid
 //!
 //!     // simply call the creator function to take a reference of the Grid
-//!     cost_fn(&grid),
+//!     &cost_fn(&grid),
 //!     // ...
 //! #     ManhattanNeighborhood::new(width, height), // the Neighborhood
 //! #     PathCacheConfig::with_chunk_size(3), // config
@@ -153,7 +154,7 @@
 //! # }
 //! # let mut pathfinding = PathCache::new(
 //! #     (width, height),
-//! #     cost_fn(&grid),
+//! #     &cost_fn(&grid),
 //! #     ManhattanNeighborhood::new(width, height),
 //! #     PathCacheConfig::with_chunk_size(3),
 //! # );
@@ -165,8 +166,8 @@
 //! let path = pathfinding.find_path(
 //!     start,
 //!     goal,
-//!     cost_fn(&grid),
-//! );
+//!     &cost_fn(&grid),
+//! ).unwrap();
 //!
 //! assert!(path.is_some());
 //! let path = path.unwrap();
@@ -191,7 +192,7 @@
 //! # }
 //! # let mut pathfinding = PathCache::new(
 //! #     (width, height),
-//! #     cost_fn(&grid),
+//! #     &cost_fn(&grid),
 //! #     ManhattanNeighborhood::new(width, height),
 //! #     PathCacheConfig::with_chunk_size(3),
 //! # );
@@ -203,8 +204,8 @@
 //! let paths = pathfinding.find_paths(
 //!     start,
 //!     &goals,
-//!     cost_fn(&grid),
-//! );
+//!     &cost_fn(&grid),
+//! ).unwrap();
 //!
 //! // (4, 4) is reachable
 //! assert!(paths.contains_key(&goals[0]));
@@ -224,11 +225,11 @@
 //! - Total Length of the Path: [`path.length()`](internals::AbstractPath::length)
 //!   - Correct for this Path, may be slightly longer than the optimal Path
 //!   - The length is simply returned; `length()` does no calculations
-//! - Next Position: [`path.next()`](internals::AbstractPath::next) | [`path.safe_next(cost_fn)`](internals::AbstractPath::safe_next)
+//! - Next Position: [`path.next()`](internals::AbstractPath::next) | [`path.safe_next(&cost_fn)`](internals::AbstractPath::safe_next)
 //!   - [`safe_next`](internals::AbstractPath::safe_next) is needed if [`config.cache_paths`](crate::PathCacheConfig::cache_paths) is set to `false`
 //!   - can be called several times to iterate Path
 //!   - path implements `Iterator<Item = (usize, usize)>`
-//! - Entire Path: `path.collect::<Vec<_>>()` | [`path.resolve(cost_fn)`](internals::AbstractPath::resolve)
+//! - Entire Path: `path.collect::<Vec<_>>()` | [`path.resolve(&cost_fn)`](internals::AbstractPath::resolve)
 //!   - [`resolve`](internals::AbstractPath::resolve) is needed if [`config.cache_paths`](crate::PathCacheConfig::cache_paths) is set to `false`
 //!   - Returns a `Vec<(usize, usize)>`
 //!
@@ -250,7 +251,7 @@
 //! # }
 //! # let mut pathfinding = PathCache::new(
 //! #     (width, height),
-//! #     cost_fn(&grid),
+//! #     &cost_fn(&grid),
 //! #     ManhattanNeighborhood::new(width, height),
 //! #     PathCacheConfig::with_chunk_size(3),
 //! # );
@@ -270,8 +271,8 @@
 //! let mut path = pathfinding.find_path(
 //!     player.pos,
 //!     goal,
-//!     cost_fn(&grid),
-//! ).unwrap();
+//!     &cost_fn(&grid),
+//! ).unwrap().unwrap();
 //!
 //! player.move_to(path.next().unwrap());
 //! assert_eq!(player.pos, (0, 1));
@@ -307,20 +308,20 @@
 //! # }
 //! # let mut pathfinding = PathCache::new(
 //! #     (width, height),
-//! #     cost_fn(&grid),
+//! #     &cost_fn(&grid),
 //! #     ManhattanNeighborhood::new(width, height),
 //! #     PathCacheConfig::with_chunk_size(3),
 //! # );
 //! #
 //! let (start, goal) = ((0, 0), (2, 0));
 //!
-//! let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+//! let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
 //! assert!(path.is_none()); // from previous example
 //!
 //! // Clear a way to the goal
 //! grid[1][2] = 0; // at (2, 1): the wall below the goal
 //!
-//! let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+//! let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
 //! assert!(path.is_some()); // there should be a Path now!
 //! ```
 //! [`tiles_changed`](PathCache::tiles_changed) must be called with all changed Tiles:
@@ -339,14 +340,14 @@
 //! # }
 //! # let mut pathfinding = PathCache::new(
 //! #     (width, height),
-//! #     cost_fn(&grid),
+//! #     &cost_fn(&grid),
 //! #     ManhattanNeighborhood::new(width, height),
 //! #     PathCacheConfig::with_chunk_size(3),
 //! # );
 //! #
 //! let (start, goal) = ((0, 0), (2, 0));
 //!
-//! let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+//! let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
 //! assert!(path.is_none());
 //!
 //! // Clear a way to the goal
@@ -354,10 +355,10 @@
 //!
 //! pathfinding.tiles_changed(
 //!     &[(2, 1)],
-//!     cost_fn(&grid),
+//!     &cost_fn(&grid),
 //! );
 //!
-//! let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+//! let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
 //! assert!(path.is_some());
 //! ```
 //! `tiles_changed` takes a slice of Points, and it is recommended to bundle changes together for
@@ -381,7 +382,7 @@
 //! # }
 //!
 //! let mut pathfinding = PathCache::new(
-//!     (width, height), // the size of the Grid
+//!     (width, height), &// the size of the Grid
 //!     cost_fn(&grid), // get the cost for walking over a Tile
 //!     ManhattanNeighborhood::new(width, height), // the Neighborhood
 //!     PathCacheConfig {
@@ -426,10 +427,16 @@ type NodeIDMap<V> = hashbrown::HashMap<NodeID, V>;
 type NodeIDSet = hashbrown::HashSet<NodeID>;
 
 mod path_cache;
-pub use self::path_cache::{PathCache, PathCacheConfig};
+pub use self::path_cache::{
+    GridPathCache, ObstacleHandle, Obstacles, PathCache, PathCacheConfig, Replanner,
+    ReservationTable,
+};
 
 mod path;
 
+mod cost_grid;
+pub use cost_grid::{CostGrid, GridMismatch, WithMoves};
+
 mod utils;
 pub(crate) use utils::*;
 
@@ -440,14 +447,18 @@ mod grid;
 
 /// Internal stuff that is returned by other function
 pub mod internals {
-    pub use crate::path::AbstractPath;
-    pub use crate::path_cache::{CacheInspector, NodeInspector};
+    pub use crate::path::{AbstractPath, SmoothedPath};
+    pub use crate::path_cache::{CacheInspector, DistanceMap, FlowField, NodeInspector, PathQuery};
 }
 
 /// The prelude for this crate.
 pub mod prelude {
     pub use crate::{
-        neighbors::{ManhattanNeighborhood, MooreNeighborhood, Neighborhood},
-        PathCache, PathCacheConfig,
+        neighbors::{
+            DiagonalRule, HexLayout, HexNeighborhood, ManhattanNeighborhood, MooreNeighborhood,
+            Neighborhood,
+        },
+        CostGrid, GridMismatch, GridPathCache, Obstacles, PathCache, PathCacheConfig, Replanner,
+        ReservationTable, WithMoves,
     };
 }
@@ -1,6 +1,6 @@
 //! A crate with the most common Neighborhoods
 
-use crate::Point;
+use crate::{path::Cost, Point};
 use std::fmt::Debug;
 
 /// Defines how a Path can move along the Grid.
@@ -15,9 +15,10 @@ use std::fmt::Debug;
 ///
 /// The most common implementations of this Trait are already provided by this Module:
 /// - [`ManhattanNeighborhood`] for Agents that can move
-/// up, down, left or right
+///   up, down, left or right
 /// - [`MooreNeighborhood`] for Agents that can move
-/// up, down, left, right, as well as the 4 diagonals (up-right, ...)
+///   up, down, left, right, as well as the 4 diagonals (up-right, ...)
+/// - [`HexNeighborhood`] for Agents that move on a hex Grid
 pub trait Neighborhood: Clone + Debug {
     /// Provides all the Neighbors of a Point.
     ///
@@ -36,6 +37,111 @@ pub trait Neighborhood: Clone + Debug {
     /// If there is no proper way of calculation how long it takes, simply return 0. This will
     /// increase the time it takes to calculate the Path, but at least it will always be correct.
     fn heuristic(&self, point: Point, goal: Point) -> usize;
+
+    /// Describes the Neighborhood as steps to adjacent Tiles on a rectangular Grid, if that is
+    /// what it does.
+    ///
+    /// This allows searches to skip over areas of uniform cost, see
+    /// [`jump_points`](crate::PathCacheConfig::jump_points). Only return `Some` if
+    /// `get_all_neighbors` returns exactly the described steps that stay within the Grid.
+    ///
+    /// The default implementation returns `None`, which means that every Tile is visited.
+    fn grid_steps(&self) -> Option<GridSteps> {
+        None
+    }
+
+    /// Returns `true` if the Grid wraps around horizontally, so that the leftmost and rightmost
+    /// column are next to each other.
+    ///
+    /// `get_all_neighbors` then has to return the Tiles on the other side of that seam as
+    /// neighbors, and `heuristic` has to use the shorter way around. The PathCache uses this to
+    /// treat the first and last column of Chunks as neighbors.
+    ///
+    /// The default implementation returns `false`.
+    fn wraps_horizontally(&self) -> bool {
+        false
+    }
+
+    /// The cost of a step from `from` to its neighbor `to`, where `from_cost` and `to_cost` are
+    /// the costs of the two Tiles.
+    ///
+    /// The default implementation returns `from_cost`: every step costs as much as the Tile it
+    /// leaves, so the cost of a Path is the sum of all its Tiles except the last one.
+    ///
+    /// Neighborhoods with steps of different lengths can make the longer steps more expensive,
+    /// like [`MooreNeighborhood::new_octile`] does for diagonals. The cost of the Tile that is
+    /// left has to be multiplied with [`cost_scale`](Neighborhood::cost_scale), and anything
+    /// added on top of that has to be the same for the step back:
+    /// ```no_code
+    /// step_cost(a, b, ca, cb) - ca * cost_scale() == step_cost(b, a, cb, ca) - cb * cost_scale()
+    /// ```
+    /// That way, a Path and its reverse only differ in the cost of their first and last Tile,
+    /// which the PathCache relies on. The [`heuristic`](Neighborhood::heuristic) has to be
+    /// scaled the same way.
+    fn step_cost(&self, from: Point, to: Point, from_cost: Cost, to_cost: Cost) -> Cost {
+        let _ = (from, to, to_cost);
+        from_cost
+    }
+
+    /// The factor by which [`step_cost`](Neighborhood::step_cost) multiplies the cost of the
+    /// Tile that is left.
+    ///
+    /// The default implementation returns `1`.
+    fn cost_scale(&self) -> Cost {
+        1
+    }
+
+    /// Checks if the step from `from` to its neighbor `to` is allowed, apart from `to` not
+    /// being solid.
+    ///
+    /// This is for rules that depend on the Tiles around a step, like the
+    /// [`DiagonalRule`]s of the [`MooreNeighborhood`]. `get_cost` returns the cost of any Tile
+    /// on the Grid, with solid Tiles being `< 0` as usual.
+    ///
+    /// The result has to be the same for the step back from `to` to `from`, and may only depend
+    /// on which of the Tiles that are neighbors of both `from` and `to` are solid. That way the
+    /// PathCache knows which steps to check again when Tiles change, and can check steps
+    /// between its Chunks without calling the cost function.
+    ///
+    /// The default implementation returns `true`.
+    fn is_step_allowed(
+        &self,
+        from: Point,
+        to: Point,
+        get_cost: impl FnMut(Point) -> isize,
+    ) -> bool {
+        let _ = (from, to, get_cost);
+        true
+    }
+
+    /// Describes the kind of the Neighborhood and everything that it depends on, like the size
+    /// of the Grid.
+    ///
+    /// [`PathCache::read_from`](crate::PathCache::read_from) only accepts Neighborhoods with the
+    /// same description as the one that was written, so it has to stay the same across versions
+    /// and platforms.
+    ///
+    /// The default implementation returns an empty description, which matches every other
+    /// Neighborhood of the same type.
+    fn describe(&self) -> Vec<u64> {
+        vec![]
+    }
+}
+
+/// The cost of a straight step of a [`MooreNeighborhood::new_octile`], per cost of the Tile
+const OCTILE_STRAIGHT: Cost = 10;
+/// The extra cost of a diagonal step of a [`MooreNeighborhood::new_octile`], per cost of each
+/// of the two Tiles
+const OCTILE_DIAGONAL_EXTRA: Cost = 2;
+
+/// The steps of a [`Neighborhood`] on a rectangular Grid, see [`Neighborhood::grid_steps`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridSteps {
+    /// The size of the Grid
+    pub size: (usize, usize),
+    /// `true` if the steps include the 4 diagonals like in [`MooreNeighborhood`], `false` if
+    /// they are only the 4 cardinal directions like in [`ManhattanNeighborhood`]
+    pub diagonals: bool,
 }
 
 /// A Neighborhood for Agents moving along the 4 cardinal directions.
@@ -55,6 +161,7 @@ pub trait Neighborhood: Clone + Debug {
 pub struct ManhattanNeighborhood {
     width: usize,
     height: usize,
+    wrap: bool,
 }
 
 impl ManhattanNeighborhood {
@@ -62,7 +169,23 @@ impl ManhattanNeighborhood {
     ///
     /// `width` and `height` are the size of the Grid to move on.
     pub fn new(width: usize, height: usize) -> ManhattanNeighborhood {
-        ManhattanNeighborhood { width, height }
+        ManhattanNeighborhood {
+            width,
+            height,
+            wrap: false,
+        }
+    }
+
+    /// Creates a new ManhattanNeighborhood on a Grid that wraps around horizontally.
+    ///
+    /// Stepping left from `x = 0` leads to `x = width - 1` and vice versa. See
+    /// [`wraps_horizontally`](Neighborhood::wraps_horizontally).
+    pub fn new_wrapping(width: usize, height: usize) -> ManhattanNeighborhood {
+        ManhattanNeighborhood {
+            width,
+            height,
+            wrap: true,
+        }
     }
 }
 
@@ -74,26 +197,64 @@ impl Neighborhood for ManhattanNeighborhood {
         static ALL_DELTAS: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
 
         for (dx, dy) in ALL_DELTAS.iter() {
-            let x = point.0 as isize + dx;
+            let mut x = point.0 as isize + dx;
             let y = point.1 as isize + dy;
+            if self.wrap {
+                x = x.rem_euclid(width as isize);
+            }
             if x >= 0 && x < width as isize && y >= 0 && y < height as isize {
                 target.push((x as usize, y as usize))
             }
         }
     }
     fn heuristic(&self, point: Point, goal: Point) -> usize {
-        let diff_0 = if goal.0 > point.0 {
-            goal.0 - point.0
-        } else {
-            point.0 - goal.0
-        };
-        let diff_1 = if goal.1 > point.1 {
-            goal.1 - point.1
-        } else {
-            point.1 - goal.1
-        };
+        let mut diff_0 = goal.0.abs_diff(point.0);
+        if self.wrap {
+            diff_0 = diff_0.min(self.width - diff_0);
+        }
+        let diff_1 = goal.1.abs_diff(point.1);
         diff_0 + diff_1
     }
+    fn grid_steps(&self) -> Option<GridSteps> {
+        // jumps don't cross the seam
+        (!self.wrap).then_some(GridSteps {
+            size: (self.width, self.height),
+            diagonals: false,
+        })
+    }
+    fn wraps_horizontally(&self) -> bool {
+        self.wrap
+    }
+    fn describe(&self) -> Vec<u64> {
+        vec![0, self.width as u64, self.height as u64, self.wrap as u64]
+    }
+}
+
+/// Decides when a [`MooreNeighborhood`] allows diagonal steps past solid Tiles.
+///
+/// A diagonal step passes the two Tiles that are next to both its start and its end. If both of
+/// them are solid, the step slips through the gap between two walls that touch at a corner.
+///
+/// ```no_code
+/// A: Agent, #: solid, o: reachable in one step, x: not reachable in one step
+///             Always   NoCorners   NoSqueezing
+/// one wall:   A #      A #         A #
+///             . o      . x         . o
+///
+/// two walls:  A #      A #         A #
+///             # o      # x         # x
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DiagonalRule {
+    /// Diagonal steps are always allowed
+    #[default]
+    Always,
+    /// Diagonal steps are only allowed if neither of the Tiles they pass is solid, so Paths
+    /// never cut corners
+    NoCorners,
+    /// Diagonal steps are only allowed if at least one of the Tiles they pass is not solid, so
+    /// Paths can cut corners, but never squeeze between two solid Tiles
+    NoSqueezing,
 }
 
 /// A Neighborhood for Agents moving along the 4 cardinal directions and the 4 diagonals.
@@ -113,6 +274,9 @@ impl Neighborhood for ManhattanNeighborhood {
 pub struct MooreNeighborhood {
     width: usize,
     height: usize,
+    wrap: bool,
+    octile: bool,
+    diagonals: DiagonalRule,
 }
 
 impl MooreNeighborhood {
@@ -120,7 +284,103 @@ impl MooreNeighborhood {
     ///
     /// `width` and `height` are the size of the Grid to move on.
     pub fn new(width: usize, height: usize) -> MooreNeighborhood {
-        MooreNeighborhood { width, height }
+        MooreNeighborhood {
+            width,
+            height,
+            wrap: false,
+            octile: false,
+            diagonals: DiagonalRule::Always,
+        }
+    }
+
+    /// Creates a new MooreNeighborhood on a Grid that wraps around horizontally.
+    ///
+    /// Stepping left from `x = 0` leads to `x = width - 1` and vice versa. See
+    /// [`wraps_horizontally`](Neighborhood::wraps_horizontally).
+    pub fn new_wrapping(width: usize, height: usize) -> MooreNeighborhood {
+        MooreNeighborhood {
+            width,
+            height,
+            wrap: true,
+            octile: false,
+            diagonals: DiagonalRule::Always,
+        }
+    }
+
+    /// Creates a new MooreNeighborhood where diagonal steps cost about `1.4` times as much as
+    /// straight steps.
+    ///
+    /// Without this, a diagonal step costs the same as a straight one, so Paths take more
+    /// diagonals than necessary and their cost doesn't match the distance they travel.
+    ///
+    /// To keep the costs whole numbers, all costs are multiplied by `10`: a straight step costs
+    /// `10` times the cost of the Tile it leaves, and a diagonal step costs `12` times that plus
+    /// `2` times the cost of the Tile it enters, which is `14` times the cost for Tiles of the
+    /// same cost. The heuristic is the matching octile distance. See
+    /// [`step_cost`](Neighborhood::step_cost).
+    ///
+    /// ## Examples
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// let neighborhood = MooreNeighborhood::new_octile(5, 5);
+    /// assert_eq!(neighborhood.step_cost((0, 0), (1, 0), 1, 1), 10);
+    /// assert_eq!(neighborhood.step_cost((0, 0), (1, 1), 1, 1), 14);
+    /// assert_eq!(neighborhood.heuristic((0, 0), (4, 2)), 48);
+    ///
+    /// let pathfinding = PathCache::new(
+    ///     (5, 5),
+    ///     &|_: (usize, usize)| 1,
+    ///     neighborhood,
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    /// let path = pathfinding.find_path((0, 0), (4, 2), &|_: (usize, usize)| 1).unwrap().unwrap();
+    /// assert_eq!(path.cost(), 48);
+    /// ```
+    pub fn new_octile(width: usize, height: usize) -> MooreNeighborhood {
+        MooreNeighborhood {
+            width,
+            height,
+            wrap: false,
+            octile: true,
+            diagonals: DiagonalRule::Always,
+        }
+    }
+}
+
+impl MooreNeighborhood {
+    /// Changes when diagonal steps past solid Tiles are allowed. The default is
+    /// [`DiagonalRule::Always`].
+    ///
+    /// Any rule other than that disables [`jump_points`](crate::PathCacheConfig::jump_points).
+    ///
+    /// ## Examples
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// // two walls that touch at a corner
+    /// let grid = [
+    ///     [1, -1],
+    ///     [-1, 1],
+    /// ];
+    /// let cost_fn = |(x, y): (usize, usize)| grid[y][x];
+    ///
+    /// let neighborhood = MooreNeighborhood::new(2, 2);
+    /// let pathfinding =
+    ///     PathCache::new((2, 2), &cost_fn, neighborhood, PathCacheConfig::with_chunk_size(1));
+    /// assert!(pathfinding.find_path((0, 0), (1, 1), &cost_fn).unwrap().is_some());
+    ///
+    /// let neighborhood = neighborhood.with_diagonal_rule(DiagonalRule::NoSqueezing);
+    /// let pathfinding =
+    ///     PathCache::new((2, 2), &cost_fn, neighborhood, PathCacheConfig::with_chunk_size(1));
+    /// assert!(pathfinding.find_path((0, 0), (1, 1), &cost_fn).unwrap().is_none());
+    /// ```
+    pub fn with_diagonal_rule(mut self, rule: DiagonalRule) -> MooreNeighborhood {
+        self.diagonals = rule;
+        self
+    }
+
+    /// When diagonal steps past solid Tiles are allowed
+    pub fn diagonal_rule(&self) -> DiagonalRule {
+        self.diagonals
     }
 }
 
@@ -132,25 +392,195 @@ impl Neighborhood for MooreNeighborhood {
         static ALL_DELTAS: [(isize, isize); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
 
         for (dx, dy) in ALL_DELTAS.iter() {
-            let x = point.0 as isize + dx;
+            let mut x = point.0 as isize + dx;
             let y = point.1 as isize + dy;
+            if self.wrap {
+                x = x.rem_euclid(width as isize);
+            }
             if x >= 0 && x < width as isize && y >= 0 && y < height as isize {
                 target.push((x as usize, y as usize))
             }
         }
     }
     fn heuristic(&self, point: Point, goal: Point) -> usize {
-        let diff_0 = if goal.0 > point.0 {
-            goal.0 - point.0
+        let mut diff_0 = goal.0.abs_diff(point.0);
+        if self.wrap {
+            diff_0 = diff_0.min(self.width - diff_0);
+        }
+        let diff_1 = goal.1.abs_diff(point.1);
+        if self.octile {
+            let (min, max) = (diff_0.min(diff_1), diff_0.max(diff_1));
+            return max * OCTILE_STRAIGHT + min * 2 * OCTILE_DIAGONAL_EXTRA;
+        }
+        diff_0.max(diff_1)
+    }
+    fn grid_steps(&self) -> Option<GridSteps> {
+        // jumps don't cross the seam and expect diagonals to cut corners
+        (!self.wrap && self.diagonals == DiagonalRule::Always).then_some(GridSteps {
+            size: (self.width, self.height),
+            diagonals: true,
+        })
+    }
+    fn wraps_horizontally(&self) -> bool {
+        self.wrap
+    }
+    fn step_cost(&self, from: Point, to: Point, from_cost: Cost, to_cost: Cost) -> Cost {
+        if !self.octile {
+            return from_cost;
+        }
+        let mut cost = from_cost * OCTILE_STRAIGHT;
+        if from.0 != to.0 && from.1 != to.1 {
+            cost += (from_cost + to_cost) * OCTILE_DIAGONAL_EXTRA;
+        }
+        cost
+    }
+    fn cost_scale(&self) -> Cost {
+        if self.octile {
+            OCTILE_STRAIGHT
         } else {
-            point.0 - goal.0
+            1
+        }
+    }
+    fn is_step_allowed(
+        &self,
+        from: Point,
+        to: Point,
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) -> bool {
+        if from.0 == to.0 || from.1 == to.1 {
+            return true;
+        }
+        // the two Tiles next to both ends of the diagonal
+        let open = [(to.0, from.1), (from.0, to.1)].map(|p| get_cost(p) >= 0);
+        match self.diagonals {
+            DiagonalRule::Always => true,
+            DiagonalRule::NoCorners => open[0] && open[1],
+            DiagonalRule::NoSqueezing => open[0] || open[1],
+        }
+    }
+    fn describe(&self) -> Vec<u64> {
+        let diagonals = match self.diagonals {
+            DiagonalRule::Always => 0,
+            DiagonalRule::NoCorners => 1,
+            DiagonalRule::NoSqueezing => 2,
         };
-        let diff_1 = if goal.1 > point.1 {
-            goal.1 - point.1
-        } else {
-            point.1 - goal.1
+        vec![
+            1,
+            self.width as u64,
+            self.height as u64,
+            self.wrap as u64,
+            self.octile as u64,
+            diagonals,
+        ]
+    }
+}
+
+/// The way the Tiles of a hex Grid are stored in the rectangular Grid of a
+/// [`HexNeighborhood`].
+///
+/// See [Red Blob Games](https://www.redblobgames.com/grids/hexagons/#coordinates) for
+/// illustrations of the layouts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexLayout {
+    /// "pointy top" hexagons in rows, with every odd row shoved right by half a Tile
+    OddR,
+    /// "flat top" hexagons in columns, with every even column shoved down by half a Tile
+    EvenQ,
+    /// Axial coordinates: `x` is the column and `y` the diagonal row of the hexagons, which
+    /// makes the Grid a rhombus
+    Axial,
+}
+
+/// A Neighborhood for Agents moving on a hex Grid, with 6 neighbors for every Tile.
+///
+/// The hexagons are stored in a rectangular Grid using one of the [`HexLayout`]s. The heuristic
+/// is the number of steps between two hexagons.
+///
+/// ```no_code
+/// A: Agent, o: reachable in one step (HexLayout::OddR, A in an even row)
+///  o o
+/// o A o
+///  o o
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct HexNeighborhood {
+    width: usize,
+    height: usize,
+    layout: HexLayout,
+}
+
+impl HexNeighborhood {
+    /// Creates a new HexNeighborhood.
+    ///
+    /// `width` and `height` are the size of the Grid to move on, and `layout` defines which
+    /// Tiles of that Grid are next to each other.
+    pub fn new(width: usize, height: usize, layout: HexLayout) -> HexNeighborhood {
+        HexNeighborhood {
+            width,
+            height,
+            layout,
+        }
+    }
+
+    /// The layout of the hexagons in the Grid
+    pub fn layout(&self) -> HexLayout {
+        self.layout
+    }
+
+    /// Converts a Point into axial coordinates
+    fn axial(&self, (x, y): Point) -> (isize, isize) {
+        let (x, y) = (x as isize, y as isize);
+        match self.layout {
+            HexLayout::OddR => (x - (y - (y & 1)) / 2, y),
+            HexLayout::EvenQ => (x, y - (x + (x & 1)) / 2),
+            HexLayout::Axial => (x, y),
+        }
+    }
+}
+
+impl Neighborhood for HexNeighborhood {
+    fn get_all_neighbors(&self, point: Point, target: &mut Vec<Point>) {
+        let (width, height) = (self.width, self.height);
+
+        #[rustfmt::skip]
+        static ODD_R_DELTAS: [[(isize, isize); 6]; 2] = [
+            [(-1, -1), (0, -1), (1, 0), (0, 1), (-1, 1), (-1, 0)],
+            [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 0)],
+        ];
+        #[rustfmt::skip]
+        static EVEN_Q_DELTAS: [[(isize, isize); 6]; 2] = [
+            [(0, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)],
+            [(0, -1), (1, -1), (1, 0), (0, 1), (-1, 0), (-1, -1)],
+        ];
+        #[rustfmt::skip]
+        static AXIAL_DELTAS: [(isize, isize); 6] = [(0, -1), (1, -1), (1, 0), (0, 1), (-1, 1), (-1, 0)];
+
+        let deltas = match self.layout {
+            HexLayout::OddR => &ODD_R_DELTAS[point.1 & 1],
+            HexLayout::EvenQ => &EVEN_Q_DELTAS[point.0 & 1],
+            HexLayout::Axial => &AXIAL_DELTAS,
         };
-        diff_0.max(diff_1)
+        for (dx, dy) in deltas.iter() {
+            let x = point.0 as isize + dx;
+            let y = point.1 as isize + dy;
+            if x >= 0 && x < width as isize && y >= 0 && y < height as isize {
+                target.push((x as usize, y as usize))
+            }
+        }
+    }
+    fn heuristic(&self, point: Point, goal: Point) -> usize {
+        let (q1, r1) = self.axial(point);
+        let (q2, r2) = self.axial(goal);
+        let (dq, dr) = (q1 - q2, r1 - r2);
+        (dq.unsigned_abs() + dr.unsigned_abs() + (dq + dr).unsigned_abs()) / 2
+    }
+    fn describe(&self) -> Vec<u64> {
+        let layout = match self.layout {
+            HexLayout::OddR => 0,
+            HexLayout::EvenQ => 1,
+            HexLayout::Axial => 2,
+        };
+        vec![2, self.width as u64, self.height as u64, layout]
     }
 }
 
@@ -172,6 +602,17 @@ mod tests {
             let neighborhood = ManhattanNeighborhood::new(5, 5);
             assert_eq!(neighborhood.heuristic((3, 1), (0, 0)), 3 + 1);
         }
+
+        #[test]
+        fn wrapping() {
+            let neighborhood = ManhattanNeighborhood::new_wrapping(5, 5);
+            let mut target = vec![];
+            neighborhood.get_all_neighbors((0, 2), &mut target);
+            assert_eq!(target, vec![(0, 1), (1, 2), (0, 3), (4, 2)],);
+            assert_eq!(neighborhood.heuristic((4, 1), (0, 0)), 1 + 1);
+            assert_eq!(neighborhood.heuristic((2, 1), (0, 0)), 2 + 1);
+            assert!(neighborhood.grid_steps().is_none());
+        }
     }
     mod moore {
         use super::*;
@@ -189,5 +630,133 @@ mod tests {
             let neighborhood = MooreNeighborhood::new(5, 5);
             assert_eq!(neighborhood.heuristic((3, 1), (0, 0)), 3);
         }
+
+        #[test]
+        fn wrapping() {
+            let neighborhood = MooreNeighborhood::new_wrapping(5, 5);
+            let mut target = vec![];
+            neighborhood.get_all_neighbors((4, 0), &mut target);
+            assert_eq!(target, vec![(0, 0), (0, 1), (4, 1), (3, 1), (3, 0)],);
+            assert_eq!(neighborhood.heuristic((3, 1), (0, 0)), 2);
+            assert!(neighborhood.wraps_horizontally());
+        }
+
+        #[test]
+        fn octile() {
+            let neighborhood = MooreNeighborhood::new_octile(5, 5);
+            assert_eq!(neighborhood.cost_scale(), 10);
+            assert_eq!(neighborhood.step_cost((1, 1), (2, 1), 3, 1), 30);
+            assert_eq!(neighborhood.step_cost((1, 1), (2, 2), 3, 1), 38);
+            assert_eq!(neighborhood.step_cost((2, 2), (1, 1), 1, 3), 18);
+            assert_eq!(neighborhood.heuristic((3, 1), (0, 0)), 34);
+            // the straight and diagonal steps of the heuristic
+            assert_eq!(
+                neighborhood.heuristic((3, 1), (0, 0)),
+                neighborhood.step_cost((0, 0), (1, 1), 1, 1)
+                    + 2 * neighborhood.step_cost((0, 0), (1, 0), 1, 1)
+            );
+        }
+
+        #[test]
+        fn diagonal_rules() {
+            // solid Tiles right of and below (1, 1)
+            let cost_fn = |p: Point| if p == (2, 1) || p == (1, 2) { -1 } else { 1 };
+            let one_wall = |p: Point| if p == (2, 1) { -1 } else { 1 };
+            let neighborhood = MooreNeighborhood::new(5, 5);
+            for (rule, expected) in [
+                (DiagonalRule::Always, [true, true]),
+                (DiagonalRule::NoCorners, [false, false]),
+                (DiagonalRule::NoSqueezing, [true, false]),
+            ] {
+                let neighborhood = neighborhood.with_diagonal_rule(rule);
+                assert_eq!(neighborhood.diagonal_rule(), rule);
+                assert_eq!(
+                    neighborhood.is_step_allowed((1, 1), (2, 2), one_wall),
+                    expected[0]
+                );
+                assert_eq!(
+                    neighborhood.is_step_allowed((1, 1), (2, 2), cost_fn),
+                    expected[1]
+                );
+                assert_eq!(
+                    neighborhood.is_step_allowed((2, 2), (1, 1), cost_fn),
+                    expected[1]
+                );
+                // straight steps and other diagonals are not affected
+                assert!(neighborhood.is_step_allowed((1, 1), (1, 0), cost_fn));
+                assert!(neighborhood.is_step_allowed((1, 1), (0, 0), cost_fn));
+                assert_eq!(
+                    neighborhood.grid_steps().is_some(),
+                    rule == DiagonalRule::Always
+                );
+            }
+        }
+    }
+    mod hex {
+        use super::*;
+
+        fn neighbors(layout: HexLayout, point: Point) -> Vec<Point> {
+            let neighborhood = HexNeighborhood::new(5, 5, layout);
+            let mut target = vec![];
+            neighborhood.get_all_neighbors(point, &mut target);
+            target
+        }
+
+        #[test]
+        fn get_all_neighbors() {
+            use HexLayout::*;
+            assert_eq!(
+                neighbors(OddR, (2, 2)),
+                vec![(1, 1), (2, 1), (3, 2), (2, 3), (1, 3), (1, 2)]
+            );
+            assert_eq!(
+                neighbors(OddR, (2, 1)),
+                vec![(2, 0), (3, 0), (3, 1), (3, 2), (2, 2), (1, 1)]
+            );
+            assert_eq!(
+                neighbors(EvenQ, (2, 2)),
+                vec![(2, 1), (3, 2), (3, 3), (2, 3), (1, 3), (1, 2)]
+            );
+            assert_eq!(
+                neighbors(EvenQ, (1, 2)),
+                vec![(1, 1), (2, 1), (2, 2), (1, 3), (0, 2), (0, 1)]
+            );
+            assert_eq!(
+                neighbors(Axial, (2, 2)),
+                vec![(2, 1), (3, 1), (3, 2), (2, 3), (1, 3), (1, 2)]
+            );
+            assert_eq!(neighbors(Axial, (0, 0)), vec![(1, 0), (0, 1)]);
+        }
+
+        #[test]
+        fn heuristic() {
+            // every neighbor is one step away, and the heuristic matches a breadth-first search
+            for layout in [HexLayout::OddR, HexLayout::EvenQ, HexLayout::Axial] {
+                let neighborhood = HexNeighborhood::new(9, 9, layout);
+                let start = (4, 4);
+                let mut distance = vec![vec![usize::MAX; 9]; 9];
+                distance[start.1][start.0] = 0;
+                let mut next = std::collections::VecDeque::from([start]);
+                let mut target = vec![];
+                while let Some(p) = next.pop_front() {
+                    target.clear();
+                    neighborhood.get_all_neighbors(p, &mut target);
+                    assert_eq!(target.len() == 6, p.0 % 8 != 0 && p.1 % 8 != 0);
+                    for &n in target.iter() {
+                        assert_eq!(neighborhood.heuristic(p, n), 1);
+                        if distance[n.1][n.0] == usize::MAX {
+                            distance[n.1][n.0] = distance[p.1][p.0] + 1;
+                            next.push_back(n);
+                        }
+                    }
+                }
+                for (y, row) in distance.iter().enumerate() {
+                    for (x, &d) in row.iter().enumerate() {
+                        assert_eq!(neighborhood.heuristic(start, (x, y)), d, "{:?}", layout);
+                    }
+                }
+                assert_eq!(neighborhood.heuristic(start, (4, 0)), 4);
+            }
+        }
     }
 }
@@ -1,5 +1,8 @@
-use super::{Cost, Path, PathSegment};
-use crate::{grid, neighbors::Neighborhood, Point};
+use super::{Cost, Path, PathSegment, SmoothedPath};
+use crate::{
+    cost_grid, grid, neighbors::Neighborhood, path_cache::MoveCosts, CostGrid, GridMismatch,
+    PathCache, Point,
+};
 
 /// A Path that may not be fully calculated yet.
 ///
@@ -14,7 +17,10 @@ use crate::{grid, neighbors::Neighborhood, Point};
 /// segments are reached.**
 ///
 /// **Warning: Keeping an AbstractPath after changing the Grid, or using a different cost function,
-/// leads to panics and wrong results.**
+/// leads to panics and wrong results.** [`is_valid`](AbstractPath::is_valid) tells if the rest of
+/// the Path crosses any Tiles that changed since it was created. Paths found on a [`CostGrid`] with
+/// a [`generation`](CostGrid::generation) also detect this in
+/// [`safe_next`](AbstractPath::safe_next).
 ///
 /// **You have been warned**
 #[derive(Debug, Clone)]
@@ -26,6 +32,16 @@ pub struct AbstractPath<N: Neighborhood> {
     end: Point,
     current_index: (usize, usize),
     steps_taken: usize,
+    /// the options for resolving unknown segments
+    options: grid::SearchOptions,
+    /// the [`generation`](CostGrid::generation) of the Grid that this Path was created on
+    generation: Option<u64>,
+    /// if that Grid has [`move_cost`](CostGrid::move_cost)s
+    moves: bool,
+    /// the Chunks that the Path crosses, with their generation and the last step within them
+    chunks: Vec<(usize, u64, usize)>,
+    /// the size of the Chunks that unknown segments stay within
+    chunk_size: Option<usize>,
 }
 
 impl<N: Neighborhood> AbstractPath<N> {
@@ -45,25 +61,112 @@ impl<N: Neighborhood> AbstractPath<N> {
         self.total_length
     }
 
+    /// Checks if the rest of the Path is still valid after the Grid changed.
+    ///
+    /// Returns `false` if any Tiles changed in a Chunk that the rest of the Path crosses, as
+    /// reported by [`tiles_changed`](PathCache::tiles_changed), or if a link that the Path
+    /// uses was removed. Steps that were already taken don't matter, so a Path stays valid once
+    /// it left the changed Chunks behind. A valid Path can still be more expensive than a new one,
+    /// since changes elsewhere can open up shorter ways.
+    ///
+    /// `cache` has to be the PathCache that created this Path.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// let mut grid = [[1; 8]; 4];
+    /// let (width, height) = (grid[0].len(), grid.len());
+    /// let mut pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &|(x, y): (usize, usize)| grid[y][x],
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(4),
+    /// );
+    ///
+    /// let mut path = pathfinding
+    ///     .find_path((0, 0), (7, 0), &|(x, y): (usize, usize)| grid[y][x]).unwrap()
+    ///     .unwrap();
+    ///
+    /// // a wall appears in the left Chunk
+    /// grid[0][2] = -1;
+    /// let changed = pathfinding.tiles_changed(&[(2, 0)], &|(x, y): (usize, usize)| grid[y][x]);
+    /// assert_eq!(changed, vec![(0, 0)]);
+    /// assert!(!path.is_valid(&pathfinding));
+    ///
+    /// // a Path that already left the left Chunk doesn't care
+    /// let mut path = pathfinding
+    ///     .find_path((0, 0), (7, 0), &|(x, y): (usize, usize)| grid[y][x]).unwrap()
+    ///     .unwrap();
+    /// while path.next().unwrap().0 < 4 {}
+    ///
+    /// grid[1][2] = -1;
+    /// pathfinding.tiles_changed(&[(2, 1)], &|(x, y): (usize, usize)| grid[y][x]);
+    /// assert!(path.is_valid(&pathfinding));
+    /// ```
+    pub fn is_valid(&self, cache: &PathCache<N>) -> bool
+    where
+        N: Sync,
+    {
+        self.chunks.iter().all(|&(index, generation, last_step)| {
+            last_step < self.steps_taken || cache.chunk_generation(index) == Some(generation)
+        })
+    }
+
     /// A variant of [`Iterator::next()`](#impl-Iterator) that can resolve unknown segments
     /// of the Path. Use this method instead of `next()` when
     /// [`config.cache_paths`](crate::PathCacheConfig::cache_paths) is set to `false`.
-    pub fn safe_next(&mut self, get_cost: impl FnMut(Point) -> isize) -> Option<Point> {
-        self.internal_next(Some(get_cost))
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] if `grid` has a different [`generation`](CostGrid::generation)
+    /// than the Grid that this Path was found on, or differs from it in having
+    /// [`move_cost`](CostGrid::move_cost)s.
+    pub fn safe_next<G: CostGrid>(&mut self, grid: &G) -> Result<Option<Point>, GridMismatch> {
+        self.check_grid(grid)?;
+        Ok(self.next_on(grid))
+    }
+
+    /// Same as [`safe_next`](AbstractPath::safe_next), but with a Grid that was already checked
+    /// against the Path
+    pub(crate) fn next_on<G: CostGrid>(&mut self, grid: &G) -> Option<Point> {
+        let neighborhood = MoveCosts::new(self.neighborhood.clone(), grid);
+        self.next_with(&neighborhood, cost_grid::cost_fn(grid))
     }
-    fn internal_next<F: FnMut(Point) -> isize>(&mut self, get_cost: Option<F>) -> Option<Point> {
+
+    /// Same as [`next_on`](AbstractPath::next_on), but resolves unknown segments with the steps
+    /// of `neighborhood` and the costs of `get_cost`
+    pub(crate) fn next_with<M: Neighborhood>(
+        &mut self,
+        neighborhood: &M,
+        get_cost: impl FnMut(Point) -> isize,
+    ) -> Option<Point> {
+        self.internal_next(Some((neighborhood, get_cost)))
+    }
+
+    fn internal_next<M: Neighborhood, F: FnMut(Point) -> isize>(
+        &mut self,
+        resolve: Option<(&M, F)>,
+    ) -> Option<Point> {
         if self.current_index.0 >= self.path.len() {
             return None;
         }
         let mut current = &self.path[self.current_index.0];
         if let PathSegment::Unknown { start, end, .. } = *current {
+            let size = self.chunk_size;
+            let chunk = |p: Point| size.map(|size| (p.0 / size, p.1 / size));
+            let (start_chunk, end_chunk) = (chunk(start), chunk(end));
+            let (neighborhood, get_cost) = resolve.expect(
+                "Tried calling next() on a Path that is not fully known. Use safe_next() instead.",
+            );
             let path = grid::a_star_search(
-                &self.neighborhood,
-                |_| true,
-                get_cost.expect("Tried calling next() on a Path that is not fully known. Use safe_next() instead."),
+                neighborhood,
+                // stay within the Chunks that the PathCache knows the segment from
+                |p| chunk(p) == start_chunk || chunk(p) == end_chunk,
+                get_cost,
                 start,
                 end,
-                self.neighborhood.heuristic(start, end) * 2
+                neighborhood.heuristic(start, end) * 2,
+                self.options,
             )
             .unwrap_or_else(|| {
                 panic!(
@@ -101,13 +204,84 @@ impl<N: Neighborhood> AbstractPath<N> {
     ///
     /// if [`config.cache_paths`](crate::PathCacheConfig::cache_paths) is set to true,
     /// then calling this method is similar to calling `path.collect::<Vec<_>>()`.
-    pub fn resolve(mut self, mut get_cost: impl FnMut(Point) -> isize) -> Vec<Point> {
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] under the same conditions as
+    /// [`safe_next`](AbstractPath::safe_next).
+    pub fn resolve<G: CostGrid>(mut self, grid: &G) -> Result<Vec<Point>, GridMismatch> {
+        self.check_grid(grid)?;
         let mut result = Vec::with_capacity(self.len());
 
-        while let Some(pos) = self.safe_next(&mut get_cost) {
+        while let Some(pos) = self.next_on(grid) {
             result.push(pos);
         }
-        result
+        Ok(result)
+    }
+
+    /// Removes unnecessary turns from the rest of the Path by walking in straight lines
+    /// wherever possible.
+    ///
+    /// Returns an Iterator over the waypoints of the new Path, see [`SmoothedPath`] for details.
+    /// Waypoints are only added where a straight line would cross a solid Tile or cost more than
+    /// the original Path, so the smoothed Path never costs more than this one. This is mostly
+    /// useful with a [`MooreNeighborhood`](crate::neighbors::MooreNeighborhood), where Paths
+    /// tend to zig-zag along the Chunk borders.
+    ///
+    /// `grid` is used to check the Tiles along the lines, as well as to resolve unknown
+    /// segments like [`safe_next`](AbstractPath::safe_next).
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] under the same conditions as
+    /// [`safe_next`](AbstractPath::safe_next).
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// let (width, height) = (10, 10);
+    /// let wall = |(x, y): (usize, usize)| if x == 5 && y < 8 { -1 } else { 1 };
+    /// let pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &wall,
+    ///     MooreNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    ///
+    /// let path = pathfinding.find_path((0, 0), (9, 0), &wall).unwrap().unwrap();
+    /// let length = path.length();
+    ///
+    /// let waypoints = path.smoothed(&wall).unwrap().collect::<Vec<_>>();
+    /// assert!(waypoints.len() < length);
+    /// // head straight for the end of the wall
+    /// assert_eq!(waypoints.first(), Some(&(5, 8)));
+    /// assert_eq!(waypoints.last(), Some(&(9, 0)));
+    /// ```
+    pub fn smoothed<G: CostGrid>(self, grid: &G) -> Result<SmoothedPath<'_, N, G>, GridMismatch> {
+        self.check_grid(grid)?;
+        Ok(SmoothedPath::new(
+            MoveCosts::new(self.neighborhood.clone(), grid),
+            self.current_pos(),
+            self,
+            grid,
+        ))
+    }
+
+    /// The position that the next step is taken from
+    pub(crate) fn current_pos(&self) -> Point {
+        match self.path.get(self.current_index.0) {
+            Some(PathSegment::Known(path)) => path[self.current_index.1 - 1],
+            Some(PathSegment::Unknown { start, .. }) => *start,
+            None => self.end,
+        }
+    }
+
+    /// The segment of the next step, and the index of the next step within it
+    pub(crate) fn current_index(&self) -> (usize, usize) {
+        self.current_index
+    }
+
+    pub(crate) fn end(&self) -> Point {
+        self.end
     }
 
     pub(crate) fn new(neighborhood: N, end: Point) -> AbstractPath<N> {
@@ -119,6 +293,11 @@ impl<N: Neighborhood> AbstractPath<N> {
             end,
             current_index: (0, 1),
             steps_taken: 0,
+            options: Default::default(),
+            generation: None,
+            moves: false,
+            chunks: vec![],
+            chunk_size: None,
         }
     }
 
@@ -132,6 +311,24 @@ impl<N: Neighborhood> AbstractPath<N> {
         }
     }
 
+    pub(crate) fn set_search_options(&mut self, options: grid::SearchOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Checks that `grid` matches the Grid that this Path was found on
+    pub(crate) fn check_grid<G: CostGrid>(&self, grid: &G) -> Result<(), GridMismatch> {
+        GridMismatch::check(self.generation, self.moves, grid)
+    }
+
+    /// Sets the [`generation`](CostGrid::generation) of the Grid that this Path was found on,
+    /// and if it has [`move_cost`](CostGrid::move_cost)s
+    pub(crate) fn set_grid(&mut self, generation: Option<u64>, moves: bool) -> &mut Self {
+        self.generation = generation;
+        self.moves = moves;
+        self
+    }
+
     pub(crate) fn add_path_segment(&mut self, path: PathSegment) -> &mut Self {
         assert!(
             self.end == path.start(),
@@ -160,6 +357,90 @@ impl<N: Neighborhood> AbstractPath<N> {
         self
     }
 
+    pub(crate) fn segments(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    pub(crate) fn set_chunks(
+        &mut self,
+        chunks: Vec<(usize, u64, usize)>,
+        chunk_size: usize,
+    ) -> &mut Self {
+        self.chunks = chunks;
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// The generation that the Chunk `index` had when the Path was created
+    pub(crate) fn chunk_generation(&self, index: usize) -> Option<u64> {
+        self.chunks
+            .binary_search_by_key(&index, |&(i, _, _)| i)
+            .ok()
+            .map(|i| self.chunks[i].1)
+    }
+
+    /// Replaces the segments `first..=last` with the segments of `other`, which has to start at
+    /// the start of `first` or, if `first` is the current segment, at the current position.
+    ///
+    /// The steps already taken in `first` are kept, with their cost taken from `grid`.
+    pub(crate) fn replace_segments<G: CostGrid>(
+        &mut self,
+        first: usize,
+        last: usize,
+        other: AbstractPath<N>,
+        grid: &G,
+    ) -> &mut Self {
+        let (index, offset) = self.current_index;
+        let start = self.path[0].start();
+        let mut segments = std::mem::take(&mut self.path);
+        let rest = segments.split_off(last + 1);
+        let replaced = segments.split_off(first);
+
+        let mut walked = None;
+        if first == index && offset > 1 {
+            let path = match &replaced[0] {
+                PathSegment::Known(path) => path,
+                PathSegment::Unknown { .. } => unreachable!(),
+            };
+            let tiles: Vec<Point> = (0..offset).map(|i| path[i]).collect();
+            let neighborhood = MoveCosts::new(self.neighborhood.clone(), grid);
+            let cost = tiles
+                .windows(2)
+                .map(|step| {
+                    let from_cost = grid.cost(step[0]).max(0) as Cost;
+                    let to_cost = grid.cost(step[1]).max(0) as Cost;
+                    neighborhood.step_cost(step[0], step[1], from_cost, to_cost)
+                })
+                .sum();
+            walked = Some(PathSegment::Known(Path::new(tiles, cost)));
+        }
+        if walked.is_some() {
+            self.current_index = (index + 1, 1);
+        }
+
+        let mut path = AbstractPath::new(self.neighborhood.clone(), start);
+        for segment in segments
+            .into_iter()
+            .chain(walked)
+            .chain(other.path)
+            .chain(rest)
+        {
+            path.add_path_segment(segment);
+        }
+        self.total_cost = path.total_cost;
+        self.total_length = path.total_length;
+        self.path = path.path;
+        self
+    }
+
+    /// Adds the segments of `other`, which must not have taken any steps yet
+    pub(crate) fn append(&mut self, other: AbstractPath<N>) -> &mut Self {
+        for segment in other.path {
+            self.add_path_segment(segment);
+        }
+        self
+    }
+
     #[allow(dead_code)]
     pub(crate) fn add_node(&mut self, node: Point, cost: Cost, len: usize) -> &mut Self {
         self.path.push(PathSegment::Unknown {
@@ -183,7 +464,7 @@ impl<N: Neighborhood> Iterator for AbstractPath<N> {
     /// Panics if a segment of the Path is not known because [`config.cache_paths`](crate::PathCacheConfig::cache_paths)
     /// is set to `false`. Use [`safe_next`](AbstractPath::safe_next) in those cases.
     fn next(&mut self) -> Option<Point> {
-        self.internal_next::<fn((usize, usize)) -> isize>(None)
+        self.internal_next::<N, fn(Point) -> isize>(None)
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
         let len = self.total_length - self.steps_taken;
@@ -197,6 +478,7 @@ impl<N: Neighborhood> Iterator for AbstractPath<N> {
     }
     fn nth(&mut self, step: usize) -> Option<Point> {
         self.current_index.1 += step;
+        self.steps_taken = (self.steps_taken + step).min(self.total_length);
         while self.current_index.0 < self.path.len() {
             let current_len = self.path[self.current_index.0].len();
             if self.current_index.1 < current_len {
@@ -334,20 +616,24 @@ mod tests {
             println!("w: {:?}", w);
             let pathfinding = PathCache::new(
                 (w, w),
-                |_| 1,
+                &|_: (usize, usize)| 1,
                 MooreNeighborhood::new(w, w),
                 Default::default(),
             );
-            let path = pathfinding.find_path((0, 0), (w - 1, w - 1), |_| 1);
+            let path = pathfinding
+                .find_path((0, 0), (w - 1, w - 1), &|_: (usize, usize)| 1)
+                .unwrap();
             assert!(path.is_some());
 
-            let pathfinding = crate::PathCache::new(
+            let pathfinding = PathCache::new(
                 (w, w),
-                |_| 1,
+                &|_: (usize, usize)| 1,
                 ManhattanNeighborhood::new(w, w),
                 Default::default(),
             );
-            let path = pathfinding.find_path((0, 0), (w - 1, w - 1), |_| 1);
+            let path = pathfinding
+                .find_path((0, 0), (w - 1, w - 1), &|_: (usize, usize)| 1)
+                .unwrap();
             assert!(path.is_some());
         }
     }
@@ -7,4 +7,7 @@ pub use generic_path::*;
 mod path_segment;
 pub use path_segment::PathSegment;
 
+mod smoothed_path;
+pub use smoothed_path::SmoothedPath;
+
 pub type Cost = usize;
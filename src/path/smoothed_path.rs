@@ -0,0 +1,123 @@
+use super::{AbstractPath, Cost};
+use crate::{
+    cost_grid::cost_fn, grid, neighbors::Neighborhood, path_cache::MoveCosts, CostGrid, Point,
+};
+
+/// The waypoints of an [`AbstractPath`] after removing unnecessary turns.
+///
+/// Created by [`AbstractPath::smoothed`]. Iterating over this yields the waypoints, starting
+/// with the first one after the current position and ending with the goal. Moving in a
+/// straight line from one waypoint to the next never crosses solid Tiles or goes against a
+/// [`move_cost`](CostGrid::move_cost), and never costs more than the original Path between them. The Tiles on that line are the ones of
+/// [Bresenham's line algorithm](https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm),
+/// with every diagonal step split in two if the Neighborhood doesn't allow it.
+///
+/// The original Path is only resolved as far as needed to find the next waypoint, so unknown
+/// segments are calculated lazily with the Grid passed to `smoothed`.
+///
+/// Both ends of a jump through a link (see [`add_link`](crate::PathCache::add_link)) are
+/// waypoints, since there is no straight line between them.
+pub struct SmoothedPath<'a, N: Neighborhood, G: CostGrid> {
+    /// the Neighborhood with the move costs of `grid`, if any
+    neighborhood: MoveCosts<'a, N>,
+    path: AbstractPath<N>,
+    grid: &'a G,
+    /// the last returned waypoint
+    anchor: Point,
+    /// the furthest Tile of the original Path that can be reached in a straight line from
+    /// `anchor`, and the cost of the original Path from `anchor` to it
+    reached: Option<(Point, Cost)>,
+    /// the end of a jump, which is the waypoint after the start of the jump
+    jump_end: Option<Point>,
+    neighbors: Vec<Point>,
+}
+
+impl<'a, N: Neighborhood, G: CostGrid> SmoothedPath<'a, N, G> {
+    pub(super) fn new(
+        neighborhood: MoveCosts<'a, N>,
+        start: Point,
+        path: AbstractPath<N>,
+        grid: &'a G,
+    ) -> Self {
+        SmoothedPath {
+            neighborhood,
+            path,
+            grid,
+            anchor: start,
+            reached: None,
+            jump_end: None,
+            neighbors: vec![],
+        }
+    }
+}
+
+impl<N: Neighborhood, G: CostGrid> Iterator for SmoothedPath<'_, N, G> {
+    type Item = Point;
+    fn next(&mut self) -> Option<Point> {
+        if let Some(jump_end) = self.jump_end.take() {
+            return Some(jump_end);
+        }
+        loop {
+            let tile =
+                if let Some(tile) = self.path.next_with(&self.neighborhood, cost_fn(self.grid)) {
+                    tile
+                } else {
+                    // the goal is the last waypoint
+                    let (goal, _) = self.reached.take()?;
+                    self.anchor = goal;
+                    return Some(goal);
+                };
+            let (previous, cost) = self.reached.unwrap_or((self.anchor, 0));
+
+            self.neighbors.clear();
+            self.neighborhood
+                .get_all_neighbors(previous, &mut self.neighbors);
+            if !self.neighbors.contains(&tile) {
+                // a jump through a link => continue from its end
+                self.anchor = tile;
+                if self.reached.take().is_some() {
+                    self.jump_end = Some(tile);
+                    return Some(previous);
+                }
+                return Some(tile);
+            }
+
+            let previous_cost = self.grid.cost(previous).max(0) as Cost;
+            let tile_cost = self.grid.cost(tile).max(0) as Cost;
+            let cost = cost
+                + self
+                    .neighborhood
+                    .step_cost(previous, tile, previous_cost, tile_cost);
+
+            let shortcut = self.reached.is_none()
+                || grid::line_cost(&self.neighborhood, self.anchor, tile, cost_fn(self.grid))
+                    .is_some_and(|line| line <= cost);
+            if shortcut {
+                self.reached = Some((tile, cost));
+                continue;
+            }
+            // the straight line can't be extended => the last reached Tile is a waypoint
+            let waypoint = previous;
+            self.anchor = waypoint;
+            let waypoint_cost = self.grid.cost(waypoint).max(0) as Cost;
+            let tile_cost = self.grid.cost(tile).max(0) as Cost;
+            let cost = self
+                .neighborhood
+                .step_cost(waypoint, tile, waypoint_cost, tile_cost);
+            self.reached = Some((tile, cost));
+            return Some(waypoint);
+        }
+    }
+}
+
+impl<N: Neighborhood, G: CostGrid> std::iter::FusedIterator for SmoothedPath<'_, N, G> {}
+
+impl<N: Neighborhood, G: CostGrid> std::fmt::Debug for SmoothedPath<'_, N, G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmoothedPath")
+            .field("path", &self.path)
+            .field("anchor", &self.anchor)
+            .field("reached", &self.reached)
+            .finish()
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
 };
 
 use std::marker::PhantomData;
+use std::task::Poll;
 
 // a Macro to log::trace the time since $timer, and restart $timer
 #[cfg(feature = "log")]
@@ -23,11 +24,61 @@ macro_rules! re_trace {
     ($msg: literal, $timer: ident) => {};
 }
 
+mod annotations;
+use annotations::{Agent, AgentGraph, Annotations};
+
 mod cache_config;
 pub use cache_config::PathCacheConfig;
 
 mod chunk;
-use chunk::Chunk;
+use chunk::{Chunk, SOLID};
+
+mod clearance;
+use clearance::Clearance;
+
+mod components;
+use components::Components;
+
+mod cost_field;
+
+mod distance_map;
+pub use distance_map::DistanceMap;
+
+mod flow_field;
+pub use flow_field::FlowField;
+
+mod grid_path_cache;
+pub use grid_path_cache::GridPathCache;
+
+mod level;
+use level::Level;
+
+mod links;
+use links::Link;
+
+mod moves;
+pub(crate) use moves::MoveCosts;
+
+mod obstacles;
+use obstacles::{ObstacleGraph, Registry, Revalidated};
+pub use obstacles::{ObstacleHandle, Obstacles};
+
+mod query;
+use query::PathPart;
+pub use query::PathQuery;
+
+mod repair;
+
+mod replanner;
+pub use replanner::Replanner;
+
+mod reservations;
+pub use reservations::ReservationTable;
+
+mod serialize;
+
+mod terrain;
+use terrain::Terrain;
 
 enum CostFnWrapper<F1, F2>
 where
@@ -46,9 +97,23 @@ pub struct PathCache<N: Neighborhood> {
     height: usize,
     chunks: Vec<Chunk>,
     num_chunks: (usize, usize),
+    /// how often the Tiles of every Chunk changed, see [`AbstractPath::is_valid`]
+    chunk_generations: Vec<u64>,
     nodes: NodeList,
+    levels: Vec<Level>,
+    components: Components,
+    annotations: Annotations,
     neighborhood: N,
     config: PathCacheConfig,
+    /// the [`generation`](CostGrid::generation) of the Grid that this PathCache was created or
+    /// last updated with
+    generation: Option<u64>,
+    /// if the Grid has [`move_cost`](CostGrid::move_cost)s
+    moves: bool,
+    /// the links between distant Tiles, see [`add_link`](PathCache::add_link)
+    links: Vec<Link>,
+    /// the Obstacles that apply to all searches, see [`add_obstacles`](PathCache::add_obstacles)
+    obstacles: Registry,
 }
 
 impl<N: Neighborhood + Sync> PathCache<N> {
@@ -56,12 +121,19 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     ///
     /// ## Arguments
     /// - `(width, height)` - the size of the Grid
-    /// - `get_cost` - get the cost for walking over a Tile. (Cost < 0 means solid Tile)
+    /// - `grid` - the cost for walking over every Tile. (Cost < 0 means solid Tile, see
+    ///   [`CostGrid`])
     /// - `neighborhood` - the Neighborhood to use. (See [`Neighborhood`])
     /// - `config` - optional config for creating the cache. (See [`PathCacheConfig`])
     ///
-    /// `get_cost((x, y))` should return the cost for walking over the Tile at (x, y).
-    /// Costs below 0 are solid Tiles.
+    /// `grid` is usually a cost function, where `get_cost((x, y))` returns the cost for walking
+    /// over the Tile at (x, y). Costs below 0 are solid Tiles. The PathCache remembers the
+    /// [`generation`](CostGrid::generation) of `grid`, so that the queries can detect a Grid
+    /// that changed without calling [`tiles_changed`](PathCache::tiles_changed).
+    ///
+    /// If `grid` has [`move_cost`](CostGrid::move_cost)s, like a [`WithMoves`], the edges
+    /// between the Nodes are directed, and every other method needs a Grid with the same move
+    /// costs.
     ///
     /// ## Examples
     /// Basic usage:
@@ -88,35 +160,43 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     ///
     /// let mut pathfinding = PathCache::new(
     ///     (width, height), // the size of the Grid
-    ///     cost_fn(&grid), // get the cost for walking over a Tile
+    ///     &cost_fn(&grid), // get the cost for walking over a Tile
     ///     ManhattanNeighborhood::new(width, height), // the Neighborhood
     ///     PathCacheConfig::with_chunk_size(3), // config
     /// );
     /// ```
-    pub fn new<F: Sync + Fn(Point) -> isize>(
+    pub fn new<G: CostGrid + Sync>(
         (width, height): (usize, usize),
-        get_cost: F,
+        grid: &G,
         neighborhood: N,
         config: PathCacheConfig,
     ) -> PathCache<N> {
+        let size = config.chunk_size;
+        let chunks =
+            (0..height.div_ceil(size)).flat_map(|y| (0..width.div_ceil(size)).map(move |x| (x, y)));
+        let rows = cost_grid::ChunkRows::new(grid, (width, height), size, chunks);
+        let get_cost = |p| rows.cost(p);
+        let moves_grid: Option<&(dyn CostGrid + Sync)> = grid.has_moves().then_some(grid);
         #[cfg(feature = "parallel")]
-        {
-            PathCache::new_internal::<F, fn(Point) -> isize>(
-                (width, height),
-                CostFnWrapper::Parallel(get_cost),
-                neighborhood,
-                config,
-            )
-        }
+        let mut cache = PathCache::new_internal::<_, fn(Point) -> isize>(
+            (width, height),
+            CostFnWrapper::Parallel(get_cost),
+            Terrain::default(),
+            neighborhood,
+            moves_grid,
+            config,
+        );
         #[cfg(not(feature = "parallel"))]
-        {
-            PathCache::new_internal::<fn(Point) -> isize, F>(
-                (width, height),
-                CostFnWrapper::Sequential(get_cost, PhantomData),
-                neighborhood,
-                config,
-            )
-        }
+        let mut cache = PathCache::new_internal::<fn(Point) -> isize, _>(
+            (width, height),
+            CostFnWrapper::Sequential(get_cost, PhantomData),
+            Terrain::default(),
+            neighborhood,
+            moves_grid,
+            config,
+        );
+        cache.generation = grid.generation();
+        cache
     }
 
     /// Same as [`new`](PathCache::new), but doesn't use threads to allow [`FnMut`].
@@ -133,7 +213,9 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         PathCache::new_internal::<fn(Point) -> isize, F>(
             (width, height),
             CostFnWrapper::Sequential(get_cost, Default::default()),
+            Terrain::default(),
             neighborhood,
+            None,
             config,
         )
     }
@@ -152,15 +234,65 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         PathCache::new_internal::<F, fn(Point) -> isize>(
             (width, height),
             CostFnWrapper::Parallel(get_cost),
+            Terrain::default(),
             neighborhood,
+            None,
             config,
         )
     }
 
+    /// Creates a new PathCache for a Grid with different kinds of terrain.
+    ///
+    /// Same as [`new`](PathCache::new), except that `get_tile((x, y))` returns both the cost
+    /// and the terrain class of the Tile at (x, y). Terrain classes are numbers below `32`, and
+    /// allow finding Paths for agents that can only enter some of them with
+    /// [`find_path_with_capabilities`](PathCache::find_path_with_capabilities).
+    ///
+    /// This requires storing the class of every Tile, as well as additional Paths wherever the
+    /// regular Paths cross terrain that not every agent can enter.
+    ///
+    /// ## Panics
+    /// Panics if a terrain class is `32` or more.
+    ///
+    /// See [`find_path_with_capabilities`](PathCache::find_path_with_capabilities) for an example.
+    pub fn new_with_terrain<F: Sync + Fn(Point) -> (isize, u8)>(
+        (width, height): (usize, usize),
+        get_tile: F,
+        neighborhood: N,
+        config: PathCacheConfig,
+    ) -> PathCache<N> {
+        let terrain = Terrain::new((width, height), |p| get_tile(p).1);
+        let get_cost = move |p| get_tile(p).0;
+        #[cfg(feature = "parallel")]
+        {
+            PathCache::new_internal::<_, fn(Point) -> isize>(
+                (width, height),
+                CostFnWrapper::Parallel(get_cost),
+                terrain,
+                neighborhood,
+                None,
+                config,
+            )
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            PathCache::new_internal::<fn(Point) -> isize, _>(
+                (width, height),
+                CostFnWrapper::Sequential(get_cost, PhantomData),
+                terrain,
+                neighborhood,
+                None,
+                config,
+            )
+        }
+    }
+
     fn new_internal<F1, F2>(
         (width, height): (usize, usize),
-        get_cost: CostFnWrapper<F1, F2>,
+        mut get_cost: CostFnWrapper<F1, F2>,
+        terrain: Terrain,
         neighborhood: N,
+        moves_grid: Option<&(dyn CostGrid + Sync)>,
         config: PathCacheConfig,
     ) -> PathCache<N>
     where
@@ -190,7 +322,31 @@ impl<N: Neighborhood + Sync> PathCache<N> {
             }
         };
 
-        let mut nodes = NodeList::new();
+        let clearance = match &mut get_cost {
+            CostFnWrapper::Sequential(get_cost, _) => {
+                Clearance::new((width, height), config.max_agent_size, get_cost)
+            }
+            #[cfg(feature = "parallel")]
+            CostFnWrapper::Parallel(get_cost) => {
+                Clearance::new((width, height), config.max_agent_size, get_cost)
+            }
+        };
+
+        let annotations = Annotations { clearance, terrain };
+
+        re_trace!("calculate clearance", timer);
+
+        // the move costs can't be shared between threads, so every thread creates its own
+        let new_moves = || MoveCosts::with_grid(neighborhood.clone(), moves_grid);
+        let moves = new_moves();
+        let new_node_list = || {
+            if moves_grid.is_some() {
+                NodeList::new_directed()
+            } else {
+                NodeList::new()
+            }
+        };
+        let mut nodes = new_node_list();
 
         // create chunks
         let chunks = match get_cost {
@@ -215,8 +371,9 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                             (w, h),
                             (width, height),
                             &mut get_cost,
-                            &neighborhood,
+                            &moves,
                             &mut nodes,
+                            &annotations,
                             config,
                         ));
                     }
@@ -248,15 +405,16 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                             config.chunk_size
                         };
 
-                        let mut node_list = NodeList::new();
+                        let mut node_list = new_node_list();
 
                         let chunk = Chunk::new(
                             (x * config.chunk_size, y * config.chunk_size),
                             (w, h),
                             (width, height),
                             &get_cost,
-                            &neighborhood,
+                            &new_moves(),
                             &mut node_list,
+                            &annotations,
                             config,
                         );
 
@@ -269,7 +427,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 chunks
                     .iter_mut()
                     .zip(node_lists)
-                    .map(|(mut chunk, new_nodes)| {
+                    .map(|(chunk, new_nodes)| {
                         chunk.nodes = nodes.absorb(new_nodes);
                         chunk
                     })
@@ -284,17 +442,33 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         let mut cache = PathCache {
             width,
             height,
+            chunk_generations: vec![0; chunks.len()],
             chunks,
             num_chunks: (num_chunks_w, num_chunks_h),
             nodes,
+            levels: vec![],
+            components: Components::default(),
+            annotations,
             neighborhood,
             config,
+            generation: None,
+            moves: moves_grid.is_some(),
+            links: vec![],
+            obstacles: Registry::default(),
         };
 
         // connect neighboring Nodes across Chunk borders
-        cache.connect_nodes(None);
+        cache.connect_nodes(None, &moves);
 
         re_trace!("connect nodes", timer);
+
+        cache.components = Components::new(&cache.chunks, cache.chunk_index_fn(), &moves, &[]);
+
+        re_trace!("label components", timer);
+
+        cache.build_levels();
+
+        re_trace!("build levels", timer);
         re_trace!("total time", outer_timer);
 
         cache
@@ -304,8 +478,17 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     ///
     /// If no Path could be found, `None` is returned.
     ///
-    /// `get_cost((x, y))` should return the cost for walking over the Tile at (x, y).
-    /// Costs below 0 are solid Tiles.
+    /// `grid` has to be the Grid that the PathCache was created or last updated with, usually a
+    /// cost function where `get_cost((x, y))` returns the cost for walking over the Tile at
+    /// (x, y). Costs below 0 are solid Tiles. The returned Path remembers the
+    /// [`generation`](CostGrid::generation) of `grid`, so that
+    /// [`safe_next`](AbstractPath::safe_next) can detect when it is used with a changed Grid.
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] if `grid` has a different generation than the Grid that this
+    /// PathCache was created or last updated with, which means that
+    /// [`tiles_changed`](PathCache::tiles_changed) wasn't called after changing it. The same
+    /// applies to all other methods that take a CostGrid.
     ///
     /// ## Examples
     /// Basic usage:
@@ -325,7 +508,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let pathfinding: PathCache<_> = // ...
     /// # PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -337,8 +520,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let path = pathfinding.find_path(
     ///     start,
     ///     goal,
-    ///     cost_fn(&grid),
-    /// );
+    ///     &cost_fn(&grid),
+    /// ).unwrap();
     ///
     /// assert!(path.is_some());
     /// let path = path.unwrap();
@@ -370,7 +553,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// # }
     /// # let pathfinding = PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -390,8 +573,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let mut path = pathfinding.find_path(
     ///     player.pos,
     ///     goal,
-    ///     cost_fn(&grid),
-    /// ).unwrap();
+    ///     &cost_fn(&grid),
+    /// ).unwrap().unwrap();
     ///
     /// player.move_to(path.next().unwrap());
     /// assert_eq!(player.pos, (0, 1));
@@ -421,7 +604,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// # }
     /// # let pathfinding = PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -430,8 +613,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// # let path = pathfinding.find_path(
     /// #     start,
     /// #     goal,
-    /// #     cost_fn(&grid),
-    /// # );
+    /// #     &cost_fn(&grid),
+    /// # ).unwrap();
     /// // ...
     /// let path = path.unwrap();
     ///
@@ -442,43 +625,371 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     ///          (2, 3),  (2, 2),  (3, 2),  (4, 2),  (4, 3),  (4, 4)],
     /// );
     /// ```
-    pub fn find_path(
+    pub fn find_path<G: CostGrid>(
         &self,
         start: Point,
         goal: Point,
-        mut get_cost: impl FnMut(Point) -> isize,
-    ) -> Option<AbstractPath<N>> {
+        grid: &G,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        self.find_path_internal(start, goal, Agent::DEFAULT, grid, None)
+    }
+
+    /// Starts calculating the Path from `start` to `goal`, to be continued in small steps.
+    ///
+    /// Long searches can take more time than is available at once, e.g. within a single frame of
+    /// a game. The returned [`PathQuery`] allows spreading the search over several calls to
+    /// [`step`](PathQuery::step), each of which visits at most a given number of Nodes or Tiles.
+    /// Once the search is done, `step` returns the same Path that
+    /// [`find_path`](PathCache::find_path) would.
+    ///
+    /// The PathCache can't be changed while the query is running, since it is borrowed by it.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// # let mut grid = [
+    /// #     [0, 2, 0, 0, 0],
+    /// #     [0, 2, 2, 2, 0],
+    /// #     [0, 1, 0, 0, 0],
+    /// #     [0, 1, 0, 2, 0],
+    /// #     [0, 0, 0, 2, 0],
+    /// # ];
+    /// # let (width, height) = (grid.len(), grid[0].len());
+    /// # fn cost_fn(grid: &[[usize; 5]; 5]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    /// #     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// # }
+    /// let pathfinding: PathCache<_> = // ...
+    /// # PathCache::new(
+    /// #     (width, height),
+    /// #     &cost_fn(&grid),
+    /// #     ManhattanNeighborhood::new(width, height),
+    /// #     PathCacheConfig::with_chunk_size(3),
+    /// # );
+    /// use std::task::Poll;
+    ///
+    /// let costs = cost_fn(&grid);
+    /// let mut query = pathfinding.begin_find_path((0, 0), (4, 4), &costs).unwrap();
+    /// let path = loop {
+    ///     // visit at most 2 Nodes per frame
+    ///     if let Poll::Ready(path) = query.step(2) {
+    ///         break path;
+    ///     }
+    ///     // ... render the frame
+    /// };
+    /// assert_eq!(path.unwrap().cost(), 12);
+    /// ```
+    pub fn begin_find_path<'a, G: CostGrid>(
+        &'a self,
+        start: Point,
+        goal: Point,
+        grid: &'a G,
+    ) -> Result<PathQuery<'a, N, G>, GridMismatch> {
+        self.check_grid(grid)?;
+        Ok(self.begin_query(start, goal, Agent::DEFAULT, grid, None))
+    }
+
+    /// Calculates the Path from `start` to `goal` for an agent that is bigger than one Tile.
+    ///
+    /// The agent is a square of `agent_size`x`agent_size` Tiles, with its position being the
+    /// top-left corner of that square. This means that every position on the Path is the
+    /// top-left corner of an area of walkable Tiles of that size. A `goal` where the agent
+    /// doesn't fit is treated like a solid goal: the Path ends on it, but the last step
+    /// may not fit.
+    ///
+    /// Otherwise, this behaves like [`find_path`](PathCache::find_path), which is the same as
+    /// calling this with an `agent_size` of `1`. The only difference is that bigger agents
+    /// don't make use of the higher [`levels`](PathCacheConfig::levels), since those only
+    /// contain the regular Paths.
+    ///
+    /// Returns `None` if `agent_size` is bigger than
+    /// [`config.max_agent_size`](PathCacheConfig::max_agent_size), since the PathCache only knows
+    /// where agents up to that size fit.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// let grid = [
+    ///     [0, 0, 0, 0, 0, 0],
+    ///     [0, 0, 0, 0, 0, 0],
+    ///     [2, 2, 0, 2, 0, 0],
+    ///     [0, 0, 0, 0, 0, 0],
+    ///     [0, 0, 0, 0, 0, 0],
+    ///     [0, 0, 0, 0, 0, 0],
+    /// ];
+    /// let (width, height) = (grid.len(), grid[0].len());
+    /// fn cost_fn(grid: &[[usize; 6]; 6]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    ///     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// }
+    /// let pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &cost_fn(&grid),
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig {
+    ///         max_agent_size: 2,
+    ///         ..PathCacheConfig::with_chunk_size(3)
+    ///     },
+    /// );
+    ///
+    /// let (start, goal) = ((0, 0), (0, 4));
+    ///
+    /// // a single Tile squeezes through the gap at (2, 2)
+    /// let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap().unwrap();
+    /// assert_eq!(path.cost(), 8);
+    ///
+    /// // a 2x2 agent has to take the wider gap at (4, 2)
+    /// let path = pathfinding
+    ///     .find_path_with_size(start, goal, 2, &cost_fn(&grid)).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 12);
+    /// assert!(path.collect::<Vec<_>>().contains(&(4, 2)));
+    /// ```
+    pub fn find_path_with_size<G: CostGrid>(
+        &self,
+        start: Point,
+        goal: Point,
+        agent_size: usize,
+        grid: &G,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        self.check_grid(grid)?;
+        if agent_size > self.config.max_agent_size {
+            return Ok(None);
+        }
+        let agent = Agent::with_size(agent_size);
+        self.find_path_internal(start, goal, agent, grid, None)
+    }
+
+    /// Calculates the Path from `start` to `goal` for an agent that can only enter some kinds
+    /// of terrain.
+    ///
+    /// Bit `i` of `capabilities` says if the agent can enter Tiles of terrain class `i`, as
+    /// returned by `get_tile` in [`new_with_terrain`](PathCache::new_with_terrain). Tiles of
+    /// any other class are treated as solid, which includes `start` and `goal`.
+    ///
+    /// Otherwise, this behaves like [`find_path`](PathCache::find_path), which is the same as
+    /// calling this with all bits set. Like with
+    /// [`find_path_with_size`](PathCache::find_path_with_size), the higher
+    /// [`levels`](PathCacheConfig::levels) are not used. The agent is always a single Tile in
+    /// size, since agent size and terrain classes are not combined.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// // 0 = ground, 1 = water
+    /// let grid: [[u8; 6]; 6] = [
+    ///     [0, 0, 1, 0, 0, 0],
+    ///     [0, 0, 1, 0, 0, 0],
+    ///     [0, 0, 1, 0, 0, 0],
+    ///     [0, 0, 1, 0, 0, 0],
+    ///     [0, 0, 1, 0, 0, 0],
+    ///     [0, 0, 0, 0, 0, 0],
+    /// ];
+    /// let (width, height) = (grid.len(), grid[0].len());
+    /// let pathfinding = PathCache::new_with_terrain(
+    ///     (width, height),
+    ///     |(x, y)| (1, grid[y][x]), // (cost, terrain class)
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    ///
+    /// let (start, goal) = ((0, 0), (4, 0));
+    /// const WALKER: u32 = 1 << 0;
+    /// const AMPHIBIAN: u32 = 1 << 0 | 1 << 1;
+    ///
+    /// // swimming across is the shortest Path
+    /// let path = pathfinding
+    ///     .find_path_with_capabilities(start, goal, AMPHIBIAN, &|_: (usize, usize)| 1).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 4);
+    ///
+    /// // walkers have to go around the water
+    /// let path = pathfinding
+    ///     .find_path_with_capabilities(start, goal, WALKER, &|_: (usize, usize)| 1).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 14);
+    /// assert!(path.collect::<Vec<_>>().contains(&(2, 5)));
+    /// ```
+    pub fn find_path_with_capabilities<G: CostGrid>(
+        &self,
+        start: Point,
+        goal: Point,
+        capabilities: u32,
+        grid: &G,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        let agent = Agent::with_capabilities(capabilities);
+        self.find_path_internal(start, goal, agent, grid, None)
+    }
+
+    /// Calculates the Path from `start` to `goal` with some Tiles temporarily blocked or
+    /// penalized.
+    ///
+    /// The `obstacles` only apply to this search, in addition to any that were registered with
+    /// [`add_obstacles`](PathCache::add_obstacles). Otherwise, this behaves like
+    /// [`find_path`](PathCache::find_path), except that the higher
+    /// [`levels`](PathCacheConfig::levels) are not used, and that the search falls back to the
+    /// A* search on the Grid if the Obstacles block all Nodes between `start` and `goal`. The
+    /// Tile at `start` is never blocked, since the agent is already standing on it. The returned
+    /// Path is already fully calculated, since resolving it later would not know about the
+    /// Obstacles.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// let grid = [
+    ///     [0, 0, 0, 0, 0, 0],
+    ///     [0, 2, 2, 2, 2, 0],
+    ///     [0, 0, 0, 0, 0, 0],
+    /// ];
+    /// let (width, height) = (grid[0].len(), grid.len());
+    /// fn cost_fn(grid: &[[usize; 6]; 3]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    ///     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// }
+    /// let pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &cost_fn(&grid),
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    /// let (start, goal) = ((0, 0), (5, 0));
+    ///
+    /// // a unit stands in the upper corridor
+    /// let mut obstacles = Obstacles::new();
+    /// obstacles.block((3, 0));
+    /// let path = pathfinding
+    ///     .find_path_with_obstacles(start, goal, &obstacles, &cost_fn(&grid)).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 9);
+    ///
+    /// // waiting for it to move on is cheaper than going around
+    /// let mut obstacles = Obstacles::new();
+    /// obstacles.penalize((3, 0), 2);
+    /// let path = pathfinding
+    ///     .find_path_with_obstacles(start, goal, &obstacles, &cost_fn(&grid)).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 7);
+    /// ```
+    pub fn find_path_with_obstacles<G: CostGrid>(
+        &self,
+        start: Point,
+        goal: Point,
+        obstacles: &Obstacles,
+        grid: &G,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        self.find_path_internal(start, goal, Agent::DEFAULT, grid, Some(obstacles))
+    }
+
+    fn find_path_internal<G: CostGrid>(
+        &self,
+        start: Point,
+        goal: Point,
+        agent: Agent,
+        grid: &G,
+        obstacles: Option<&Obstacles>,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        self.check_grid(grid)?;
+
         #[cfg(feature = "log")]
-        let (outer_timer, timer) = (std::time::Instant::now(), std::time::Instant::now());
+        let timer = std::time::Instant::now();
+
+        let mut query = self.begin_query(start, goal, agent, grid, obstacles);
+        let res = match query.step(usize::MAX) {
+            Poll::Ready(path) => path,
+            Poll::Pending => panic!("Internal Error #4 in PathCache. Please report this"),
+        };
 
+        re_trace!("find path", timer);
+
+        Ok(res)
+    }
+
+    fn begin_query<'a, G: CostGrid>(
+        &'a self,
+        start: Point,
+        goal: Point,
+        agent: Agent,
+        grid: &'a G,
+        obstacles: Option<&'a Obstacles>,
+    ) -> PathQuery<'a, N, G> {
+        #[cfg(feature = "log")]
+        let timer = std::time::Instant::now();
+
+        let mut get_cost = self.agent_cost_fn(agent, grid);
         if get_cost(start) < 0 {
             // cannot start on a wall
-            return None;
+            return PathQuery::done(self, (start, goal), grid, None);
         }
 
         let neighborhood = self.neighborhood.clone();
+        let moves = MoveCosts::new(neighborhood.clone(), grid);
 
         if start == goal {
-            return Some(AbstractPath::from_known_path(
-                neighborhood,
-                Path::from_slice(&[start, start], 0),
-            ));
+            let path = Path::from_slice(&[start, start], 0);
+            let res = AbstractPath::from_known_path(neighborhood, path);
+            return PathQuery::done(self, (start, goal), grid, Some(res));
         }
 
-        let (start_id, start_path) =
-            if let Some(s) = self.find_nearest_node(start, &mut get_cost, false) {
-                s
-            } else {
-                // no path from start to any Node => start is in cave within chunk
-                // => hope that goal is in the same cave
-                return self
-                    .get_chunk(start)
-                    .find_path(start, goal, get_cost, &neighborhood)
-                    .map(|path| AbstractPath::from_known_path(neighborhood, path));
-            };
+        let component = match self.component_at(start) {
+            Some(component) if self.can_reach(component, goal) => component,
+            _ => return PathQuery::done(self, (start, goal), grid, None),
+        };
+
+        let obstacles = self.obstacles.with(obstacles, start);
 
-        // try-operator: see above, but we know that start is not in a cave
-        let (goal_id, goal_path) = self.find_nearest_node(goal, &mut get_cost, true)?;
+        let start_node =
+            self.find_nearest_node(start, obstacles.cost_fn(&mut get_cost), None, &moves);
+        let start_node = if let Some(s) = start_node {
+            s
+        } else {
+            // no path from start to any Node => start is in cave within chunk
+            // => hope that goal is in the same cave
+            let res = self.get_chunk(start).find_path(
+                start,
+                goal,
+                obstacles.cost_fn(&mut get_cost),
+                &moves,
+                self.config.search_options(),
+            );
+            if res.is_none() && !obstacles.is_empty() {
+                // or the Obstacles cut start off from the Nodes
+                return PathQuery::grid(self, (start, goal), grid, moves, obstacles);
+            }
+            let res = res.map(|path| AbstractPath::from_known_path(neighborhood, path));
+            return PathQuery::done(self, (start, goal), grid, res);
+        };
+
+        let goal_node = self.find_nearest_node(
+            goal,
+            obstacles.cost_fn(&mut get_cost),
+            Some(component),
+            &moves,
+        );
+        let goal_node = if let Some(g) = goal_node {
+            g
+        } else {
+            // see above. start is not in a cave, but with one-way moves, goal can be in
+            // one that is only reachable from inside the chunk
+            let chunk = self.get_chunk(start);
+            let res = chunk
+                .in_chunk(goal)
+                .then(|| {
+                    chunk.find_path(
+                        start,
+                        goal,
+                        obstacles.cost_fn(&mut get_cost),
+                        &moves,
+                        self.config.search_options(),
+                    )
+                })
+                .flatten();
+            if res.is_none() && !obstacles.is_empty() {
+                return PathQuery::grid(self, (start, goal), grid, moves, obstacles);
+            }
+            let res = res.map(|path| AbstractPath::from_known_path(neighborhood, path));
+            return PathQuery::done(self, (start, goal), grid, res);
+        };
 
         re_trace!("find nodes", timer);
 
@@ -490,49 +1001,17 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         let max_size = self.nodes.len();
         let size_hint = heuristic as f32 / max_heuristic as f32 * max_size as f32;
 
-        let path = graph::a_star_search(
-            &self.nodes,
-            start_id,
-            goal_id,
-            &neighborhood,
+        PathQuery::search(
+            self,
+            (start, goal),
+            agent,
+            grid,
+            moves,
+            obstacles,
+            start_node,
+            goal_node,
             size_hint as usize,
-        )?;
-
-        re_trace!("graph::a_star_search", timer);
-
-        if path.len() == 2 || (self.config.a_star_fallback && path.len() <= 4) {
-            // 2: start_id == goal_id
-            // <= 4: start_id X X goal_id
-            let res = self
-                .grid_a_star(start, goal, get_cost)
-                .map(|path| AbstractPath::from_known_path(neighborhood, path));
-
-            re_trace!("A* fallback", timer);
-            re_trace!("total time", outer_timer);
-
-            return res;
-        }
-
-        let mut paths = NodeIDMap::default();
-        paths.insert(goal_id, path);
-
-        #[allow(clippy::let_and_return)]
-        let res = self
-            .resolve_paths(
-                start,
-                start_path,
-                &[(goal, goal_id, goal_path)],
-                &paths,
-                get_cost,
-            )
-            .into_iter()
-            .next()
-            .map(|(_, path)| path);
-
-        re_trace!("resolve_paths", timer);
-        re_trace!("total time", outer_timer);
-
-        res
+        )
     }
 
     /// Calculates the Paths from one `start` to several `goals` on the Grid.
@@ -567,7 +1046,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let pathfinding: PathCache<_> = // ...
     /// # PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -579,8 +1058,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let paths = pathfinding.find_paths(
     ///     start,
     ///     &goals,
-    ///     cost_fn(&grid),
-    /// );
+    ///     &cost_fn(&grid),
+    /// ).unwrap();
     ///
     /// // (4, 4) is reachable
     /// assert!(paths.contains_key(&goals[0]));
@@ -605,7 +1084,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// # }
     /// # let pathfinding = PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -615,25 +1094,25 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let paths = pathfinding.find_paths(
     ///     start,
     ///     &[goal],
-    ///     cost_fn(&grid),
-    /// );
+    ///     &cost_fn(&grid),
+    /// ).unwrap();
     /// let dijkstra_path: Vec<_> = paths[&goal].clone().collect();
     ///
     /// let a_star_path: Vec<_> = pathfinding.find_path(
     ///     start,
     ///     goal,
-    ///     cost_fn(&grid),
-    /// ).unwrap().collect();
+    ///     &cost_fn(&grid),
+    /// ).unwrap().unwrap().collect();
     ///
     /// assert_eq!(dijkstra_path, a_star_path);
     /// ```
-    pub fn find_paths(
+    pub fn find_paths<G: CostGrid>(
         &self,
         start: Point,
         goals: &[Point],
-        get_cost: impl FnMut(Point) -> isize,
-    ) -> PointMap<AbstractPath<N>> {
-        self.find_paths_internal(start, goals, get_cost, false)
+        grid: &G,
+    ) -> Result<PointMap<AbstractPath<N>>, GridMismatch> {
+        self.find_paths_internal(start, goals, grid, false)
     }
 
     /// Finds the closest from a list of goals.
@@ -662,7 +1141,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let pathfinding: PathCache<_> = // ...
     /// # PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -674,13 +1153,13 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let (goal, path) = pathfinding.find_closest_goal(
     ///     start,
     ///     &goals,
-    ///     cost_fn(&grid),
-    /// ).unwrap();
+    ///     &cost_fn(&grid),
+    /// ).unwrap().unwrap();
     ///
     /// assert_eq!(goal, goals[2]);
     ///
     /// let naive_closest = pathfinding
-    ///     .find_paths(start, &goals, cost_fn(&grid))
+    ///     .find_paths(start, &goals, &cost_fn(&grid)).unwrap()
     ///     .into_iter()
     ///     .min_by_key(|(_, path)| path.cost())
     ///     .unwrap();
@@ -707,7 +1186,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// # }
     /// # let pathfinding = PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -716,11 +1195,11 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let (goal, path) = pathfinding.find_closest_goal(
     ///     start,
     ///     &goals,
-    ///     cost_fn(&grid),
-    /// ).unwrap();
+    ///     &cost_fn(&grid),
+    /// ).unwrap().unwrap();
     ///
     /// let naive_closest = pathfinding
-    ///     .find_paths(start, &goals, cost_fn(&grid))
+    ///     .find_paths(start, &goals, &cost_fn(&grid)).unwrap()
     ///     .into_iter()
     ///     .min_by_key(|(_, path)| path.cost())
     ///     .unwrap();
@@ -731,48 +1210,67 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let naive_path: Vec<_> = naive_closest.1.collect();
     /// assert_eq!(path, naive_path);
     /// ```
-    pub fn find_closest_goal(
+    pub fn find_closest_goal<G: CostGrid>(
         &self,
         start: Point,
         goals: &[Point],
-        get_cost: impl FnMut(Point) -> isize,
-    ) -> Option<(Point, AbstractPath<N>)> {
-        self.find_paths_internal(start, goals, get_cost, true)
-            .into_iter()
-            .next()
+        grid: &G,
+    ) -> Result<Option<(Point, AbstractPath<N>)>, GridMismatch> {
+        let paths = self.find_paths_internal(start, goals, grid, true)?;
+        // goals next to start that are only reachable in the Chunk are found besides the closest
+        // goal of the search
+        Ok(paths.into_iter().min_by_key(|(_, path)| path.cost()))
     }
 
-    fn find_paths_internal(
+    fn find_paths_internal<G: CostGrid>(
         &self,
         start: Point,
         goals: &[Point],
-        mut get_cost: impl FnMut(Point) -> isize,
+        grid: &G,
         only_closest_goal: bool,
-    ) -> PointMap<AbstractPath<N>> {
+    ) -> Result<PointMap<AbstractPath<N>>, GridMismatch> {
+        self.check_grid(grid)?;
+        let get_cost = cost_grid::cost_fn(grid);
         if get_cost(start) < 0 || goals.is_empty() {
-            return PointMap::default();
+            return Ok(PointMap::default());
         }
 
         if goals.len() == 1 {
             let goal = goals[0];
-            return self
-                .find_path(start, goal, get_cost)
-                .map(|path| (goal, path))
-                .into_iter()
-                .collect();
+            let path = self.find_path_internal(start, goal, Agent::DEFAULT, grid, None)?;
+            return Ok(path.map(|path| (goal, path)).into_iter().collect());
         }
 
-        let neighborhood = self.neighborhood.clone();
+        let component = if let Some(c) = self.component_at(start) {
+            c
+        } else {
+            return Ok(PointMap::default());
+        };
+        let goals = goals
+            .iter()
+            .copied()
+            .filter(|goal| self.can_reach(component, *goal))
+            .to_vec();
+        let goals = &goals[..];
 
-        let (start_id, start_path) =
-            if let Some(s) = self.find_nearest_node(start, &mut get_cost, false) {
+        if goals.is_empty() {
+            return Ok(PointMap::default());
+        }
+
+        let neighborhood = self.neighborhood.clone();
+        let moves = MoveCosts::new(neighborhood.clone(), grid);
+        let obstacles = self.obstacles.with(None, start);
+        let mut get_cost = obstacles.cost_fn(get_cost);
+
+        let (start_id, start_path) =
+            if let Some(s) = self.find_nearest_node(start, &mut get_cost, None, &moves) {
                 s
             } else {
                 // no path from start to any Node => start is in cave within chunk
                 // => find all goals in the same cave
-                return self
+                let mut ret = self
                     .get_chunk(start)
-                    .find_paths(start, goals, get_cost, &neighborhood)
+                    .find_paths(start, goals, &mut get_cost, &moves)
                     .into_iter()
                     .map(|(goal, path)| {
                         (
@@ -781,10 +1279,20 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                         )
                     })
                     .collect();
+                if !obstacles.is_empty() {
+                    // or the Obstacles cut start off from the Nodes
+                    self.grid_fallback(start, goals, &mut ret, &moves, get_cost);
+                }
+                for path in ret.values_mut() {
+                    self.track_chunks(path);
+                    path.set_grid(self.generation, self.moves);
+                }
+                return Ok(ret);
             };
 
         let mut goal_data = Vec::with_capacity(goals.len());
         let mut goal_ids = Vec::with_capacity(goals.len());
+        let mut cave_goals = vec![];
 
         let mut ret = PointMap::default();
         let mut heuristic = 0;
@@ -799,12 +1307,17 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 continue;
             }
 
-            let (goal_id, goal_path) =
-                if let Some(g) = self.find_nearest_node(goal, &mut get_cost, true) {
-                    g
-                } else {
-                    continue;
-                };
+            let (goal_id, goal_path) = if let Some(g) =
+                self.find_nearest_node(goal, &mut get_cost, Some(component), &moves)
+            {
+                g
+            } else {
+                // with one-way moves, goals next to start can still be reachable
+                if moves.has_moves() && self.get_chunk(start).in_chunk(goal) {
+                    cave_goals.push(goal);
+                }
+                continue;
+            };
 
             goal_data.push((goal, goal_id, goal_path));
             goal_ids.push(goal_id);
@@ -819,15 +1332,272 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         let max_size = self.nodes.len();
         let size_hint = heuristic as f32 / max_heuristic as f32 * max_size as f32;
 
-        let paths = graph::dijkstra_search(
-            &self.nodes,
-            start_id,
-            &goal_ids,
-            only_closest_goal,
-            size_hint as usize,
-        );
+        let mut revalidated = Revalidated::new(self, &obstacles);
+        let paths = if obstacles.is_empty() {
+            let mut special = goal_data.iter().map(|(goal, _, _)| *goal).to_vec();
+            special.push(start);
+            let graph = self.query_graph(&special);
+            graph::dijkstra_search(
+                &graph,
+                start_id,
+                &goal_ids,
+                only_closest_goal,
+                size_hint as usize,
+            )
+            .into_iter()
+            .map(|(id, path)| (id, graph.expand(path)))
+            .collect()
+        } else {
+            // see PathQuery::search: the higher levels don't know about the Obstacles
+            let graph = AgentGraph::new(&self.nodes, &self.annotations, Agent::DEFAULT);
+            let graph = ObstacleGraph::new(
+                self,
+                &graph,
+                &obstacles,
+                &moves,
+                &mut get_cost,
+                &mut revalidated,
+            );
+            graph::dijkstra_search(
+                &graph,
+                start_id,
+                &goal_ids,
+                only_closest_goal,
+                size_hint as usize,
+            )
+        };
+
+        let cave_paths = if cave_goals.is_empty() {
+            PointMap::default()
+        } else {
+            self.get_chunk(start)
+                .find_paths(start, &cave_goals, &mut get_cost, &moves)
+        };
+
+        ret.extend(self.resolve_paths(
+            start,
+            start_path,
+            &goal_data,
+            &paths,
+            &revalidated,
+            Agent::DEFAULT,
+            &mut get_cost,
+            &moves,
+        ));
+        for (goal, path) in cave_paths {
+            ret.insert(
+                goal,
+                AbstractPath::from_known_path(neighborhood.clone(), path),
+            );
+        }
+        if !obstacles.is_empty() {
+            // see PathQuery::step: the Obstacles can cut start or the goals off from their Nodes
+            self.grid_fallback(start, goals, &mut ret, &moves, get_cost);
+        } else if self.nodes.is_directed() {
+            // see PathQuery::step: the Nodes of start and goal might not be connected
+            let goals = goal_data.iter().map(|(goal, _, _)| *goal).to_vec();
+            self.grid_fallback(start, &goals, &mut ret, &moves, get_cost);
+        }
+        for path in ret.values_mut() {
+            self.track_chunks(path);
+            path.set_grid(self.generation, self.moves);
+        }
+        Ok(ret)
+    }
+
+    /// Searches the Grid for the `goals` that `find_paths` found no Path to
+    fn grid_fallback(
+        &self,
+        start: Point,
+        goals: &[Point],
+        ret: &mut PointMap<AbstractPath<N>>,
+        neighborhood: &MoveCosts<N>,
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) {
+        for goal in goals.iter().copied() {
+            if ret.contains_key(&goal) {
+                continue;
+            }
+            if let Some(path) = self.grid_a_star_with(neighborhood, start, goal, &mut get_cost) {
+                let path = AbstractPath::from_known_path(self.neighborhood.clone(), path);
+                ret.insert(goal, path);
+            }
+        }
+    }
+
+    /// Checks if there is any Path from `start` to `goal`, without calculating it.
+    ///
+    /// The PathCache keeps track of which Tiles are connected, so this check takes constant
+    /// time, unlike [`find_path`](PathCache::find_path) for unreachable goals, which has to
+    /// search the entire reachable area first. If `is_reachable` returns `false`, `find_path`
+    /// returns `None` as well.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// # let mut grid = [
+    /// #     [0, 2, 0, 0, 0],
+    /// #     [0, 2, 2, 2, 2],
+    /// #     [0, 1, 0, 0, 0],
+    /// #     [0, 1, 0, 2, 0],
+    /// #     [0, 0, 0, 2, 0],
+    /// # ];
+    /// # let (width, height) = (grid.len(), grid[0].len());
+    /// # fn cost_fn(grid: &[[usize; 5]; 5]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    /// #     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// # }
+    /// let pathfinding: PathCache<_> = // ...
+    /// # PathCache::new(
+    /// #     (width, height),
+    /// #     &cost_fn(&grid),
+    /// #     ManhattanNeighborhood::new(width, height),
+    /// #     PathCacheConfig::with_chunk_size(3),
+    /// # );
+    ///
+    /// assert!(pathfinding.is_reachable((0, 0), (4, 4), &cost_fn(&grid)).unwrap());
+    /// // (2, 0) is walled off
+    /// assert!(!pathfinding.is_reachable((0, 0), (2, 0), &cost_fn(&grid)).unwrap());
+    /// ```
+    pub fn is_reachable<G: CostGrid>(
+        &self,
+        start: Point,
+        goal: Point,
+        grid: &G,
+    ) -> Result<bool, GridMismatch> {
+        self.check_grid(grid)?;
+        if grid.cost(start) < 0 {
+            // cannot start on a wall
+            return Ok(false);
+        }
+        if start == goal {
+            return Ok(true);
+        }
+        Ok(self
+            .component_at(start)
+            .is_some_and(|component| self.can_reach(component, goal)))
+    }
+
+    /// Creates a [`FlowField`] that leads every Tile of the Grid to `goal`.
+    ///
+    /// This is useful when many agents move towards the same goal: Instead of calculating a Path
+    /// for each of them, every agent looks up its next step with
+    /// [`next_step`](FlowField::next_step).
+    ///
+    /// Creating the FlowField runs a reverse search from `goal` over all Nodes. The directions
+    /// of the individual Tiles are only calculated once a Tile in their Chunk is looked up, so
+    /// Chunks that no agent enters cost nothing. The steps follow the Paths between the Nodes,
+    /// which means that they are about as good as the Paths of
+    /// [`find_path`](PathCache::find_path).
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// # let mut grid = [
+    /// #     [0, 2, 0, 0, 0],
+    /// #     [0, 2, 2, 2, 2],
+    /// #     [0, 1, 0, 0, 0],
+    /// #     [0, 1, 0, 2, 0],
+    /// #     [0, 0, 0, 2, 0],
+    /// # ];
+    /// # let (width, height) = (grid.len(), grid[0].len());
+    /// # fn cost_fn(grid: &[[usize; 5]; 5]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    /// #     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// # }
+    /// let pathfinding: PathCache<_> = // ...
+    /// # PathCache::new(
+    /// #     (width, height),
+    /// #     &cost_fn(&grid),
+    /// #     ManhattanNeighborhood::new(width, height),
+    /// #     PathCacheConfig::with_chunk_size(3),
+    /// # );
+    ///
+    /// let costs = cost_fn(&grid);
+    /// let mut field = pathfinding.flow_field((4, 4), &costs).unwrap();
+    ///
+    /// let mut agents = [(0, 0), (2, 2), (0, 4)];
+    /// for agent in agents.iter_mut() {
+    ///     while let Some(next) = field.next_step(*agent) {
+    ///         *agent = next;
+    ///     }
+    /// }
+    /// assert_eq!(agents, [(4, 4), (4, 4), (4, 4)]);
+    ///
+    /// assert_eq!(field.cost((0, 0)), Some(12));
+    /// // (2, 0) is walled off
+    /// assert_eq!(field.next_step((2, 0)), None);
+    /// assert_eq!(field.cost((2, 0)), None);
+    /// ```
+    pub fn flow_field<'a, G: CostGrid>(
+        &'a self,
+        goal: Point,
+        grid: &'a G,
+    ) -> Result<FlowField<'a, N, G>, GridMismatch> {
+        self.check_grid(grid)?;
+        Ok(FlowField::new(self, goal, grid))
+    }
 
-        self.resolve_paths(start, start_path, &goal_data, &paths, get_cost)
+    /// Creates a [`DistanceMap`] with the cost of reaching the nearest of `sources` from every
+    /// Tile of the Grid.
+    ///
+    /// Only costs of up to `max_cost` are calculated. Use `usize::MAX` to include every Tile that
+    /// can reach a source.
+    ///
+    /// Like a [`FlowField`], this runs a reverse search from the `sources` over all Nodes, and
+    /// only calculates the costs of the other Tiles once a Tile in their Chunk is looked up. This
+    /// is a lot cheaper than a full search on the Grid if only some Tiles are needed, and the
+    /// costs are about as good as those of [`find_path`](PathCache::find_path).
+    ///
+    /// ## Panics
+    /// Panics if any of the `sources` is outside of the Grid.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// # let mut grid = [
+    /// #     [0, 2, 0, 0, 0],
+    /// #     [0, 2, 2, 2, 2],
+    /// #     [0, 1, 0, 0, 0],
+    /// #     [0, 1, 0, 2, 0],
+    /// #     [0, 0, 0, 2, 0],
+    /// # ];
+    /// # let (width, height) = (grid.len(), grid[0].len());
+    /// # fn cost_fn(grid: &[[usize; 5]; 5]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    /// #     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// # }
+    /// let pathfinding: PathCache<_> = // ...
+    /// # PathCache::new(
+    /// #     (width, height),
+    /// #     &cost_fn(&grid),
+    /// #     ManhattanNeighborhood::new(width, height),
+    /// #     PathCacheConfig::with_chunk_size(3),
+    /// # );
+    ///
+    /// let water = [(0, 0), (4, 4)];
+    /// let costs = cost_fn(&grid);
+    /// let mut map = pathfinding.distance_map(&water, 10, &costs).unwrap();
+    ///
+    /// assert_eq!(map.cost((0, 0)), Some(0));
+    /// assert_eq!(map.cost((0, 3)), Some(3));
+    /// assert_eq!(map.cost((4, 2)), Some(2));
+    /// // (2, 0) is walled off
+    /// assert_eq!(map.cost((2, 0)), None);
+    /// // (1, 2) costs 10 to leave, which is already too much
+    /// assert_eq!(map.cost((1, 2)), None);
+    ///
+    /// let mut map = pathfinding.distance_map(&water, usize::MAX, &costs).unwrap();
+    /// assert_eq!(map.cost((1, 2)), Some(12));
+    /// ```
+    pub fn distance_map<'a, G: CostGrid>(
+        &'a self,
+        sources: &[Point],
+        max_cost: Cost,
+        grid: &'a G,
+    ) -> Result<DistanceMap<'a, N, G>, GridMismatch> {
+        self.check_grid(grid)?;
+        Ok(DistanceMap::new(self, sources, max_cost, grid))
     }
 
     /// Notifies the PathCache that the Grid changed.
@@ -838,8 +1608,20 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// changes as possible into a single call to `tiles_changed` to avoid unnecessary
     /// recalculations.
     ///
+    /// Returns the top-left corners of the Chunks that contain any of the `tiles`, sorted by row.
+    /// With diagonal steps, this includes the Chunks around a changed Tile on a Chunk corner.
+    /// Paths crossing these Chunks might no longer be valid, see
+    /// [`AbstractPath::is_valid`].
+    ///
+    /// A change to the [`move_cost`](CostGrid::move_cost) of a step has to be reported as a
+    /// change of both of its Tiles.
+    ///
     /// Side note: if anybody has a way to improve this method, open a GitHub Issue / Pull Request.
     ///
+    /// ## Panics
+    /// Panics if `grid` has move costs and the PathCache was created without them, or the other
+    /// way around.
+    ///
     /// ## Examples
     /// Basic usage:
     /// ```
@@ -858,14 +1640,14 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let mut pathfinding: PathCache<_> = // ...
     /// # PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
     ///
     /// let (start, goal) = ((0, 0), (2, 0));
     ///
-    /// let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+    /// let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
     /// assert!(path.is_none());
     ///
     /// grid[1][2] = 0;
@@ -879,29 +1661,34 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     ///     [0, 0, 0, 2, 0],
     /// ]);
     ///
-    /// pathfinding.tiles_changed(
+    /// let changed = pathfinding.tiles_changed(
     ///     &[(2, 1), (2, 3)],
-    ///     cost_fn(&grid),
+    ///     &cost_fn(&grid),
     /// );
+    /// assert_eq!(changed, vec![(0, 0), (0, 3)]);
     ///
-    /// let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+    /// let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
     /// assert!(path.is_some());
     /// ```
-    pub fn tiles_changed<F: Sync + Fn(Point) -> isize>(&mut self, tiles: &[Point], get_cost: F) {
+    pub fn tiles_changed<G: CostGrid + Sync>(&mut self, tiles: &[Point], grid: &G) -> Vec<Point> {
+        self.assert_moves(grid);
+        let rows = self.changed_rows(grid, tiles);
+        let get_cost = |p| rows.cost(p);
+        let moves_grid: Option<&(dyn CostGrid + Sync)> = grid.has_moves().then_some(grid);
         #[cfg(feature = "parallel")]
-        {
-            self.tiles_changed_internal::<F, fn(Point) -> isize>(
-                tiles,
-                CostFnWrapper::Parallel(get_cost),
-            )
-        }
+        let changed = self.tiles_changed_internal::<_, fn(Point) -> isize>(
+            tiles,
+            CostFnWrapper::Parallel(get_cost),
+            moves_grid,
+        );
         #[cfg(not(feature = "parallel"))]
-        {
-            self.tiles_changed_internal::<fn(Point) -> isize, F>(
-                tiles,
-                CostFnWrapper::Sequential(get_cost, PhantomData),
-            )
-        }
+        let changed = self.tiles_changed_internal::<fn(Point) -> isize, _>(
+            tiles,
+            CostFnWrapper::Sequential(get_cost, PhantomData),
+            moves_grid,
+        );
+        self.generation = grid.generation();
+        changed
     }
 
     /// Same as [`tiles_changed`](PathCache::tiles_changed), but doesn't use threads to allow [`FnMut`].
@@ -909,32 +1696,92 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// Equivalent to `tiles_changed` if `parallel` feature is disabled.
     ///
     /// Note that this is _**way**_ slower than `tiles_changed` with `parallel`.
+    ///
+    /// ## Panics
+    /// Panics if the PathCache was created with move costs, since `get_cost` doesn't have any.
     pub fn tiles_changed_with_fn_mut<F: FnMut(Point) -> isize>(
         &mut self,
         tiles: &[Point],
         get_cost: F,
-    ) {
+    ) -> Vec<Point> {
+        assert!(
+            !self.moves,
+            "a PathCache with move costs has to be updated with tiles_changed"
+        );
         self.tiles_changed_internal::<fn(Point) -> isize, F>(
             tiles,
             CostFnWrapper::Sequential(get_cost, PhantomData),
+            None,
         )
     }
 
+    /// Same as [`tiles_changed`](PathCache::tiles_changed), but for a PathCache created with
+    /// [`new_with_terrain`](PathCache::new_with_terrain).
+    ///
+    /// Changes to the terrain class of a Tile have to be reported like changes to its cost.
+    ///
+    /// ## Panics
+    /// Panics if a terrain class is `32` or more.
+    pub fn tiles_changed_with_terrain<F: Sync + Fn(Point) -> (isize, u8)>(
+        &mut self,
+        tiles: &[Point],
+        get_tile: F,
+    ) -> Vec<Point> {
+        self.annotations
+            .terrain
+            .update((self.width, self.height), tiles, |p| get_tile(p).1);
+        self.tiles_changed(tiles, &move |p| get_tile(p).0)
+    }
+
+    /// Copies the Chunks of `tiles` and their neighbors from `grid`, since those are scanned
+    /// by [`tiles_changed`](PathCache::tiles_changed)
+    fn changed_rows<'a, G: CostGrid>(
+        &self,
+        grid: &'a G,
+        tiles: &[Point],
+    ) -> cost_grid::ChunkRows<'a, G> {
+        let size = self.config.chunk_size;
+        let (num_chunks_w, num_chunks_h) = self.num_chunks;
+        let chunks = tiles.iter().flat_map(|&(x, y)| {
+            let (cx, cy) = (x / size, y / size);
+            let columns = cx.saturating_sub(1)..(cx + 2).min(num_chunks_w);
+            (cy.saturating_sub(1)..(cy + 2).min(num_chunks_h))
+                .flat_map(move |y| columns.clone().map(move |x| (x, y)))
+        });
+        cost_grid::ChunkRows::new(grid, (self.width, self.height), size, chunks)
+    }
+
     fn tiles_changed_internal<F1, F2>(
         &mut self,
         tiles: &[Point],
         mut get_cost: CostFnWrapper<F1, F2>,
-    ) where
+        moves_grid: Option<&(dyn CostGrid + Sync)>,
+    ) -> Vec<Point>
+    where
         F1: Sync + Fn(Point) -> isize,
         F2: FnMut(Point) -> isize,
     {
-        let size = self.config.chunk_size;
+        // a cost function doesn't tell which version of the Grid it belongs to
+        self.generation = None;
+
+        let moves = MoveCosts::with_grid(self.neighborhood.clone(), moves_grid);
 
         #[cfg(feature = "log")]
         let (outer_timer, timer) = (std::time::Instant::now(), std::time::Instant::now());
 
+        // Tiles whose clearance changed need new Paths for bigger agents
+        let clearance_changed = match &mut get_cost {
+            CostFnWrapper::Sequential(get_cost, _) => {
+                self.annotations.clearance.update(tiles, get_cost)
+            }
+            #[cfg(feature = "parallel")]
+            CostFnWrapper::Parallel(get_cost) => self.annotations.clearance.update(tiles, get_cost),
+        };
+
+        re_trace!("update clearance", timer);
+
         let mut dirty = PointMap::default();
-        for &p in tiles {
+        for &p in tiles.iter().chain(clearance_changed.iter()) {
             let chunk_pos = self.get_chunk_pos(p);
             dirty.entry(chunk_pos).or_insert_with(Vec::new).push(p);
         }
@@ -949,6 +1796,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
 
         // map of chunk_pos => array: [Renew; 4] where array[side] says if chunk[side] needs to be renewed
         let mut renew = PointMap::default();
+        let mut neighbors = vec![];
 
         for (&cp, positions) in dirty.iter() {
             let chunk = self.get_chunk(cp);
@@ -958,7 +1806,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 for dir in Dir::all().filter(|dir| chunk.sides[dir.num()] && chunk.at_side(p, *dir))
                 {
                     // if there is a chunk in that direction
-                    let other_pos = jump_in_dir(cp, dir, size, (0, 0), (self.width, self.height))
+                    let other_pos = self
+                        .chunk_in_dir(cp, dir)
                         .expect("Internal Error #2 in PathCache. Please report this");
 
                     // mark the current and other side
@@ -986,12 +1835,62 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                         *other = Renew::Inner;
                     }
                 }
+
+                // the Tile can also be crossed to from Tiles that aren't straight across a side,
+                // see Chunk::calculate_crossing_nodes
+                neighbors.clear();
+                self.neighborhood.get_all_neighbors(p, &mut neighbors);
+                for &other in neighbors.iter().filter(|o| !chunk.in_chunk(**o)) {
+                    let other_pos = self.get_chunk_pos(other);
+                    let other_chunk = self.get_chunk(other_pos);
+                    for dir in Dir::all() {
+                        if other_chunk.sides[dir.num()] && other_chunk.at_side(other, dir) {
+                            let side =
+                                &mut renew.entry(other_pos).or_insert([Renew::No; 4])[dir.num()];
+                            if *side == Renew::No {
+                                *side = Renew::Inner;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // a Tile on the corner of a Chunk can decide if the diagonal steps between the Chunks
+        // around it are allowed, and neither end of those steps is in a changed Chunk
+        let mut corner_chunks = vec![];
+        let mut steps = vec![];
+        for (&cp, positions) in dirty.iter() {
+            let chunk = self.get_chunk(cp);
+            for &p in positions.iter().filter(|p| chunk.is_corner(**p)) {
+                neighbors.clear();
+                self.neighborhood.get_all_neighbors(p, &mut neighbors);
+                neighbors.retain(|o| !chunk.in_chunk(*o));
+                for &other in neighbors.iter() {
+                    let other_pos = self.get_chunk_pos(other);
+                    steps.clear();
+                    self.neighborhood.get_all_neighbors(other, &mut steps);
+                    if neighbors.iter().any(|third| {
+                        self.get_chunk_pos(*third) != other_pos && steps.contains(third)
+                    }) {
+                        corner_chunks.push(other_pos);
+                    }
+                }
             }
         }
+        for cp in corner_chunks {
+            dirty.entry(cp).or_insert_with(Vec::new);
+        }
 
         re_trace!("establish renew", timer);
 
-        // remove all nodes of sides in renew
+        match &mut get_cost {
+            CostFnWrapper::Sequential(get_cost, _) => self.update_link_nodes(tiles, get_cost),
+            #[cfg(feature = "parallel")]
+            CostFnWrapper::Parallel(get_cost) => self.update_link_nodes(tiles, get_cost),
+        }
+
+        // remove all nodes of sides in renew, except for the ends of links
 
         for (&cp, sides) in renew.iter() {
             let chunk_index = self.get_chunk_index(cp);
@@ -1001,6 +1900,9 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 .iter()
                 .filter(|id| {
                     let pos = self.nodes[**id].pos;
+                    if self.is_link_end(pos) {
+                        return false;
+                    }
                     let corner = chunk.is_corner(pos);
                     Dir::all().any(|dir| match sides[dir.num()] {
                             Renew::No => false,
@@ -1029,7 +1931,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
             let chunk_index = self.get_chunk_index(*cp);
             let chunk = &self.chunks[chunk_index];
             for id in chunk.nodes.iter() {
-                self.nodes[*id].edges.clear();
+                self.nodes.clear_edges(*id);
             }
         }
 
@@ -1052,9 +1954,18 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                             dir,
                             (self.width, self.height),
                             &mut get_cost,
+                            &moves,
+                            &self.annotations,
                             self.config,
                             &mut candidates,
                         );
+                        chunk.calculate_crossing_nodes(
+                            dir,
+                            (self.width, self.height),
+                            &mut get_cost,
+                            &moves,
+                            &mut candidates,
+                        );
                     }
                 }
 
@@ -1066,9 +1977,10 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 }
 
                 let all_nodes = &mut self.nodes;
+                let scale = moves.cost_scale();
                 let nodes = candidates
                     .into_iter()
-                    .map(|p| all_nodes.add_node(p, get_cost(p) as usize))
+                    .map(|p| all_nodes.add_node(p, get_cost(p) as usize * scale))
                     .to_vec();
 
                 let chunk = &mut self.chunks[chunk_index];
@@ -1079,8 +1991,9 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                     chunk.add_nodes(
                         &nodes,
                         &mut get_cost,
-                        &self.neighborhood,
+                        &moves,
                         &mut self.nodes,
+                        &self.annotations,
                         &self.config,
                     );
                 } else {
@@ -1093,6 +2006,31 @@ impl<N: Neighborhood + Sync> PathCache<N> {
 
         re_trace!("recreates sides in renew", timer);
 
+        {
+            let mut get_cost: &mut dyn FnMut(Point) -> isize = match &mut get_cost {
+                CostFnWrapper::Sequential(get_cost, _) => get_cost,
+                #[cfg(feature = "parallel")]
+                CostFnWrapper::Parallel(get_cost) => get_cost,
+            };
+
+            for cp in dirty.keys() {
+                let chunk_index = self.get_chunk_index(*cp);
+                self.chunks[chunk_index].calculate_regions(&mut get_cost, &moves);
+            }
+        }
+
+        let changed = dirty.keys().map(|cp| self.get_chunk_index(*cp)).to_vec();
+        let portals = self.portals();
+        self.components.update(
+            &self.chunks,
+            changed,
+            self.chunk_index_fn(),
+            &moves,
+            &portals,
+        );
+
+        re_trace!("update components", timer);
+
         match get_cost {
             CostFnWrapper::Sequential(mut get_cost, _) => {
                 for cp in dirty.keys() {
@@ -1108,8 +2046,9 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                     chunk.add_nodes(
                         &nodes,
                         &mut get_cost,
-                        &self.neighborhood,
+                        &moves,
                         &mut self.nodes,
+                        &self.annotations,
                         &self.config,
                     );
                 }
@@ -1126,7 +2065,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 let paths: Vec<_> = {
                     let neighborhood = &self.neighborhood;
                     let all_nodes = &self.nodes;
-                    let cache_paths = self.config.cache_paths;
+                    let annotations = &self.annotations;
+                    let config = &self.config;
 
                     self.chunks
                         .par_iter()
@@ -1136,8 +2076,10 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                             chunk.connect_nodes_parallel(
                                 &get_cost,
                                 neighborhood,
+                                moves_grid,
                                 all_nodes,
-                                cache_paths,
+                                annotations,
+                                config,
                             )
                         })
                         .collect()
@@ -1145,8 +2087,8 @@ impl<N: Neighborhood + Sync> PathCache<N> {
 
                 re_trace!("get paths", timer);
 
-                for (id, other_id, path) in paths.into_iter().flatten() {
-                    self.nodes.add_edge(id, other_id, path);
+                for edge in paths.into_iter().flatten() {
+                    edge.insert(&mut self.nodes);
                 }
 
                 for chunk_index in dirty_indices.iter() {
@@ -1160,10 +2102,99 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         }
 
         // re-establish cross-chunk connections
-        self.connect_nodes(Some(changed_nodes));
+        self.connect_nodes(Some(changed_nodes), &moves);
+        self.connect_links();
 
         re_trace!("connect nodes", timer);
+
+        self.update_levels(renew.keys().chain(dirty.keys()).copied());
+
+        re_trace!("update levels", timer);
+
+        // Paths through the changed Chunks are no longer valid
+        let mut changed = dirty.into_keys().to_vec();
+        changed.sort_unstable_by_key(|&(x, y)| (y, x));
+        for &chunk_pos in changed.iter() {
+            let index = self.get_chunk_index(chunk_pos);
+            self.chunk_generations[index] += 1;
+        }
+
         re_trace!("total time", outer_timer);
+
+        changed
+    }
+
+    /// Registers `obstacles` that apply to all searches until they are removed.
+    ///
+    /// Unlike [`tiles_changed`](PathCache::tiles_changed), this doesn't update the PathCache at
+    /// all, which makes it cheap enough to call every frame. Instead, the searches check the
+    /// cached Paths against the Obstacles when they use them. See [`Obstacles`] for details.
+    ///
+    /// The returned handle can be used to [`update`](PathCache::update_obstacles) or
+    /// [`remove`](PathCache::remove_obstacles) the Obstacles again. Blocks of several
+    /// registered Obstacles on the same Tile are combined, and their penalties add up.
+    ///
+    /// As long as any Obstacles are registered, searches don't use the higher
+    /// [`levels`](PathCacheConfig::levels) and return fully calculated Paths. [`is_reachable`](PathCache::is_reachable),
+    /// [`flow_field`](PathCache::flow_field) and [`distance_map`](PathCache::distance_map)
+    /// ignore the Obstacles.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// # let grid = [
+    /// #     [0, 0, 0, 0, 0, 0],
+    /// #     [0, 2, 2, 2, 2, 0],
+    /// #     [0, 0, 0, 0, 0, 0],
+    /// # ];
+    /// # let (width, height) = (grid[0].len(), grid.len());
+    /// # fn cost_fn(grid: &[[usize; 6]; 3]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    /// #     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// # }
+    /// let mut pathfinding: PathCache<_> = // ...
+    /// # PathCache::new(
+    /// #     (width, height),
+    /// #     &cost_fn(&grid),
+    /// #     ManhattanNeighborhood::new(width, height),
+    /// #     PathCacheConfig::with_chunk_size(3),
+    /// # );
+    /// let (start, goal) = ((0, 0), (5, 0));
+    ///
+    /// // a door in the upper corridor closes
+    /// let mut door = Obstacles::new();
+    /// door.block((3, 0));
+    /// let handle = pathfinding.add_obstacles(door);
+    ///
+    /// let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap().unwrap();
+    /// assert_eq!(path.cost(), 9);
+    ///
+    /// // and opens again
+    /// pathfinding.remove_obstacles(handle);
+    ///
+    /// let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap().unwrap();
+    /// assert_eq!(path.cost(), 5);
+    /// ```
+    pub fn add_obstacles(&mut self, obstacles: Obstacles) -> ObstacleHandle {
+        self.obstacles.add(obstacles)
+    }
+
+    /// Replaces the Obstacles registered with `handle`, e.g. after a unit moved.
+    ///
+    /// Returns `false` if the Obstacles were already removed.
+    pub fn update_obstacles(&mut self, handle: ObstacleHandle, obstacles: Obstacles) -> bool {
+        self.obstacles.update(handle, obstacles)
+    }
+
+    /// Removes the Obstacles registered with `handle` and returns them, or `None` if they were
+    /// already removed.
+    pub fn remove_obstacles(&mut self, handle: ObstacleHandle) -> Option<Obstacles> {
+        self.obstacles.remove(handle)
+    }
+
+    /// Removes all registered Obstacles. Any handles to them become invalid.
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
     }
 
     /// Allows for debugging and visualizing the PathCache
@@ -1189,7 +2220,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     /// let pathfinding: PathCache<_> = // ...
     /// # PathCache::new(
     /// #     (width, height),
-    /// #     cost_fn(&grid),
+    /// #     &cost_fn(&grid),
     /// #     ManhattanNeighborhood::new(width, height),
     /// #     PathCacheConfig::with_chunk_size(3),
     /// # );
@@ -1210,7 +2241,7 @@ impl<N: Neighborhood + Sync> PathCache<N> {
     ///     }
     /// }
     /// ```
-    pub fn inspect_nodes(&self) -> CacheInspector<N> {
+    pub fn inspect_nodes(&self) -> CacheInspector<'_, N> {
         CacheInspector::new(self)
     }
 
@@ -1233,6 +2264,21 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         ((point.0 / size) * size, (point.1 / size) * size)
     }
 
+    /// The position of the Chunk next to the one at `chunk_pos` in `dir`, across the seam if the
+    /// Grid wraps around
+    fn chunk_in_dir(&self, chunk_pos: Point, dir: Dir) -> Option<Point> {
+        let size = self.config.chunk_size;
+        let next = jump_in_dir(chunk_pos, dir, size, (0, 0), (self.width, self.height));
+        if next.is_some() || !self.neighborhood.wraps_horizontally() {
+            return next;
+        }
+        match dir {
+            LEFT => Some(self.get_chunk_pos((self.width - 1, chunk_pos.1))),
+            RIGHT => Some((0, chunk_pos.1)),
+            _ => None,
+        }
+    }
+
     fn get_chunk(&self, point: Point) -> &Chunk {
         let index = self.get_chunk_index(point);
         &self.chunks[index]
@@ -1244,9 +2290,40 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         y * self.num_chunks.0 + x
     }
 
-    fn same_chunk(&self, a: Point, b: Point) -> bool {
-        let size = self.config.chunk_size;
-        a.0 / size == b.0 / size && a.1 / size == b.1 / size
+    pub(crate) fn chunk_generation(&self, index: usize) -> Option<u64> {
+        self.chunk_generations.get(index).copied()
+    }
+
+    /// Records the Chunks that `path` crosses, see [`AbstractPath::is_valid`]
+    fn track_chunks(&self, path: &mut AbstractPath<N>) {
+        let mut chunks: hashbrown::HashMap<usize, usize> = Default::default();
+        let mut step = 0;
+        for segment in path.segments() {
+            match segment {
+                PathSegment::Known(path) => {
+                    for (i, &pos) in path.iter().enumerate() {
+                        chunks.insert(self.get_chunk_index(pos), step + i);
+                    }
+                }
+                PathSegment::Unknown { start, end, .. } => {
+                    // either a Path within one Chunk or a single step between two Chunks
+                    chunks.insert(self.get_chunk_index(*start), step);
+                    chunks.insert(self.get_chunk_index(*end), step + segment.len() - 1);
+                }
+            }
+            step += segment.len() - 1;
+        }
+        let mut chunks = chunks
+            .into_iter()
+            .map(|(index, last)| (index, self.chunk_generations[index], last))
+            .to_vec();
+        chunks.sort_unstable();
+        path.set_chunks(chunks, self.config.chunk_size);
+    }
+
+    fn same_chunk(&self, a: Point, b: Point) -> bool {
+        let size = self.config.chunk_size;
+        a.0 / size == b.0 / size && a.1 / size == b.1 / size
     }
 
     fn node_at(&self, pos: Point) -> Option<NodeID> {
@@ -1258,50 +2335,146 @@ impl<N: Neighborhood + Sync> PathCache<N> {
         &self.config
     }
 
+    /// Returns the [`generation`](CostGrid::generation) of the Grid that this PathCache was
+    /// created or last updated with, or `None` if that was done with a cost function.
+    pub fn grid_generation(&self) -> Option<u64> {
+        self.generation
+    }
+
+    /// Finds the closest Node to `pos` within its Chunk.
+    ///
+    /// `reverse_component` is `None` for Paths starting at `pos`. For Paths ending at `pos`, it
+    /// is the component that the Path starts in.
     fn find_nearest_node(
         &self,
         pos: Point,
         get_cost: impl FnMut(Point) -> isize,
-        reverse: bool,
+        reverse_component: Option<u32>,
+        neighborhood: &MoveCosts<N>,
     ) -> Option<(NodeID, Option<Path<Point>>)> {
         if let Some(id) = self.node_at(pos) {
             return Some((id, None));
         }
         self.get_chunk(pos)
-            .nearest_node(&self.nodes, pos, get_cost, &self.neighborhood, reverse)
+            .nearest_node(
+                &self.nodes,
+                pos,
+                get_cost,
+                neighborhood,
+                reverse_component.is_some(),
+                |id| reverse_component == self.component_at(self.nodes[id].pos),
+                self.config.search_options(),
+            )
             .map(|(id, path)| (id, Some(path)))
     }
 
-    fn grid_a_star(
+    /// The component of a Tile, or `None` if the Tile is solid
+    fn component_at(&self, point: Point) -> Option<u32> {
+        let index = self.get_chunk_index(point);
+        self.components.get(index, &self.chunks[index], point)
+    }
+
+    /// Checks if `goal` can be reached from the Tiles in `component`
+    fn can_reach(&self, component: u32, goal: Point) -> bool {
+        if let Some(goal_component) = self.component_at(goal) {
+            return goal_component == component;
+        }
+        // solid goals can be reached from any of their neighbors
+        let mut neighbors = vec![];
+        self.neighborhood.get_all_neighbors(goal, &mut neighbors);
+        neighbors.into_iter().any(|p| {
+            self.component_at(p) == Some(component)
+                && self.neighborhood.is_step_allowed(p, goal, self.solid_fn())
+        })
+    }
+
+    /// A cost function that only knows which Tiles are solid, from the regions of the Chunks.
+    /// Enough for [`Neighborhood::is_step_allowed`]
+    fn solid_fn(&self) -> impl Fn(Point) -> isize + '_ {
+        move |p| {
+            if self.chunks[self.get_chunk_index(p)].region_at(p) == SOLID {
+                -1
+            } else {
+                0
+            }
+        }
+    }
+
+    /// Checks that `grid` matches the Grid that the PathCache was created or last updated with
+    fn check_grid<G: CostGrid>(&self, grid: &G) -> Result<(), GridMismatch> {
+        GridMismatch::check(self.generation, self.moves, grid)
+    }
+
+    /// Panics if `grid` doesn't have move costs like the Grid that the PathCache was created with,
+    /// for updates that can't return a [`GridMismatch`]
+    fn assert_moves<G: CostGrid>(&self, grid: &G) {
+        assert_eq!(
+            grid.has_moves(),
+            self.moves,
+            "the Grid has to have move costs if and only if the PathCache was created with them"
+        );
+    }
+
+    /// The costs of `grid` for `agent`, with the Tiles it can't enter being solid
+    fn agent_cost_fn<'a, G: CostGrid>(
+        &'a self,
+        agent: Agent,
+        grid: &'a G,
+    ) -> impl Fn(Point) -> isize + 'a {
+        move |p| {
+            if agent.is_default() || self.annotations.allows(agent, p) {
+                grid.cost(p)
+            } else {
+                -1
+            }
+        }
+    }
+
+    /// A standalone version of [`get_chunk_index`](PathCache::get_chunk_index)
+    fn chunk_index_fn(&self) -> impl Fn(Point) -> usize {
+        let (size, num_chunks_w) = (self.config.chunk_size, self.num_chunks.0);
+        move |(x, y)| (y / size) * num_chunks_w + x / size
+    }
+
+    fn grid_a_star_with(
         &self,
+        neighborhood: &impl Neighborhood,
         start: Point,
         goal: Point,
         get_cost: impl FnMut(Point) -> isize,
     ) -> Option<Path<Point>> {
-        let heuristic = self.neighborhood.heuristic(start, goal);
-        let max_heuristic = self
-            .neighborhood
-            .heuristic((0, 0), (self.width - 1, self.height - 1));
-        let max_size = self.width * self.height;
-        let size_hint = heuristic as f32 / max_heuristic as f32 * max_size as f32;
-
         grid::a_star_search(
-            &self.neighborhood,
+            neighborhood,
             |_| true,
             get_cost,
             start,
             goal,
-            size_hint as usize,
+            self.grid_size_hint(start, goal),
+            self.config.search_options(),
         )
     }
 
+    /// size hint for the number of Tiles visited by a [`grid::a_star_search`]
+    fn grid_size_hint(&self, start: Point, goal: Point) -> usize {
+        let heuristic = self.neighborhood.heuristic(start, goal);
+        let max_heuristic = self
+            .neighborhood
+            .heuristic((0, 0), (self.width - 1, self.height - 1));
+        let max_size = self.width * self.height;
+        (heuristic as f32 / max_heuristic as f32 * max_size as f32) as usize
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn resolve_paths(
         &self,
         start: Point,
         start_path: Option<Path<Point>>,
         goal_data: &[(Point, NodeID, Option<Path<Point>>)],
         paths: &NodeIDMap<Path<NodeID>>,
+        revalidated: &Revalidated,
+        agent: Agent,
         mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &MoveCosts<N>,
     ) -> PointMap<AbstractPath<N>> {
         let mut start_path_map = PointMap::default();
         let mut ret = PointMap::default();
@@ -1313,85 +2486,189 @@ impl<N: Neighborhood + Sync> PathCache<N> {
                 continue;
             };
 
-            let mut start_path = start_path.as_ref();
-            let mut skip_first = false;
-            let mut skip_last = false;
-            if start_path.is_some() {
-                let after_start = self.nodes[path[1]].pos;
-                if self.same_chunk(start, after_start) {
-                    start_path = Some(start_path_map.entry(after_start).or_insert_with(|| {
-                        // this is contained within a chunk, because start_path is contained and
-                        // (start_id, after_start) must be contained:
-                        // Direct paths between nodes are only added in chunk::(connect/add)_nodes,
-                        // or in the cross-chunk connect_nodes
-                        self.get_chunk(start)
-                            .find_path(start, after_start, &mut get_cost, &self.neighborhood)
-                            .expect("Inconsistency in Pathfinding")
-                    }));
-                    skip_first = true;
+            if path.len() <= 2 && !self.uses_link(path) {
+                // start and goal are close enough to share their nearest Node, or to skip both
+                // Nodes below => search directly, like find_path
+                if let Some(path) = self.grid_a_star_with(neighborhood, start, *goal, &mut get_cost)
+                {
+                    let path = AbstractPath::from_known_path(self.neighborhood.clone(), path);
+                    ret.insert(*goal, path);
                 }
+                continue;
             }
 
-            // path: ... -> before_goal (len-2) -> goal_id (len-1) (-> actual goal (would be next))
-            // check if direct connection of before_goal -> actual goal is feasible
-            let before_goal = self.nodes[path[path.len() - 2]].pos;
-            if goal_path.is_some() && self.same_chunk(*goal, before_goal) {
-                skip_last = true;
+            let mut final_path = AbstractPath::new(self.neighborhood.clone(), start);
+            final_path.set_search_options(self.config.search_options());
+            let parts = self.path_parts(
+                (start, start_path.as_ref()),
+                (*goal, goal_path.as_ref()),
+                path,
+                revalidated,
+                agent,
+            );
+            for part in parts {
+                match part {
+                    PathPart::Known(segment) => {
+                        final_path.add_path_segment(segment);
+                    }
+                    PathPart::Search {
+                        start: from,
+                        goal: to,
+                        chunk,
+                    } => {
+                        let mut search = || {
+                            let valid =
+                                |p| chunk.is_none_or(|index| self.chunks[index].in_chunk(p));
+                            grid::a_star_search(
+                                neighborhood,
+                                valid,
+                                &mut get_cost,
+                                from,
+                                to,
+                                self.grid_size_hint(from, to),
+                                self.config.search_options(),
+                            )
+                            .expect("Inconsistency in Pathfinding")
+                        };
+                        let path = if from == start && chunk.is_some() {
+                            // the way into the Graph is the same for all goals
+                            start_path_map.entry(to).or_insert_with(search).clone()
+                        } else {
+                            search()
+                        };
+                        final_path.add_path(path);
+                    }
+                }
             }
+            ret.insert(*goal, final_path);
+        }
+        ret
+    }
 
-            let mut final_path = if let Some(path) = start_path {
-                AbstractPath::from_known_path(self.neighborhood.clone(), path.clone())
+    /// Splits the Path from `start` over the Nodes of `path` to `goal` into the parts that are
+    /// already known and the parts that still have to be searched on the Grid.
+    ///
+    /// `path` has to have more than 2 Nodes or use a link.
+    fn path_parts(
+        &self,
+        (start, start_path): (Point, Option<&Path<Point>>),
+        (goal, goal_path): (Point, Option<&Path<Point>>),
+        path: &Path<NodeID>,
+        revalidated: &Revalidated,
+        agent: Agent,
+    ) -> Vec<PathPart> {
+        let graph = AgentGraph::new(&self.nodes, &self.annotations, agent);
+        let mut parts = Vec::with_capacity(path.len() + 1);
+
+        let mut skip_first = false;
+        let mut skip_last = false;
+        if let Some(start_path) = start_path {
+            let after_start = self.nodes[path[1]].pos;
+            let start_node = self.nodes[path[0]].pos;
+            if self.same_chunk(start, after_start) && !self.is_link(start_node, after_start) {
+                // this is contained within a chunk, because start_path is contained and
+                // (start_id, after_start) must be contained:
+                // Direct paths between nodes are only added in chunk::(connect/add)_nodes,
+                // or in the cross-chunk connect_nodes
+                parts.push(PathPart::Search {
+                    start,
+                    goal: after_start,
+                    chunk: Some(self.get_chunk_index(start)),
+                });
+                skip_first = true;
             } else {
-                AbstractPath::new(self.neighborhood.clone(), start)
-            };
+                parts.push(PathPart::Known(PathSegment::Known(start_path.clone())));
+            }
+        }
 
-            for (i, (a, b)) in path.iter().zip(path.iter().skip(1)).enumerate() {
-                if (skip_first && i == 0) || (skip_last && i == path.len() - 2) {
-                    // len() - 2 because skip(1) already removes one
-                    continue;
+        // path: ... -> before_goal (len-2) -> goal_id (len-1) (-> actual goal (would be next))
+        // check if direct connection of before_goal -> actual goal is feasible
+        let before_goal = self.nodes[path[path.len() - 2]].pos;
+        let goal_node = self.nodes[path[path.len() - 1]].pos;
+        if goal_path.is_some()
+            && self.same_chunk(goal, before_goal)
+            && !self.is_link(before_goal, goal_node)
+        {
+            skip_last = true;
+        }
+
+        for (i, (a, b)) in path.iter().zip(path.iter().skip(1)).enumerate() {
+            if (skip_first && i == 0) || (skip_last && i == path.len() - 2) {
+                // len() - 2 because skip(1) already removes one
+                continue;
+            }
+            let segment = revalidated
+                .edge(&graph, *a, *b)
+                .expect("Internal Error #3 in PathCache. Please report this");
+            match segment {
+                PathSegment::Unknown { start, end, .. }
+                    if !agent.is_default() || revalidated.is_active() =>
+                {
+                    // resolving the Path later would not respect the restrictions of the agent
+                    // or the Obstacles
+                    parts.push(PathPart::Search {
+                        start: *start,
+                        goal: *end,
+                        chunk: None,
+                    });
                 }
-                final_path.add_path_segment(self.nodes[*a].edges[b].clone());
+                _ => parts.push(PathPart::Known(segment.clone())),
             }
+        }
 
-            if skip_last {
-                final_path.add_path(
-                    // reasoning for chunk containment: see start_path equivalent
-                    self.get_chunk(before_goal)
-                        .find_path(before_goal, *goal, &mut get_cost, &self.neighborhood)
-                        .expect("Inconsistency in Pathfinding"),
-                );
-            } else if let Some(path) = goal_path {
-                final_path.add_path(path.clone());
-            }
-            ret.insert(*goal, final_path);
+        if skip_last {
+            // reasoning for chunk containment: see start_path equivalent
+            parts.push(PathPart::Search {
+                start: before_goal,
+                goal,
+                chunk: Some(self.get_chunk_index(before_goal)),
+            });
+        } else if let Some(path) = goal_path {
+            parts.push(PathPart::Known(PathSegment::Known(path.clone())));
         }
-        ret
+        parts
     }
 
-    fn connect_nodes(&mut self, ids: Option<NodeIDSet>) {
+    fn connect_nodes(&mut self, ids: Option<NodeIDSet>, neighborhood: &MoveCosts<N>) {
         let ids = ids.unwrap_or_else(|| self.nodes.keys().collect());
         let mut target = vec![];
         for id in ids {
-            let (pos, cost) = {
-                let node = &self.nodes[id];
-                (node.pos, node.walk_cost)
-            };
+            let pos = self.nodes[id].pos;
             target.clear();
-            self.neighborhood.get_all_neighbors(pos, &mut target);
+            neighborhood.get_all_neighbors(pos, &mut target);
             for &other_pos in target.iter() {
                 if let Some(other_id) = self.node_at(other_pos) {
-                    self.nodes.add_edge(
-                        id,
-                        other_id,
-                        PathSegment::new(
-                            Path::from_slice(&[pos, other_pos], cost),
-                            self.config.cache_paths,
-                        ),
-                    );
+                    self.connect_step(id, other_id, neighborhood);
+                    if self.nodes.is_directed() {
+                        // otherwise, add_edge already added the step back
+                        self.connect_step(other_id, id, neighborhood);
+                    }
                 }
             }
         }
     }
+
+    /// Adds the edge for a single step between two neighboring Nodes, if it is allowed
+    fn connect_step(&mut self, id: NodeID, other_id: NodeID, neighborhood: &MoveCosts<N>) {
+        let scale = neighborhood.cost_scale();
+        let (pos, cost) = (self.nodes[id].pos, self.nodes[id].walk_cost / scale);
+        let (other_pos, other_cost) = (
+            self.nodes[other_id].pos,
+            self.nodes[other_id].walk_cost / scale,
+        );
+        if !neighborhood.is_step_allowed(pos, other_pos, self.solid_fn()) {
+            return;
+        }
+        let cost = neighborhood.step_cost(pos, other_pos, cost, other_cost);
+        self.nodes.add_edge(
+            id,
+            other_id,
+            PathSegment::new(
+                Path::from_slice(&[pos, other_pos], cost),
+                self.config.cache_paths,
+            ),
+        );
+    }
 }
 
 /// Allows for debugging and visualizing a PathCache.
@@ -1419,7 +2696,7 @@ impl<'a, N: Neighborhood> CacheInspector<'a, N> {
     /// Provides the handle to a specific Node.
     ///
     /// It is recommended to use the `Iterator` implementation instead
-    pub fn get_node(&self, id: NodeID) -> NodeInspector<N> {
+    pub fn get_node(&self, id: NodeID) -> NodeInspector<'_, N> {
         NodeInspector::new(self.src, id)
     }
 }
@@ -1492,13 +2769,13 @@ mod tests {
         }
         let pathfinding = PathCache::new(
             (width, height),
-            cost_fn(&grid),
+            &cost_fn(&grid),
             ManhattanNeighborhood::new(width, height),
             PathCacheConfig::with_chunk_size(3),
         );
         let start = (0, 0);
         let goal = (4, 4);
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         let path = path.unwrap();
         let points: Vec<(usize, usize)> = path.collect();
         #[rustfmt::skip]
@@ -1523,7 +2800,7 @@ mod tests {
         }
         let pathfinding = PathCache::new(
             (width, height),
-            cost_fn(&grid),
+            &cost_fn(&grid),
             ManhattanNeighborhood::new(width, height),
             PathCacheConfig::with_chunk_size(3),
         );
@@ -1560,14 +2837,14 @@ mod tests {
 
         let mut pathfinding = PathCache::new(
             (width, height),
-            cost_fn(&grid),
+            &cost_fn(&grid),
             MooreNeighborhood::new(width, height),
             PathCacheConfig::with_chunk_size(3),
         );
 
         let start = (0, 0);
         let goal = (4, 4);
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         let path = path.unwrap();
         let points: Vec<(usize, usize)> = path.collect();
         #[rustfmt::skip]
@@ -1579,11 +2856,11 @@ mod tests {
         grid[2][1] = 0;
         let changed_tiles = [(1, 2)];
 
-        pathfinding.tiles_changed(&changed_tiles, cost_fn(&grid));
+        pathfinding.tiles_changed(&changed_tiles, &cost_fn(&grid));
 
         let start = (0, 0);
         let goal = (4, 4);
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         let path = path.unwrap();
         let points: Vec<(usize, usize)> = path.collect();
         #[rustfmt::skip]
@@ -1595,19 +2872,19 @@ mod tests {
         // Add walls along chunk borders
         let start = (0, 0);
         let goal = (3, 4);
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         assert!(path.is_some());
 
         // Vertical wall
         let changed_tiles: Vec<_> = (0..grid.len()).map(|y| (2, y)).collect();
         grid.iter_mut().for_each(|row| row[2] = 2);
-        pathfinding.tiles_changed(&changed_tiles, cost_fn(&grid));
+        pathfinding.tiles_changed(&changed_tiles, &cost_fn(&grid));
 
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         assert!(path.is_none());
 
         let goal = (2, 4);
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         assert!(path.is_some());
 
         // Horizontal wall
@@ -1619,12 +2896,1424 @@ mod tests {
                 changed_tiles.push((x, y));
             }
         }
-        pathfinding.tiles_changed(&changed_tiles, cost_fn(&grid));
+        pathfinding.tiles_changed(&changed_tiles, &cost_fn(&grid));
 
-        let path = pathfinding.find_path(start, goal, cost_fn(&grid));
+        let path = pathfinding.find_path(start, goal, &cost_fn(&grid)).unwrap();
         assert!(path.is_none());
     }
 
+    #[test]
+    fn multi_level() {
+        let size = 64;
+        let mut grid = vec![vec![1_isize; size]; size];
+        let config = PathCacheConfig {
+            chunk_size: 3,
+            levels: 3,
+            ..Default::default()
+        };
+
+        let mut pathfinding = PathCache::new(
+            (size, size),
+            &|(x, y): (usize, usize)| grid[y][x],
+            ManhattanNeighborhood::new(size, size),
+            config,
+        );
+        // a third level would only have a single Chunk
+        assert_eq!(pathfinding.levels.len(), 2);
+        assert!(!pathfinding.levels[1].edges.is_empty());
+
+        let (start, goal) = ((0, 0), (size - 1, 0));
+        let find = |pathfinding: &PathCache<ManhattanNeighborhood>, grid: &Vec<Vec<isize>>| {
+            let cost_fn = |(x, y): (usize, usize)| grid[y][x];
+            let reachable = pathfinding.is_reachable(start, goal, &cost_fn).unwrap();
+            let path = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+            assert_eq!(reachable, path.is_some());
+            path.map(|path| path.collect::<Vec<_>>())
+        };
+
+        let path = find(&pathfinding, &grid).unwrap();
+        assert_eq!(path.len(), size - 1);
+
+        // a wall across the whole Grid with a single gap
+        let mut changed = vec![];
+        for (y, row) in grid.iter_mut().enumerate() {
+            row[17] = -1;
+            changed.push((17, y));
+        }
+        grid[40][17] = 1;
+        pathfinding.tiles_changed(&changed, &|(x, y): (usize, usize)| grid[y][x]);
+
+        let path = find(&pathfinding, &grid).unwrap();
+        assert!(path.contains(&(17, 40)));
+        assert_eq!(path.len(), 2 * 40 + size - 1);
+
+        grid[40][17] = -1;
+        pathfinding.tiles_changed(&[(17, 40)], &|(x, y): (usize, usize)| grid[y][x]);
+
+        assert_eq!(find(&pathfinding, &grid), None);
+    }
+
+    #[test]
+    fn levels_expand_fewer_nodes() {
+        use crate::Point;
+        use std::task::Poll;
+
+        // a room with a wall across the middle that has a single gap on the far side
+        let size = 96;
+        let cost_fn = |(x, y): Point| if y == size / 2 && x < size - 2 { -1 } else { 1 };
+        let (start, goal) = ((1, 1), (2, size - 2));
+
+        let expanded = |levels: usize| {
+            let pathfinding = PathCache::new(
+                (size, size),
+                &cost_fn,
+                ManhattanNeighborhood::new(size, size),
+                PathCacheConfig {
+                    levels,
+                    ..PathCacheConfig::with_chunk_size(4)
+                },
+            );
+            let mut query = pathfinding.begin_find_path(start, goal, &cost_fn).unwrap();
+            let mut steps = 0;
+            let path = loop {
+                match query.step(1) {
+                    Poll::Ready(path) => break path,
+                    Poll::Pending => steps += 1,
+                }
+            };
+            // the way through the gap costs 278, and going from Node to Node can add a detour of a
+            // Tile, depending on how the ties between equally short Paths are broken
+            let cost = path.unwrap().cost();
+            assert!(cost == 278 || cost == 280, "levels {}: {}", levels, cost);
+            steps
+        };
+
+        let (one, two, three) = (expanded(1), expanded(2), expanded(3));
+        assert!(two < one / 2, "{} vs {}", two, one);
+        assert!(three < two, "{} vs {}", three, two);
+    }
+
+    #[test]
+    fn update_levels() {
+        use crate::{path::Cost, NodeID, Point};
+
+        let size = 96;
+        let mut grid = vec![vec![1_isize; size]; size];
+        let config = PathCacheConfig {
+            levels: 3,
+            ..PathCacheConfig::with_chunk_size(3)
+        };
+        let mut pathfinding = PathCache::new(
+            (size, size),
+            &|(x, y): Point| grid[y][x],
+            ManhattanNeighborhood::new(size, size),
+            config,
+        );
+
+        // the edges on every level, by the positions of their Nodes
+        let level_edges = |pathfinding: &PathCache<ManhattanNeighborhood>| {
+            let pos = |id: &NodeID| pathfinding.nodes[*id].pos;
+            pathfinding
+                .levels
+                .iter()
+                .map(|level| {
+                    let mut edges: Vec<(Point, Point, Cost)> = level
+                        .edges
+                        .iter()
+                        .flat_map(|(id, edges)| {
+                            edges
+                                .iter()
+                                .map(move |(other, path)| (pos(id), pos(other), path.cost()))
+                        })
+                        .collect();
+                    edges.sort_unstable();
+                    edges
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the incoming edges that bidirectional searches follow match the edges
+        let check_incoming = |pathfinding: &PathCache<ManhattanNeighborhood>| {
+            for level in pathfinding.levels.iter() {
+                let mut indexed = level.clone();
+                indexed.index_edges();
+                assert_eq!(level.incoming, indexed.incoming);
+                assert!(pathfinding
+                    .nodes
+                    .keys()
+                    .all(|id| level.contains(id) == level.edges.contains_key(&id)));
+            }
+        };
+
+        // a change deep inside a Chunk, which doesn't change any Paths between its Nodes, then a
+        // wall across several Chunks of every level
+        let changes = [
+            vec![(25, 25)],
+            (2..90).map(|y| (40, y)).collect(),
+            (2..90).map(|y| (40, y)).collect(),
+        ];
+        for (i, changed) in changes.iter().enumerate() {
+            for &(x, y) in changed {
+                grid[y][x] = [5, -1, 1][i];
+            }
+            pathfinding.tiles_changed(changed, &|(x, y): Point| grid[y][x]);
+            let fresh = PathCache::new(
+                (size, size),
+                &|(x, y): Point| grid[y][x],
+                ManhattanNeighborhood::new(size, size),
+                config,
+            );
+            assert_eq!(
+                level_edges(&pathfinding),
+                level_edges(&fresh),
+                "change {}",
+                i
+            );
+            check_incoming(&pathfinding);
+        }
+
+        // the ends of a link are Nodes on every level, until the link is removed again
+        let cost_fn = |(x, y): Point| grid[y][x];
+        pathfinding.add_link((5, 5), (80, 80), 3, true, &cost_fn);
+        check_incoming(&pathfinding);
+        assert!(pathfinding.remove_link((5, 5), (80, 80), &cost_fn));
+        check_incoming(&pathfinding);
+    }
+
+    #[test]
+    fn wrapping() {
+        use crate::Point;
+
+        // a wall in the middle that can only be passed across the seam
+        let mut tiles = [
+            "......#.....",
+            "......#.....",
+            "......#.....",
+            "......#.....",
+        ]
+        .map(|row| row.as_bytes().to_vec());
+        let (width, height) = (tiles[0].len(), tiles.len());
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Fn(Point) -> isize {
+            move |(x, y)| if tiles[y][x] == b'#' { -1 } else { 1 }
+        }
+        let config = PathCacheConfig {
+            levels: 2,
+            ..PathCacheConfig::with_chunk_size(3)
+        };
+        let mut pathfinding = PathCache::new(
+            (width, height),
+            &cost_fn(&tiles),
+            ManhattanNeighborhood::new_wrapping(width, height),
+            config,
+        );
+
+        let path = pathfinding
+            .find_path((1, 1), (10, 1), &cost_fn(&tiles))
+            .unwrap();
+        let path: Vec<_> = path.unwrap().collect();
+        assert_eq!(path, [(0, 1), (11, 1), (10, 1)]);
+
+        let path = pathfinding
+            .find_path((10, 1), (1, 1), &cost_fn(&tiles))
+            .unwrap();
+        let path: Vec<_> = path.unwrap().collect();
+        assert_eq!(path, [(11, 1), (0, 1), (1, 1)]);
+
+        // only the bottom row is left open at the seam
+        for row in &mut tiles[..3] {
+            row[0] = b'#';
+        }
+        let changed = [(0, 0), (0, 1), (0, 2)];
+        pathfinding.tiles_changed(&changed, &cost_fn(&tiles));
+
+        let path = pathfinding
+            .find_path((1, 1), (10, 1), &cost_fn(&tiles))
+            .unwrap();
+        let path = path.unwrap();
+        assert_eq!(path.cost(), 7);
+        let path: Vec<_> = path.collect();
+        assert!(path.contains(&(0, 3)) && path.contains(&(11, 3)));
+
+        tiles[3][0] = b'#';
+        pathfinding.tiles_changed(&[(0, 3)], &cost_fn(&tiles));
+
+        let path = pathfinding
+            .find_path((1, 1), (10, 1), &cost_fn(&tiles))
+            .unwrap();
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn hex_grid() {
+        use crate::neighbors::{HexLayout, HexNeighborhood};
+        use crate::Point;
+
+        // a diagonal corridor that can't be followed on a square Grid
+        #[rustfmt::skip]
+        let fixtures = [
+            (HexLayout::OddR, (0, 5), (3, 0), [
+                "###.##",
+                "##.###",
+                "##.###",
+                "#.####",
+                "#.####",
+                ".#####",
+            ]),
+            (HexLayout::EvenQ, (0, 0), (5, 3), [
+                ".#####",
+                "#..###",
+                "###..#",
+                "#####.",
+                "######",
+                "######",
+            ]),
+            (HexLayout::Axial, (0, 5), (5, 0), [
+                "#####.",
+                "####.#",
+                "###.##",
+                "##.###",
+                "#.####",
+                ".#####",
+            ]),
+        ];
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Fn(Point) -> isize {
+            move |(x, y)| if tiles[y][x] == b'#' { -1 } else { 1 }
+        }
+
+        for (layout, start, goal, tiles) in fixtures {
+            let mut tiles = tiles.map(|row| row.as_bytes().to_vec());
+            let (width, height) = (tiles[0].len(), tiles.len());
+            let neighborhood = HexNeighborhood::new(width, height, layout);
+            let mut pathfinding = PathCache::new(
+                (width, height),
+                &cost_fn(&tiles),
+                neighborhood,
+                PathCacheConfig::with_chunk_size(3),
+            );
+
+            let path = pathfinding
+                .find_path(start, goal, &cost_fn(&tiles))
+                .unwrap();
+            let path = path.unwrap();
+            assert_eq!(path.cost(), 5, "{:?}", layout);
+            let corridor: Vec<_> = path.collect();
+            let mut prev = start;
+            let mut target = vec![];
+            for &p in &corridor {
+                target.clear();
+                neighborhood.get_all_neighbors(prev, &mut target);
+                assert!(target.contains(&p), "{:?}: {:?} -> {:?}", layout, prev, p);
+                prev = p;
+            }
+            assert_eq!(prev, goal);
+
+            let square = PathCache::new(
+                (width, height),
+                &cost_fn(&tiles),
+                ManhattanNeighborhood::new(width, height),
+                PathCacheConfig::with_chunk_size(3),
+            );
+            let path = square.find_path(start, goal, &cost_fn(&tiles)).unwrap();
+            assert!(path.is_none(), "{:?}", layout);
+
+            // close the corridor in the middle
+            let (x, y) = corridor[2];
+            tiles[y][x] = b'#';
+            pathfinding.tiles_changed(&[(x, y)], &cost_fn(&tiles));
+
+            let path = pathfinding
+                .find_path(start, goal, &cost_fn(&tiles))
+                .unwrap();
+            assert!(path.is_none(), "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn diagonal_rules() {
+        use crate::neighbors::DiagonalRule;
+        use crate::Point;
+
+        // a wall with a diagonal gap on the corner between four Chunks
+        #[rustfmt::skip]
+        let rows = [
+            "......",
+            "......",
+            "###...",
+            "...###",
+            "......",
+            "......",
+        ];
+        let mut tiles = rows.map(|row| row.as_bytes().to_vec());
+        let (width, height) = (tiles[0].len(), tiles.len());
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Fn(Point) -> isize {
+            move |(x, y)| if tiles[y][x] == b'#' { -1 } else { 1 }
+        }
+        let rules = [
+            DiagonalRule::Always,
+            DiagonalRule::NoCorners,
+            DiagonalRule::NoSqueezing,
+        ];
+        let mut caches = rules.map(|rule| {
+            let neighborhood = MooreNeighborhood::new(width, height).with_diagonal_rule(rule);
+            let config = PathCacheConfig::with_chunk_size(3);
+            (
+                neighborhood,
+                PathCache::new((width, height), &cost_fn(&tiles), neighborhood, config),
+            )
+        });
+
+        let find = |(neighborhood, pathfinding): &(MooreNeighborhood, PathCache<_>),
+                    tiles: &[Vec<u8>]| {
+            let (start, goal) = ((0, 0), (0, 5));
+            let cost_fn = cost_fn(tiles);
+            let path = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+            let reachable = pathfinding.is_reachable(start, goal, &cost_fn).unwrap();
+            assert_eq!(reachable, path.is_some());
+            let path = path?;
+            let cost = path.cost();
+            let mut prev = start;
+            for p in path {
+                assert!(neighborhood.is_step_allowed(prev, p, &cost_fn));
+                prev = p;
+            }
+            assert_eq!(prev, goal);
+            Some(cost)
+        };
+
+        // only squeezing between the walls gets through
+        let costs: Vec<_> = caches.iter().map(|cache| find(cache, &tiles)).collect();
+        assert_eq!(costs, [Some(6), None, None]);
+
+        // open the corner of the gap, so that the diagonal step only cuts it
+        tiles[3][3] = b'.';
+        for (_, pathfinding) in &mut caches {
+            pathfinding.tiles_changed(&[(3, 3)], &cost_fn(&tiles));
+        }
+        let costs: Vec<_> = caches.iter().map(|cache| find(cache, &tiles)).collect();
+        assert_eq!(costs, [Some(6), Some(8), Some(6)]);
+    }
+
+    #[test]
+    fn diagonal_rules_with_levels() {
+        use crate::neighbors::DiagonalRule;
+        use crate::Point;
+
+        // a wall with a diagonal gap on the corner between four Chunks of the second level
+        let size = 16;
+        let mut tiles = vec![vec![b'.'; size]; size];
+        tiles[7][..8].fill(b'#');
+        tiles[8][8..].fill(b'#');
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Fn(Point) -> isize {
+            move |(x, y)| if tiles[y][x] == b'#' { -1 } else { 1 }
+        }
+        let neighborhood =
+            MooreNeighborhood::new(size, size).with_diagonal_rule(DiagonalRule::NoCorners);
+        let mut pathfinding = PathCache::new(
+            (size, size),
+            &cost_fn(&tiles),
+            neighborhood,
+            PathCacheConfig {
+                levels: 2,
+                ..PathCacheConfig::with_chunk_size(2)
+            },
+        );
+        let (start, goal) = ((0, 0), (0, size - 1));
+
+        let path = pathfinding
+            .find_path(start, goal, &cost_fn(&tiles))
+            .unwrap();
+        assert!(path.is_none());
+
+        tiles[8][8] = b'.';
+        pathfinding.tiles_changed(&[(8, 8)], &cost_fn(&tiles));
+
+        let path = pathfinding
+            .find_path(start, goal, &cost_fn(&tiles))
+            .unwrap();
+        let path: Vec<_> = path.unwrap().collect();
+        // the diagonal step through the gap would cut the corners
+        assert!(path.contains(&(8, 8)));
+        let mut prev = start;
+        for &p in &path {
+            assert!(neighborhood.is_step_allowed(prev, p, cost_fn(&tiles)));
+            prev = p;
+        }
+        assert_eq!(prev, goal);
+
+        tiles[8][8] = b'#';
+        pathfinding.tiles_changed(&[(8, 8)], &cost_fn(&tiles));
+
+        let path = pathfinding
+            .find_path(start, goal, &cost_fn(&tiles))
+            .unwrap();
+        assert!(path.is_none());
+    }
+
+    #[test]
+    fn octile() {
+        use crate::{path::Cost, Point};
+
+        // a straight corridor and a diagonal one with a more expensive Tile
+        #[rustfmt::skip]
+        let rows = [
+            "......",
+            "#.####",
+            "##3###",
+            "###.##",
+            "####.#",
+            "#####.",
+        ];
+        let (width, height) = (rows[0].len(), rows.len());
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Fn(Point) -> isize {
+            move |(x, y)| match tiles[y][x] {
+                b'#' => -1,
+                b'.' => 1,
+                c => (c - b'0') as isize,
+            }
+        }
+        let neighborhood = MooreNeighborhood::new_octile(width, height);
+
+        for cache_paths in [true, false] {
+            let mut tiles = rows.map(|row| row.as_bytes().to_vec());
+            let mut pathfinding = PathCache::new(
+                (width, height),
+                &cost_fn(&tiles),
+                neighborhood,
+                PathCacheConfig {
+                    cache_paths,
+                    ..PathCacheConfig::with_chunk_size(3)
+                },
+            );
+
+            // the cost of the Path is the sum of its steps
+            let find = |pathfinding: &PathCache<MooreNeighborhood>, tiles: &[Vec<u8>], goal| {
+                let cost_fn = cost_fn(tiles);
+                let start = (0, 0);
+                let mut path = pathfinding
+                    .find_path(start, goal, &cost_fn)
+                    .unwrap()
+                    .unwrap();
+                let mut prev = start;
+                let mut total = 0;
+                while let Some(p) = path.safe_next(&cost_fn).unwrap() {
+                    total +=
+                        neighborhood.step_cost(prev, p, cost_fn(prev) as Cost, cost_fn(p) as Cost);
+                    prev = p;
+                }
+                assert_eq!(prev, goal);
+                assert_eq!(total, path.cost(), "cache_paths: {}", cache_paths);
+                total
+            };
+
+            assert_eq!(find(&pathfinding, &tiles, (5, 0)), 5 * 10);
+            // leaving the Tile with cost 3 diagonally costs 12 * 3 + 2 * 1, and entering it
+            // 12 * 1 + 2 * 3
+            assert_eq!(find(&pathfinding, &tiles, (5, 5)), 3 * 14 + 18 + 38);
+
+            tiles[2][2] = b'.';
+            pathfinding.tiles_changed(&[(2, 2)], &cost_fn(&tiles));
+
+            assert_eq!(find(&pathfinding, &tiles, (5, 5)), 5 * 14);
+        }
+    }
+
+    #[test]
+    fn moves() {
+        use super::MoveCosts;
+        use crate::{path::Cost, Point};
+
+        // a conveyor belt below a floor that costs twice as much
+        #[rustfmt::skip]
+        let rows = [
+            "............",
+            ">>>>>>>>>>>>",
+        ];
+        let (width, height) = (rows[0].len(), rows.len());
+
+        // moving along a belt costs 1, moving against it is not allowed, and getting off it
+        // costs twice as much as the belt
+        fn move_cost(tiles: &[Vec<u8>], from: Point, to: Point) -> isize {
+            let dx = to.0 as isize - from.0 as isize;
+            let along = match tiles[from.1][from.0] {
+                b'>' => 1,
+                b'<' => -1,
+                _ => return 2,
+            };
+            if to.1 == from.1 && dx == along {
+                1
+            } else if to.1 == from.1 && dx == -along {
+                -1
+            } else {
+                4
+            }
+        }
+        fn grid(tiles: &[Vec<u8>]) -> impl '_ + Sync + CostGrid {
+            WithMoves(|_: Point| 2, move |from, to| move_cost(tiles, from, to))
+        }
+
+        fn check<N: Neighborhood + Sync>(neighborhood: N, rows: [&str; 2], costs: [Cost; 2]) {
+            let (width, height) = (rows[0].len(), rows.len());
+            let (left, right) = ((0, 1), (width - 1, 1));
+            for config in [
+                PathCacheConfig::with_chunk_size(3),
+                PathCacheConfig {
+                    cache_paths: false,
+                    levels: 2,
+                    ..PathCacheConfig::with_chunk_size(2)
+                },
+            ] {
+                let mut tiles = rows.map(|row| row.as_bytes().to_vec());
+                let mut pathfinding =
+                    PathCache::new((width, height), &grid(&tiles), neighborhood.clone(), config);
+
+                // every step is allowed, and the Path costs as much as its steps
+                let find = |pathfinding: &PathCache<N>, tiles: &[Vec<u8>], start, goal| {
+                    let grid = grid(tiles);
+                    let moves = MoveCosts::new(neighborhood.clone(), &grid);
+                    let path = pathfinding.find_path(start, goal, &grid).unwrap().unwrap();
+                    let mut prev = start;
+                    let mut total = 0;
+                    for p in path.clone().resolve(&grid).unwrap() {
+                        assert!(moves.is_step_allowed(prev, p, |_| 2));
+                        total += moves.step_cost(prev, p, 0, 2);
+                        prev = p;
+                    }
+                    assert_eq!(prev, goal);
+                    assert_eq!(total, path.cost());
+                    total
+                };
+
+                assert_eq!(find(&pathfinding, &tiles, left, right), costs[0]);
+                assert_eq!(find(&pathfinding, &tiles, right, left), costs[1]);
+
+                // turn the belt around
+                let mut changed = vec![];
+                for (x, tile) in tiles[1].iter_mut().enumerate() {
+                    *tile = b'<';
+                    changed.push((x, 1));
+                }
+                pathfinding.tiles_changed(&changed, &grid(&tiles));
+
+                assert_eq!(find(&pathfinding, &tiles, left, right), costs[1]);
+                assert_eq!(find(&pathfinding, &tiles, right, left), costs[0]);
+            }
+        }
+
+        // the way back gets off the belt, walks over the floor and steps back down
+        check(
+            ManhattanNeighborhood::new(width, height),
+            rows,
+            [11, 4 + 11 * 2 + 2],
+        );
+        // with the diagonal steps to get off and back on the belt
+        check(
+            MooreNeighborhood::new_octile(width, height),
+            rows,
+            [11 * 10, (12 * 4 + 2 * 2) + 9 * 2 * 10 + (12 * 2 + 2 * 2)],
+        );
+    }
+
+    #[test]
+    fn moves_in_every_search() {
+        use crate::Point;
+
+        // a corridor with a conveyor belt that only moves to the right, and a way back below
+        #[rustfmt::skip]
+        let tiles = [
+            "..>>>>..",
+            ".######.",
+            "........",
+        ];
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let tile = |(x, y): Point| tiles[y].as_bytes()[x];
+        let cost_fn = |pos: Point| if tile(pos) == b'#' { -1 } else { 1 };
+        let grid = WithMoves(cost_fn, |from: Point, to: Point| {
+            if tile(from) == b'>' && to.0 < from.0 {
+                -1
+            } else {
+                1
+            }
+        });
+
+        let way_back: Vec<Point> = [(7, 1), (7, 2)]
+            .into_iter()
+            .chain((0..7).rev().map(|x| (x, 2)))
+            .chain([(0, 1), (0, 0)])
+            .collect();
+
+        for cache_paths in [true, false] {
+            let pathfinding = PathCache::new(
+                (width, height),
+                &grid,
+                ManhattanNeighborhood::new(width, height),
+                PathCacheConfig {
+                    cache_paths,
+                    ..PathCacheConfig::with_chunk_size(3)
+                },
+            );
+            let (start, goal) = ((7, 0), (0, 0));
+
+            let path = pathfinding.find_path(start, goal, &grid).unwrap().unwrap();
+            assert_eq!(path.cost(), 11);
+            assert_eq!(path.resolve(&grid).unwrap(), way_back);
+            let path = pathfinding.find_path(goal, start, &grid).unwrap().unwrap();
+            assert_eq!(path.cost(), 7);
+
+            let mut path = pathfinding.find_path(start, goal, &grid).unwrap().unwrap();
+            let mut steps = vec![];
+            while let Some(pos) = path.safe_next(&grid).unwrap() {
+                steps.push(pos);
+            }
+            assert_eq!(steps, way_back);
+
+            let path = pathfinding.find_path(start, goal, &grid).unwrap().unwrap();
+            let waypoints: Vec<Point> = path.smoothed(&grid).unwrap().collect();
+            assert_eq!(waypoints, [(6, 2), (0, 2), (0, 0)]);
+
+            let path = pathfinding.find_path_with_size(start, goal, 1, &grid);
+            assert_eq!(path.unwrap().unwrap().cost(), 11);
+            let path = pathfinding.find_path_with_capabilities(start, goal, 1, &grid);
+            assert_eq!(path.unwrap().unwrap().cost(), 11);
+            let path = pathfinding.find_path_with_obstacles(start, goal, &Obstacles::new(), &grid);
+            assert_eq!(path.unwrap().unwrap().cost(), 11);
+
+            let mut query = pathfinding.begin_find_path(start, goal, &grid).unwrap();
+            let path = loop {
+                if let std::task::Poll::Ready(path) = query.step(4) {
+                    break path;
+                }
+            };
+            assert_eq!(path.unwrap().cost(), 11);
+
+            let paths = pathfinding
+                .find_paths(start, &[goal, (1, 0)], &grid)
+                .unwrap();
+            assert_eq!(paths[&goal].cost(), 11);
+            assert_eq!(paths[&(1, 0)].cost(), 12);
+            let (closest, _) = pathfinding
+                .find_closest_goal(start, &[goal, (5, 0)], &grid)
+                .unwrap()
+                .unwrap();
+            assert_eq!(closest, (5, 0));
+
+            let mut flow_field = pathfinding.flow_field(goal, &grid).unwrap();
+            assert_eq!(flow_field.cost(start), Some(11));
+            // stepping onto the belt leads into a dead end
+            assert_eq!(flow_field.cost((6, 0)), Some(12));
+            assert_eq!(flow_field.next_step((6, 0)), Some(start));
+
+            let mut distance_map = pathfinding.distance_map(&[goal], 20, &grid).unwrap();
+            assert_eq!(distance_map.cost(start), Some(11));
+            assert_eq!(distance_map.cost((3, 0)), Some(15));
+
+            let mut replanner = Replanner::new(start, goal);
+            let path = replanner.find_path(&pathfinding, &grid).unwrap();
+            assert_eq!(path.unwrap().cost(), 11);
+
+            // the unit was pushed onto the belt
+            let mut path = pathfinding.find_path(start, goal, &grid).unwrap().unwrap();
+            let changed = pathfinding.repair_path(&mut path, (6, 0), &grid).unwrap();
+            assert_eq!(changed, Some(true));
+            assert_eq!(path.cost(), 12);
+            assert_eq!(path.safe_next(&grid).unwrap(), Some(start));
+
+            // the PathCache only works with the Grid it was created with
+            assert_eq!(
+                pathfinding.find_path(start, goal, &cost_fn).unwrap_err(),
+                GridMismatch::Moves { expected: true }
+            );
+        }
+    }
+
+    #[test]
+    fn agent_size() {
+        use crate::Point;
+
+        // a wall with a gap for single Tiles and one for agents of size 2
+        let mut tiles = [
+            "..........",
+            "..........",
+            "..........",
+            "#.###..###",
+            "..........",
+            "..........",
+            "..........",
+        ]
+        .map(|row| row.as_bytes().to_vec());
+        let (width, height) = (tiles[0].len(), tiles.len());
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Sync + Fn(Point) -> isize {
+            move |(x, y)| if tiles[y][x] == b'#' { -1 } else { 1 }
+        }
+        let mut pathfinding = PathCache::new(
+            (width, height),
+            &cost_fn(&tiles),
+            ManhattanNeighborhood::new(width, height),
+            PathCacheConfig {
+                max_agent_size: 3,
+                ..PathCacheConfig::with_chunk_size(4)
+            },
+        );
+
+        let find = |pathfinding: &PathCache<ManhattanNeighborhood>,
+                    tiles: &[Vec<u8>],
+                    goal: Point,
+                    agent_size: usize| {
+            let path = pathfinding
+                .find_path_with_size((0, 0), goal, agent_size, &cost_fn(tiles))
+                .unwrap()?;
+            let steps: Vec<Point> = path.collect();
+            // the whole agent fits at every position
+            for &(x, y) in steps.iter() {
+                assert!(x + agent_size <= width && y + agent_size <= height);
+                for row in tiles[y..y + agent_size].iter() {
+                    assert!(!row[x..x + agent_size].contains(&b'#'), "{:?}", (x, y));
+                }
+            }
+            Some(steps)
+        };
+
+        let path = find(&pathfinding, &tiles, (0, 6), 1).unwrap();
+        assert_eq!(path.len(), 8);
+        assert!(path.contains(&(1, 3)));
+
+        let path = find(&pathfinding, &tiles, (0, 5), 2).unwrap();
+        assert_eq!(path.len(), 15);
+        assert!(path.contains(&(5, 3)));
+
+        assert_eq!(find(&pathfinding, &tiles, (0, 4), 3), None);
+        // bigger than max_agent_size
+        assert_eq!(find(&pathfinding, &tiles, (5, 0), 4), None);
+
+        // widen the second gap
+        tiles[3][7] = b'.';
+        pathfinding.tiles_changed(&[(7, 3)], &cost_fn(&tiles));
+
+        let path = find(&pathfinding, &tiles, (0, 4), 3).unwrap();
+        assert_eq!(path.len(), 14);
+        assert!(path.contains(&(5, 3)));
+        let path = find(&pathfinding, &tiles, (0, 5), 2).unwrap();
+        assert_eq!(path.len(), 15);
+    }
+
+    #[test]
+    fn time_sliced() {
+        use nanorand::{Rng, WyRand};
+        use std::task::Poll;
+
+        let size = 64;
+        let mut rng = WyRand::new_seed(13);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| rng.generate_range(-1_isize..4));
+        }
+        let cost_fn = |(x, y): (usize, usize)| grid[y][x];
+        let neighborhood = ManhattanNeighborhood::new(size, size);
+        let pathfinding = PathCache::new(
+            (size, size),
+            &cost_fn,
+            neighborhood,
+            PathCacheConfig {
+                levels: 2,
+                ..PathCacheConfig::with_chunk_size(4)
+            },
+        );
+
+        let mut max_steps = 0;
+        for _ in 0..50 {
+            let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let budget = rng.generate_range(1..4);
+
+            let mut query = pathfinding.begin_find_path(start, goal, &cost_fn).unwrap();
+            let mut steps = 1;
+            let path = loop {
+                if let Poll::Ready(path) = query.step(budget) {
+                    break path;
+                }
+                steps += 1;
+            };
+            max_steps = max_steps.max(steps);
+
+            let expected = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+            assert_eq!(path.is_some(), expected.is_some());
+            if let (Some(path), Some(expected)) = (path, expected) {
+                assert_eq!(path.cost(), expected.cost());
+                assert_eq!(path.resolve(&cost_fn).unwrap().last(), Some(&goal));
+            }
+        }
+        assert!(max_steps > 10);
+    }
+
+    #[test]
+    fn time_sliced_resolve() {
+        use nanorand::{Rng, WyRand};
+        use std::cell::Cell;
+        use std::task::Poll;
+
+        let size = 64;
+        let mut rng = WyRand::new_seed(26);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| if rng.generate_range(0..8) == 0 { -1 } else { 1 });
+        }
+        let neighborhood = ManhattanNeighborhood::new(size, size);
+        let pathfinding = PathCache::new(
+            (size, size),
+            &|(x, y): (usize, usize)| grid[y][x],
+            neighborhood,
+            PathCacheConfig {
+                levels: 2,
+                ..PathCacheConfig::with_chunk_size(16)
+            },
+        );
+
+        // the Tiles looked at in a single call to step
+        let calls = Cell::new(0);
+        let cost_fn = |(x, y): (usize, usize)| {
+            calls.set(calls.get() + 1);
+            grid[y][x]
+        };
+        let mut max_calls = 0;
+        for _ in 0..50 {
+            let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+
+            let mut query = pathfinding.begin_find_path(start, goal, &cost_fn).unwrap();
+            let path = loop {
+                calls.set(0);
+                let result = query.step(1);
+                max_calls = max_calls.max(calls.get());
+                if let Poll::Ready(path) = result {
+                    break path;
+                }
+            };
+
+            let expected = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+            assert_eq!(path.is_some(), expected.is_some());
+            if let (Some(path), Some(expected)) = (path, expected) {
+                assert_eq!(path.cost(), expected.cost());
+            }
+        }
+        // a visited Tile looks at itself and its 4 neighbors
+        assert!(max_calls <= 5, "{}", max_calls);
+    }
+
+    #[test]
+    fn smoothed() {
+        use crate::Point;
+
+        // an open room with an expensive Tile at the top
+        #[rustfmt::skip]
+        let tiles = [
+            "....9.....",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+        ];
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let cost_fn = |(x, y): Point| match tiles[y].as_bytes()[x] {
+            b'.' => 1,
+            c => (c - b'0') as isize,
+        };
+        let neighborhood = MooreNeighborhood::new(width, height);
+
+        for cache_paths in [true, false] {
+            let pathfinding = PathCache::new(
+                (width, height),
+                &cost_fn,
+                neighborhood,
+                PathCacheConfig {
+                    cache_paths,
+                    ..PathCacheConfig::with_chunk_size(3)
+                },
+            );
+            // the waypoints and the cost of the straight lines between them
+            let smoothed = |start, goal| {
+                let path = pathfinding
+                    .find_path(start, goal, &cost_fn)
+                    .unwrap()
+                    .unwrap();
+                let cost = path.cost();
+                let waypoints = path.smoothed(&cost_fn).unwrap().collect::<Vec<_>>();
+                let mut anchor = start;
+                let mut smoothed_cost = 0;
+                for &waypoint in waypoints.iter() {
+                    smoothed_cost +=
+                        crate::grid::line_cost(&neighborhood, anchor, waypoint, cost_fn)
+                            .expect("waypoints must be connected by clear lines");
+                    anchor = waypoint;
+                }
+                assert_eq!(smoothed_cost, cost);
+                waypoints
+            };
+
+            // a single line, no matter how the Path zig-zags between the Nodes
+            assert_eq!(smoothed((0, 5), (9, 2)), [(9, 2)]);
+
+            // the line along the top would cross the expensive Tile
+            let waypoints = smoothed((0, 0), (9, 0));
+            assert!(waypoints.len() > 1);
+            assert_eq!(waypoints.last(), Some(&(9, 0)));
+        }
+    }
+
+    #[test]
+    fn jump_points() {
+        use crate::Point;
+
+        // a wall and an expensive area that the jumps have to stop at
+        #[rustfmt::skip]
+        let tiles = [
+            "............",
+            "............",
+            "...#........",
+            "...#...33...",
+            "...#...33...",
+            "...#........",
+            "............",
+            "............",
+        ];
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let cost_fn = |(x, y): Point| match tiles[y].as_bytes()[x] {
+            b'#' => -1,
+            b'.' => 1,
+            c => (c - b'0') as isize,
+        };
+        let neighborhood = MooreNeighborhood::new(width, height);
+        let queries = [((0, 0), (11, 7)), ((1, 3), (10, 4)), ((11, 0), (0, 7))];
+
+        for cache_paths in [true, false] {
+            let config = PathCacheConfig {
+                cache_paths,
+                ..PathCacheConfig::with_chunk_size(4)
+            };
+            let regular = PathCache::new((width, height), &cost_fn, neighborhood, config);
+            let config = PathCacheConfig {
+                jump_points: true,
+                ..config
+            };
+            let pathfinding = PathCache::new((width, height), &cost_fn, neighborhood, config);
+
+            for (start, goal) in queries {
+                let path = pathfinding
+                    .find_path(start, goal, &cost_fn)
+                    .unwrap()
+                    .unwrap();
+                // the jumps find Paths within the Chunks that cost just as much
+                let expected = regular.find_path(start, goal, &cost_fn).unwrap().unwrap();
+                assert_eq!(path.cost(), expected.cost(), "{:?} -> {:?}", start, goal);
+
+                let cost = path.cost();
+                let mut current = start;
+                let mut actual_cost = 0;
+                let mut neighbors = vec![];
+                for p in path.resolve(&cost_fn).unwrap() {
+                    neighbors.clear();
+                    neighborhood.get_all_neighbors(current, &mut neighbors);
+                    assert!(neighbors.contains(&p));
+                    actual_cost += cost_fn(current) as usize;
+                    current = p;
+                }
+                assert_eq!(current, goal);
+                assert_eq!(actual_cost, cost, "{:?} -> {:?}", start, goal);
+            }
+        }
+    }
+
+    #[test]
+    fn cost_grid() {
+        use crate::{GridMismatch, Point};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Grid {
+            size: usize,
+            costs: Vec<isize>,
+            generation: u64,
+            /// the calls to cost and row
+            reads: [AtomicUsize; 2],
+        }
+        impl CostGrid for Grid {
+            fn cost(&self, (x, y): Point) -> isize {
+                self.reads[0].fetch_add(1, Ordering::Relaxed);
+                self.costs[y * self.size + x]
+            }
+            fn row(&self, y: usize) -> Option<&[isize]> {
+                self.reads[1].fetch_add(1, Ordering::Relaxed);
+                Some(&self.costs[y * self.size..(y + 1) * self.size])
+            }
+            fn generation(&self) -> Option<u64> {
+                Some(self.generation)
+            }
+        }
+
+        let size = 16;
+        let mut grid = Grid {
+            size,
+            costs: vec![1; size * size],
+            generation: 3,
+            reads: Default::default(),
+        };
+        let neighborhood = ManhattanNeighborhood::new(size, size);
+        let config = PathCacheConfig {
+            cache_paths: false,
+            ..PathCacheConfig::with_chunk_size(4)
+        };
+        let mut pathfinding = PathCache::new((size, size), &grid, neighborhood, config);
+        assert_eq!(pathfinding.grid_generation(), Some(3));
+        // every row of every Chunk is read once as a slice
+        assert_eq!(grid.reads[0].load(Ordering::Relaxed), 0);
+        assert_eq!(grid.reads[1].load(Ordering::Relaxed), size * size / 4);
+
+        let (start, goal) = ((0, 0), (15, 15));
+        let mut path = pathfinding.find_path(start, goal, &grid).unwrap().unwrap();
+        assert_eq!(path.cost(), 30);
+        let first = path.safe_next(&grid).unwrap().unwrap();
+        assert!(first == (0, 1) || first == (1, 0));
+
+        // wall off the goal without telling the PathCache
+        grid.costs[14 * size + 15] = -1;
+        grid.costs[15 * size + 14] = -1;
+        grid.generation += 1;
+        let mismatch = GridMismatch::Generation {
+            expected: 3,
+            found: 4,
+        };
+        assert_eq!(
+            pathfinding.find_path(start, goal, &grid).err(),
+            Some(mismatch)
+        );
+        assert_eq!(
+            pathfinding.find_paths(start, &[goal], &grid).err(),
+            Some(mismatch)
+        );
+        assert_eq!(
+            pathfinding.is_reachable(start, goal, &grid).err(),
+            Some(mismatch)
+        );
+        assert_eq!(path.safe_next(&grid), Err(mismatch));
+
+        grid.reads[0].store(0, Ordering::Relaxed);
+        grid.reads[1].store(0, Ordering::Relaxed);
+        pathfinding.tiles_changed(&[(15, 14), (14, 15)], &grid);
+        assert_eq!(pathfinding.grid_generation(), Some(4));
+        // only the rows of the bottom right Chunk and its three neighbors
+        assert_eq!(grid.reads[0].load(Ordering::Relaxed), 0);
+        assert_eq!(grid.reads[1].load(Ordering::Relaxed), 4 * 4);
+        assert!(pathfinding.find_path(start, goal, &grid).unwrap().is_none());
+        // the old Path still belongs to the old Grid
+        assert_eq!(path.safe_next(&grid), Err(mismatch));
+
+        // changes in opposite corners don't copy the rows in between
+        grid.costs[0] = 2;
+        grid.costs[size * size - 1] = 2;
+        grid.generation += 1;
+        grid.reads[1].store(0, Ordering::Relaxed);
+        pathfinding.tiles_changed(&[(0, 0), (15, 15)], &grid);
+        assert_eq!(grid.reads[1].load(Ordering::Relaxed), 2 * 4 * 4);
+
+        // closures have no generation
+        let cost_fn = |p| grid.cost(p);
+        assert!(pathfinding.find_path(start, (15, 0), &cost_fn).is_ok());
+        pathfinding.tiles_changed(&[], &cost_fn);
+        assert_eq!(pathfinding.grid_generation(), None);
+        grid.generation += 1;
+        assert!(pathfinding.find_path(start, (15, 0), &grid).is_ok());
+    }
+
+    #[test]
+    fn terrain() {
+        use crate::Point;
+
+        // a river (~) that can only be crossed by swimming, apart from a mountain pass (^)
+        let mut tiles = [
+            "....~.....",
+            "....~.....",
+            "....~.....",
+            "....~.....",
+            "....^.....",
+            "....^.....",
+        ]
+        .map(|row| row.as_bytes().to_vec());
+        let (width, height) = (tiles[0].len(), tiles.len());
+        // (cost, terrain class)
+        fn tile_fn(tiles: &[Vec<u8>]) -> impl '_ + Sync + Fn(Point) -> (isize, u8) {
+            move |(x, y)| match tiles[y][x] {
+                b'~' => (2, 1),
+                b'^' => (1, 2),
+                _ => (1, 0),
+            }
+        }
+        let mut pathfinding = PathCache::new_with_terrain(
+            (width, height),
+            tile_fn(&tiles),
+            ManhattanNeighborhood::new(width, height),
+            PathCacheConfig::with_chunk_size(3),
+        );
+
+        let find = |pathfinding: &PathCache<ManhattanNeighborhood>,
+                    tiles: &[Vec<u8>],
+                    capabilities: u32| {
+            let get_tile = tile_fn(tiles);
+            let path = pathfinding
+                .find_path_with_capabilities((0, 0), (9, 0), capabilities, &|p| get_tile(p).0)
+                .unwrap()?;
+            let cost = path.cost();
+            for p in path {
+                assert!(capabilities & (1 << get_tile(p).1) != 0, "{:?}", p);
+            }
+            Some(cost)
+        };
+
+        // swimming is the shortest way, but costs more per Tile
+        assert_eq!(find(&pathfinding, &tiles, 0b111), Some(10));
+        assert_eq!(find(&pathfinding, &tiles, 0b011), Some(10));
+        assert_eq!(find(&pathfinding, &tiles, 0b101), Some(17));
+        assert_eq!(find(&pathfinding, &tiles, 0b001), None);
+
+        // a bridge
+        tiles[2][4] = b'.';
+        pathfinding.tiles_changed_with_terrain(&[(4, 2)], tile_fn(&tiles));
+
+        assert_eq!(find(&pathfinding, &tiles, 0b001), Some(13));
+        assert_eq!(find(&pathfinding, &tiles, 0b101), Some(13));
+        assert_eq!(find(&pathfinding, &tiles, 0b111), Some(10));
+    }
+
+    #[test]
+    fn path_validity() {
+        for cache_paths in [true, false] {
+            let mut grid = [[1; 12]; 12];
+            let mut pathfinding = PathCache::new(
+                (12, 12),
+                &|(x, y): (usize, usize)| grid[y][x],
+                ManhattanNeighborhood::new(12, 12),
+                PathCacheConfig {
+                    cache_paths,
+                    ..PathCacheConfig::with_chunk_size(4)
+                },
+            );
+
+            let (start, goal) = ((0, 0), (11, 0));
+            let path = pathfinding
+                .find_path(start, goal, &|(x, y): (usize, usize)| grid[y][x])
+                .unwrap();
+            let path = path.unwrap();
+            let paths = pathfinding
+                .find_paths(start, &[goal, (0, 3)], &|(x, y): (usize, usize)| grid[y][x])
+                .unwrap();
+            let mut walked = path.clone();
+            while walked
+                .safe_next(&|(x, y): (usize, usize)| grid[y][x])
+                .unwrap()
+                .unwrap()
+                .0
+                < 8
+            {}
+
+            // far away from all Paths
+            grid[10][6] = 2;
+            let changed =
+                pathfinding.tiles_changed(&[(6, 10)], &|(x, y): (usize, usize)| grid[y][x]);
+            assert_eq!(changed, vec![(4, 8)]);
+            assert!(path.is_valid(&pathfinding));
+            assert!(paths.values().all(|path| path.is_valid(&pathfinding)));
+
+            // in the middle Chunk of the top row
+            grid[2][6] = 2;
+            let changed =
+                pathfinding.tiles_changed(&[(6, 2), (0, 11)], &|(x, y): (usize, usize)| grid[y][x]);
+            assert_eq!(changed, vec![(4, 0), (0, 8)]);
+            assert!(!path.is_valid(&pathfinding));
+            assert!(!paths[&goal].is_valid(&pathfinding));
+            assert!(paths[&(0, 3)].is_valid(&pathfinding));
+            assert!(walked.is_valid(&pathfinding));
+
+            // removing a link invalidates the Paths that use it
+            pathfinding.add_link((0, 11), (11, 11), 1, true, &|(x, y): (usize, usize)| {
+                grid[y][x]
+            });
+            let path = pathfinding
+                .find_path((0, 11), (11, 11), &|(x, y): (usize, usize)| grid[y][x])
+                .unwrap();
+            let path = path.unwrap();
+            assert_eq!(path.cost(), 1);
+            assert!(path.is_valid(&pathfinding));
+            pathfinding.remove_link((0, 11), (11, 11), &|(x, y): (usize, usize)| grid[y][x]);
+            assert!(!path.is_valid(&pathfinding));
+        }
+    }
+
+    #[test]
+    fn bidirectional() {
+        use super::annotations::{Agent, AgentGraph};
+        use crate::graph::{self, ReverseGraph};
+        use crate::{path::Cost, NodeID, Point};
+        use nanorand::{Rng, WyRand};
+        use std::task::Poll;
+
+        // the cheapest Path on the Graph, checked against a Dijkstra search
+        fn check_graph<N: Neighborhood>(
+            graph: &impl ReverseGraph,
+            neighborhood: &N,
+            ids: &[NodeID],
+            use_heuristic: bool,
+        ) {
+            for &start in ids {
+                let expected = graph::dijkstra_search(graph, start, ids, false, ids.len());
+                for &goal in ids {
+                    let mut search =
+                        graph::BidirectionalSearch::new(start, goal, use_heuristic, 16);
+                    let mut budget = usize::MAX;
+                    let path = match search.step(graph, neighborhood, &mut budget) {
+                        Poll::Ready(path) => path,
+                        Poll::Pending => unreachable!(),
+                    };
+                    let expected = expected.get(&goal).map(|path| path.cost());
+                    assert_eq!(path.as_ref().map(|path| path.cost()), expected);
+                    let path = match path {
+                        Some(path) if start != goal => path,
+                        _ => continue,
+                    };
+                    assert_eq!((path[0], path[path.len() - 1]), (start, goal));
+                    let mut total = 0;
+                    for (&a, &b) in path.iter().zip(path.iter().skip(1)) {
+                        let mut cost = None;
+                        graph.for_each_edge(a, |id, c| {
+                            if id == b {
+                                cost = Some(c);
+                            }
+                        });
+                        let mut incoming = None;
+                        graph.for_each_incoming_edge(b, |id, c| {
+                            if id == a {
+                                incoming = Some(c);
+                            }
+                        });
+                        assert_eq!(cost, incoming);
+                        total += cost.unwrap();
+                    }
+                    assert_eq!(total, path.cost());
+                }
+            }
+        }
+
+        let size = 32;
+        let mut rng = WyRand::new_seed(11);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| [-1, 1, 1, 2, 3][rng.generate_range(0..5_usize)]);
+        }
+        let link = ((1, 1), (30, 30));
+        grid[1][1] = 1;
+        grid[30][30] = 1;
+        let cost_fn = |(x, y): Point| grid[y][x];
+        // every third row can only be walked to the right
+        let move_fn = |from: Point, to: Point| {
+            if from.1.is_multiple_of(3) && to.0 < from.0 {
+                -1
+            } else {
+                cost_fn(from)
+            }
+        };
+        let move_grid = WithMoves(cost_fn, move_fn);
+        let neighborhood = ManhattanNeighborhood::new(size, size);
+
+        for (directed, levels, with_link) in [
+            (false, 1, false),
+            (false, 2, false),
+            (false, 2, true),
+            (true, 1, false),
+            (true, 2, false),
+        ] {
+            let config = PathCacheConfig {
+                levels,
+                ..PathCacheConfig::with_chunk_size(4)
+            };
+            let bidirectional = PathCacheConfig {
+                bidirectional: true,
+                ..config
+            };
+            let (mut pathfinding, mut forward) = if directed {
+                (
+                    PathCache::new((size, size), &move_grid, neighborhood, bidirectional),
+                    PathCache::new((size, size), &move_grid, neighborhood, config),
+                )
+            } else {
+                (
+                    PathCache::new((size, size), &cost_fn, neighborhood, bidirectional),
+                    PathCache::new((size, size), &cost_fn, neighborhood, config),
+                )
+            };
+            assert_eq!(pathfinding.nodes.is_directed(), directed);
+            if with_link {
+                pathfinding.add_link(link.0, link.1, 2, true, &cost_fn);
+                forward.add_link(link.0, link.1, 2, true, &cost_fn);
+            }
+            let use_heuristic = !with_link;
+
+            let ids = pathfinding.nodes.keys().step_by(8).collect::<Vec<_>>();
+            let agent_graph =
+                AgentGraph::new(&pathfinding.nodes, &pathfinding.annotations, Agent::DEFAULT);
+            check_graph(&agent_graph, &neighborhood, &ids, use_heuristic);
+            let special = [pathfinding.nodes[ids[0]].pos, (size - 1, size - 1)];
+            let query_graph = pathfinding.query_graph(&special);
+            check_graph(&query_graph, &neighborhood, &ids, use_heuristic);
+
+            // Paths are found for the same goals, and they are walkable
+            for _ in 0..100 {
+                let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+                let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+                if start == goal || cost_fn(goal) < 0 {
+                    continue;
+                }
+                let (path, forward_path) = if directed {
+                    (
+                        pathfinding.find_path(start, goal, &move_grid).unwrap(),
+                        forward.find_path(start, goal, &move_grid).unwrap(),
+                    )
+                } else {
+                    (
+                        pathfinding.find_path(start, goal, &cost_fn).unwrap(),
+                        forward.find_path(start, goal, &cost_fn).unwrap(),
+                    )
+                };
+                assert_eq!(
+                    path.is_some(),
+                    forward_path.is_some(),
+                    "{:?} -> {:?}",
+                    start,
+                    goal
+                );
+                if let Some(path) = path {
+                    let mut prev = start;
+                    let mut target = vec![];
+                    let mut total: Cost = 0;
+                    for p in path.clone() {
+                        if [(prev, p), (p, prev)].contains(&link) && with_link {
+                            total += 2;
+                            prev = p;
+                            continue;
+                        }
+                        target.clear();
+                        neighborhood.get_all_neighbors(prev, &mut target);
+                        assert!(target.contains(&p));
+                        assert!(!directed || move_fn(prev, p) >= 0);
+                        total += if directed {
+                            move_fn(prev, p)
+                        } else {
+                            cost_fn(prev)
+                        } as Cost;
+                        prev = p;
+                    }
+                    assert_eq!(prev, goal);
+                    assert_eq!(total, path.cost(), "{:?} -> {:?}", start, goal);
+                }
+            }
+        }
+    }
+
     #[allow(unused)]
     // #[test]
     #[cfg(feature = "parallel")]
@@ -1644,17 +4333,22 @@ mod tests {
                 println!("size: {}, chunk_size: {}", size, chunk_size);
                 let pathfinding = PathCache::new(
                     (size, size),
-                    cost_fn,
+                    &cost_fn,
                     ManhattanNeighborhood::new(size, size),
                     PathCacheConfig::with_chunk_size(chunk_size),
                 );
                 // for _ in 0..100 {
-                [0..100].par_iter().for_each(|_| {
+                (0..100).into_par_iter().for_each(|_| {
                     let mut rng = nanorand::tls_rng();
                     let start = (rng.generate_range(0..size), rng.generate_range(0..size));
                     let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
-                    let a_star_path = pathfinding.grid_a_star(start, goal, cost_fn);
-                    let path = pathfinding.find_path(start, goal, cost_fn);
+                    let a_star_path = pathfinding.grid_a_star_with(
+                        &pathfinding.neighborhood,
+                        start,
+                        goal,
+                        cost_fn,
+                    );
+                    let path = pathfinding.find_path(start, goal, &cost_fn).unwrap();
                     if a_star_path.is_some() != path.is_some() {
                         use std::io::Write;
                         let mut out = std::fs::File::create("cache.txt").unwrap();
@@ -0,0 +1,140 @@
+use crate::{
+    graph::{Graph, NodeList, ReverseGraph},
+    path::{Cost, PathSegment},
+    *,
+};
+
+use super::{clearance::Clearance, terrain::Terrain};
+
+/// Additional information about the Tiles of the Grid, used to find Paths for agents that
+/// can't enter every walkable Tile
+#[derive(Clone, Debug)]
+pub struct Annotations {
+    pub clearance: Clearance,
+    pub terrain: Terrain,
+}
+
+impl Annotations {
+    /// Checks if `agent` can enter the walkable Tile at `pos`
+    pub fn allows(&self, agent: Agent, pos: Point) -> bool {
+        self.clearance.get(pos) >= agent.size && self.terrain.mask(pos) & agent.capabilities != 0
+    }
+}
+
+/// The properties of the agent that a Path is searched for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Agent {
+    /// the width and height of the agent
+    pub size: usize,
+    /// the terrain classes the agent can enter, one bit per class
+    pub capabilities: u32,
+}
+
+impl Agent {
+    /// An agent that is one Tile in size and can enter every walkable Tile
+    pub const DEFAULT: Agent = Agent {
+        size: 1,
+        capabilities: u32::MAX,
+    };
+
+    pub fn with_size(size: usize) -> Agent {
+        Agent {
+            size,
+            ..Agent::DEFAULT
+        }
+    }
+
+    pub fn with_capabilities(capabilities: u32) -> Agent {
+        Agent {
+            capabilities,
+            ..Agent::DEFAULT
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.size <= 1 && self.capabilities == u32::MAX
+    }
+}
+
+/// The Node Graph as seen by a certain agent.
+///
+/// Edges are only used if the agent can traverse them, and the alternatives in
+/// [`Node::wide_edges`](crate::graph::Node::wide_edges) and
+/// [`Node::terrain_edges`](crate::graph::Node::terrain_edges) are used where necessary.
+pub struct AgentGraph<'a> {
+    nodes: &'a NodeList,
+    annotations: &'a Annotations,
+    agent: Agent,
+}
+
+impl<'a> AgentGraph<'a> {
+    pub fn new(nodes: &'a NodeList, annotations: &'a Annotations, agent: Agent) -> Self {
+        AgentGraph {
+            nodes,
+            annotations,
+            agent,
+        }
+    }
+
+    /// The cheapest Path from `a` to `b` that the agent can traverse
+    pub fn edge(&self, a: NodeID, b: NodeID) -> Option<&'a PathSegment> {
+        let node = &self.nodes[a];
+        let path = node.edges.get(&b)?;
+        let (pos_a, pos_b) = (node.pos, self.nodes[b].pos);
+        if self.agent.size > 1 {
+            if let Some(wide) = node.wide_edges.get(&b) {
+                wide.iter()
+                    .find(|(clearance, _)| *clearance >= self.agent.size)
+                    .map(|(_, path)| path)
+            } else {
+                // without alternatives, the Path is as wide as the Nodes themselves
+                let clearance = &self.annotations.clearance;
+                let width = clearance.get(pos_a).min(clearance.get(pos_b));
+                (width >= self.agent.size).then_some(path)
+            }
+        } else if self.agent.capabilities != u32::MAX {
+            let forbidden = !self.agent.capabilities;
+            if let Some(alternatives) = node.terrain_edges.get(&b) {
+                alternatives
+                    .iter()
+                    .find(|(required, _)| required & forbidden == 0)
+                    .map(|(_, path)| path)
+            } else {
+                // without alternatives, the Path only crosses the terrain of the Nodes themselves
+                let terrain = &self.annotations.terrain;
+                let required = terrain.mask(pos_a) | terrain.mask(pos_b);
+                (required & forbidden == 0).then_some(path)
+            }
+        } else {
+            Some(path)
+        }
+    }
+
+    /// Calls `f` with the target and Path of every outgoing edge that the agent can traverse
+    pub fn for_each_edge_segment(&self, id: NodeID, mut f: impl FnMut(NodeID, &'a PathSegment)) {
+        for &other_id in self.nodes[id].edges.keys() {
+            if let Some(path) = self.edge(id, other_id) {
+                f(other_id, path);
+            }
+        }
+    }
+}
+
+impl Graph for AgentGraph<'_> {
+    fn pos(&self, id: NodeID) -> Point {
+        self.nodes[id].pos
+    }
+    fn for_each_edge(&self, id: NodeID, mut f: impl FnMut(NodeID, Cost)) {
+        self.for_each_edge_segment(id, |other_id, path| f(other_id, path.cost()));
+    }
+}
+
+impl ReverseGraph for AgentGraph<'_> {
+    fn for_each_incoming_edge(&self, id: NodeID, mut f: impl FnMut(NodeID, Cost)) {
+        self.nodes.for_each_incoming(id, |other_id| {
+            if let Some(path) = self.edge(other_id, id) {
+                f(other_id, path.cost());
+            }
+        });
+    }
+}
@@ -1,3 +1,5 @@
+use crate::grid;
+
 /// Options for configuring the [`PathCache`](crate::PathCache)
 ///
 /// Default options:
@@ -9,6 +11,10 @@
 ///         cache_paths: true,
 ///         a_star_fallback: true,
 ///         perfect_paths: false,
+///         levels: 1,
+///         max_agent_size: 1,
+///         jump_points: false,
+///         bidirectional: false,
 ///     },
 ///     Default::default()
 /// );
@@ -97,9 +103,72 @@ pub struct PathCacheConfig {
     /// It is questionable weather or not you should use Hierarchical Pathfinding if you enable
     /// this...
     pub perfect_paths: bool,
+    /// The number of abstraction levels (defaults to `1`)
+    ///
+    /// `1`: Only the Chunks themselves are used, as described above.
+    ///
+    /// `> 1`: Every additional level groups 4x4 Chunks of the previous level into a bigger
+    /// Chunk, with its own Graph of the Nodes on its border. Paths are searched on the
+    /// highest level possible, and only the Chunks around the start and goal are searched on
+    /// the lower levels.
+    ///
+    /// This greatly reduces the number of Nodes visited for long Paths on large Grids, at the
+    /// cost of additional Memory and longer creation and update times. Levels whose Chunks
+    /// cover the entire Grid have no effect.
+    pub levels: usize,
+    /// The size of the biggest agent that Paths are needed for (defaults to `1`, at most `255`)
+    ///
+    /// Agents are squares of `agent_size`x`agent_size` Tiles. Setting this to more than `1`
+    /// allows the PathCache to find Paths for bigger agents with
+    /// [`find_path_with_size`](crate::PathCache::find_path_with_size), in addition to the
+    /// regular Paths.
+    ///
+    /// This requires storing the clearance of every Tile, as well as additional Nodes and Paths
+    /// wherever the regular Paths are too narrow for bigger agents. Changing a Tile also affects
+    /// the clearance of the Tiles up to `max_agent_size - 1` Tiles above and left of it, which
+    /// increases the update time in `tiles_changed`.
+    pub max_agent_size: usize,
+    /// `true`: The A* searches on the Grid skip over areas of uniform cost, using
+    /// [Jump Point Search](https://en.wikipedia.org/wiki/Jump_point_search).
+    ///
+    /// `false` (default): The A* searches on the Grid visit every Tile on the way.
+    ///
+    /// This affects the A* fallback, the A* searches within Chunks and resolving unknown segments
+    /// of a Path. In open areas where every Tile has the same cost, only the Tiles where a Path
+    /// could turn are visited, which makes those searches a lot faster. Wherever the cost
+    /// changes, the search falls back to visiting every Tile. The resulting Paths have the same
+    /// cost either way, but may take different (equally good) turns.
+    ///
+    /// Only works with Neighborhoods that support it, like
+    /// [`ManhattanNeighborhood`](crate::neighbors::ManhattanNeighborhood) and
+    /// [`MooreNeighborhood`](crate::neighbors::MooreNeighborhood) (see
+    /// [`Neighborhood::grid_steps`](crate::neighbors::Neighborhood::grid_steps)).
+    pub jump_points: bool,
+    /// `true`: The searches on the Node Graph run from the start and the goal at the same time
+    /// until they meet in the middle.
+    ///
+    /// `false` (default): The searches on the Node Graph only run forward from the start.
+    ///
+    /// Searching from both ends visits fewer Nodes for long Paths, and especially for goals
+    /// that can't be reached because they are enclosed, since the search from the goal runs
+    /// out of Nodes early instead of the search from the start flooding everything else. It
+    /// also always finds the cheapest Path through the Graph.
+    /// One-way edges (see [`move_cost`](crate::CostGrid::move_cost)) are followed backwards by
+    /// the search from the goal, so they are only used in their direction.
+    ///
+    /// Searches that have to avoid [`Obstacles`](crate::PathCache::add_obstacles) always only
+    /// run forward.
+    pub bidirectional: bool,
 }
 
 impl PathCacheConfig {
+    /// The options that affect the searches on the Grid
+    pub(crate) fn search_options(&self) -> grid::SearchOptions {
+        grid::SearchOptions {
+            jump_points: self.jump_points,
+        }
+    }
+
     /// Creates a new PathCacheConfig with the given `chunk_size`.
     /// ```
     /// # use hierarchical_pathfinding::PathCacheConfig;
@@ -130,6 +199,10 @@ impl PathCacheConfig {
     ///         cache_paths: false,
     ///         a_star_fallback: true,
     ///         perfect_paths: false,
+    ///         levels: 1,
+    ///         max_agent_size: 1,
+    ///         jump_points: false,
+    ///         bidirectional: false,
     ///     },
     ///     PathCacheConfig::LOW_MEM
     /// );
@@ -139,6 +212,10 @@ impl PathCacheConfig {
         cache_paths: false,
         a_star_fallback: true,
         perfect_paths: false,
+        levels: 1,
+        max_agent_size: 1,
+        jump_points: false,
+        bidirectional: false,
     };
     /// an example PathCacheConfig with options set to improve Performance
     ///
@@ -151,6 +228,10 @@ impl PathCacheConfig {
     ///         cache_paths: true,
     ///         a_star_fallback: false,
     ///         perfect_paths: false,
+    ///         levels: 1,
+    ///         max_agent_size: 1,
+    ///         jump_points: false,
+    ///         bidirectional: false,
     ///     },
     ///     PathCacheConfig::HIGH_PERFORMANCE
     /// );
@@ -160,6 +241,10 @@ impl PathCacheConfig {
         cache_paths: true,
         a_star_fallback: false,
         perfect_paths: false,
+        levels: 1,
+        max_agent_size: 1,
+        jump_points: false,
+        bidirectional: false,
     };
 }
 
@@ -170,6 +255,10 @@ impl Default for PathCacheConfig {
             cache_paths: true,
             a_star_fallback: true,
             perfect_paths: false,
+            levels: 1,
+            max_agent_size: 1,
+            jump_points: false,
+            bidirectional: false,
         }
     }
 }
@@ -1,19 +1,52 @@
 use crate::{
     graph::*,
+    grid::SearchOptions,
     neighbors::Neighborhood,
     path::{Path, PathSegment},
     *,
 };
 
+use super::{annotations::Annotations, clearance::Clearance, moves::Reversed, terrain::Terrain};
+
+/// Marks a solid Tile in [`Chunk::regions`]
+pub const SOLID: u32 = u32::MAX;
+
+/// An edge between two Nodes of a Chunk, together with its alternatives for agents that can't
+/// use it. See [`Node::wide_edges`] and [`Node::terrain_edges`]
+pub struct ChunkEdge {
+    pub start: NodeID,
+    pub end: NodeID,
+    pub path: PathSegment,
+    pub wide: Option<Vec<(usize, PathSegment)>>,
+    pub terrain: Option<Vec<(u32, PathSegment)>>,
+}
+
+impl ChunkEdge {
+    pub fn insert(self, all_nodes: &mut NodeList) {
+        all_nodes.add_edge(self.start, self.end, self.path);
+        if let Some(wide) = self.wide {
+            all_nodes.add_wide_edges(self.start, self.end, wide);
+        }
+        if let Some(terrain) = self.terrain {
+            all_nodes.add_terrain_edges(self.start, self.end, terrain);
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Chunk {
     pub pos: Point,
     pub size: Point,
     pub nodes: NodeIDSet,
     pub sides: [bool; 4],
+    /// the region of every Tile in the Chunk, or SOLID. Tiles are in the same region if they
+    /// are connected within the Chunk, ignoring the direction of one-way moves
+    pub regions: Vec<u32>,
+    pub num_regions: u32,
 }
 
 impl Chunk {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<N: Neighborhood>(
         pos: Point,
         size: (usize, usize),
@@ -21,6 +54,7 @@ impl Chunk {
         mut get_cost: impl FnMut(Point) -> isize,
         neighborhood: &N,
         all_nodes: &mut NodeList,
+        annotations: &Annotations,
         config: PathCacheConfig,
     ) -> Chunk {
         let mut chunk = Chunk {
@@ -28,42 +62,134 @@ impl Chunk {
             size,
             nodes: NodeIDSet::default(),
             sides: [false; 4],
+            regions: vec![],
+            num_regions: 0,
         };
 
         let mut candidates = PointSet::default();
+        // the left and right edge of the Grid are a border to the Chunks on the other side
+        let wrap = neighborhood.wraps_horizontally();
 
         for dir in Dir::all() {
             if dir == UP && chunk.top() == 0
-                || dir == RIGHT && chunk.right() == total_size.0
+                || dir == RIGHT && chunk.right() == total_size.0 && !wrap
                 || dir == DOWN && chunk.bottom() == total_size.1
-                || dir == LEFT && chunk.left() == 0
+                || dir == LEFT && chunk.left() == 0 && !wrap
             {
                 continue;
             }
             chunk.sides[dir.num()] = true;
 
-            chunk.calculate_side_nodes(dir, total_size, &mut get_cost, config, &mut candidates);
+            chunk.calculate_side_nodes(
+                dir,
+                total_size,
+                &mut get_cost,
+                neighborhood,
+                annotations,
+                config,
+                &mut candidates,
+            );
+            chunk.calculate_crossing_nodes(
+                dir,
+                total_size,
+                &mut get_cost,
+                neighborhood,
+                &mut candidates,
+            );
         }
 
+        let scale = neighborhood.cost_scale();
         let nodes = candidates
             .into_iter()
-            .map(|p| all_nodes.add_node(p, get_cost(p) as usize))
+            .map(|p| all_nodes.add_node(p, get_cost(p) as usize * scale))
             .to_vec();
 
-        chunk.add_nodes(&nodes, &mut get_cost, neighborhood, all_nodes, &config);
+        chunk.add_nodes(
+            &nodes,
+            &mut get_cost,
+            neighborhood,
+            all_nodes,
+            annotations,
+            &config,
+        );
+
+        chunk.calculate_regions(&mut get_cost, neighborhood);
 
         chunk
     }
 
-    pub fn calculate_side_nodes(
+    /// Splits the Tiles of the Chunk into regions that are connected within the Chunk
+    pub fn calculate_regions<N: Neighborhood>(
+        &mut self,
+        mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &N,
+    ) {
+        const UNVISITED: u32 = SOLID - 1;
+
+        let mut regions = vec![UNVISITED; self.size.0 * self.size.1];
+        let mut num_regions = 0;
+        let mut stack = vec![];
+        let mut neighbors = vec![];
+
+        for y in self.top()..self.bottom() {
+            for x in self.left()..self.right() {
+                let index = self.tile_index((x, y));
+                if regions[index] != UNVISITED {
+                    continue;
+                }
+                if get_cost((x, y)) < 0 {
+                    regions[index] = SOLID;
+                    continue;
+                }
+                regions[index] = num_regions;
+                stack.push((x, y));
+                while let Some(p) = stack.pop() {
+                    neighbors.clear();
+                    neighborhood.get_all_neighbors(p, &mut neighbors);
+                    for &other in neighbors.iter().filter(|p| self.in_chunk(**p)) {
+                        let other_index = self.tile_index(other);
+                        if regions[other_index] != UNVISITED {
+                            continue;
+                        }
+                        if get_cost(other) < 0 {
+                            regions[other_index] = SOLID;
+                        } else if neighborhood.is_step_allowed(p, other, &mut get_cost)
+                            || neighborhood.is_step_allowed(other, p, &mut get_cost)
+                        {
+                            regions[other_index] = num_regions;
+                            stack.push(other);
+                        }
+                    }
+                }
+                num_regions += 1;
+            }
+        }
+        self.regions = regions;
+        self.num_regions = num_regions;
+    }
+
+    /// The region of a Tile in this Chunk, or SOLID
+    pub fn region_at(&self, point: Point) -> u32 {
+        self.regions[self.tile_index(point)]
+    }
+
+    pub fn tile_index(&self, point: Point) -> usize {
+        (point.1 - self.pos.1) * self.size.0 + (point.0 - self.pos.0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_side_nodes<N: Neighborhood>(
         &self,
         dir: Dir,
         total_size: (usize, usize),
         mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &N,
+        annotations: &Annotations,
         config: PathCacheConfig,
         candidates: &mut PointSet,
     ) {
-        let mut current = [
+        let Annotations { clearance, terrain } = annotations;
+        let current = [
             (self.pos.0, self.pos.1),
             (self.pos.0 + self.size.0 - 1, self.pos.1),
             (self.pos.0, self.pos.1 + self.size.1 - 1),
@@ -78,95 +204,227 @@ impl Chunk {
         // 1 == right: start at top-right, go down
         // 2 == down: start at bottom-left, go right
         // 3 == left: start at top-left, go down
-        if get_in_dir(current, dir, (0, 0), total_size).is_none() {
+        if !self.sides[dir.num()] {
             return;
         }
 
+        // sides on the edge of the Grid only exist if it wraps around
         let opposite = |p: Point| {
-            get_in_dir(p, dir, (0, 0), total_size)
+            get_in_dir_wrapping(p, dir, total_size)
                 .expect("Internal Error #1 in Chunk. Please report this")
         };
 
-        let costs = (0..length)
+        let points = (0..length)
             .map(|i| {
                 jump_in_dir(current, next_dir, i, self.pos, self.size)
                     .expect("Internal Error #3 in Chunk. Please report this")
             })
-            .map(|p| (get_cost(p), get_cost(opposite(p))))
+            .to_vec();
+        let costs = points
+            .iter()
+            .map(|&p| (get_cost(p), get_cost(opposite(p))))
+            .to_vec();
+
+        // the directions in which each crossing can be taken. Both, unless the Neighborhood
+        // forbids some moves
+        let directions = points
+            .iter()
+            .map(|&p| {
+                (
+                    neighborhood.is_step_allowed(p, opposite(p), &mut get_cost),
+                    neighborhood.is_step_allowed(opposite(p), p, &mut get_cost),
+                )
+            })
+            .to_vec();
+        // if the Tiles of crossing `i` can be walked to and from those of crossing `i + 1`
+        let mut open = |a: Point, b: Point| {
+            neighborhood.is_step_allowed(a, b, &mut get_cost)
+                && neighborhood.is_step_allowed(b, a, &mut get_cost)
+        };
+        let connected = (1..length)
+            .map(|i| {
+                let (a, b) = (points[i - 1], points[i]);
+                open(a, b) && open(opposite(a), opposite(b))
+            })
             .to_vec();
 
         let solid = |i: usize| {
             let (c1, c2) = &costs[i];
-            *c1 < 0 || *c2 < 0
+            *c1 < 0 || *c2 < 0 || directions[i] == (false, false)
         };
         let total_cost = |i: usize| {
             let (c1, c2) = &costs[i];
             *c1 + *c2
         };
 
-        let mut has_gap = false;
-        let mut gap_start = 0;
-        let mut gap_start_pos = current;
-        let mut previous = current;
-
+        // a gap is a run of crossings that are connected along the side, so that any of them
+        // can be reached from any other
+        let mut gaps = vec![];
+        let mut gap_start = None;
         for i in 0..length {
-            let is_last = i == length - 1;
-            let solid = solid(i);
-
-            if !solid && !has_gap {
-                has_gap = true;
-                gap_start = i;
-                gap_start_pos = current;
+            if solid(i) {
+                if let Some(start) = gap_start.take() {
+                    gaps.push((start, i - 1));
+                }
+                continue;
             }
-            if (solid || is_last) && has_gap {
-                has_gap = false;
-                let (gap_end, gap_end_pos) = if solid {
-                    (i - 1, previous)
-                } else {
-                    (i, current)
-                };
+            match gap_start {
+                None => gap_start = Some(i),
+                Some(start) if !connected[i - 1] => {
+                    gaps.push((start, i - 1));
+                    gap_start = Some(i);
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(start) = gap_start {
+            gaps.push((start, length - 1));
+        }
 
-                let gap_len = gap_end - gap_start + 1;
+        for (gap_start, gap_end) in gaps {
+            let (gap_start_pos, gap_end_pos) = (points[gap_start], points[gap_end]);
+            let gap_len = gap_end - gap_start + 1;
 
-                candidates.insert(gap_start_pos);
-                candidates.insert(gap_end_pos);
+            candidates.insert(gap_start_pos);
+            candidates.insert(gap_end_pos);
 
-                if config.perfect_paths {
-                    let mut p = gap_start_pos;
-                    for _ in (gap_start + 1)..gap_end {
-                        p = get_in_dir(p, next_dir, (0, 0), total_size)
-                            .expect("Internal Error #6 in Chunk. Please report this");
-                        candidates.insert(p);
-                    }
+            if clearance.max() > 1 {
+                // the widest crossing of the gap, so that bigger agents can get through
+                let crossing = |gi: usize| {
+                    let p = points[gi];
+                    clearance.get(p).min(clearance.get(opposite(p)))
+                };
+                let widest = (gap_start..=gap_end)
+                    .max_by_key(|gi| (crossing(*gi), std::cmp::Reverse(*gi)))
+                    .expect("Internal Error #7 in Chunk. Please report this");
+                if crossing(widest) > 1 {
+                    candidates.insert(points[widest]);
+                }
+            }
+
+            // the start of every run of crossings with the same terrain and directions, so that
+            // agents that can't enter every terrain class and one-way moves can get through
+            let crossing = |gi: usize| {
+                let p = points[gi];
+                let mask = if terrain.is_empty() {
+                    0
                 } else {
-                    if gap_len > 2 {
-                        let mut min = total_cost(gap_start).min(total_cost(gap_end));
-                        let mut p = gap_start_pos;
-                        for gi in (gap_start + 1)..gap_end {
-                            p = get_in_dir(p, next_dir, (0, 0), total_size)
-                                .expect("Internal Error #2 in Chunk. Please report this");
-                            let cost = total_cost(gi);
-                            if cost < min {
-                                candidates.insert(p);
-                                min = cost;
-                            }
-                        }
+                    terrain.mask(p) | terrain.mask(opposite(p))
+                };
+                (mask, directions[gi])
+            };
+            let mut run_start = gap_start;
+            for gi in gap_start..=gap_end {
+                if gi == gap_end || crossing(gi + 1) != crossing(run_start) {
+                    if run_start != gap_start || gi != gap_end {
+                        candidates.insert(points[run_start]);
                     }
+                    run_start = gi + 1;
+                }
+            }
 
-                    if gap_len > 6 {
-                        let mid = (
-                            (gap_start_pos.0 + gap_end_pos.0) / 2,
-                            (gap_start_pos.1 + gap_end_pos.1) / 2,
-                        );
-                        candidates.insert(mid);
+            if config.perfect_paths {
+                for &p in &points[gap_start + 1..gap_end] {
+                    candidates.insert(p);
+                }
+            } else {
+                if gap_len > 2 {
+                    let mut min = total_cost(gap_start).min(total_cost(gap_end));
+                    for (gi, &p) in points.iter().enumerate().take(gap_end).skip(gap_start + 1) {
+                        let cost = total_cost(gi);
+                        if cost < min {
+                            candidates.insert(p);
+                            min = cost;
+                        }
                     }
                 }
+
+                if gap_len > 6 {
+                    let mid = (
+                        (gap_start_pos.0 + gap_end_pos.0) / 2,
+                        (gap_start_pos.1 + gap_end_pos.1) / 2,
+                    );
+                    candidates.insert(mid);
+                }
             }
+        }
+    }
 
-            if !is_last {
-                previous = current;
-                current = get_in_dir(current, next_dir, self.pos, self.size)
-                    .expect("Internal Error #3 in Chunk. Please report this");
+    /// Finds the Tiles on a side that have a neighbor in another Chunk, which the gaps of
+    /// [`calculate_side_nodes`](Chunk::calculate_side_nodes) don't cover.
+    ///
+    /// Those gaps only look at the Tile straight across the side. Neighborhoods that also step
+    /// to other Tiles across it (like the diagonals of a MooreNeighborhood or the neighbors on
+    /// a hex Grid) would lose those connections otherwise. A Tile is needed if the straight
+    /// crossing of itself or of its neighbor is solid, or if the neighbor is in a diagonal
+    /// Chunk. Since that is symmetric, the neighbor becomes a Node of its own Chunk as well.
+    ///
+    /// If the Neighborhood forbids moves in some directions, "solid" above also includes
+    /// crossings that can't be walked in both directions.
+    pub fn calculate_crossing_nodes<N: Neighborhood>(
+        &self,
+        dir: Dir,
+        total_size: (usize, usize),
+        mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &N,
+        candidates: &mut PointSet,
+    ) {
+        if !self.sides[dir.num()] {
+            return;
+        }
+        let (start, next_dir, length) = match dir {
+            UP => ((self.left(), self.top()), RIGHT, self.size.0),
+            RIGHT => ((self.right() - 1, self.top()), DOWN, self.size.1),
+            DOWN => ((self.left(), self.bottom() - 1), RIGHT, self.size.0),
+            LEFT => ((self.left(), self.top()), DOWN, self.size.1),
+        };
+        // the Tile in the Chunk that `other` is straight across from, if any
+        let back = |other: Point, dir: Dir| {
+            get_in_dir_wrapping(other, dir.opposite(), total_size).filter(|p| self.in_chunk(*p))
+        };
+
+        let allowed = |a: Point, b: Point, get_cost: &mut dyn FnMut(Point) -> isize| {
+            neighborhood.is_step_allowed(a, b, &mut *get_cost)
+                || neighborhood.is_step_allowed(b, a, get_cost)
+        };
+        // both Tiles are walkable and can be walked between in both directions
+        let open = |a: Point, b: Point, get_cost: &mut dyn FnMut(Point) -> isize| {
+            get_cost(a) >= 0
+                && get_cost(b) >= 0
+                && neighborhood.is_step_allowed(a, b, &mut *get_cost)
+                && neighborhood.is_step_allowed(b, a, get_cost)
+        };
+
+        let mut neighbors = vec![];
+        for i in 0..length {
+            let p = jump_in_dir(start, next_dir, i, self.pos, self.size)
+                .expect("Internal Error #8 in Chunk. Please report this");
+            if get_cost(p) < 0 {
+                continue;
+            }
+            let straight = get_in_dir_wrapping(p, dir, total_size)
+                .expect("Internal Error #9 in Chunk. Please report this");
+            neighbors.clear();
+            neighborhood.get_all_neighbors(p, &mut neighbors);
+            for &other in neighbors.iter() {
+                if other == straight || self.in_chunk(other) || get_cost(other) < 0 {
+                    continue;
+                }
+                if !allowed(p, other, &mut get_cost) {
+                    continue;
+                }
+                let needed = if let Some(other_back) = back(other, dir) {
+                    !(open(p, straight, &mut get_cost)
+                        && open(straight, other, &mut get_cost)
+                        && open(p, other_back, &mut get_cost)
+                        && open(other_back, other, &mut get_cost))
+                } else {
+                    // crossings of the other sides are handled there
+                    !Dir::all().any(|d| back(other, d).is_some())
+                };
+                if needed {
+                    candidates.insert(p);
+                }
             }
         }
     }
@@ -177,29 +435,57 @@ impl Chunk {
         mut get_cost: impl FnMut(Point) -> isize,
         neighborhood: &N,
         all_nodes: &mut NodeList,
+        annotations: &Annotations,
         config: &PathCacheConfig,
     ) {
         // first to_visit, then the rest => slicing works the same on both lists
-        let points = to_visit
-            .iter()
-            .chain(self.nodes.iter())
-            .map(|id| all_nodes[*id].pos)
-            .to_vec();
+        let ids = to_visit.iter().chain(self.nodes.iter()).copied().to_vec();
+        let points = ids.iter().map(|id| all_nodes[*id].pos).to_vec();
 
         for &id in to_visit.iter() {
             self.nodes.insert(id);
         }
 
-        for (i, &id) in to_visit.iter().enumerate() {
-            let point = points[i];
-            let remaining = &points[(i + 1)..];
-            let paths = self.find_paths(point, remaining, &mut get_cost, neighborhood);
-            for (other_pos, path) in paths {
-                let other_id = all_nodes
-                    .id_at(other_pos)
-                    .expect("Internal Error #5 in Chunk. Please report this");
+        if all_nodes.is_directed() {
+            // edges only go one way => every Node needs its own Paths to the new Nodes, and the
+            // new Nodes need Paths to all others
+            for (i, &id) in ids.iter().enumerate() {
+                let targets = if i < to_visit.len() {
+                    &points[..]
+                } else {
+                    &points[..to_visit.len()]
+                };
+                let others = targets.iter().copied().filter(|p| *p != points[i]).to_vec();
+                let edges = self.connect_node(
+                    id,
+                    points[i],
+                    &others,
+                    &mut get_cost,
+                    neighborhood,
+                    all_nodes,
+                    annotations,
+                    config,
+                );
+                for edge in edges {
+                    edge.insert(all_nodes);
+                }
+            }
+            return;
+        }
 
-                all_nodes.add_edge(id, other_id, PathSegment::new(path, config.cache_paths));
+        for (i, &id) in to_visit.iter().enumerate() {
+            let edges = self.connect_node(
+                id,
+                points[i],
+                &points[(i + 1)..],
+                &mut get_cost,
+                neighborhood,
+                all_nodes,
+                annotations,
+                config,
+            );
+            for edge in edges {
+                edge.insert(all_nodes);
             }
         }
     }
@@ -209,9 +495,11 @@ impl Chunk {
         &self,
         get_cost: F1,
         neighborhood: &N,
+        moves_grid: Option<&(dyn CostGrid + Sync)>,
         all_nodes: &NodeList,
-        cache_paths: bool,
-    ) -> Vec<(NodeID, NodeID, PathSegment)> {
+        annotations: &Annotations,
+        config: &PathCacheConfig,
+    ) -> Vec<ChunkEdge> {
         use rayon::prelude::*;
 
         let mut ids = Vec::with_capacity(self.nodes.len());
@@ -224,21 +512,86 @@ impl Chunk {
         // connect every Node to every other Node
         ids.par_iter()
             .flat_map(|&(i, id)| {
-                let point = points[i];
-                let remaining = &points[(i + 1)..];
-                self.find_paths(point, remaining, &get_cost, neighborhood)
-                    .into_par_iter()
-                    .map(move |(other_pos, path)| {
-                        let other_id = all_nodes
-                            .id_at(other_pos)
-                            .expect("Internal Error #5 in Chunk. Please report this");
-
-                        (id, other_id, PathSegment::new(path, cache_paths))
-                    })
+                let others = if all_nodes.is_directed() {
+                    // edges only go one way => every Node needs its own Paths
+                    points.iter().copied().filter(|p| *p != points[i]).to_vec()
+                } else {
+                    points[(i + 1)..].to_vec()
+                };
+                // the move costs can't be shared between threads
+                let moves = super::moves::MoveCosts::with_grid(neighborhood.clone(), moves_grid);
+                self.connect_node(
+                    id,
+                    points[i],
+                    &others,
+                    &get_cost,
+                    &moves,
+                    all_nodes,
+                    annotations,
+                    config,
+                )
             })
             .collect()
     }
 
+    /// Finds the edges from the Node `id` at `point` to the Nodes at `others`
+    #[allow(clippy::too_many_arguments)]
+    fn connect_node<N: Neighborhood>(
+        &self,
+        id: NodeID,
+        point: Point,
+        others: &[Point],
+        mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &N,
+        all_nodes: &NodeList,
+        annotations: &Annotations,
+        config: &PathCacheConfig,
+    ) -> Vec<ChunkEdge> {
+        let cache_paths = config.cache_paths;
+        let paths = self.find_paths(point, others, &mut get_cost, neighborhood);
+        let mut wide_paths = self.find_wide_paths(
+            point,
+            &paths,
+            &mut get_cost,
+            neighborhood,
+            &annotations.clearance,
+        );
+        let mut terrain_paths = self.find_terrain_paths(
+            point,
+            &paths,
+            &mut get_cost,
+            neighborhood,
+            &annotations.terrain,
+        );
+        paths
+            .into_iter()
+            .map(|(other_pos, path)| {
+                let other_id = all_nodes
+                    .id_at(other_pos)
+                    .expect("Internal Error #5 in Chunk. Please report this");
+
+                let wide = wide_paths.remove(&other_pos).map(|wide| {
+                    wide.into_iter()
+                        .map(|(c, path)| (c, PathSegment::new(path, cache_paths)))
+                        .to_vec()
+                });
+                let terrain = terrain_paths.remove(&other_pos).map(|paths| {
+                    paths
+                        .into_iter()
+                        .map(|(r, path)| (r, PathSegment::new(path, cache_paths)))
+                        .to_vec()
+                });
+                ChunkEdge {
+                    start: id,
+                    end: other_id,
+                    path: PathSegment::new(path, cache_paths),
+                    wide,
+                    terrain,
+                }
+            })
+            .to_vec()
+    }
+
     pub fn find_paths<N: Neighborhood>(
         &self,
         start: Point,
@@ -268,6 +621,164 @@ impl Chunk {
         )
     }
 
+    /// Finds the Paths from `start` to the goals of `paths` for agents that don't fit through
+    /// those Paths, but do fit on both ends.
+    ///
+    /// Returns the alternatives as (clearance, Path) for every such goal, starting with the
+    /// original Path. See [`Node::wide_edges`]
+    pub fn find_wide_paths<N: Neighborhood>(
+        &self,
+        start: Point,
+        paths: &PointMap<Path<Point>>,
+        mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &N,
+        clearance: &Clearance,
+    ) -> PointMap<Vec<(usize, Path<Point>)>> {
+        let mut ret = PointMap::default();
+        if clearance.max() <= 1 {
+            return ret;
+        }
+        let width = |path: &Path<Point>| path.iter().map(|p| clearance.get(*p)).min().unwrap_or(0);
+        let start_clearance = clearance.get(start);
+
+        // goal => (biggest agent that fits on both ends, width of the widest Path so far)
+        let mut open = PointMap::default();
+        for (&goal, path) in paths.iter() {
+            let fits = start_clearance.min(clearance.get(goal));
+            let path_width = width(path);
+            if path_width < fits {
+                open.insert(goal, (fits, path_width));
+                ret.insert(goal, vec![(path_width, path.clone())]);
+            }
+        }
+
+        while let Some(size) = open.values().map(|(_, w)| w + 1).min() {
+            let targets = open
+                .iter()
+                .filter(|(_, (_, w))| w + 1 == size)
+                .map(|(goal, _)| *goal)
+                .to_vec();
+            let mut found = self.find_paths(
+                start,
+                &targets,
+                |p| {
+                    if clearance.get(p) >= size {
+                        get_cost(p)
+                    } else {
+                        -1
+                    }
+                },
+                neighborhood,
+            );
+            for goal in targets {
+                let path = if let Some(path) = found.remove(&goal) {
+                    path
+                } else {
+                    // no Path for agents of this size or bigger
+                    open.remove(&goal);
+                    continue;
+                };
+                let path_width = width(&path);
+                ret.get_mut(&goal)
+                    .expect("Internal Error #8 in Chunk. Please report this")
+                    .push((path_width, path));
+                let (fits, w) = open
+                    .get_mut(&goal)
+                    .expect("Internal Error #8 in Chunk. Please report this");
+                if path_width >= *fits {
+                    open.remove(&goal);
+                } else {
+                    *w = path_width;
+                }
+            }
+        }
+        ret
+    }
+
+    /// Finds the Paths from `start` to the goals of `paths` for agents that can't enter every
+    /// terrain class on those Paths, but can enter both ends.
+    ///
+    /// Returns the alternatives as (required capabilities, Path) for every such goal, sorted by
+    /// cost and starting with the original Path. See [`Node::terrain_edges`]
+    pub fn find_terrain_paths<N: Neighborhood>(
+        &self,
+        start: Point,
+        paths: &PointMap<Path<Point>>,
+        mut get_cost: impl FnMut(Point) -> isize,
+        neighborhood: &N,
+        terrain: &Terrain,
+    ) -> PointMap<Vec<(u32, Path<Point>)>> {
+        let mut ret = PointMap::default();
+        if terrain.is_empty() {
+            return ret;
+        }
+        let required = |path: &Path<Point>| path.iter().fold(0, |r, p| r | terrain.mask(*p));
+        let goals = paths.keys().copied().to_vec();
+
+        // forbidden classes => the Paths that avoid them
+        let mut cache = hashbrown::HashMap::<u32, PointMap<Path<Point>>>::new();
+        cache.insert(0, paths.clone());
+
+        for &goal in goals.iter() {
+            let ends = terrain.mask(start) | terrain.mask(goal);
+            if required(&paths[&goal]) & !ends == 0 {
+                continue;
+            }
+            // The best Path for an agent is the best Path that avoids the forbidden classes it
+            // can't enter. Any such Path either avoids them all, or the agent can't enter one of
+            // the classes on it, so forbidding each of those in turn finds all the best Paths.
+            let mut alternatives: Vec<(u32, Path<Point>)> = vec![];
+            let mut visited = hashbrown::HashSet::new();
+            let mut stack = vec![0u32];
+            while let Some(forbidden) = stack.pop() {
+                if !visited.insert(forbidden) {
+                    continue;
+                }
+                let found = cache.entry(forbidden).or_insert_with(|| {
+                    self.find_paths(
+                        start,
+                        &goals,
+                        |p| {
+                            if terrain.mask(p) & forbidden == 0 {
+                                get_cost(p)
+                            } else {
+                                -1
+                            }
+                        },
+                        neighborhood,
+                    )
+                });
+                let path = if let Some(path) = found.get(&goal) {
+                    path
+                } else {
+                    continue;
+                };
+                let path_required = required(path);
+                let mut others = path_required & !ends;
+                while others != 0 {
+                    let class = others & others.wrapping_neg();
+                    stack.push(forbidden | class);
+                    others &= !class;
+                }
+                if !alternatives.iter().any(|(r, _)| *r == path_required) {
+                    alternatives.push((path_required, path.clone()));
+                }
+            }
+            // stable sort => the original Path stays first among equal costs
+            alternatives.sort_by_key(|(_, path)| path.cost());
+            let mut result: Vec<(u32, Path<Point>)> = vec![];
+            for (r, path) in alternatives {
+                // a Path that requires more than a cheaper one is never used
+                if !result.iter().any(|(other, _)| other & !r == 0) {
+                    result.push((r, path));
+                }
+            }
+            ret.insert(goal, result);
+        }
+        ret
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn nearest_node<N: Neighborhood>(
         &self,
         all_nodes: &NodeList,
@@ -275,16 +786,25 @@ impl Chunk {
         mut get_cost: impl FnMut(Point) -> isize,
         neighborhood: &N,
         reverse: bool,
+        mut is_valid: impl FnMut(NodeID) -> bool,
+        options: SearchOptions,
     ) -> Option<(NodeID, Path<Point>)> {
         let start_cost = get_cost(start);
         if start_cost < 0 {
             if !reverse {
                 return None;
             }
-            self.nodes.iter().copied().find_map(|id| {
-                self.find_path(all_nodes[id].pos, start, &mut get_cost, neighborhood)
-                    .map(|path| (id, path))
-            })
+            // start is solid => any Node that can reach it. is_valid makes sure that the Node
+            // is connected to wherever the Path comes from
+            self.nodes
+                .iter()
+                .copied()
+                .filter(|id| is_valid(*id))
+                .find_map(|id| {
+                    let pos = all_nodes[id].pos;
+                    self.find_path(pos, start, &mut get_cost, neighborhood, options)
+                        .map(|path| (id, path))
+                })
         } else {
             let mut points = Vec::with_capacity(self.nodes.len());
             let mut map = PointMap::default();
@@ -293,6 +813,10 @@ impl Chunk {
             for id in self.nodes.iter() {
                 let node = &all_nodes[*id];
                 let point = node.pos;
+                if get_cost(point) < 0 {
+                    // the cost function may exclude Nodes, e.g. for bigger agents
+                    continue;
+                }
                 points.push(point);
                 map.insert(point, (*id, node.walk_cost));
                 min_heuristic = min_heuristic.min(neighborhood.heuristic(start, point));
@@ -300,28 +824,43 @@ impl Chunk {
 
             let max_size = self.size.0 * self.size.1;
             let size_hint = min_heuristic as f32 / max_heuristic as f32 * max_size as f32;
-            grid::dijkstra_search(
-                neighborhood,
-                |p| self.in_chunk(p),
-                get_cost,
-                start,
-                &points,
-                true,
-                size_hint as usize,
-            )
-            .into_iter()
-            .next()
-            .map(|(point, path)| {
-                let (id, node_cost) = map[&point];
-                (
-                    id,
-                    if reverse {
-                        path.reversed(start_cost as usize, node_cost)
-                    } else {
-                        path
-                    },
+            let (id, path) = if reverse && all_nodes.is_directed() {
+                // the steps back may cost something else or not be allowed at all => search
+                // with the costs of the steps towards start, which then only needs to be turned
+                // around
+                let (point, path) = grid::dijkstra_search(
+                    &Reversed(neighborhood),
+                    |p| self.in_chunk(p),
+                    get_cost,
+                    start,
+                    &points,
+                    true,
+                    size_hint as usize,
                 )
-            })
+                .into_iter()
+                .next()?;
+                (map[&point].0, path.reversed(0, 0))
+            } else {
+                let (point, path) = grid::dijkstra_search(
+                    neighborhood,
+                    |p| self.in_chunk(p),
+                    get_cost,
+                    start,
+                    &points,
+                    true,
+                    size_hint as usize,
+                )
+                .into_iter()
+                .next()?;
+                let (id, node_cost) = map[&point];
+                if reverse {
+                    let start_cost = start_cost as usize * neighborhood.cost_scale();
+                    (id, path.reversed(start_cost, node_cost))
+                } else {
+                    (id, path)
+                }
+            };
+            Some((id, path))
         }
     }
     pub fn find_path<N: Neighborhood>(
@@ -330,6 +869,7 @@ impl Chunk {
         goal: Point,
         get_cost: impl FnMut(Point) -> isize,
         neighborhood: &N,
+        options: SearchOptions,
     ) -> Option<Path<Point>> {
         if !self.in_chunk(start) || !self.in_chunk(goal) {
             return None;
@@ -346,6 +886,7 @@ impl Chunk {
             start,
             goal,
             size_hint as usize,
+            options,
         )
     }
 
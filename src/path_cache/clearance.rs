@@ -0,0 +1,119 @@
+use crate::*;
+
+/// The clearance of every Tile on the Grid.
+///
+/// The clearance of a Tile is the size of the biggest square agent that can stand with its
+/// top-left corner on that Tile, without overlapping any solid Tiles or leaving the Grid.
+/// Solid Tiles have a clearance of `0`. All values are capped at `max`.
+#[derive(Clone, Debug)]
+pub struct Clearance {
+    width: usize,
+    height: usize,
+    max: usize,
+    /// empty if `max <= 1`, since every walkable Tile has a clearance of `1` then
+    values: Vec<u8>,
+}
+
+impl Clearance {
+    pub fn new(
+        (width, height): (usize, usize),
+        max: usize,
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) -> Clearance {
+        let max = max.clamp(1, u8::MAX as usize);
+        let mut clearance = Clearance {
+            width,
+            height,
+            max,
+            values: vec![],
+        };
+        if max > 1 {
+            clearance.values = vec![0; width * height];
+            for y in (0..height).rev() {
+                for x in (0..width).rev() {
+                    clearance.values[y * width + x] = clearance.calculate((x, y), &mut get_cost);
+                }
+            }
+        }
+        clearance
+    }
+
+    /// Creates a Clearance from previously stored values
+    pub fn from_values((width, height): (usize, usize), max: usize, values: Vec<u8>) -> Clearance {
+        Clearance {
+            width,
+            height,
+            max: max.clamp(1, u8::MAX as usize),
+            values,
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// The clearance of a walkable Tile
+    pub fn get(&self, (x, y): Point) -> usize {
+        if self.values.is_empty() {
+            self.max
+        } else {
+            self.values[y * self.width + x] as usize
+        }
+    }
+
+    /// The clearance of a Tile, based on the Tiles to the right and below it
+    fn calculate(&self, (x, y): Point, get_cost: &mut impl FnMut(Point) -> isize) -> u8 {
+        if get_cost((x, y)) < 0 {
+            return 0;
+        }
+        let at = |x: usize, y: usize| {
+            if x < self.width && y < self.height {
+                self.values[y * self.width + x]
+            } else {
+                0
+            }
+        };
+        let min = at(x + 1, y).min(at(x, y + 1)).min(at(x + 1, y + 1));
+        (min as usize + 1).min(self.max) as u8
+    }
+
+    /// Recalculates the clearance around the changed `tiles`.
+    ///
+    /// Returns all Tiles whose clearance changed.
+    pub fn update(
+        &mut self,
+        tiles: &[Point],
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) -> Vec<Point> {
+        if self.values.is_empty() {
+            return vec![];
+        }
+        // a Tile only affects the clearance of the Tiles up to max - 1 Tiles above and left of it
+        let mut affected = vec![];
+        for &(x, y) in tiles {
+            for ay in (y + 1).saturating_sub(self.max)..=y {
+                for ax in (x + 1).saturating_sub(self.max)..=x {
+                    affected.push((ax, ay));
+                }
+            }
+        }
+        // every Tile depends on the Tiles after it => calculate them in reverse order
+        affected.sort_unstable_by_key(|&(x, y)| std::cmp::Reverse((y, x)));
+        affected.dedup();
+
+        let mut changed = vec![];
+        for (x, y) in affected {
+            let value = self.calculate((x, y), &mut get_cost);
+            let old = &mut self.values[y * self.width + x];
+            if *old != value {
+                *old = value;
+                changed.push((x, y));
+            }
+        }
+        changed
+    }
+}
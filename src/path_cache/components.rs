@@ -0,0 +1,269 @@
+use crate::{neighbors::Neighborhood, *};
+
+use super::chunk::{Chunk, SOLID};
+
+/// The connected components of the Grid.
+///
+/// Every Chunk splits its Tiles into regions (see [`Chunk::regions`]). The regions of
+/// neighboring Chunks are linked if any of their Tiles are neighbors, and all linked regions
+/// form a component, together with the regions at both ends of any portal (the links of the
+/// PathCache). Two walkable Tiles are connected if and only if they are in the same component.
+#[derive(Clone, Debug, Default)]
+pub struct Components {
+    /// for every Chunk: the component of every region
+    labels: Vec<Vec<u32>>,
+    /// for every Chunk: (own region, other Chunk, other region), sorted. Every link is also
+    /// stored the other way around by the other Chunk
+    links: Vec<Vec<(u32, usize, u32)>>,
+    /// the label of the next new component
+    next_label: u32,
+}
+
+impl Components {
+    pub fn new<N: Neighborhood>(
+        chunks: &[Chunk],
+        chunk_index: impl Fn(Point) -> usize,
+        neighborhood: &N,
+        portals: &[(Point, Point)],
+    ) -> Components {
+        let mut components = Components {
+            labels: vec![vec![]; chunks.len()],
+            links: vec![vec![]; chunks.len()],
+            next_label: 0,
+        };
+        let all = 0..chunks.len();
+        components.update(chunks, all, chunk_index, neighborhood, portals);
+        components
+    }
+
+    /// Recalculates the components after the regions of the `changed` Chunks were recalculated
+    pub fn update<N: Neighborhood>(
+        &mut self,
+        chunks: &[Chunk],
+        changed: impl IntoIterator<Item = usize>,
+        chunk_index: impl Fn(Point) -> usize,
+        neighborhood: &N,
+        portals: &[(Point, Point)],
+    ) {
+        // Links of neighboring Chunks point into the changed Chunks and need to be renewed as well
+        let mut renew = hashbrown::HashSet::<usize>::default();
+        let width = chunks.last().map_or(0, |chunk| chunk.right()) as isize;
+        let wrap = neighborhood.wraps_horizontally();
+        for index in changed {
+            let chunk = &chunks[index];
+            let (left, top) = (chunk.left() as isize, chunk.top() as isize);
+            let (right, bottom) = (chunk.right() as isize, chunk.bottom() as isize);
+            for (mut x, y) in [
+                (left - 1, top - 1),
+                (left, top - 1),
+                (right, top - 1),
+                (right, top),
+                (right, bottom),
+                (left, bottom),
+                (left - 1, bottom),
+                (left - 1, top),
+            ] {
+                if wrap {
+                    x = x.rem_euclid(width);
+                }
+                if x >= 0 && y >= 0 {
+                    let p = (x as usize, y as usize);
+                    let other = chunk_index(p);
+                    if other < chunks.len() && chunks[other].in_chunk(p) {
+                        renew.insert(other);
+                    }
+                }
+            }
+            renew.insert(index);
+            // the regions of the Chunk were numbered anew
+            self.labels[index] = vec![0; chunk.num_regions as usize];
+        }
+
+        // the regions know which Tiles are solid, which is all that is_step_allowed needs
+        let get_cost = |p: Point| {
+            let chunk = &chunks[chunk_index(p)];
+            if chunk.region_at(p) == SOLID {
+                -1
+            } else {
+                0
+            }
+        };
+
+        let mut neighbors = vec![];
+        for &index in renew.iter() {
+            let chunk = &chunks[index];
+            let links = &mut self.links[index];
+            links.clear();
+            for y in chunk.top()..chunk.bottom() {
+                for x in chunk.left()..chunk.right() {
+                    if !Dir::all().any(|dir| chunk.sides[dir.num()] && chunk.at_side((x, y), dir)) {
+                        continue;
+                    }
+                    let region = chunk.region_at((x, y));
+                    if region == SOLID {
+                        continue;
+                    }
+                    neighbors.clear();
+                    neighborhood.get_all_neighbors((x, y), &mut neighbors);
+                    for &p in neighbors.iter().filter(|p| !chunk.in_chunk(**p)) {
+                        let other = chunk_index(p);
+                        let other_region = chunks[other].region_at(p);
+                        // components ignore the direction of a step, and that keeps the
+                        // links of both Chunks the same
+                        if other_region != SOLID
+                            && (neighborhood.is_step_allowed((x, y), p, get_cost)
+                                || neighborhood.is_step_allowed(p, (x, y), get_cost))
+                        {
+                            links.push((region, other, other_region));
+                        }
+                    }
+                }
+            }
+            links.sort_unstable();
+            links.dedup();
+        }
+
+        // only the components that touch the renewed Chunks can have split or merged
+        let seeds = renew
+            .iter()
+            .flat_map(|&index| (0..chunks[index].num_regions).map(move |region| (index, region)));
+        self.relabel_from(chunks, chunk_index, portals, seeds);
+    }
+
+    /// Assigns new components to the Tiles at `ends` after the `portals` (pairs of Tiles that
+    /// are connected regardless of their distance) between them changed
+    pub fn relabel(
+        &mut self,
+        chunks: &[Chunk],
+        chunk_index: impl Fn(Point) -> usize,
+        portals: &[(Point, Point)],
+        ends: &[Point],
+    ) {
+        let seeds = ends
+            .iter()
+            .map(|&p| (chunk_index(p), chunks[chunk_index(p)].region_at(p)))
+            .filter(|&(_, region)| region != SOLID)
+            .to_vec();
+        self.relabel_from(chunks, chunk_index, portals, seeds);
+    }
+
+    /// Gives a new component to every region that is linked to one of the `seeds`, which are
+    /// pairs of Chunk index and region
+    fn relabel_from(
+        &mut self,
+        chunks: &[Chunk],
+        chunk_index: impl Fn(Point) -> usize,
+        portals: &[(Point, Point)],
+        seeds: impl IntoIterator<Item = (usize, u32)>,
+    ) {
+        let mut portal_ends = hashbrown::HashMap::<(usize, u32), Vec<(usize, u32)>>::default();
+        for &(a, b) in portals {
+            let (index, other) = (chunk_index(a), chunk_index(b));
+            let (region, other_region) = (chunks[index].region_at(a), chunks[other].region_at(b));
+            if region != SOLID && other_region != SOLID {
+                let (a, b) = ((index, region), (other, other_region));
+                portal_ends.entry(a).or_default().push(b);
+                portal_ends.entry(b).or_default().push(a);
+            }
+        }
+
+        let mut visited = hashbrown::HashSet::<(usize, u32)>::default();
+        let mut next = vec![];
+        for seed in seeds {
+            if !visited.insert(seed) {
+                continue;
+            }
+            let label = self.next_label;
+            self.next_label += 1;
+            next.push(seed);
+            while let Some((index, region)) = next.pop() {
+                self.labels[index][region as usize] = label;
+                let links = &self.links[index];
+                let first = links.partition_point(|link| link.0 < region);
+                let linked = links[first..]
+                    .iter()
+                    .take_while(|link| link.0 == region)
+                    .map(|&(_, other, other_region)| (other, other_region));
+                let ported = portal_ends.get(&(index, region)).into_iter().flatten();
+                for other in linked.chain(ported.copied()) {
+                    if visited.insert(other) {
+                        next.push(other);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The component of a Tile, or `None` if the Tile is solid
+    pub fn get(&self, chunk_index: usize, chunk: &Chunk, point: Point) -> Option<u32> {
+        match chunk.region_at(point) {
+            SOLID => None,
+            region => Some(self.labels[chunk_index][region as usize]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Components;
+    use crate::{prelude::*, Point};
+
+    #[test]
+    fn chokepoint() {
+        let size = 16;
+        // a wall with a single gap, on the corner of a Chunk
+        let mut grid = vec![vec![1_isize; size]; size];
+        for row in grid.iter_mut() {
+            row[8] = -1;
+        }
+        grid[7][8] = 1;
+        let neighborhood = MooreNeighborhood::new(size, size);
+        let mut pathfinding = PathCache::new(
+            (size, size),
+            &|(x, y): (usize, usize)| grid[y][x],
+            neighborhood,
+            PathCacheConfig::with_chunk_size(4),
+        );
+
+        // the labels have to split the Tiles the same way as labelling everything from scratch
+        let check = |pathfinding: &PathCache<MooreNeighborhood>, connected: bool| {
+            let fresh = Components::new(
+                &pathfinding.chunks,
+                pathfinding.chunk_index_fn(),
+                &neighborhood,
+                &[],
+            );
+            let fresh_at = |p: Point| {
+                let index = pathfinding.get_chunk_index(p);
+                fresh.get(index, &pathfinding.chunks[index], p)
+            };
+            let mut pairs = hashbrown::HashMap::new();
+            let mut reverse = hashbrown::HashMap::new();
+            for y in 0..size {
+                for x in 0..size {
+                    let label = pathfinding.component_at((x, y));
+                    let fresh_label = fresh_at((x, y));
+                    assert_eq!(label.is_some(), fresh_label.is_some());
+                    if let (Some(label), Some(fresh_label)) = (label, fresh_label) {
+                        assert_eq!(*pairs.entry(label).or_insert(fresh_label), fresh_label);
+                        assert_eq!(*reverse.entry(fresh_label).or_insert(label), label);
+                    }
+                }
+            }
+            let (left, right) = ((0, 0), (size - 1, size - 1));
+            assert_eq!(
+                pathfinding.component_at(left) == pathfinding.component_at(right),
+                connected
+            );
+        };
+        check(&pathfinding, true);
+
+        grid[7][8] = -1;
+        pathfinding.tiles_changed(&[(8, 7)], &|(x, y): (usize, usize)| grid[y][x]);
+        check(&pathfinding, false);
+
+        grid[7][8] = 1;
+        pathfinding.tiles_changed(&[(8, 7)], &|(x, y): (usize, usize)| grid[y][x]);
+        check(&pathfinding, true);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{grid::Element, neighbors::Neighborhood, path::Cost, *};
+
+use super::{chunk::Chunk, moves::MoveCosts, PathCache};
+
+use std::collections::BinaryHeap;
+
+/// The direction of a Tile that has no next step
+const NO_STEP: u8 = u8::MAX;
+/// The direction of a Tile whose next step is a jump through a link
+const JUMP: u8 = u8::MAX - 1;
+
+/// The directions and costs of all Tiles in a Chunk. Both are empty if no Tile in the Chunk can
+/// reach a goal
+struct ChunkField {
+    /// the index of the next step in the neighbors of every Tile, or [`NO_STEP`] or [`JUMP`]
+    dirs: Vec<u8>,
+    /// the cost of reaching the nearest goal from every Tile, or `Cost::MAX` if it can't be
+    /// reached
+    costs: Vec<Cost>,
+    /// the other end of the link for every Tile marked with [`JUMP`]
+    jumps: PointMap<Point>,
+}
+
+/// The cost of reaching the nearest of several goals, and the next step on the way there, for
+/// every Tile of the Grid.
+///
+/// This is the shared part of [`FlowField`](super::FlowField) and
+/// [`DistanceMap`](super::DistanceMap). A reverse Dijkstra search from the goals over all Nodes
+/// is run when it is created, and the Tiles of a Chunk are calculated the first time one of them
+/// is looked up.
+pub struct CostField<'a, N: Neighborhood, G: CostGrid> {
+    cache: &'a PathCache<N>,
+    grid: &'a G,
+    /// the goals in every Chunk
+    goals: Vec<Vec<Point>>,
+    max_cost: Cost,
+    /// the cost of reaching the nearest goal from every Node that can reach one, and the next
+    /// Node on the way there, or `None` if the goal is reached within the Chunk of the Node
+    nodes: NodeIDMap<(Cost, Option<NodeID>)>,
+    chunks: Vec<Option<ChunkField>>,
+    neighbors: Vec<Point>,
+}
+
+impl<'a, N, G> CostField<'a, N, G>
+where
+    N: Neighborhood + Sync,
+    G: CostGrid,
+{
+    /// Creates a CostField for all Tiles that can reach one of `goals` for at most `max_cost`
+    pub fn new(cache: &'a PathCache<N>, goals: &[Point], max_cost: Cost, grid: &'a G) -> Self {
+        let mut field = CostField {
+            cache,
+            grid,
+            goals: vec![vec![]; cache.chunks.len()],
+            max_cost,
+            nodes: NodeIDMap::default(),
+            chunks: (0..cache.chunks.len()).map(|_| None).collect(),
+            neighbors: vec![],
+        };
+        for &goal in goals {
+            field.check_bounds(goal);
+            field.goals[cache.get_chunk_index(goal)].push(goal);
+        }
+
+        // the Nodes in the Chunks of the goals can reach them directly
+        let mut next = BinaryHeap::new();
+        for (index, chunk) in cache.chunks.iter().enumerate() {
+            if field.goals[index].is_empty() {
+                continue;
+            }
+            let goals = std::mem::take(&mut field.goals[index]);
+            let local = field.calculate_chunk(chunk, &goals, false);
+            field.goals[index] = goals;
+            if local.costs.is_empty() {
+                continue;
+            }
+            for &id in chunk.nodes.iter() {
+                let cost = local.costs[chunk.tile_index(cache.nodes[id].pos)];
+                if cost != Cost::MAX {
+                    field.nodes.insert(id, (cost, None));
+                    next.push(Element(id, cost));
+                }
+            }
+        }
+
+        // reverse Dijkstra: the cost of an edge is the cost of the Node it starts at
+        while let Some(Element(current_id, current_cost)) = next.pop() {
+            if current_cost > field.nodes[&current_id].0 {
+                continue;
+            }
+            cache.nodes.for_each_incoming(current_id, |other_id| {
+                let path = match cache.nodes[other_id].edges.get(&current_id) {
+                    Some(path) => path,
+                    None => return,
+                };
+                let other_cost = current_cost + path.cost();
+                if other_cost > max_cost {
+                    return;
+                }
+                let needs_visit = field
+                    .nodes
+                    .get(&other_id)
+                    .is_none_or(|&(prev_cost, _)| other_cost < prev_cost);
+                if needs_visit {
+                    field.nodes.insert(other_id, (other_cost, Some(current_id)));
+                    next.push(Element(other_id, other_cost));
+                }
+            });
+        }
+
+        field
+    }
+
+    /// The Tile to move to from `pos` on the way to the nearest goal
+    pub fn next_step(&mut self, pos: Point) -> Option<Point> {
+        let (field, index) = self.lookup(pos);
+        let dir = *field.dirs.get(index)?;
+        if dir == NO_STEP {
+            return None;
+        }
+        if dir == JUMP {
+            return Some(field.jumps[&pos]);
+        }
+        self.neighbors.clear();
+        self.cache
+            .neighborhood
+            .get_all_neighbors(pos, &mut self.neighbors);
+        Some(self.neighbors[dir as usize])
+    }
+
+    /// The cost of reaching the nearest goal from `pos`
+    pub fn cost(&mut self, pos: Point) -> Option<Cost> {
+        let (field, index) = self.lookup(pos);
+        let cost = *field.costs.get(index)?;
+        (cost != Cost::MAX).then_some(cost)
+    }
+
+    /// The number of Nodes that can reach a goal
+    pub fn reachable_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The number of Chunks whose Tiles were calculated
+    pub fn calculated_chunks(&self) -> usize {
+        self.chunks.iter().filter(|c| c.is_some()).count()
+    }
+
+    fn check_bounds(&self, pos: Point) {
+        assert!(
+            pos.0 < self.cache.width && pos.1 < self.cache.height,
+            "{:?} is outside of the Grid",
+            pos
+        );
+    }
+
+    /// The field of the Chunk that contains `pos` and the index of `pos` in that field
+    fn lookup(&mut self, pos: Point) -> (&ChunkField, usize) {
+        self.check_bounds(pos);
+        let cache = self.cache;
+        let chunk_index = cache.get_chunk_index(pos);
+        let chunk = &cache.chunks[chunk_index];
+        if self.chunks[chunk_index].is_none() {
+            let goals = std::mem::take(&mut self.goals[chunk_index]);
+            let field = self.calculate_chunk(chunk, &goals, true);
+            self.goals[chunk_index] = goals;
+            self.chunks[chunk_index] = Some(field);
+        }
+        let field = self.chunks[chunk_index].as_ref().unwrap();
+        (field, chunk.tile_index(pos))
+    }
+
+    /// Runs a reverse Dijkstra search from the `goals` in `chunk` and, if `exits` is set, from
+    /// every Node whose next step leads out of `chunk`.
+    fn calculate_chunk(&mut self, chunk: &Chunk, goals: &[Point], exits: bool) -> ChunkField {
+        let neighborhood = MoveCosts::new(self.cache.neighborhood.clone(), self.grid);
+        let neighbors = &mut self.neighbors;
+        let size = chunk.size.0 * chunk.size.1;
+        let mut costs = vec![Cost::MAX; size];
+        let mut next_steps = vec![None; size];
+        let mut next = BinaryHeap::new();
+
+        for &goal in goals {
+            costs[chunk.tile_index(goal)] = 0;
+            next.push(Element(goal, 0));
+        }
+        if exits {
+            for &id in chunk.nodes.iter() {
+                let (cost, next_id) = match self.nodes.get(&id) {
+                    Some(&(cost, Some(next_id))) => (cost, next_id),
+                    _ => continue,
+                };
+                let (pos, next_pos) = (self.cache.nodes[id].pos, self.cache.nodes[next_id].pos);
+                if chunk.in_chunk(next_pos) && !self.cache.is_link(pos, next_pos) {
+                    // the step is part of a Path within the Chunk, which is recalculated below
+                    continue;
+                }
+                let index = chunk.tile_index(pos);
+                if cost < costs[index] {
+                    costs[index] = cost;
+                    next_steps[index] = Some(next_pos);
+                    next.push(Element(pos, cost));
+                }
+            }
+        }
+        if next.is_empty() {
+            return ChunkField {
+                dirs: vec![],
+                costs: vec![],
+                jumps: PointMap::default(),
+            };
+        }
+
+        while let Some(Element(current, current_cost)) = next.pop() {
+            if current_cost > costs[chunk.tile_index(current)] {
+                continue;
+            }
+            neighbors.clear();
+            neighborhood.get_all_neighbors(current, neighbors);
+            for &other in neighbors.iter() {
+                if !chunk.in_chunk(other) {
+                    continue;
+                }
+                let cost = self.grid.cost(other);
+                if cost < 0
+                    || !neighborhood.is_step_allowed(other, current, cost_grid::cost_fn(self.grid))
+                {
+                    continue;
+                }
+                let current_tile = self.grid.cost(current).max(0) as Cost;
+                let step = neighborhood.step_cost(other, current, cost as Cost, current_tile);
+                let other_cost = current_cost + step;
+                let index = chunk.tile_index(other);
+                if other_cost <= self.max_cost && other_cost < costs[index] {
+                    costs[index] = other_cost;
+                    next_steps[index] = Some(current);
+                    next.push(Element(other, other_cost));
+                }
+            }
+        }
+
+        let mut jumps = PointMap::default();
+        let dirs = next_steps
+            .iter()
+            .enumerate()
+            .map(|(index, next_step)| {
+                let next_step = match next_step {
+                    Some(next_step) => *next_step,
+                    None => return NO_STEP,
+                };
+                let pos = (
+                    chunk.pos.0 + index % chunk.size.0,
+                    chunk.pos.1 + index / chunk.size.0,
+                );
+                neighbors.clear();
+                neighborhood.get_all_neighbors(pos, neighbors);
+                match neighbors.iter().position(|p| *p == next_step) {
+                    Some(dir) => dir as u8,
+                    None if self.cache.is_link(pos, next_step) => {
+                        jumps.insert(pos, next_step);
+                        JUMP
+                    }
+                    None => panic!("Internal Error #1 in CostField. Please report this"),
+                }
+            })
+            .collect();
+
+        ChunkField { dirs, costs, jumps }
+    }
+}
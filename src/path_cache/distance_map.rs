@@ -0,0 +1,132 @@
+use crate::{neighbors::Neighborhood, path::Cost, *};
+
+use super::{cost_field::CostField, PathCache};
+
+/// The cost of reaching the nearest of several sources from every Tile of the Grid.
+///
+/// Created by [`distance_map`](PathCache::distance_map). Only the costs at the Nodes are
+/// calculated upfront. The costs of the other Tiles are calculated one Chunk at a time, the
+/// first time a Tile in that Chunk is looked up, and Chunks that can't reach any source within
+/// the maximum cost don't store anything.
+///
+/// The PathCache can't be changed while the DistanceMap exists, since it is borrowed by it.
+/// Create a new DistanceMap after the Grid changed.
+pub struct DistanceMap<'a, N: Neighborhood, G: CostGrid> {
+    max_cost: Cost,
+    field: CostField<'a, N, G>,
+}
+
+impl<'a, N, G> DistanceMap<'a, N, G>
+where
+    N: Neighborhood + Sync,
+    G: CostGrid,
+{
+    pub(super) fn new(
+        cache: &'a PathCache<N>,
+        sources: &[Point],
+        max_cost: Cost,
+        grid: &'a G,
+    ) -> Self {
+        DistanceMap {
+            max_cost,
+            field: CostField::new(cache, sources, max_cost, grid),
+        }
+    }
+
+    /// The highest cost that is stored in this DistanceMap
+    pub fn max_cost(&self) -> Cost {
+        self.max_cost
+    }
+
+    /// Returns the cost of walking from `pos` to the nearest source, or `None` if no source can
+    /// be reached from `pos` for at most [`max_cost`](DistanceMap::max_cost).
+    ///
+    /// Like the cost of a Path returned by [`find_path`](PathCache::find_path), this is the sum of
+    /// the costs of all Tiles on the way, except for the source. The sources themselves have a
+    /// cost of `0`.
+    ///
+    /// ## Panics
+    /// Panics if `pos` is outside of the Grid.
+    pub fn cost(&mut self, pos: Point) -> Option<Cost> {
+        self.field.cost(pos)
+    }
+}
+
+impl<N: Neighborhood + Sync, G: CostGrid> std::fmt::Debug for DistanceMap<'_, N, G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DistanceMap")
+            .field("max_cost", &self.max_cost)
+            .field("reachable_nodes", &self.field.reachable_nodes())
+            .field("calculated_chunks", &self.field.calculated_chunks())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, Point};
+
+    #[test]
+    fn two_sources() {
+        // a wall with a gap between the top and the bottom source
+        #[rustfmt::skip]
+        let tiles = [
+            ".........",
+            ".........",
+            ".........",
+            "######.##",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+        ];
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let cost_fn = |(x, y): Point| {
+            if tiles[y].as_bytes()[x] == b'#' {
+                -1
+            } else {
+                1
+            }
+        };
+        let neighborhood = ManhattanNeighborhood::new(width, height);
+        let pathfinding = PathCache::new(
+            (width, height),
+            &cost_fn,
+            neighborhood,
+            PathCacheConfig::with_chunk_size(3),
+        );
+
+        let sources = [(0, 0), (0, 8)];
+        let max_cost = 10;
+        let mut map = pathfinding
+            .distance_map(&sources, max_cost, &cost_fn)
+            .unwrap();
+        assert_eq!(map.max_cost(), max_cost);
+        for &source in &sources {
+            assert_eq!(map.cost(source), Some(0));
+        }
+
+        // the cost to the nearest source
+        assert_eq!(map.cost((0, 2)), Some(2));
+        assert_eq!(map.cost((0, 4)), Some(4));
+        assert_eq!(map.cost((8, 0)), Some(8));
+        assert_eq!(map.cost((8, 8)), Some(8));
+        // both sources are just within reach
+        assert_eq!(map.cost((6, 4)), Some(10));
+        // but not from here
+        assert_eq!(map.cost((8, 4)), None);
+        assert_eq!(map.cost((6, 3)), Some(9));
+        // and solid Tiles don't have a cost
+        assert_eq!(map.cost((0, 3)), None);
+
+        // nothing but the source is within a cost of 0
+        let source = sources[0];
+        let mut map = pathfinding.distance_map(&[source], 0, &cost_fn).unwrap();
+        assert_eq!(map.cost(source), Some(0));
+        assert_eq!(map.cost((1, 0)), None);
+        assert_eq!(map.cost((0, 1)), None);
+        assert!(map.field.reachable_nodes() <= 1);
+        assert_eq!(map.field.calculated_chunks(), 1);
+    }
+}
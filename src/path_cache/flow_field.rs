@@ -0,0 +1,140 @@
+use crate::{neighbors::Neighborhood, path::Cost, *};
+
+use super::{cost_field::CostField, PathCache};
+
+/// The next step towards a common goal for every Tile of the Grid.
+///
+/// Created by [`flow_field`](PathCache::flow_field). The directions are calculated one Chunk at
+/// a time, the first time a Tile in that Chunk is looked up. After that, every lookup takes
+/// constant time.
+///
+/// The PathCache can't be changed while the FlowField exists, since it is borrowed by it. Create
+/// a new FlowField after the Grid changed.
+pub struct FlowField<'a, N: Neighborhood, G: CostGrid> {
+    goal: Point,
+    field: CostField<'a, N, G>,
+}
+
+impl<'a, N, G> FlowField<'a, N, G>
+where
+    N: Neighborhood + Sync,
+    G: CostGrid,
+{
+    pub(super) fn new(cache: &'a PathCache<N>, goal: Point, grid: &'a G) -> Self {
+        FlowField {
+            goal,
+            field: CostField::new(cache, &[goal], Cost::MAX, grid),
+        }
+    }
+
+    /// The goal that all the steps lead to
+    pub fn goal(&self) -> Point {
+        self.goal
+    }
+
+    /// Returns the Tile to move to from `pos` on the way to the goal.
+    ///
+    /// This is one of the neighbors of `pos`, or the other end of a link (see
+    /// [`add_link`](PathCache::add_link)) if the way jumps through one.
+    ///
+    /// Returns `None` if `pos` is the goal or if the goal can't be reached from `pos`. Following
+    /// the steps from any Tile leads to the goal, for the cost returned by
+    /// [`cost`](FlowField::cost).
+    ///
+    /// ## Panics
+    /// Panics if `pos` is outside of the Grid.
+    pub fn next_step(&mut self, pos: Point) -> Option<Point> {
+        self.field.next_step(pos)
+    }
+
+    /// Returns the cost of walking from `pos` to the goal, or `None` if the goal can't be reached
+    /// from `pos`.
+    ///
+    /// Like the cost of a Path returned by [`find_path`](PathCache::find_path), this is the sum of
+    /// the costs of all Tiles on the way, except for the goal.
+    ///
+    /// ## Panics
+    /// Panics if `pos` is outside of the Grid.
+    pub fn cost(&mut self, pos: Point) -> Option<Cost> {
+        self.field.cost(pos)
+    }
+}
+
+impl<N: Neighborhood + Sync, G: CostGrid> std::fmt::Debug for FlowField<'_, N, G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlowField")
+            .field("goal", &self.goal)
+            .field("reachable_nodes", &self.field.reachable_nodes())
+            .field("calculated_chunks", &self.field.calculated_chunks())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, Point};
+
+    #[test]
+    fn walls() {
+        // a wall with a gap, and a corner that is walled off
+        #[rustfmt::skip]
+        let tiles = [
+            ".........",
+            ".........",
+            ".........",
+            "######.##",
+            ".........",
+            ".........",
+            "...#.....",
+            "...#....#",
+            "...#...#.",
+        ];
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let cost_fn = |(x, y): Point| {
+            if tiles[y].as_bytes()[x] == b'#' {
+                -1
+            } else {
+                1
+            }
+        };
+        let neighborhood = ManhattanNeighborhood::new(width, height);
+        let pathfinding = PathCache::new(
+            (width, height),
+            &cost_fn,
+            neighborhood,
+            PathCacheConfig::with_chunk_size(3),
+        );
+
+        let goal = (0, 8);
+        let mut field = pathfinding.flow_field(goal, &cost_fn).unwrap();
+        assert_eq!(field.goal(), goal);
+        assert_eq!(field.next_step(goal), None);
+        assert_eq!(field.cost(goal), Some(0));
+        // only the Chunks that were looked up are calculated
+        assert_eq!(field.field.calculated_chunks(), 1);
+
+        assert_eq!(field.cost((8, 8)), None);
+        assert_eq!(field.next_step((8, 8)), None);
+        assert_eq!(field.field.calculated_chunks(), 2);
+
+        // following the steps reaches the goal for the promised cost
+        let start = (0, 0);
+        let cost = field.cost(start).unwrap();
+        assert_eq!(cost, 20);
+        let mut neighbors = vec![];
+        let mut current = start;
+        let mut total = 0;
+        while let Some(next) = field.next_step(current) {
+            neighbors.clear();
+            neighborhood.get_all_neighbors(current, &mut neighbors);
+            assert!(neighbors.contains(&next));
+            assert_eq!(field.cost(next), Some(cost - total - 1));
+            total += 1;
+            current = next;
+        }
+        assert_eq!(current, goal);
+        assert_eq!(total, cost);
+        // nothing in the bottom middle Chunk was looked up
+        assert_eq!(field.field.calculated_chunks(), 8);
+    }
+}
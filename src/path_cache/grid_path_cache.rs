@@ -0,0 +1,396 @@
+use crate::{
+    neighbors::Neighborhood,
+    path::{AbstractPath, Cost},
+    *,
+};
+
+use super::{ObstacleHandle, Obstacles, PathCache, PathCacheConfig, ReservationTable};
+
+/// The costs stored by a [`GridPathCache`]
+#[derive(Clone, Debug)]
+struct Costs {
+    width: usize,
+    values: Vec<i16>,
+    generation: u64,
+}
+
+impl CostGrid for Costs {
+    fn cost(&self, (x, y): Point) -> isize {
+        self.values[y * self.width + x] as isize
+    }
+    fn generation(&self) -> Option<u64> {
+        Some(self.generation)
+    }
+}
+
+/// A [`PathCache`] that stores the costs of its Grid itself.
+///
+/// Instead of passing a cost function to every method, the costs are kept in a compact array
+/// and changed with [`set_cost`](GridPathCache::set_cost) and
+/// [`set_costs`](GridPathCache::set_costs). Changes are collected and passed on to
+/// [`tiles_changed`](PathCache::tiles_changed) in a single batch the next time a Path is
+/// searched, so there is no way to use a cost function that doesn't match the PathCache.
+///
+/// Costs below `0` are solid Tiles, like everywhere else. The GridPathCache is also a
+/// [`CostGrid`], whose [`generation`](CostGrid::generation) increases with every change. Use it
+/// with [`safe_next`](AbstractPath::safe_next) to resolve the Paths if
+/// [`cache_paths`](PathCacheConfig::cache_paths) is disabled.
+///
+/// ## Examples
+/// Basic usage:
+/// ```
+/// use hierarchical_pathfinding::prelude::*;
+///
+/// let (width, height) = (5, 5);
+/// #[rustfmt::skip]
+/// let costs = vec![
+///     1, -1,  1,  1,  1,
+///     1, -1, -1, -1,  1,
+///     1, 10,  1,  1,  1,
+///     1, 10,  1, -1,  1,
+///     1,  1,  1, -1,  1,
+/// ];
+///
+/// let mut pathfinding = GridPathCache::new(
+///     (width, height),
+///     costs,
+///     ManhattanNeighborhood::new(width, height),
+///     PathCacheConfig::with_chunk_size(3),
+/// );
+///
+/// let path = pathfinding.find_path((0, 0), (4, 4));
+/// assert_eq!(path.unwrap().cost(), 12);
+///
+/// // close the shortcut through the bottom
+/// pathfinding.set_cost((2, 4), -1);
+/// let path = pathfinding.find_path((0, 0), (4, 4));
+/// assert_eq!(path.unwrap().cost(), 17);
+///
+/// // open up the top row
+/// pathfinding.set_costs((1, 0), (3, 2), |_| 1);
+/// let path = pathfinding.find_path((0, 0), (4, 4));
+/// assert_eq!(path.unwrap().cost(), 8);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GridPathCache<N: Neighborhood> {
+    cache: PathCache<N>,
+    costs: Costs,
+    /// the Tiles that changed since the last update of `cache`
+    changed: PointSet,
+}
+
+impl<N: Neighborhood + Sync> GridPathCache<N> {
+    /// Creates a new GridPathCache from the costs of all Tiles, row by row.
+    ///
+    /// ## Panics
+    /// Panics if the number of `costs` doesn't match the size of the Grid.
+    pub fn new(
+        (width, height): (usize, usize),
+        costs: Vec<i16>,
+        neighborhood: N,
+        config: PathCacheConfig,
+    ) -> GridPathCache<N> {
+        assert_eq!(
+            costs.len(),
+            width * height,
+            "the number of costs doesn't match the size of the Grid"
+        );
+        let costs = Costs {
+            width,
+            values: costs,
+            generation: 0,
+        };
+        let cache = PathCache::new((width, height), &costs, neighborhood, config);
+        GridPathCache {
+            cache,
+            costs,
+            changed: PointSet::default(),
+        }
+    }
+
+    /// Returns the cost of the Tile at `pos`.
+    pub fn cost(&self, pos: Point) -> i16 {
+        self.costs.values[self.index(pos)]
+    }
+
+    /// Returns the costs of all Tiles, row by row.
+    pub fn costs(&self) -> &[i16] {
+        &self.costs.values
+    }
+
+    /// Changes the cost of the Tile at `pos`.
+    ///
+    /// The PathCache is updated the next time it is used.
+    pub fn set_cost(&mut self, pos: Point, cost: i16) {
+        let index = self.index(pos);
+        if self.costs.values[index] != cost {
+            self.costs.values[index] = cost;
+            self.costs.generation += 1;
+            self.changed.insert(pos);
+        }
+    }
+
+    /// Changes the costs of all Tiles in the rectangle at `pos` with the given `size` to
+    /// `get_cost(tile)`.
+    ///
+    /// The PathCache is updated the next time it is used.
+    ///
+    /// ## Panics
+    /// Panics if the rectangle is not within the Grid.
+    pub fn set_costs(
+        &mut self,
+        pos: Point,
+        (width, height): (usize, usize),
+        mut get_cost: impl FnMut(Point) -> i16,
+    ) {
+        for y in pos.1..pos.1 + height {
+            for x in pos.0..pos.0 + width {
+                self.set_cost((x, y), get_cost((x, y)));
+            }
+        }
+    }
+
+    /// Connects two Tiles that don't have to be neighbors, like the ends of a teleporter.
+    ///
+    /// See [`PathCache::add_link`] for details.
+    ///
+    /// ## Panics
+    /// Panics if `a` or `b` is outside of the Grid.
+    pub fn add_link(&mut self, a: Point, b: Point, cost: Cost, bidirectional: bool) {
+        self.update();
+        self.cache.add_link(a, b, cost, bidirectional, &self.costs);
+    }
+
+    /// Removes the link between `a` and `b`, in either direction.
+    ///
+    /// Returns `false` if there is no such link. See [`PathCache::remove_link`] for details.
+    ///
+    /// ## Panics
+    /// Panics if `a` or `b` is outside of the Grid.
+    pub fn remove_link(&mut self, a: Point, b: Point) -> bool {
+        self.update();
+        self.cache.remove_link(a, b, &self.costs)
+    }
+
+    /// Registers `obstacles` that apply to all searches until they are removed.
+    ///
+    /// See [`PathCache::add_obstacles`] for details.
+    pub fn add_obstacles(&mut self, obstacles: Obstacles) -> ObstacleHandle {
+        self.cache.add_obstacles(obstacles)
+    }
+
+    /// Replaces the Obstacles registered with `handle`.
+    ///
+    /// Returns `false` if the Obstacles were already removed.
+    pub fn update_obstacles(&mut self, handle: ObstacleHandle, obstacles: Obstacles) -> bool {
+        self.cache.update_obstacles(handle, obstacles)
+    }
+
+    /// Removes the Obstacles registered with `handle` and returns them, or `None` if they were
+    /// already removed.
+    pub fn remove_obstacles(&mut self, handle: ObstacleHandle) -> Option<Obstacles> {
+        self.cache.remove_obstacles(handle)
+    }
+
+    /// Updates the PathCache with all changes since the last update.
+    ///
+    /// This happens automatically in all methods that need the PathCache, but can be called
+    /// earlier to control when the work is done.
+    ///
+    /// Returns the changed Chunks like [`tiles_changed`](PathCache::tiles_changed). Use
+    /// [`AbstractPath::is_valid`] to check Paths after automatic updates.
+    pub fn update(&mut self) -> Vec<Point> {
+        if self.changed.is_empty() {
+            return vec![];
+        }
+        let tiles = self.changed.drain().to_vec();
+        self.cache.tiles_changed(&tiles, &self.costs)
+    }
+
+    /// Returns the PathCache with all changes applied, for any methods not provided by the
+    /// GridPathCache itself.
+    ///
+    /// Those methods still need the costs of the Grid, for which the GridPathCache can be used as
+    /// a [`CostGrid`].
+    pub fn cache(&mut self) -> &PathCache<N> {
+        self.update();
+        &self.cache
+    }
+
+    /// Calculates the Path from `start` to `goal` on the Grid.
+    ///
+    /// See [`PathCache::find_path`] for details.
+    pub fn find_path(&mut self, start: Point, goal: Point) -> Option<AbstractPath<N>> {
+        self.update();
+        self.cache
+            .find_path(start, goal, &self.costs)
+            .expect("Internal Error #1 in GridPathCache. Please report this")
+    }
+
+    /// Calculates the Paths from one `start` to several `goals` on the Grid.
+    ///
+    /// See [`PathCache::find_paths`] for details.
+    pub fn find_paths(&mut self, start: Point, goals: &[Point]) -> PointMap<AbstractPath<N>> {
+        self.update();
+        self.cache
+            .find_paths(start, goals, &self.costs)
+            .expect("Internal Error #2 in GridPathCache. Please report this")
+    }
+
+    /// Calculates the Path from `start` to `goal` with some Tiles temporarily blocked or
+    /// penalized.
+    ///
+    /// See [`PathCache::find_path_with_obstacles`] for details.
+    pub fn find_path_with_obstacles(
+        &mut self,
+        start: Point,
+        goal: Point,
+        obstacles: &Obstacles,
+    ) -> Option<AbstractPath<N>> {
+        self.update();
+        self.cache
+            .find_path_with_obstacles(start, goal, obstacles, &self.costs)
+            .expect("Internal Error #3 in GridPathCache. Please report this")
+    }
+
+    /// Calculates the Path from `start` to `goal` for `agent`, avoiding the Tiles that other
+    /// agents reserved in `reservations`.
+    ///
+    /// See [`PathCache::find_path_cooperative`] for details.
+    pub fn find_path_cooperative(
+        &mut self,
+        start: Point,
+        goal: Point,
+        agent: usize,
+        reservations: &ReservationTable,
+    ) -> Option<AbstractPath<N>> {
+        self.update();
+        self.cache
+            .find_path_cooperative(start, goal, agent, reservations, &self.costs)
+            .expect("Internal Error #4 in GridPathCache. Please report this")
+    }
+
+    /// Repairs a Path that crosses Tiles which changed since it was found.
+    ///
+    /// See [`PathCache::repair_path`] for details.
+    pub fn repair_path(&mut self, path: &mut AbstractPath<N>, current_pos: Point) -> Option<bool> {
+        self.update();
+        self.cache
+            .repair_path(path, current_pos, &self.costs)
+            .expect("Internal Error #5 in GridPathCache. Please report this")
+    }
+
+    /// Checks if there is any Path from `start` to `goal`, without calculating it.
+    ///
+    /// See [`PathCache::is_reachable`] for details.
+    pub fn is_reachable(&mut self, start: Point, goal: Point) -> bool {
+        self.update();
+        self.cache
+            .is_reachable(start, goal, &self.costs)
+            .expect("Internal Error #6 in GridPathCache. Please report this")
+    }
+
+    /// The cost of the Path from `start` to `goal`, if there is one.
+    ///
+    /// Shorthand for `find_path(start, goal).map(|path| path.cost())`.
+    pub fn path_cost(&mut self, start: Point, goal: Point) -> Option<Cost> {
+        self.find_path(start, goal).map(|path| path.cost())
+    }
+
+    fn index(&self, (x, y): Point) -> usize {
+        assert!(
+            x < self.costs.width && y < self.cache.height,
+            "{:?} is outside of the Grid",
+            (x, y)
+        );
+        y * self.costs.width + x
+    }
+}
+
+impl<N: Neighborhood> CostGrid for GridPathCache<N> {
+    fn cost(&self, pos: Point) -> isize {
+        self.costs.cost(pos)
+    }
+    fn generation(&self) -> Option<u64> {
+        self.costs.generation()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{grid, prelude::*, Point};
+    use nanorand::{Rng, WyRand};
+
+    #[test]
+    fn random_changes() {
+        let size = 32;
+        let mut rng = WyRand::new_seed(20);
+        let costs = (0..size * size)
+            .map(|_| [-1, 1, 1, 2, 5][rng.generate_range(0..5_usize)])
+            .collect::<Vec<i16>>();
+        let neighborhood = MooreNeighborhood::new(size, size);
+        let config = PathCacheConfig {
+            cache_paths: false,
+            ..PathCacheConfig::with_chunk_size(8)
+        };
+        let mut pathfinding = GridPathCache::new((size, size), costs, neighborhood, config);
+
+        for _ in 0..10 {
+            let pos = (
+                rng.generate_range(0..size - 4),
+                rng.generate_range(0..size - 4),
+            );
+            let cost = [-1, 1, 3][rng.generate_range(0..3_usize)];
+            pathfinding.set_costs(pos, (4, 4), |_| cost);
+            for _ in 0..5 {
+                let pos = (rng.generate_range(0..size), rng.generate_range(0..size));
+                pathfinding.set_cost(pos, [-1, 1, 3][rng.generate_range(0..3_usize)]);
+            }
+            assert!(!pathfinding.changed.is_empty());
+
+            let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let path = pathfinding.find_path(start, goal);
+            assert!(pathfinding.changed.is_empty());
+            if pathfinding.cost(start) < 0 || pathfinding.cost(goal) < 0 {
+                continue;
+            }
+
+            let cost_fn = |(x, y): Point| pathfinding.costs()[y * size + x] as isize;
+            let expected = grid::a_star_search(
+                &neighborhood,
+                |_| true,
+                cost_fn,
+                start,
+                goal,
+                40,
+                Default::default(),
+            );
+            assert_eq!(
+                path.is_some(),
+                expected.is_some(),
+                "{:?} -> {:?}",
+                start,
+                goal
+            );
+            let mut path = match path {
+                Some(path) => path,
+                None => continue,
+            };
+
+            // the Path only crosses open Tiles of the current Grid
+            let mut current = start;
+            while let Some(next) = path.safe_next(&pathfinding).unwrap() {
+                assert!(current == start || pathfinding.cost(current) >= 0);
+                current = next;
+            }
+            assert_eq!(current, goal);
+
+            if start != goal {
+                let mut path = pathfinding.find_path(start, goal).unwrap();
+                pathfinding.set_cost(goal, pathfinding.cost(goal) % 5 + 1);
+                assert!(path.safe_next(&pathfinding).is_err());
+            }
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use crate::{
+    graph::{Element, Graph, NodeList, ReverseGraph},
+    neighbors::Neighborhood,
+    path::{Cost, Path},
+    *,
+};
+
+use std::collections::BinaryHeap;
+
+use super::PathCache;
+
+/// The number of Chunks of the previous level along each axis of a Chunk on the next level
+pub const LEVEL_FACTOR: usize = 4;
+
+/// A higher abstraction level of the PathCache.
+///
+/// Level 0 are the regular Chunks with the edges stored in the NodeList. Every higher level
+/// groups `LEVEL_FACTOR`x`LEVEL_FACTOR` Chunks of the level below, and connects the Nodes on
+/// the border of these bigger Chunks (and the ends of links) with Paths through the level below.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub chunk_size: usize,
+    pub num_chunks: (usize, usize),
+    /// the Nodes on the border of each Chunk on this level, and the ends of links
+    pub chunk_nodes: Vec<NodeIDSet>,
+    /// the edges of every Node on this level. The Path of each edge consists of the Nodes on
+    /// the level below
+    pub edges: LevelEdges,
+    /// the sources of the edges that lead to every Node on this level, with their costs
+    pub incoming: NodeIDMap<NodeIDMap<Cost>>,
+    /// if the Node with each NodeID has edges on this level
+    members: Vec<bool>,
+}
+
+/// The edges of some Nodes on a level
+type LevelEdges = NodeIDMap<NodeIDMap<Path<NodeID>>>;
+
+impl Level {
+    pub fn new(chunk_size: usize, (width, height): (usize, usize)) -> Level {
+        let num_chunks = (width.div_ceil(chunk_size), height.div_ceil(chunk_size));
+        Level {
+            chunk_size,
+            num_chunks,
+            chunk_nodes: vec![NodeIDSet::default(); num_chunks.0 * num_chunks.1],
+            edges: NodeIDMap::default(),
+            incoming: NodeIDMap::default(),
+            members: vec![],
+        }
+    }
+
+    /// If a Node has edges on this level
+    pub fn contains(&self, id: NodeID) -> bool {
+        self.members.get(id as usize).copied().unwrap_or(false)
+    }
+
+    /// Adds a Node with its edges to this level
+    fn insert_node(&mut self, id: NodeID, edges: NodeIDMap<Path<NodeID>>) {
+        let slot = id as usize;
+        if self.members.len() <= slot {
+            self.members.resize(slot + 1, false);
+        }
+        self.members[slot] = true;
+        for (&other_id, path) in edges.iter() {
+            self.incoming
+                .entry(other_id)
+                .or_default()
+                .insert(id, path.cost());
+        }
+        self.edges.insert(id, edges);
+    }
+
+    /// Adds an edge from a Node on this level to another one
+    fn insert_edge(&mut self, id: NodeID, other_id: NodeID, path: Path<NodeID>) {
+        self.incoming
+            .entry(other_id)
+            .or_default()
+            .insert(id, path.cost());
+        self.edges
+            .get_mut(&id)
+            .expect("Internal Error #1 in Level. Please report this")
+            .insert(other_id, path);
+    }
+
+    /// Removes all edges that lead to a Node, after its own `edges` were removed
+    fn unlink(&mut self, id: NodeID, edges: &NodeIDMap<Path<NodeID>>) {
+        self.members[id as usize] = false;
+        for other_id in edges.keys() {
+            if let Some(incoming) = self.incoming.get_mut(other_id) {
+                incoming.remove(&id);
+                if incoming.is_empty() {
+                    self.incoming.remove(other_id);
+                }
+            }
+        }
+        for other_id in self.incoming.remove(&id).unwrap_or_default().keys() {
+            if let Some(other_edges) = self.edges.get_mut(other_id) {
+                other_edges.remove(&id);
+            }
+        }
+    }
+
+    /// Recreates the incoming edges and the members from the edges of this level
+    pub fn index_edges(&mut self) {
+        let edges = std::mem::take(&mut self.edges);
+        self.incoming.clear();
+        self.members.clear();
+        for (id, edges) in edges {
+            self.insert_node(id, edges);
+        }
+    }
+
+    pub fn chunk_index(&self, point: Point) -> usize {
+        let size = self.chunk_size;
+        (point.1 / size) * self.num_chunks.0 + point.0 / size
+    }
+
+    /// If the Nodes of a Chunk have exactly the edges in `edges`, apart from the Nodes that the
+    /// edges lead through
+    fn has_edges(&self, index: usize, edges: &LevelEdges) -> bool {
+        let nodes = &self.chunk_nodes[index];
+        nodes.len() == edges.len()
+            && nodes.iter().all(|id| {
+                let (new, old) = (&self.edges[id], &edges.get(id));
+                old.is_some_and(|old| {
+                    new.len() == old.len()
+                        && new.iter().all(|(other_id, path)| {
+                            old.get(other_id).is_some_and(|o| o.cost() == path.cost())
+                        })
+                })
+            })
+    }
+
+    /// Returns (top-left, bottom-right exclusive) of a Chunk on this level
+    pub fn chunk_bounds(&self, index: usize, (width, height): (usize, usize)) -> (Point, Point) {
+        let size = self.chunk_size;
+        let pos = (
+            (index % self.num_chunks.0) * size,
+            (index / self.num_chunks.0) * size,
+        );
+        (pos, ((pos.0 + size).min(width), (pos.1 + size).min(height)))
+    }
+}
+
+fn in_bounds(point: Point, (min, max): (Point, Point)) -> bool {
+    point.0 >= min.0 && point.0 < max.0 && point.1 >= min.1 && point.1 < max.1
+}
+
+/// The Nodes of a Chunk on a level with the edges between them on the level below.
+///
+/// The Nodes are numbered by their index in `ids`, with the `members` of the Chunk first, so
+/// that searching from every member doesn't need any hashing.
+struct ChunkGraph {
+    ids: Vec<NodeID>,
+    members: usize,
+    edges: Vec<Vec<(usize, Cost)>>,
+}
+
+impl ChunkGraph {
+    /// Dijkstra from member `start` to the members in `goals`. Returns the Path to every goal,
+    /// or `None` if it can't be reached within the Chunk
+    fn search(&self, start: usize, goals: std::ops::Range<usize>) -> Vec<Option<Path<NodeID>>> {
+        let mut remaining = goals.len() - goals.contains(&start) as usize;
+        if remaining == 0 {
+            return vec![None; goals.len()];
+        }
+        let mut costs = vec![Cost::MAX; self.ids.len()];
+        let mut prev = vec![start; self.ids.len()];
+        let mut next = BinaryHeap::new();
+        costs[start] = 0;
+        next.push(Element(start, 0));
+
+        while let Some(Element(current, current_cost)) = next.pop() {
+            if current_cost > costs[current] {
+                continue;
+            }
+            if current != start && goals.contains(&current) {
+                remaining -= 1;
+                if remaining == 0 {
+                    break;
+                }
+            }
+            for &(other, cost) in self.edges[current].iter() {
+                let other_cost = current_cost + cost;
+                if other_cost < costs[other] {
+                    costs[other] = other_cost;
+                    prev[other] = current;
+                    next.push(Element(other, other_cost));
+                }
+            }
+        }
+
+        goals
+            .map(|goal| {
+                if goal == start || costs[goal] == Cost::MAX {
+                    return None;
+                }
+                let mut steps = vec![self.ids[goal]];
+                let mut current = goal;
+                while current != start {
+                    current = prev[current];
+                    steps.push(self.ids[current]);
+                }
+                steps.reverse();
+                Some(Path::new(steps, costs[goal]))
+            })
+            .collect()
+    }
+}
+
+/// The Graph used when searching for a Path.
+///
+/// Every Node is expanded on the highest level, where its Chunk contains none of the
+/// `special` Points (usually the start and goal), since those Chunks need to be entered.
+pub struct QueryGraph<'a> {
+    nodes: &'a NodeList,
+    levels: &'a [Level],
+    /// for every level: the Chunks that contain any of the special Points
+    special: Vec<Vec<usize>>,
+    /// if every edge of the NodeList has a counterpart that was created with
+    /// [`PathSegment::reversed`](crate::path::PathSegment::reversed)
+    symmetric: bool,
+}
+
+impl<'a> QueryGraph<'a> {
+    pub fn new(
+        nodes: &'a NodeList,
+        levels: &'a [Level],
+        special: &[Point],
+        symmetric: bool,
+    ) -> QueryGraph<'a> {
+        let special = levels
+            .iter()
+            .map(|level| special.iter().map(|p| level.chunk_index(*p)).collect())
+            .collect();
+        QueryGraph {
+            nodes,
+            levels,
+            special,
+            symmetric,
+        }
+    }
+
+    /// the level on which the edges of a Node are used. `0` means the regular NodeList
+    fn level_of(&self, id: NodeID) -> usize {
+        let pos = self.nodes[id].pos;
+        for (i, level) in self.levels.iter().enumerate().rev() {
+            if level.contains(id) && !self.special[i].contains(&level.chunk_index(pos)) {
+                return i + 1;
+            }
+        }
+        0
+    }
+
+    /// Expands a Path found on this Graph to a Path of Nodes that are directly connected in
+    /// the NodeList
+    pub fn expand(&self, path: Path<NodeID>) -> Path<NodeID> {
+        if self.levels.is_empty() || path.len() < 2 {
+            return path;
+        }
+        let mut steps = vec![path[0]];
+        for (a, b) in path.iter().zip(path.iter().skip(1)) {
+            self.expand_hop(self.level_of(*a), *a, *b, &mut steps);
+        }
+        Path::new(steps, path.cost())
+    }
+
+    fn expand_hop(&self, level: usize, a: NodeID, b: NodeID, steps: &mut Vec<NodeID>) {
+        if level == 0 || a == b {
+            steps.push(b);
+            return;
+        }
+        let path = &self.levels[level - 1].edges[&a][&b];
+        for (x, y) in path.iter().zip(path.iter().skip(1)) {
+            self.expand_hop(level - 1, *x, *y, steps);
+        }
+    }
+}
+
+impl Graph for QueryGraph<'_> {
+    fn pos(&self, id: NodeID) -> Point {
+        self.nodes[id].pos
+    }
+    fn for_each_edge(&self, id: NodeID, mut f: impl FnMut(NodeID, Cost)) {
+        match self.level_of(id) {
+            0 => self.nodes.for_each_edge(id, f),
+            level => {
+                for (&other_id, path) in self.levels[level - 1].edges[&id].iter() {
+                    f(other_id, path.cost());
+                }
+            }
+        }
+    }
+}
+
+impl ReverseGraph for QueryGraph<'_> {
+    fn for_each_incoming_edge(&self, id: NodeID, mut f: impl FnMut(NodeID, Cost)) {
+        if self.symmetric {
+            // the NodeList doesn't keep the incoming edges then, but the counterpart of an edge
+            // costs as much as the edge itself, apart from the costs of the Nodes on either end
+            let walk_cost = self.nodes[id].walk_cost;
+            for (&other_id, path) in self.nodes[id].edges.iter() {
+                if self.level_of(other_id) == 0 {
+                    f(
+                        other_id,
+                        path.cost() + self.nodes[other_id].walk_cost - walk_cost,
+                    );
+                }
+            }
+        } else {
+            self.nodes.for_each_incoming(id, |other_id| {
+                if self.level_of(other_id) > 0 {
+                    return;
+                }
+                if let Some(path) = self.nodes[other_id].edges.get(&id) {
+                    f(other_id, path.cost());
+                }
+            });
+        }
+        for (i, level) in self.levels.iter().enumerate() {
+            if !level.contains(id) {
+                // the higher levels only contain some of the Nodes of the lower ones
+                break;
+            }
+            if let Some(incoming) = level.incoming.get(&id) {
+                for (&other_id, &cost) in incoming.iter() {
+                    if self.level_of(other_id) == i + 1 {
+                        f(other_id, cost);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<N: Neighborhood + Sync> PathCache<N> {
+    /// Creates all the higher levels from scratch
+    pub(super) fn build_levels(&mut self) {
+        let size = (self.width, self.height);
+        let mut chunk_size = self.config.chunk_size;
+        self.levels = (1..self.config.levels.max(1))
+            .map(|_| {
+                chunk_size *= LEVEL_FACTOR;
+                Level::new(chunk_size, size)
+            })
+            .to_vec();
+
+        let all = self.chunks.iter().map(|chunk| chunk.pos).to_vec();
+        self.update_levels(all);
+    }
+
+    /// Recreates the Chunks on all higher levels that contain any of the given Chunks of
+    /// level 0.
+    ///
+    /// A Chunk on a level only depends on the edges of the Chunks it contains on the level
+    /// below, so the next level is only updated where the edges actually changed.
+    pub(super) fn update_levels(&mut self, dirty: impl IntoIterator<Item = Point>) {
+        if self.levels.is_empty() {
+            return;
+        }
+        let size = (self.width, self.height);
+        let mut dirty = dirty.into_iter().to_vec();
+        for l in 0..self.levels.len() {
+            let mut indices = dirty
+                .iter()
+                .map(|p| self.levels[l].chunk_index(*p))
+                .to_vec();
+            indices.sort_unstable();
+            indices.dedup();
+
+            let old = self.clear_level_chunks(l, &indices);
+            for &index in indices.iter() {
+                self.fill_level_chunk(l, index);
+            }
+            for &index in indices.iter() {
+                self.connect_level_chunk(l, index);
+            }
+
+            let level = &self.levels[l];
+            dirty = indices
+                .iter()
+                .zip(old)
+                .filter(|(index, old)| !level.has_edges(**index, old))
+                .map(|(index, _)| level.chunk_bounds(*index, size).0)
+                .to_vec();
+            if dirty.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Removes all Nodes of some Chunks on a level, together with any edges to and from them.
+    ///
+    /// Returns the removed edges of the Nodes of each Chunk.
+    fn clear_level_chunks(&mut self, l: usize, indices: &[usize]) -> Vec<LevelEdges> {
+        let level = &mut self.levels[l];
+        let mut old = Vec::with_capacity(indices.len());
+        for &index in indices {
+            let mut chunk_edges = LevelEdges::default();
+            for id in std::mem::take(&mut level.chunk_nodes[index]) {
+                if let Some(edges) = level.edges.remove(&id) {
+                    chunk_edges.insert(id, edges);
+                }
+            }
+            old.push(chunk_edges);
+        }
+        // the edges to the Nodes are only removed afterwards, so that the returned edges still
+        // contain the ones between the removed Nodes
+        for (&id, edges) in old.iter().flatten() {
+            level.unlink(id, edges);
+        }
+        old
+    }
+
+    /// Finds the Nodes on the border of a Chunk on a level and connects them within the Chunk
+    fn fill_level_chunk(&mut self, l: usize, index: usize) {
+        let size = (self.width, self.height);
+        let bounds = self.levels[l].chunk_bounds(index, size);
+        let ((left, top), (right, bottom)) = bounds;
+
+        // all Nodes of level 0 in this Chunk that are on a border to another Chunk, or at the end
+        // of a link, which can lead anywhere
+        let base_size = self.config.chunk_size;
+        let wrap = self.neighborhood.wraps_horizontally();
+        let mut members = vec![];
+        for y in (top..bottom).step_by(base_size) {
+            for x in (left..right).step_by(base_size) {
+                for &id in self.get_chunk((x, y)).nodes.iter() {
+                    let (px, py) = self.nodes[id].pos;
+                    if (px == left && (left > 0 || wrap))
+                        || (py == top && top > 0)
+                        || (px == right - 1 && (right < size.0 || wrap))
+                        || (py == bottom - 1 && bottom < size.1)
+                        || self.is_link_end((px, py))
+                    {
+                        members.push(id);
+                    }
+                }
+            }
+        }
+
+        let graph = self.chunk_graph(l, bounds, members);
+        let count = graph.members;
+        let mut edges = vec![NodeIDMap::default(); count];
+        let symmetric = self.symmetric_edges();
+        for i in 0..count {
+            // the Paths back to the members before `i` are the reverse of the ones they found
+            let goals = if symmetric { i + 1..count } else { 0..count };
+            let paths = graph.search(i, goals.clone());
+            let id = graph.ids[i];
+            for (j, path) in goals.zip(paths) {
+                let path = match path {
+                    Some(path) => path,
+                    None => continue,
+                };
+                let other_id = graph.ids[j];
+                if symmetric {
+                    let (cost, other_cost) =
+                        (self.nodes[id].walk_cost, self.nodes[other_id].walk_cost);
+                    edges[j].insert(id, path.reversed(cost, other_cost));
+                }
+                edges[i].insert(other_id, path);
+            }
+        }
+
+        let level = &mut self.levels[l];
+        for (&id, paths) in graph.ids.iter().zip(edges) {
+            level.chunk_nodes[index].insert(id);
+            level.insert_node(id, paths);
+        }
+    }
+
+    /// The `members` of a Chunk on a level, and all Nodes within `bounds` on the level below
+    /// with the edges between them
+    fn chunk_graph(&self, l: usize, bounds: (Point, Point), members: Vec<NodeID>) -> ChunkGraph {
+        let ((left, top), (right, bottom)) = bounds;
+        let mut ids = members;
+        let count = ids.len();
+        let mut local: NodeIDMap<usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+        // the Chunks of the level below are smaller and aligned with this one
+        let below = l.checked_sub(1).map(|below| &self.levels[below]);
+        let step = below.map_or(self.config.chunk_size, |level| level.chunk_size);
+        for y in (top..bottom).step_by(step) {
+            for x in (left..right).step_by(step) {
+                let nodes = match below {
+                    Some(level) => &level.chunk_nodes[level.chunk_index((x, y))],
+                    None => &self.get_chunk((x, y)).nodes,
+                };
+                for &id in nodes.iter() {
+                    local.entry(id).or_insert_with(|| {
+                        ids.push(id);
+                        ids.len() - 1
+                    });
+                }
+            }
+        }
+
+        let edges = ids
+            .iter()
+            .map(|id| {
+                let mut edges = vec![];
+                let mut add = |other_id: &NodeID, cost: Cost| {
+                    if let Some(&other) = local.get(other_id) {
+                        edges.push((other, cost));
+                    }
+                };
+                match below {
+                    Some(level) => {
+                        for (other_id, path) in level.edges[id].iter() {
+                            add(other_id, path.cost());
+                        }
+                    }
+                    None => {
+                        for (other_id, path) in self.nodes[*id].edges.iter() {
+                            add(other_id, path.cost());
+                        }
+                    }
+                }
+                edges
+            })
+            .to_vec();
+
+        ChunkGraph {
+            ids,
+            members: count,
+            edges,
+        }
+    }
+
+    /// Connects the Nodes of a Chunk on a level to the neighboring Nodes in other Chunks
+    fn connect_level_chunk(&mut self, l: usize, index: usize) {
+        let bounds = self.levels[l].chunk_bounds(index, (self.width, self.height));
+        let mut connections = vec![];
+        let outside = |other_id: NodeID| {
+            !in_bounds(self.nodes[other_id].pos, bounds) && self.levels[l].contains(other_id)
+        };
+        for &id in self.levels[l].chunk_nodes[index].iter() {
+            for (&other_id, path) in self.nodes[id].edges.iter() {
+                if outside(other_id) {
+                    connections.push((id, other_id, path.cost()));
+                }
+            }
+            self.nodes.for_each_incoming(id, |other_id| {
+                if !outside(other_id) {
+                    return;
+                }
+                if let Some(path) = self.nodes[other_id].edges.get(&id) {
+                    connections.push((other_id, id, path.cost()));
+                }
+            });
+        }
+        let level = &mut self.levels[l];
+        for (id, other_id, cost) in connections {
+            level.insert_edge(id, other_id, Path::from_slice(&[id, other_id], cost));
+        }
+    }
+
+    /// If every edge has a counterpart that costs as much apart from the costs of its ends
+    fn symmetric_edges(&self) -> bool {
+        // links are inserted in both directions with the same cost
+        !self.nodes.is_directed() && self.links.is_empty()
+    }
+
+    /// Creates the Graph used to search for Paths involving the `special` Points
+    pub(super) fn query_graph(&self, special: &[Point]) -> QueryGraph<'_> {
+        QueryGraph::new(&self.nodes, &self.levels, special, self.symmetric_edges())
+    }
+}
@@ -0,0 +1,549 @@
+use crate::{
+    neighbors::Neighborhood,
+    path::{Cost, Path, PathSegment},
+    *,
+};
+
+use super::{moves::MoveCosts, PathCache};
+
+/// A connection between two Tiles that don't have to be neighbors. See
+/// [`add_link`](PathCache::add_link)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Link {
+    pub from: Point,
+    pub to: Point,
+    /// the cost of the jump, without the `cost_scale` of the Neighborhood
+    pub cost: Cost,
+    pub bidirectional: bool,
+}
+
+impl Link {
+    /// If the Link can be used to jump from `from` to `to`
+    pub fn leads(&self, from: Point, to: Point) -> bool {
+        (self.from == from && self.to == to)
+            || (self.bidirectional && self.from == to && self.to == from)
+    }
+
+    /// If the Link connects `a` and `b` in any direction
+    pub fn connects(&self, a: Point, b: Point) -> bool {
+        (self.from == a && self.to == b) || (self.from == b && self.to == a)
+    }
+}
+
+impl<N: Neighborhood + Sync> PathCache<N> {
+    /// Connects two Tiles that don't have to be neighbors, like the ends of a teleporter, an
+    /// elevator or a ladder.
+    ///
+    /// Paths can jump from `a` to `b` for `cost`, which is in the same unit as the costs of the
+    /// Tiles and replaces the cost of leaving `a`. If `bidirectional` is set, the jump back from
+    /// `b` to `a` costs the same. Otherwise, the edges of the PathCache become directed (like
+    /// for a Grid with [`move_cost`](CostGrid::move_cost)s), which makes updating it a bit
+    /// slower from then on. An existing link between `a` and `b` is replaced.
+    ///
+    /// Both ends get a Node, which is connected to the other Nodes of its Chunk, so that
+    /// [`find_path`](PathCache::find_path) and all other searches can route through the link.
+    /// The jump is a single step of the returned Paths. Links stay in place when the Grid
+    /// changes, but are not usable while either end is a solid Tile.
+    ///
+    /// `grid` has to be the current Grid, like for [`tiles_changed`](PathCache::tiles_changed).
+    ///
+    /// ## Panics
+    /// Panics if `a` or `b` is outside of the Grid, or if `grid` has move costs and the PathCache
+    /// was created without them, or the other way around.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// let (width, height) = (8, 4);
+    /// // a wall splits the Grid in two
+    /// let wall = |(x, _): (usize, usize)| if x == 4 { -1 } else { 1 };
+    /// let mut pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &wall,
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    /// assert!(pathfinding.find_path((0, 0), (7, 0), &wall).unwrap().is_none());
+    ///
+    /// // a teleporter through the wall
+    /// pathfinding.add_link((1, 2), (6, 2), 1, true, &wall);
+    ///
+    /// let path = pathfinding.find_path((0, 0), (7, 0), &wall).unwrap().unwrap();
+    /// // 3 steps to the teleporter, the jump, and 3 steps to the goal
+    /// assert_eq!(path.cost(), 7);
+    /// let steps = path.collect::<Vec<_>>();
+    /// assert!(steps.windows(2).any(|w| w == [(1, 2), (6, 2)]));
+    ///
+    /// pathfinding.remove_link((1, 2), (6, 2), &wall);
+    /// assert!(pathfinding.find_path((0, 0), (7, 0), &wall).unwrap().is_none());
+    /// ```
+    pub fn add_link<G: CostGrid>(
+        &mut self,
+        a: Point,
+        b: Point,
+        cost: Cost,
+        bidirectional: bool,
+        grid: &G,
+    ) {
+        self.check_link_bounds(a, b);
+        self.assert_moves(grid);
+        let mut get_cost = cost_grid::cost_fn(grid);
+        let moves = MoveCosts::new(self.neighborhood.clone(), grid);
+        if a == b {
+            return;
+        }
+        if self.links.iter().any(|link| link.connects(a, b)) {
+            self.remove_link(a, b, grid);
+        }
+        if !bidirectional {
+            self.nodes.make_directed();
+        }
+        self.links.push(Link {
+            from: a,
+            to: b,
+            cost,
+            bidirectional,
+        });
+
+        let mut added = NodeIDSet::default();
+        for pos in [a, b] {
+            if self.node_at(pos).is_some() || get_cost(pos) < 0 {
+                continue;
+            }
+            let id = self.add_link_node(pos, &mut get_cost);
+            self.connect_chunk_node(id, &mut get_cost, &moves);
+            added.insert(id);
+        }
+        self.connect_nodes(Some(added), &moves);
+        self.connect_links();
+
+        self.relabel_components(&[a, b]);
+        self.update_levels([a, b]);
+    }
+
+    /// Removes the link between `a` and `b` that was added with
+    /// [`add_link`](PathCache::add_link), in either direction.
+    ///
+    /// Returns `false` if there is no such link. `grid` has to be the current Grid, to restore
+    /// the regular Paths between the ends.
+    ///
+    /// ## Panics
+    /// Panics if `a` or `b` is outside of the Grid, or if `grid` doesn't match the move costs of
+    /// the PathCache, see [`add_link`](PathCache::add_link).
+    pub fn remove_link<G: CostGrid>(&mut self, a: Point, b: Point, grid: &G) -> bool {
+        self.check_link_bounds(a, b);
+        self.assert_moves(grid);
+        let mut get_cost = cost_grid::cost_fn(grid);
+        let moves = MoveCosts::new(self.neighborhood.clone(), grid);
+        let index = match self.links.iter().position(|link| link.connects(a, b)) {
+            Some(index) => index,
+            None => return false,
+        };
+        let link = self.links.remove(index);
+        // Paths that jump through the link are no longer valid
+        for pos in [link.from, link.to] {
+            let chunk_index = self.get_chunk_index(pos);
+            self.chunk_generations[chunk_index] += 1;
+        }
+
+        let mut reconnect = NodeIDSet::default();
+        for (pos, other_pos) in [(link.from, link.to), (link.to, link.from)] {
+            let (id, other_id) = match (self.node_at(pos), self.node_at(other_pos)) {
+                (Some(id), Some(other_id)) => (id, other_id),
+                _ => continue,
+            };
+            if self.nodes[id].edges.contains_key(&other_id) {
+                self.nodes.remove_edge(id, other_id);
+                reconnect.insert(id);
+            }
+        }
+
+        for pos in [link.from, link.to] {
+            let id = match self.node_at(pos) {
+                Some(id) => id,
+                None => continue,
+            };
+            let chunk_index = self.get_chunk_index(pos);
+            let chunk = &self.chunks[chunk_index];
+            let on_side = Dir::all().any(|dir| chunk.sides[dir.num()] && chunk.at_side(pos, dir));
+            if !on_side && !self.is_link_end(pos) {
+                // only links need Nodes inside of a Chunk
+                self.chunks[chunk_index].nodes.remove(&id);
+                self.nodes.remove_node(id);
+                reconnect.remove(&id);
+            }
+        }
+
+        // the link might have replaced a regular Path between its ends
+        for &id in reconnect.iter() {
+            self.connect_chunk_node(id, &mut get_cost, &moves);
+        }
+        self.connect_nodes(Some(reconnect), &moves);
+        self.connect_links();
+
+        self.relabel_components(&[a, b]);
+        self.update_levels([a, b]);
+        true
+    }
+
+    fn check_link_bounds(&self, a: Point, b: Point) {
+        for p in [a, b] {
+            assert!(
+                p.0 < self.width && p.1 < self.height,
+                "{:?} is outside of the Grid",
+                p
+            );
+        }
+    }
+
+    /// If `pos` is one end of a link
+    pub(super) fn is_link_end(&self, pos: Point) -> bool {
+        self.links
+            .iter()
+            .any(|link| link.from == pos || link.to == pos)
+    }
+
+    /// If there is a link that jumps from `from` to `to`
+    pub(super) fn is_link(&self, from: Point, to: Point) -> bool {
+        self.links.iter().any(|link| link.leads(from, to))
+    }
+
+    /// The Tiles that links lead to from `from`, with the scaled costs of the jumps
+    pub(super) fn link_jumps(&self, from: Point) -> impl Iterator<Item = (Point, Cost)> + '_ {
+        let scale = self.neighborhood.cost_scale();
+        self.links.iter().filter_map(move |link| {
+            if link.from == from {
+                Some((link.to, link.cost * scale))
+            } else if link.bidirectional && link.to == from {
+                Some((link.from, link.cost * scale))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// If any step of a Path of Nodes uses a link
+    pub(super) fn uses_link(&self, path: &Path<NodeID>) -> bool {
+        !self.links.is_empty()
+            && path
+                .iter()
+                .zip(path.iter().skip(1))
+                .any(|(a, b)| self.is_link(self.nodes[*a].pos, self.nodes[*b].pos))
+    }
+
+    /// The ends of all links, for the [`Components`](super::Components)
+    pub(super) fn portals(&self) -> Vec<(Point, Point)> {
+        self.links.iter().map(|link| (link.from, link.to)).to_vec()
+    }
+
+    /// Recalculates the components after the links between `ends` changed
+    fn relabel_components(&mut self, ends: &[Point]) {
+        let portals = self.portals();
+        self.components
+            .relabel(&self.chunks, self.chunk_index_fn(), &portals, ends);
+    }
+
+    /// Adds the Node for one end of a link to the NodeList and its Chunk, without connecting it
+    pub(super) fn add_link_node(
+        &mut self,
+        pos: Point,
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) -> NodeID {
+        let walk_cost = get_cost(pos) as usize * self.neighborhood.cost_scale();
+        let id = self.nodes.add_node(pos, walk_cost);
+        let chunk_index = self.get_chunk_index(pos);
+        self.chunks[chunk_index].nodes.insert(id);
+        id
+    }
+
+    /// Updates the Nodes at the ends of links among the changed `tiles`: solid ends lose their
+    /// Node, walkable ones get one with the new cost. Connecting them is left to the recreation
+    /// of their (dirty) Chunks
+    pub(super) fn update_link_nodes(
+        &mut self,
+        tiles: &[Point],
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) {
+        if self.links.is_empty() {
+            return;
+        }
+        for &pos in tiles {
+            if !self.is_link_end(pos) {
+                continue;
+            }
+            let cost = get_cost(pos);
+            match self.node_at(pos) {
+                Some(id) if cost < 0 => {
+                    let chunk_index = self.get_chunk_index(pos);
+                    self.chunks[chunk_index].nodes.remove(&id);
+                    self.nodes.remove_node(id);
+                }
+                Some(id) => {
+                    self.nodes[id].walk_cost = cost as usize * self.neighborhood.cost_scale();
+                }
+                None if cost >= 0 => {
+                    self.add_link_node(pos, &mut get_cost);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Connects a Node to the other Nodes of its Chunk, replacing its existing Paths
+    fn connect_chunk_node(
+        &mut self,
+        id: NodeID,
+        get_cost: impl FnMut(Point) -> isize,
+        moves: &MoveCosts<N>,
+    ) {
+        let chunk_index = self.get_chunk_index(self.nodes[id].pos);
+        let chunk = &mut self.chunks[chunk_index];
+        chunk.nodes.remove(&id);
+        chunk.add_nodes(
+            &[id],
+            get_cost,
+            moves,
+            &mut self.nodes,
+            &self.annotations,
+            &self.config,
+        );
+    }
+
+    /// Inserts the edges of all links whose ends have Nodes, unless a regular Path between the
+    /// ends is cheaper
+    pub(super) fn connect_links(&mut self) {
+        let scale = self.neighborhood.cost_scale();
+        for i in 0..self.links.len() {
+            let link = self.links[i];
+            let (id, other_id) = match (self.node_at(link.from), self.node_at(link.to)) {
+                (Some(id), Some(other_id)) => (id, other_id),
+                _ => continue,
+            };
+            let cost = link.cost * scale;
+            let mut steps = vec![(id, other_id, link.from, link.to)];
+            if link.bidirectional {
+                steps.push((other_id, id, link.to, link.from));
+            }
+            for (src, target, from, to) in steps {
+                let cheaper = self.nodes[src]
+                    .edges
+                    .get(&target)
+                    .is_none_or(|path| path.cost() >= cost);
+                if cheaper {
+                    let path = PathSegment::Known(Path::from_slice(&[from, to], cost));
+                    self.nodes.insert_edge(src, target, path);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{path::Cost, prelude::*, Point};
+
+    type Links = Vec<(Point, Point, Cost, bool)>;
+
+    /// Checks that every step of `path` is allowed or a jump through one of the `links`, and
+    /// returns what the steps cost
+    fn walk<N: Neighborhood>(
+        neighborhood: &N,
+        links: &Links,
+        start: Point,
+        path: impl IntoIterator<Item = Point>,
+        get_cost: impl Fn(Point) -> isize,
+    ) -> (Point, Cost) {
+        let mut prev = start;
+        let mut total = 0;
+        let mut target = vec![];
+        for p in path {
+            target.clear();
+            neighborhood.get_all_neighbors(prev, &mut target);
+            let jump = links.iter().find(|(a, b, _, bidirectional)| {
+                (*a == prev && *b == p) || (*bidirectional && *b == prev && *a == p)
+            });
+            if let Some(&(_, _, link_cost, _)) = jump {
+                total += link_cost * neighborhood.cost_scale();
+            } else {
+                assert!(target.contains(&p), "{:?} -> {:?}", prev, p);
+                assert!(neighborhood.is_step_allowed(prev, p, &get_cost));
+                let (prev_cost, p_cost) = (get_cost(prev), get_cost(p));
+                total += neighborhood.step_cost(prev, p, prev_cost as Cost, p_cost as Cost);
+            }
+            prev = p;
+        }
+        (prev, total)
+    }
+
+    /// `costs` are the costs of a step and of a diagonal step
+    fn check<N: Neighborhood + Sync>(neighborhood: N, [step, diagonal]: [Cost; 2]) {
+        // walls that split the Grid into four rooms
+        #[rustfmt::skip]
+        let rows = [
+            "....#....",
+            "....#....",
+            "....#....",
+            "....#....",
+            "#########",
+            "....#....",
+            "....#....",
+            "....#....",
+            "....#....",
+        ];
+        let (width, height) = (rows[0].len(), rows.len());
+        fn cost_fn(tiles: &[Vec<u8>]) -> impl '_ + Sync + Fn(Point) -> isize {
+            move |(x, y)| if tiles[y][x] == b'#' { -1 } else { 1 }
+        }
+        // from the insides of the top Chunks, and through the walls on the right
+        let links: Links = vec![
+            ((1, 1), (7, 1), 3, true),
+            ((8, 3), (8, 5), 2, false),
+            ((7, 7), (1, 7), 1, false),
+        ];
+
+        for config in [
+            PathCacheConfig::with_chunk_size(3),
+            PathCacheConfig {
+                cache_paths: false,
+                levels: 2,
+                ..PathCacheConfig::with_chunk_size(2)
+            },
+        ] {
+            let mut tiles = rows.map(|row| row.as_bytes().to_vec());
+            let mut pathfinding = PathCache::new(
+                (width, height),
+                &cost_fn(&tiles),
+                neighborhood.clone(),
+                config,
+            );
+            let find = |pathfinding: &PathCache<N>, tiles: &[Vec<u8>], start, goal| {
+                let cost_fn = cost_fn(tiles);
+                let reachable = pathfinding.is_reachable(start, goal, &cost_fn).unwrap();
+                let path = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+                // the areas are connected in both directions, even through one-way links
+                assert!(reachable || path.is_none(), "{:?} -> {:?}", start, goal);
+                let path = path?;
+                let cost = path.cost();
+                let path = path.resolve(&cost_fn).unwrap();
+                assert_eq!(
+                    walk(&neighborhood, &links, start, path, cost_fn),
+                    (goal, cost)
+                );
+                Some(cost)
+            };
+            assert_eq!(find(&pathfinding, &tiles, (0, 0), (8, 0)), None);
+
+            for &(a, b, cost, bidirectional) in &links {
+                pathfinding.add_link(a, b, cost, bidirectional, &cost_fn(&tiles));
+            }
+
+            // to the link, the jump, and away from the link
+            let expected = 2 * diagonal + 3 * step;
+            assert_eq!(find(&pathfinding, &tiles, (0, 0), (8, 0)), Some(expected));
+            assert_eq!(find(&pathfinding, &tiles, (8, 0), (0, 0)), Some(expected));
+
+            // all the way around, but not back through the one-way links
+            let expected = 4 * diagonal + 8 * step;
+            assert_eq!(find(&pathfinding, &tiles, (0, 0), (0, 8)), Some(expected));
+            assert_eq!(find(&pathfinding, &tiles, (0, 8), (0, 0)), None);
+
+            let paths = pathfinding
+                .find_paths((0, 0), &[(0, 8), (8, 8)], &cost_fn(&tiles))
+                .unwrap();
+            assert_eq!(paths[&(0, 8)].cost(), expected);
+            assert_eq!(paths[&(8, 8)].cost(), 2 * diagonal + 9 * step);
+
+            // flow fields jump through the links as well
+            {
+                let grid = cost_fn(&tiles);
+                let mut field = pathfinding.flow_field((0, 8), &grid).unwrap();
+                let mut current = (0, 0);
+                while let Some(next) = field.next_step(current) {
+                    assert!(field.cost(next).unwrap() < field.cost(current).unwrap());
+                    current = next;
+                }
+                assert_eq!(current, (0, 8));
+                assert_eq!(field.cost((8, 0)), Some(2 * diagonal + 7 * step));
+            }
+
+            // links can't be used while either end is solid
+            tiles[1][7] = b'#';
+            pathfinding.tiles_changed(&[(7, 1)], &cost_fn(&tiles));
+            assert_eq!(find(&pathfinding, &tiles, (0, 0), (8, 0)), None);
+
+            tiles[1][7] = b'.';
+            pathfinding.tiles_changed(&[(7, 1)], &cost_fn(&tiles));
+            let expected = 2 * diagonal + 3 * step;
+            assert_eq!(find(&pathfinding, &tiles, (0, 0), (8, 0)), Some(expected));
+
+            // removing a link works from either end
+            assert!(pathfinding.remove_link((7, 1), (1, 1), &cost_fn(&tiles)));
+            assert!(!pathfinding.remove_link((1, 1), (7, 1), &cost_fn(&tiles)));
+            assert_eq!(find(&pathfinding, &tiles, (0, 0), (8, 0)), None);
+        }
+    }
+
+    #[test]
+    fn rooms() {
+        check(ManhattanNeighborhood::new(9, 9), [1, 2]);
+        check(MooreNeighborhood::new_octile(9, 9), [10, 14]);
+    }
+
+    #[test]
+    fn moves() {
+        use crate::path_cache::MoveCosts;
+
+        // the top row is a conveyor belt that only moves to the right
+        let grid = [
+            [1, 1, 1, 1, 1, 1],
+            [1, 1, 3, 1, 1, 1],
+            [1, -1, -1, -1, -1, 1],
+            [1, 1, 1, 1, 1, 1],
+        ];
+        let (width, height) = (grid[0].len(), grid.len());
+        let cost_fn = |(x, y): Point| grid[y][x];
+        let move_grid = WithMoves(cost_fn, |from: Point, to: Point| {
+            if from.1 == 0 && to.0 < from.0 {
+                -1
+            } else {
+                cost_fn(from)
+            }
+        });
+        let neighborhood = ManhattanNeighborhood::new(width, height);
+        let moves = MoveCosts::new(neighborhood, &move_grid);
+
+        let mut pathfinding = PathCache::new(
+            (width, height),
+            &move_grid,
+            neighborhood,
+            PathCacheConfig::with_chunk_size(3),
+        );
+        // a one-way link between the insides of two Chunks, whose Node in the left Chunk is
+        // cheapest to reach from the right side by walking against the belt
+        let links: Links = vec![((4, 1), (1, 1), 1, false)];
+        pathfinding.add_link((4, 1), (1, 1), 1, false, &move_grid);
+
+        let path = pathfinding.find_path((5, 1), (0, 1), &move_grid).unwrap();
+        assert_eq!(path.unwrap().cost(), 3);
+
+        let find = |start, goal| {
+            let path = pathfinding
+                .find_path(start, goal, &move_grid)
+                .unwrap()
+                .unwrap();
+            let cost = path.cost();
+            let path = path.resolve(&move_grid).unwrap();
+            assert_eq!(walk(&moves, &links, start, path, cost_fn), (goal, cost));
+            cost
+        };
+        // the link only leads to the left
+        assert_eq!(find((4, 1), (1, 1)), 1);
+        assert_eq!(find((1, 1), (4, 1)), 5);
+
+        assert!(pathfinding.remove_link((4, 1), (1, 1), &move_grid));
+        let path = pathfinding.find_path((4, 1), (1, 1), &move_grid).unwrap();
+        // over the expensive Tile instead
+        assert_eq!(path.unwrap().cost(), 5);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    neighbors::{GridSteps, Neighborhood},
+    path::Cost,
+    *,
+};
+
+/// A Neighborhood that also applies the [`move_cost`](CostGrid::move_cost)s of a Grid, if
+/// there are any.
+///
+/// A step is only allowed if its move cost is not negative, and the move cost replaces the
+/// cost of the Tile that is left in [`step_cost`](Neighborhood::step_cost). Unlike the trait
+/// requires, steps can therefore cost something else or be forbidden in the other direction,
+/// which only the Chunks and the directed NodeList of the PathCache are prepared for.
+#[derive(Clone)]
+pub struct MoveCosts<'a, N: Neighborhood> {
+    inner: N,
+    /// the Grid with the move costs, only if it [`has_moves`](CostGrid::has_moves)
+    grid: Option<&'a dyn CostGrid>,
+}
+
+impl<'a, N: Neighborhood> MoveCosts<'a, N> {
+    /// Applies the move costs of `grid`, if there are any
+    pub fn new<G: CostGrid>(inner: N, grid: &'a G) -> Self {
+        MoveCosts {
+            inner,
+            grid: grid.has_moves().then_some(grid),
+        }
+    }
+
+    /// Applies the move costs of `grid`, which has to be `None` for a Grid without them. Since
+    /// MoveCosts can't be shared between threads, this creates them from a Grid that can.
+    pub fn with_grid(inner: N, grid: Option<&'a (dyn CostGrid + Sync)>) -> Self {
+        let grid: Option<&dyn CostGrid> = match grid {
+            Some(grid) => Some(grid),
+            None => None,
+        };
+        MoveCosts { inner, grid }
+    }
+
+    pub fn has_moves(&self) -> bool {
+        self.grid.is_some()
+    }
+}
+
+impl<N: Neighborhood> std::fmt::Debug for MoveCosts<'_, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MoveCosts")
+            .field("inner", &self.inner)
+            .field("has_moves", &self.has_moves())
+            .finish()
+    }
+}
+
+impl<N: Neighborhood> Neighborhood for MoveCosts<'_, N> {
+    fn get_all_neighbors(&self, point: Point, target: &mut Vec<Point>) {
+        self.inner.get_all_neighbors(point, target)
+    }
+    fn heuristic(&self, point: Point, goal: Point) -> usize {
+        self.inner.heuristic(point, goal)
+    }
+    fn grid_steps(&self) -> Option<GridSteps> {
+        // jumps assume that every step over a Tile costs the same
+        if self.has_moves() {
+            None
+        } else {
+            self.inner.grid_steps()
+        }
+    }
+    fn wraps_horizontally(&self) -> bool {
+        self.inner.wraps_horizontally()
+    }
+    fn describe(&self) -> Vec<u64> {
+        self.inner.describe()
+    }
+    fn step_cost(&self, from: Point, to: Point, from_cost: Cost, to_cost: Cost) -> Cost {
+        let from_cost = match self.grid {
+            Some(grid) => grid.move_cost(from, to).max(0) as Cost,
+            None => from_cost,
+        };
+        self.inner.step_cost(from, to, from_cost, to_cost)
+    }
+    fn cost_scale(&self) -> Cost {
+        self.inner.cost_scale()
+    }
+    fn is_step_allowed(
+        &self,
+        from: Point,
+        to: Point,
+        get_cost: impl FnMut(Point) -> isize,
+    ) -> bool {
+        self.inner.is_step_allowed(from, to, get_cost)
+            && self.grid.is_none_or(|grid| grid.move_cost(from, to) >= 0)
+    }
+}
+
+/// A Neighborhood where every step costs as much as the step back in `N`, for searches that
+/// run backwards from the end of a Path
+#[derive(Clone, Debug)]
+pub struct Reversed<'a, N: Neighborhood>(pub &'a N);
+
+impl<N: Neighborhood> Neighborhood for Reversed<'_, N> {
+    fn get_all_neighbors(&self, point: Point, target: &mut Vec<Point>) {
+        self.0.get_all_neighbors(point, target)
+    }
+    fn heuristic(&self, point: Point, goal: Point) -> usize {
+        self.0.heuristic(goal, point)
+    }
+    fn wraps_horizontally(&self) -> bool {
+        self.0.wraps_horizontally()
+    }
+    fn describe(&self) -> Vec<u64> {
+        self.0.describe()
+    }
+    fn step_cost(&self, from: Point, to: Point, from_cost: Cost, to_cost: Cost) -> Cost {
+        self.0.step_cost(to, from, to_cost, from_cost)
+    }
+    fn cost_scale(&self) -> Cost {
+        self.0.cost_scale()
+    }
+    fn is_step_allowed(
+        &self,
+        from: Point,
+        to: Point,
+        get_cost: impl FnMut(Point) -> isize,
+    ) -> bool {
+        self.0.is_step_allowed(to, from, get_cost)
+    }
+}
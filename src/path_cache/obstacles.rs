@@ -0,0 +1,458 @@
+use crate::{
+    graph::Graph,
+    neighbors::Neighborhood,
+    path::{Cost, Path, PathSegment},
+    *,
+};
+
+use super::{annotations::AgentGraph, moves::MoveCosts, PathCache};
+
+use std::{borrow::Cow, cell::RefCell};
+
+/// Temporarily blocked or penalized Tiles, which searches respect without updating the
+/// PathCache.
+///
+/// Calling [`tiles_changed`](PathCache::tiles_changed) is too expensive for things that change
+/// every frame, like units standing in a corridor or doors that open and close. Obstacles can
+/// instead be passed to a single search with
+/// [`find_path_with_obstacles`](PathCache::find_path_with_obstacles), or registered with
+/// [`add_obstacles`](PathCache::add_obstacles) to apply to all searches until they are removed.
+///
+/// Blocked Tiles are treated like solid Tiles, and penalized Tiles cost more to walk over. The
+/// cached Paths between the Nodes of the PathCache are only checked against the Obstacles when
+/// a search actually uses them, and only recalculated if they cross an obstacle.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Obstacles {
+    /// the extra cost of every Tile, or a negative number if it is blocked
+    tiles: PointMap<isize>,
+}
+
+impl Obstacles {
+    /// Creates an empty set of Obstacles
+    pub fn new() -> Obstacles {
+        Obstacles::default()
+    }
+
+    /// Blocks `tile`, so that no Path can cross it.
+    pub fn block(&mut self, tile: Point) {
+        self.tiles.insert(tile, -1);
+    }
+
+    /// Adds `extra_cost` to the cost of walking over `tile`.
+    ///
+    /// Penalizing the same Tile several times adds up the costs. Blocked Tiles stay blocked.
+    pub fn penalize(&mut self, tile: Point, extra_cost: usize) {
+        let cost = self.tiles.entry(tile).or_insert(0);
+        if *cost >= 0 {
+            *cost = cost.saturating_add(extra_cost as isize);
+        }
+    }
+
+    /// Removes any block or penalty of `tile`. Returns `true` if there was one.
+    pub fn remove(&mut self, tile: Point) -> bool {
+        self.tiles.remove(&tile).is_some()
+    }
+
+    /// Removes all blocks and penalties
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+
+    /// If there are no blocked or penalized Tiles
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// If `tile` is blocked
+    pub fn is_blocked(&self, tile: Point) -> bool {
+        self.tiles.get(&tile).is_some_and(|cost| *cost < 0)
+    }
+
+    /// The extra cost of walking over `tile`, which is `0` for blocked Tiles
+    pub fn penalty(&self, tile: Point) -> usize {
+        self.tiles
+            .get(&tile)
+            .map_or(0, |cost| (*cost).max(0) as usize)
+    }
+
+    /// If `tile` is blocked or penalized
+    fn contains(&self, tile: Point) -> bool {
+        self.tiles.contains_key(&tile)
+    }
+
+    /// Adds the blocks and penalties of `other` to these
+    fn extend(&mut self, other: &Obstacles) {
+        for (&tile, &cost) in other.tiles.iter() {
+            if cost < 0 {
+                self.block(tile);
+            } else {
+                self.penalize(tile, cost as usize);
+            }
+        }
+    }
+
+    /// Applies the Obstacles to the `cost` of `tile`
+    pub(super) fn cost(&self, tile: Point, cost: isize) -> isize {
+        match self.tiles.get(&tile) {
+            Some(_) if cost < 0 => cost,
+            Some(&extra) if extra < 0 => -1,
+            Some(&extra) => cost.saturating_add(extra),
+            None => cost,
+        }
+    }
+
+    /// Applies the Obstacles to a cost function
+    pub(super) fn cost_fn<'a>(
+        &'a self,
+        mut get_cost: impl FnMut(Point) -> isize + 'a,
+    ) -> impl FnMut(Point) -> isize + 'a {
+        move |p| self.cost(p, get_cost(p))
+    }
+}
+
+/// A handle to Obstacles registered with [`add_obstacles`](PathCache::add_obstacles)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObstacleHandle(usize);
+
+/// The Obstacles registered on a PathCache
+#[derive(Clone, Debug, Default)]
+pub(super) struct Registry {
+    entries: hashbrown::HashMap<usize, Obstacles>,
+    next: usize,
+    /// all entries combined
+    combined: Obstacles,
+}
+
+impl Registry {
+    pub fn add(&mut self, obstacles: Obstacles) -> ObstacleHandle {
+        let handle = ObstacleHandle(self.next);
+        self.next += 1;
+        self.combined.extend(&obstacles);
+        self.entries.insert(handle.0, obstacles);
+        handle
+    }
+
+    pub fn update(&mut self, handle: ObstacleHandle, obstacles: Obstacles) -> bool {
+        match self.entries.get_mut(&handle.0) {
+            Some(entry) => {
+                *entry = obstacles;
+                self.combine();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, handle: ObstacleHandle) -> Option<Obstacles> {
+        let obstacles = self.entries.remove(&handle.0)?;
+        self.combine();
+        Some(obstacles)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.combined.clear();
+    }
+
+    /// The registered Obstacles combined with the `extra` ones of a search from `start`.
+    ///
+    /// `start` is never blocked, since agents usually register the Tiles they stand on.
+    pub fn with<'a>(&'a self, extra: Option<&'a Obstacles>, start: Point) -> Cow<'a, Obstacles> {
+        let mut obstacles = match extra {
+            Some(extra) if !extra.is_empty() && !self.combined.is_empty() => {
+                let mut combined = self.combined.clone();
+                combined.extend(extra);
+                Cow::Owned(combined)
+            }
+            Some(extra) if !extra.is_empty() => Cow::Borrowed(extra),
+            _ => Cow::Borrowed(&self.combined),
+        };
+        if obstacles.contains(start) {
+            obstacles.to_mut().remove(start);
+        }
+        obstacles
+    }
+
+    fn combine(&mut self) {
+        self.combined.clear();
+        for obstacles in self.entries.values() {
+            self.combined.extend(obstacles);
+        }
+    }
+}
+
+/// The edges of the Node Graph that were checked against the Obstacles of a search
+#[derive(Debug, Default)]
+pub(super) struct Revalidated {
+    /// the indices of the Chunks that contain any Obstacles
+    chunks: hashbrown::HashSet<usize>,
+    /// the edges that changed, with `None` if they are blocked
+    edges: hashbrown::HashMap<(NodeID, NodeID), Option<PathSegment>>,
+}
+
+impl Revalidated {
+    pub fn new<N: Neighborhood + Sync>(cache: &PathCache<N>, obstacles: &Obstacles) -> Self {
+        Revalidated {
+            chunks: obstacles
+                .tiles
+                .keys()
+                .map(|p| cache.get_chunk_index(*p))
+                .collect(),
+            edges: Default::default(),
+        }
+    }
+
+    /// If the search has any Obstacles to check
+    pub fn is_active(&self) -> bool {
+        !self.chunks.is_empty()
+    }
+
+    /// The edge from `a` to `b` after checking it, or the one of `graph` if it wasn't changed
+    pub fn edge<'a>(
+        &'a self,
+        graph: &AgentGraph<'a>,
+        a: NodeID,
+        b: NodeID,
+    ) -> Option<&'a PathSegment> {
+        match self.edges.get(&(a, b)) {
+            Some(segment) => segment.as_ref(),
+            None => graph.edge(a, b),
+        }
+    }
+}
+
+/// The lowest level of the Node Graph with the edges that cross Obstacles recalculated
+pub(super) struct ObstacleGraph<'a, 'b, N: Neighborhood> {
+    cache: &'a PathCache<N>,
+    graph: &'b AgentGraph<'a>,
+    obstacles: &'b Obstacles,
+    neighborhood: &'b MoveCosts<'a, N>,
+    /// the cost function with the Obstacles applied
+    get_cost: RefCell<&'b mut dyn FnMut(Point) -> isize>,
+    revalidated: RefCell<&'b mut Revalidated>,
+}
+
+impl<'a, 'b, N: Neighborhood + Sync> ObstacleGraph<'a, 'b, N> {
+    pub fn new(
+        cache: &'a PathCache<N>,
+        graph: &'b AgentGraph<'a>,
+        obstacles: &'b Obstacles,
+        neighborhood: &'b MoveCosts<'a, N>,
+        get_cost: &'b mut dyn FnMut(Point) -> isize,
+        revalidated: &'b mut Revalidated,
+    ) -> Self {
+        ObstacleGraph {
+            cache,
+            graph,
+            obstacles,
+            neighborhood,
+            get_cost: RefCell::new(get_cost),
+            revalidated: RefCell::new(revalidated),
+        }
+    }
+
+    /// The cost of the edge from `a` to `b` with the Obstacles, or `None` if it is blocked
+    fn edge_cost(&self, a: NodeID, b: NodeID, segment: &PathSegment) -> Option<Cost> {
+        if let Some(segment) = self.revalidated.borrow().edges.get(&(a, b)) {
+            return segment.as_ref().map(PathSegment::cost);
+        }
+        let cache = self.cache;
+        let (pos_a, pos_b) = (cache.nodes[a].pos, cache.nodes[b].pos);
+        let obstacles = self.obstacles;
+
+        let changed = if obstacles.is_blocked(pos_a) || obstacles.is_blocked(pos_b) {
+            None
+        } else if segment.len() == 2 && cache.is_link(pos_a, pos_b) {
+            // jumps through links don't cross any other Tiles
+            return Some(segment.cost());
+        } else if !cache.same_chunk(pos_a, pos_b) {
+            // a single step between two Chunks
+            if !obstacles.contains(pos_a) && !obstacles.contains(pos_b) {
+                return Some(segment.cost());
+            }
+            let mut get_cost = self.get_cost.borrow_mut();
+            let (cost_a, cost_b) = (get_cost(pos_a) as Cost, get_cost(pos_b) as Cost);
+            let cost = self.neighborhood.step_cost(pos_a, pos_b, cost_a, cost_b);
+            Some(PathSegment::Known(Path::from_slice(&[pos_a, pos_b], cost)))
+        } else {
+            let index = cache.get_chunk_index(pos_a);
+            if !self.revalidated.borrow().chunks.contains(&index) {
+                return Some(segment.cost());
+            }
+            if let PathSegment::Known(path) = segment {
+                // Obstacles only make Tiles more expensive, so Paths that avoid them stay optimal
+                if !path.iter().any(|p| obstacles.contains(*p)) {
+                    return Some(segment.cost());
+                }
+            }
+            let mut get_cost = self.get_cost.borrow_mut();
+            cache.chunks[index]
+                .find_path(
+                    pos_a,
+                    pos_b,
+                    &mut **get_cost,
+                    self.neighborhood,
+                    cache.config.search_options(),
+                )
+                .map(PathSegment::Known)
+        };
+        let cost = changed.as_ref().map(PathSegment::cost);
+        self.revalidated.borrow_mut().edges.insert((a, b), changed);
+        cost
+    }
+}
+
+impl<N: Neighborhood + Sync> Graph for ObstacleGraph<'_, '_, N> {
+    fn pos(&self, id: NodeID) -> Point {
+        self.graph.pos(id)
+    }
+    fn for_each_edge(&self, id: NodeID, mut f: impl FnMut(NodeID, Cost)) {
+        self.graph.for_each_edge_segment(id, |other_id, segment| {
+            if let Some(cost) = self.edge_cost(id, other_id, segment) {
+                f(other_id, cost);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Obstacles;
+    use crate::{prelude::*, Point};
+    use std::task::Poll;
+
+    #[test]
+    fn corridors() {
+        // a short corridor at the top and a long one around the bottom
+        #[rustfmt::skip]
+        let tiles = [
+            "........",
+            ".######.",
+            ".######.",
+            "........",
+        ];
+        let (width, height) = (tiles[0].len(), tiles.len());
+        let cost_fn = |(x, y): Point| {
+            if tiles[y].as_bytes()[x] == b'#' {
+                -1
+            } else {
+                1
+            }
+        };
+        let (start, goal) = ((0, 0), (width - 1, 0));
+
+        for cache_paths in [true, false] {
+            let mut pathfinding = PathCache::new(
+                (width, height),
+                &cost_fn,
+                ManhattanNeighborhood::new(width, height),
+                PathCacheConfig {
+                    cache_paths,
+                    ..PathCacheConfig::with_chunk_size(3)
+                },
+            );
+            let find = |obstacles: &Obstacles| {
+                let path = pathfinding
+                    .find_path_with_obstacles(start, goal, obstacles, &cost_fn)
+                    .unwrap()?;
+                let cost = path.cost();
+                let path: Vec<_> = path.resolve(&cost_fn).unwrap();
+                assert_eq!(path.last(), Some(&goal));
+                Some((cost, path))
+            };
+
+            let (cost, path) = find(&Obstacles::new()).unwrap();
+            assert_eq!(cost, 7);
+            assert!(path.contains(&(3, 0)));
+
+            // the Tile that is left costs its penalty on top
+            let mut obstacles = Obstacles::new();
+            obstacles.penalize((3, 0), 4);
+            assert_eq!(find(&obstacles).unwrap().0, 11);
+
+            // penalties add up until the long way is cheaper
+            obstacles.penalize((3, 0), 4);
+            let (cost, path) = find(&obstacles).unwrap();
+            assert_eq!(cost, 13);
+            assert!(path.contains(&(3, 3)));
+
+            let mut obstacles = Obstacles::new();
+            obstacles.block((3, 0));
+            let (cost, path) = find(&obstacles).unwrap();
+            assert_eq!(cost, 13);
+            assert!(!path.contains(&(3, 0)));
+
+            obstacles.block((3, 3));
+            assert_eq!(find(&Obstacles::new()).unwrap().0, 7);
+            assert_eq!(find(&obstacles), None);
+
+            // registered Obstacles apply to every search until they are removed
+            let mut blocked = Obstacles::new();
+            blocked.block((3, 0));
+            let handle = pathfinding.add_obstacles(blocked.clone());
+
+            let path = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+            assert_eq!(path.unwrap().cost(), 13);
+            let mut query = pathfinding.begin_find_path(start, goal, &cost_fn).unwrap();
+            let path = loop {
+                if let Poll::Ready(path) = query.step(3) {
+                    break path;
+                }
+            };
+            assert_eq!(path.unwrap().cost(), 13);
+            let paths = pathfinding
+                .find_paths(start, &[goal, (5, 0)], &cost_fn)
+                .unwrap();
+            assert_eq!(paths[&goal].cost(), 13);
+            assert_eq!(paths[&(5, 0)].cost(), 15);
+
+            assert_eq!(pathfinding.remove_obstacles(handle), Some(blocked));
+            assert_eq!(pathfinding.remove_obstacles(handle), None);
+            let path = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+            assert_eq!(path.unwrap().cost(), 7);
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let mut pathfinding = PathCache::new(
+            (8, 8),
+            &|_: (usize, usize)| 1,
+            ManhattanNeighborhood::new(8, 8),
+            PathCacheConfig::with_chunk_size(4),
+        );
+        let mut a = Obstacles::new();
+        a.block((1, 0));
+        a.penalize((2, 0), 3);
+        let mut b = Obstacles::new();
+        b.penalize((2, 0), 4);
+        b.penalize((1, 0), 4);
+        let handle_a = pathfinding.add_obstacles(a.clone());
+        let handle_b = pathfinding.add_obstacles(b);
+        assert_ne!(handle_a, handle_b);
+
+        let combined = pathfinding.obstacles.with(None, (0, 0));
+        assert!(combined.is_blocked((1, 0)));
+        assert_eq!(combined.penalty((2, 0)), 7);
+
+        // start is never blocked
+        let combined = pathfinding.obstacles.with(None, (1, 0));
+        assert!(!combined.is_blocked((1, 0)));
+
+        let mut c = Obstacles::new();
+        c.penalize((3, 0), 1);
+        let combined = pathfinding.obstacles.with(Some(&c), (0, 0));
+        assert_eq!(combined.penalty((3, 0)), 1);
+        assert_eq!(combined.penalty((2, 0)), 7);
+
+        assert!(pathfinding.update_obstacles(handle_a, c));
+        let combined = pathfinding.obstacles.with(None, (0, 0));
+        assert!(!combined.is_blocked((1, 0)));
+        assert_eq!(combined.penalty((1, 0)), 4);
+        assert_eq!(combined.penalty((2, 0)), 4);
+
+        pathfinding.clear_obstacles();
+        assert!(pathfinding.obstacles.with(None, (0, 0)).is_empty());
+        assert!(!pathfinding.update_obstacles(handle_b, a));
+    }
+}
@@ -0,0 +1,417 @@
+use crate::{
+    graph::{self, Graph, ReverseGraph},
+    grid,
+    neighbors::Neighborhood,
+    path::{AbstractPath, Cost, Path, PathSegment},
+    *,
+};
+
+use super::{
+    annotations::{Agent, AgentGraph},
+    level::QueryGraph,
+    moves::MoveCosts,
+    obstacles::{ObstacleGraph, Obstacles, Revalidated},
+    PathCache,
+};
+
+use std::{borrow::Cow, task::Poll};
+
+/// The Graph that a [`PathQuery`] searches on
+enum SearchGraph<'a> {
+    /// all levels of the PathCache, for regular agents
+    Query(QueryGraph<'a>),
+    /// only the lowest level, for agents that can't use all edges and for searches with
+    /// Obstacles
+    Agent(AgentGraph<'a>),
+}
+
+impl Graph for SearchGraph<'_> {
+    fn pos(&self, id: NodeID) -> Point {
+        match self {
+            SearchGraph::Query(graph) => graph.pos(id),
+            SearchGraph::Agent(graph) => graph.pos(id),
+        }
+    }
+    fn for_each_edge(&self, id: NodeID, f: impl FnMut(NodeID, Cost)) {
+        match self {
+            SearchGraph::Query(graph) => graph.for_each_edge(id, f),
+            SearchGraph::Agent(graph) => graph.for_each_edge(id, f),
+        }
+    }
+}
+
+impl ReverseGraph for SearchGraph<'_> {
+    fn for_each_incoming_edge(&self, id: NodeID, f: impl FnMut(NodeID, Cost)) {
+        match self {
+            SearchGraph::Query(graph) => graph.for_each_incoming_edge(id, f),
+            SearchGraph::Agent(graph) => graph.for_each_incoming_edge(id, f),
+        }
+    }
+}
+
+/// The search that a [`PathQuery`] runs on the Node Graph
+enum GraphSearch {
+    Forward(graph::AStarSearch),
+    /// see [`bidirectional`](crate::PathCacheConfig::bidirectional)
+    Bidirectional(Box<graph::BidirectionalSearch>),
+}
+
+/// A piece of the final Path of a search on the Node Graph
+pub(super) enum PathPart {
+    /// a part that is already known
+    Known(PathSegment),
+    /// a Path that still has to be searched on the Grid, only within the Chunk with the
+    /// index `chunk` if set
+    Search {
+        start: Point,
+        goal: Point,
+        chunk: Option<usize>,
+    },
+}
+
+/// Puts the final Path together from its [`PathPart`]s
+struct Resolve<N: Neighborhood> {
+    parts: std::vec::IntoIter<PathPart>,
+    /// the Path so far, until it is returned
+    path: Option<AbstractPath<N>>,
+    /// the search for the current part and the Chunk it is limited to
+    search: Option<(grid::AStarSearch, Option<usize>)>,
+}
+
+impl<N: Neighborhood + Sync> Resolve<N> {
+    /// Adds parts to the Path until either all of them are added or `budget` ran out.
+    ///
+    /// Every part counts as one visited Node, and the searches on the Grid also count every
+    /// Tile they visit.
+    fn step(
+        &mut self,
+        cache: &PathCache<N>,
+        neighborhood: &MoveCosts<N>,
+        mut get_cost: impl FnMut(Point) -> isize,
+        budget: &mut usize,
+    ) -> Poll<AbstractPath<N>> {
+        let path = self
+            .path
+            .as_mut()
+            .expect("Resolve::step called after it was done");
+        loop {
+            if let Some((search, chunk)) = &mut self.search {
+                let valid = |p| chunk.is_none_or(|index| cache.chunks[index].in_chunk(p));
+                match search.step(neighborhood, valid, &mut get_cost, budget) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(part) => {
+                        path.add_path(part.expect("Inconsistency in Pathfinding"));
+                        self.search = None;
+                    }
+                }
+            }
+            if self.parts.as_slice().is_empty() {
+                return Poll::Ready(self.path.take().unwrap());
+            }
+            if *budget == 0 {
+                return Poll::Pending;
+            }
+            *budget -= 1;
+            match self.parts.next().unwrap() {
+                PathPart::Known(segment) => {
+                    path.add_path_segment(segment);
+                }
+                PathPart::Search { start, goal, chunk } => {
+                    let search = grid::AStarSearch::new(
+                        start,
+                        goal,
+                        cache.grid_size_hint(start, goal),
+                        cache.config.search_options(),
+                    );
+                    self.search = Some((search, chunk));
+                }
+            }
+        }
+    }
+}
+
+enum State<'a, N: Neighborhood> {
+    /// searching the Node Graph
+    Graph {
+        graph: SearchGraph<'a>,
+        search: GraphSearch,
+        start_path: Option<Path<Point>>,
+        goal_path: Option<Path<Point>>,
+    },
+    /// putting the final Path together from the Path on the Node Graph
+    Resolve(Box<Resolve<N>>),
+    /// the A* fallback on the Grid
+    Grid(grid::AStarSearch),
+    Done(Option<AbstractPath<N>>),
+    /// the result was already returned
+    Finished,
+}
+
+/// A Path search that can be spread over several calls.
+///
+/// Created by [`begin_find_path`](PathCache::begin_find_path). Call [`step`](PathQuery::step)
+/// until it returns the result.
+pub struct PathQuery<'a, N: Neighborhood, G: CostGrid> {
+    cache: &'a PathCache<N>,
+    start: Point,
+    goal: Point,
+    agent: Agent,
+    grid: &'a G,
+    /// the Neighborhood with the move costs of the search, if any
+    neighborhood: MoveCosts<'a, N>,
+    /// the Obstacles that apply to the search
+    obstacles: Cow<'a, Obstacles>,
+    /// the edges that were checked against the Obstacles
+    revalidated: Revalidated,
+    state: State<'a, N>,
+}
+
+impl<'a, N, G> PathQuery<'a, N, G>
+where
+    N: Neighborhood + Sync,
+    G: CostGrid,
+{
+    /// Creates a PathQuery that is already done
+    pub(super) fn done(
+        cache: &'a PathCache<N>,
+        (start, goal): (Point, Point),
+        grid: &'a G,
+        result: Option<AbstractPath<N>>,
+    ) -> Self {
+        PathQuery {
+            cache,
+            start,
+            goal,
+            agent: Agent::DEFAULT,
+            grid,
+            neighborhood: MoveCosts::new(cache.neighborhood.clone(), grid),
+            obstacles: Cow::Owned(Obstacles::new()),
+            revalidated: Revalidated::default(),
+            state: State::Done(result),
+        }
+    }
+
+    /// Creates a PathQuery that searches the Grid directly
+    pub(super) fn grid(
+        cache: &'a PathCache<N>,
+        (start, goal): (Point, Point),
+        grid: &'a G,
+        neighborhood: MoveCosts<'a, N>,
+        obstacles: Cow<'a, Obstacles>,
+    ) -> Self {
+        PathQuery {
+            cache,
+            start,
+            goal,
+            agent: Agent::DEFAULT,
+            grid,
+            neighborhood,
+            obstacles,
+            revalidated: Revalidated::default(),
+            state: State::Grid(grid::AStarSearch::new(
+                start,
+                goal,
+                cache.grid_size_hint(start, goal),
+                cache.config.search_options(),
+            )),
+        }
+    }
+
+    /// Creates a PathQuery that searches the Node Graph from `start_id` to `goal_id`
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn search(
+        cache: &'a PathCache<N>,
+        (start, goal): (Point, Point),
+        agent: Agent,
+        grid: &'a G,
+        neighborhood: MoveCosts<'a, N>,
+        obstacles: Cow<'a, Obstacles>,
+        (start_id, start_path): (NodeID, Option<Path<Point>>),
+        (goal_id, goal_path): (NodeID, Option<Path<Point>>),
+        size_hint: usize,
+    ) -> Self {
+        let graph = if agent.is_default() && obstacles.is_empty() {
+            SearchGraph::Query(cache.query_graph(&[start, goal]))
+        } else {
+            // the higher levels only contain the regular Paths, and the Paths between their
+            // Nodes would have to be checked against the Obstacles all at once
+            SearchGraph::Agent(AgentGraph::new(&cache.nodes, &cache.annotations, agent))
+        };
+        let search = if cache.config.bidirectional && obstacles.is_empty() {
+            // links can be cheaper than any heuristic
+            let use_heuristic = cache.links.is_empty();
+            GraphSearch::Bidirectional(Box::new(graph::BidirectionalSearch::new(
+                start_id,
+                goal_id,
+                use_heuristic,
+                size_hint,
+            )))
+        } else {
+            GraphSearch::Forward(graph::AStarSearch::new(start_id, goal_id, size_hint))
+        };
+        let revalidated = Revalidated::new(cache, &obstacles);
+        PathQuery {
+            cache,
+            start,
+            goal,
+            agent,
+            grid,
+            neighborhood,
+            obstacles,
+            revalidated,
+            state: State::Graph {
+                graph,
+                search,
+                start_path,
+                goal_path,
+            },
+        }
+    }
+
+    /// Advances the search by visiting at most `budget` Nodes or Tiles.
+    ///
+    /// Returns `Poll::Pending` if the search is not done yet, and the result of the search
+    /// otherwise. The result is the same as that of [`find_path`](PathCache::find_path).
+    ///
+    /// The searches on the Node Graph and the A* fallback count every Node or Tile they visit.
+    /// Putting the final Path together counts every part of it as one Node, and the Tiles that
+    /// are visited to resolve the parts that aren't known yet. Only connecting `start` and
+    /// `goal` to the Graph happens all at once, in [`begin_find_path`](PathCache::begin_find_path),
+    /// and it only involves searches within their Chunks.
+    ///
+    /// ## Panics
+    /// Panics if called again after returning the result.
+    pub fn step(&mut self, budget: usize) -> Poll<Option<AbstractPath<N>>> {
+        let mut budget = budget;
+        let obstacles = &*self.obstacles;
+        let mut get_cost = obstacles.cost_fn(self.cache.agent_cost_fn(self.agent, self.grid));
+        loop {
+            let next = match &mut self.state {
+                State::Graph {
+                    graph,
+                    search,
+                    start_path,
+                    goal_path,
+                } => {
+                    let neighborhood = &self.cache.neighborhood;
+                    let result = match (&*graph, search) {
+                        (SearchGraph::Agent(graph), GraphSearch::Forward(search))
+                            if !obstacles.is_empty() =>
+                        {
+                            let graph = ObstacleGraph::new(
+                                self.cache,
+                                graph,
+                                obstacles,
+                                &self.neighborhood,
+                                &mut get_cost,
+                                &mut self.revalidated,
+                            );
+                            search.step(&graph, neighborhood, &mut budget)
+                        }
+                        (graph, GraphSearch::Forward(search)) => {
+                            search.step(graph, neighborhood, &mut budget)
+                        }
+                        (graph, GraphSearch::Bidirectional(search)) => {
+                            search.step(graph, neighborhood, &mut budget)
+                        }
+                    };
+                    let path = match result {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(path) => path,
+                    };
+                    let grid_search = || {
+                        State::Grid(grid::AStarSearch::new(
+                            self.start,
+                            self.goal,
+                            self.cache.grid_size_hint(self.start, self.goal),
+                            self.cache.config.search_options(),
+                        ))
+                    };
+                    match path {
+                        // with one-way edges, the Nodes next to start and goal don't have to be
+                        // connected even though start and goal are. Obstacles can also block a
+                        // Node while the Tiles next to it are still free
+                        None if self.cache.nodes.is_directed() || !obstacles.is_empty() => {
+                            grid_search()
+                        }
+                        None => State::Done(None),
+                        Some(path) => {
+                            let path = match graph {
+                                SearchGraph::Query(graph) => graph.expand(path),
+                                SearchGraph::Agent(_) => path,
+                            };
+                            let fallback = self.cache.config.a_star_fallback;
+                            let short = path.len() == 2 || (fallback && path.len() <= 4);
+                            // the search on the Grid can't jump through links
+                            if short && !self.cache.uses_link(&path) {
+                                // 2: start_id == goal_id
+                                // <= 4: start_id X X goal_id
+                                grid_search()
+                            } else {
+                                let mut path_so_far =
+                                    AbstractPath::new(self.cache.neighborhood.clone(), self.start);
+                                path_so_far.set_search_options(self.cache.config.search_options());
+                                let parts = self.cache.path_parts(
+                                    (self.start, start_path.as_ref()),
+                                    (self.goal, goal_path.as_ref()),
+                                    &path,
+                                    &self.revalidated,
+                                    self.agent,
+                                );
+                                State::Resolve(Box::new(Resolve {
+                                    parts: parts.into_iter(),
+                                    path: Some(path_so_far),
+                                    search: None,
+                                }))
+                            }
+                        }
+                    }
+                }
+                State::Resolve(resolve) => {
+                    match resolve.step(self.cache, &self.neighborhood, &mut get_cost, &mut budget) {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(path) => State::Done(Some(path)),
+                    }
+                }
+                State::Grid(search) => {
+                    let result =
+                        search.step(&self.neighborhood, |_| true, &mut get_cost, &mut budget);
+                    match result {
+                        Poll::Pending => return Poll::Pending,
+                        Poll::Ready(path) => State::Done(path.map(|path| {
+                            AbstractPath::from_known_path(self.cache.neighborhood.clone(), path)
+                        })),
+                    }
+                }
+                State::Done(_) | State::Finished => {
+                    return match std::mem::replace(&mut self.state, State::Finished) {
+                        State::Done(result) => Poll::Ready(result.map(|mut path| {
+                            self.cache.track_chunks(&mut path);
+                            path.set_grid(self.cache.generation, self.cache.moves);
+                            path
+                        })),
+                        _ => panic!("PathQuery::step called after the Path was returned"),
+                    };
+                }
+            };
+            self.state = next;
+        }
+    }
+}
+
+impl<N: Neighborhood, G: CostGrid> std::fmt::Debug for PathQuery<'_, N, G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self.state {
+            State::Graph { .. } => "Graph",
+            State::Resolve(_) => "Resolve",
+            State::Grid(_) => "Grid",
+            State::Done(_) => "Done",
+            State::Finished => "Finished",
+        };
+        f.debug_struct("PathQuery")
+            .field("start", &self.start)
+            .field("goal", &self.goal)
+            .field("state", &state)
+            .finish()
+    }
+}
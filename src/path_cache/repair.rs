@@ -0,0 +1,249 @@
+use crate::{
+    neighbors::Neighborhood,
+    path::{AbstractPath, PathSegment},
+    *,
+};
+
+use super::PathCache;
+
+impl<N: Neighborhood + Sync> PathCache<N> {
+    /// Repairs a Path that crosses Tiles which changed since it was found, see
+    /// [`AbstractPath::is_valid`].
+    ///
+    /// Instead of searching the whole Path again, only the part from the first to the last
+    /// segment that crosses a changed Chunk is replaced with a new Path through the abstract
+    /// graph, while the segments before and after it are kept. The steps already taken stay part
+    /// of the Path, so that [`cost`](AbstractPath::cost) and [`length`](AbstractPath::length)
+    /// still cover the whole way from the original start.
+    ///
+    /// `current_pos` is the Tile that the unit following the Path stands on, which is usually
+    /// the last Tile returned by the Path. If the unit left the Path, the rest is replaced with a
+    /// new Path from `current_pos`, which also starts the count of steps anew.
+    ///
+    /// `grid` has to be the current Grid, and the repaired Path belongs to its
+    /// [`generation`](CostGrid::generation) afterwards.
+    ///
+    /// Returns whether the [`cost`](AbstractPath::cost) of the Path changed, or `None` if there
+    /// is no longer a Path to the goal, in which case `path` is not changed.
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] if the PathCache wasn't updated with `grid`, see
+    /// [`find_path`](PathCache::find_path).
+    ///
+    /// Note that the kept segments are not searched again, so the repaired Path can be more
+    /// expensive than a new one if the changes opened up a shorter way.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// // two ways around a wall, the lower one is more expensive
+    /// let mut grid = [
+    ///     [0, 0, 0, 0, 0, 0, 0],
+    ///     [0, 2, 2, 2, 2, 2, 0],
+    ///     [0, 1, 1, 1, 1, 1, 0],
+    /// ];
+    /// let (width, height) = (grid[0].len(), grid.len());
+    /// fn cost_fn(grid: &[[usize; 7]; 3]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    ///     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// }
+    /// let mut pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &cost_fn(&grid),
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    ///
+    /// let mut path = pathfinding
+    ///     .find_path((0, 1), (6, 1), &cost_fn(&grid)).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.next(), Some((0, 0)));
+    ///
+    /// // a door closes on the upper way
+    /// grid[0][3] = 2;
+    /// pathfinding.tiles_changed(&[(3, 0)], &cost_fn(&grid));
+    /// assert!(!path.is_valid(&pathfinding));
+    ///
+    /// let changed = pathfinding.repair_path(&mut path, (0, 0), &cost_fn(&grid)).unwrap();
+    /// assert_eq!(changed, Some(true));
+    /// assert!(path.is_valid(&pathfinding));
+    /// assert!(path.collect::<Vec<_>>().contains(&(3, 2)));
+    /// ```
+    pub fn repair_path<G: CostGrid>(
+        &self,
+        path: &mut AbstractPath<N>,
+        current_pos: Point,
+        grid: &G,
+    ) -> Result<Option<bool>, GridMismatch> {
+        self.check_grid(grid)?;
+        let old_cost = path.cost();
+        if current_pos != path.current_pos() {
+            *path = match self.find_path(current_pos, path.end(), grid)? {
+                Some(path) => path,
+                None => return Ok(None),
+            };
+            return Ok(Some(path.cost() != old_cost));
+        }
+
+        let (index, offset) = path.current_index();
+        let segments = path.segments();
+        let changed = |i: usize| {
+            // the steps already taken in the current segment don't matter
+            let skip = if i == index { offset - 1 } else { 0 };
+            self.segment_changed(path, &segments[i], skip)
+        };
+        let first = match (index..segments.len()).find(|&i| changed(i)) {
+            Some(first) => first,
+            None => return Ok(Some(false)),
+        };
+        let last = match (first..segments.len()).rev().find(|&i| changed(i)) {
+            Some(last) => last,
+            None => return Ok(None),
+        };
+
+        let start = if first == index {
+            current_pos
+        } else {
+            segments[first].start()
+        };
+        let replacement = match self.find_path(start, segments[last].end(), grid)? {
+            Some(replacement) => replacement,
+            None => return Ok(None),
+        };
+        // the repaired Path now belongs to the current Grid
+        path.replace_segments(first, last, replacement, grid)
+            .set_grid(self.generation, self.moves);
+        self.track_chunks(path);
+        Ok(Some(path.cost() != old_cost))
+    }
+
+    /// If any Chunk that `segment` crosses after the first `skip` Tiles changed since `path` was
+    /// found
+    fn segment_changed(&self, path: &AbstractPath<N>, segment: &PathSegment, skip: usize) -> bool {
+        let changed = |pos: Point| {
+            let index = self.get_chunk_index(pos);
+            path.chunk_generation(index) != self.chunk_generation(index)
+        };
+        match segment {
+            PathSegment::Known(known) => (skip..known.len()).any(|i| changed(known[i])),
+            PathSegment::Unknown { start, end, .. } => changed(*start) || changed(*end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{path::Cost, prelude::*, Point};
+    use nanorand::{Rng, WyRand};
+
+    fn check<N: Neighborhood + Sync>(neighborhood: N, cache_paths: bool, seed: u64) {
+        let size = 24;
+        let mut rng = WyRand::new_seed(seed);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| [-1, 1, 1, 1, 2][rng.generate_range(0..5_usize)]);
+        }
+        let mut pathfinding = PathCache::new(
+            (size, size),
+            &|(x, y): (usize, usize)| grid[y][x],
+            neighborhood.clone(),
+            PathCacheConfig {
+                cache_paths,
+                ..PathCacheConfig::with_chunk_size(4)
+            },
+        );
+
+        for _ in 0..50 {
+            let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+            if grid[goal.1][goal.0] < 0 {
+                continue;
+            }
+            let mut path = match pathfinding
+                .find_path(start, goal, &|(x, y): (usize, usize)| grid[y][x])
+                .unwrap()
+            {
+                Some(path) if path.length() > 2 => path,
+                _ => continue,
+            };
+
+            // walk a bit and then block a Tile further ahead
+            let mut walked = vec![start];
+            for _ in 0..rng.generate_range(0..path.length() - 2) {
+                walked.push(
+                    path.safe_next(&|(x, y): (usize, usize)| grid[y][x])
+                        .unwrap()
+                        .unwrap(),
+                );
+            }
+            let current = *walked.last().unwrap();
+            let ahead = path
+                .clone()
+                .resolve(&|(x, y): (usize, usize)| grid[y][x])
+                .unwrap();
+            let blocked = ahead[rng.generate_range(0..ahead.len() - 1)];
+            let old_cost = grid[blocked.1][blocked.0];
+            grid[blocked.1][blocked.0] = -1;
+            pathfinding.tiles_changed(&[blocked], &|(x, y): (usize, usize)| grid[y][x]);
+            assert!(!path.is_valid(&pathfinding));
+
+            let cost_fn = |(x, y): Point| grid[y][x];
+            let before_cost = path.cost();
+            let changed = pathfinding
+                .repair_path(&mut path, current, &cost_fn)
+                .unwrap();
+            let changed = match changed {
+                Some(changed) => changed,
+                None => {
+                    assert!(pathfinding
+                        .find_path(current, goal, &cost_fn)
+                        .unwrap()
+                        .is_none());
+                    grid[blocked.1][blocked.0] = old_cost;
+                    pathfinding.tiles_changed(&[blocked], &|(x, y): (usize, usize)| grid[y][x]);
+                    continue;
+                }
+            };
+            assert!(path.is_valid(&pathfinding));
+            let before = walked.len() - 1;
+            assert_eq!(path.len() + before, path.length());
+
+            // the rest of the Path is walkable and the Path costs as much as its steps
+            let tiles = walked
+                .iter()
+                .copied()
+                .chain(path.clone().resolve(&cost_fn).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(tiles.last(), Some(&goal));
+            assert!(!tiles[before..].contains(&blocked));
+            let mut total: Cost = 0;
+            let mut neighbors = vec![];
+            for step in tiles.windows(2) {
+                neighbors.clear();
+                neighborhood.get_all_neighbors(step[0], &mut neighbors);
+                assert!(neighbors.contains(&step[1]));
+                let (from_cost, to_cost) = (cost_fn(step[0]), cost_fn(step[1]));
+                assert!(to_cost >= 0 || step[1] == blocked);
+                let (from_cost, to_cost) = (from_cost.max(0) as Cost, to_cost.max(0) as Cost);
+                total += neighborhood.step_cost(step[0], step[1], from_cost, to_cost);
+            }
+            if !walked.contains(&blocked) {
+                assert_eq!(path.cost(), total);
+            }
+            assert_eq!(changed, path.cost() != before_cost);
+
+            grid[blocked.1][blocked.0] = old_cost;
+            pathfinding.tiles_changed(&[blocked], &|(x, y): (usize, usize)| grid[y][x]);
+        }
+    }
+
+    #[test]
+    fn random() {
+        for seed in 0..4 {
+            for cache_paths in [true, false] {
+                check(ManhattanNeighborhood::new(24, 24), cache_paths, seed);
+                check(MooreNeighborhood::new(24, 24), cache_paths, seed);
+            }
+        }
+    }
+}
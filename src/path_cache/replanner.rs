@@ -0,0 +1,654 @@
+use crate::{
+    graph::Graph,
+    neighbors::Neighborhood,
+    path::{AbstractPath, Cost, Path},
+    *,
+};
+
+use super::{
+    annotations::{Agent, AgentGraph},
+    moves::MoveCosts,
+    obstacles::Revalidated,
+    PathCache,
+};
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+const INFINITY: Cost = Cost::MAX;
+
+/// The priority of a Node in the open list, compared lexicographically
+type Key = (Cost, Cost);
+
+/// The search state of a Node
+#[derive(Clone, Debug)]
+struct NodeState {
+    /// the position of the Node, to notice if its NodeID was reused
+    pos: Point,
+    /// the cost to the goal Node, as far as the search knows
+    g: Cost,
+    /// the cost to the goal Node through the best edge, based on the `g` of its target
+    rhs: Cost,
+    /// the Key that the Node is in the open list with, if it is inconsistent
+    queued: Option<Key>,
+}
+
+/// A Path search from a moving start to a fixed goal, that keeps its state between searches.
+///
+/// This is meant for agents that discover the costs of the Grid as they move: Instead of
+/// searching the whole Path again every time that the PathCache was updated with
+/// [`tiles_changed`](PathCache::tiles_changed) or that the agent took a step, the Replanner
+/// only searches the parts of the Node Graph around the changed Chunks again. The Paths from
+/// start and goal to the Graph are kept until they move or their Chunks change.
+///
+/// This is an implementation of [D\* Lite](https://en.wikipedia.org/wiki/D*#D*_Lite), which
+/// searches backwards from the goal, on the lowest level of the PathCache.
+///
+/// The Replanner doesn't borrow the PathCache, so that the PathCache can be updated in between.
+/// Every call has to use the same PathCache though, otherwise the Replanner starts over. It
+/// notices changes on its own, so there is no need to tell it about them.
+///
+/// Registered [`Obstacles`](super::Obstacles) are ignored.
+///
+/// ## Examples
+/// Basic usage:
+/// ```
+/// # use hierarchical_pathfinding::prelude::*;
+/// let mut grid = [[1; 16]; 16];
+/// let (width, height) = (grid[0].len(), grid.len());
+/// let mut pathfinding = PathCache::new(
+///     (width, height),
+///     &|(x, y): (usize, usize)| grid[y][x],
+///     ManhattanNeighborhood::new(width, height),
+///     PathCacheConfig::with_chunk_size(4),
+/// );
+///
+/// let mut replanner = Replanner::new((0, 0), (15, 0));
+/// let mut path = replanner
+///     .find_path(&pathfinding, &|(x, y): (usize, usize)| grid[y][x]).unwrap()
+///     .unwrap();
+/// assert_eq!(path.cost(), 15);
+///
+/// // the agent takes a step and discovers a wall ahead
+/// let pos = path.next().unwrap();
+/// replanner.move_to(pos);
+/// let wall = (0..15).map(|y| (8, y)).collect::<Vec<_>>();
+/// for &(x, y) in wall.iter() {
+///     grid[y][x] = -1;
+/// }
+/// pathfinding.tiles_changed(&wall, &|(x, y): (usize, usize)| grid[y][x]);
+///
+/// let path = replanner
+///     .find_path(&pathfinding, &|(x, y): (usize, usize)| grid[y][x]).unwrap()
+///     .unwrap();
+/// assert_eq!(path.cost(), 14 + 2 * 15);
+/// assert!(path.clone().any(|pos| pos == (8, 15)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Replanner {
+    start: Point,
+    goal: Point,
+    /// the Node where the search from start enters the Graph, and the Path to it
+    start_node: Option<(NodeID, Option<Path<Point>>)>,
+    /// if `start_node` has to be found again
+    start_moved: bool,
+    /// the Node where the search leaves the Graph to goal, and the Path from it
+    goal_node: Option<(NodeID, Option<Path<Point>>)>,
+    /// the position of the start Node when `km` was last updated
+    last_start: Point,
+    /// the sum of the heuristics between all start Nodes, see D* Lite
+    km: Cost,
+    states: NodeIDMap<NodeState>,
+    open: BinaryHeap<Reverse<(Key, NodeID)>>,
+    /// the generations of the Chunks that the states are based on
+    generations: Vec<u64>,
+    /// the Nodes expanded by the last search
+    expanded: usize,
+}
+
+impl Replanner {
+    /// Creates a Replanner for an agent at `start` that wants to reach `goal`.
+    ///
+    /// No searching happens until the first call to [`find_path`](Replanner::find_path).
+    pub fn new(start: Point, goal: Point) -> Replanner {
+        Replanner {
+            start,
+            goal,
+            start_node: None,
+            start_moved: true,
+            goal_node: None,
+            last_start: start,
+            km: 0,
+            states: NodeIDMap::default(),
+            open: BinaryHeap::new(),
+            generations: vec![],
+            expanded: 0,
+        }
+    }
+
+    /// The current position of the agent
+    pub fn start(&self) -> Point {
+        self.start
+    }
+
+    /// The position that the agent wants to reach
+    pub fn goal(&self) -> Point {
+        self.goal
+    }
+
+    /// Moves the agent to `pos`, which is usually the next step of the last Path.
+    ///
+    /// This is cheap, the work happens in the next call to [`find_path`](Replanner::find_path).
+    pub fn move_to(&mut self, pos: Point) {
+        if pos != self.start {
+            self.start = pos;
+            self.start_moved = true;
+        }
+    }
+
+    /// The number of Nodes that the last call to [`find_path`](Replanner::find_path) expanded.
+    ///
+    /// This is `0` if nothing changed since the call before, and usually less than for the
+    /// first call if only a few Tiles changed.
+    pub fn expanded_nodes(&self) -> usize {
+        self.expanded
+    }
+
+    /// Calculates the Path from the current [`start`](Replanner::start) to the
+    /// [`goal`](Replanner::goal), reusing the work of the previous calls.
+    ///
+    /// `cache` has to be the same PathCache in every call, and `grid` its current Grid. Changes
+    /// to the PathCache have to be made with [`tiles_changed`](PathCache::tiles_changed) or its
+    /// variants before this is called.
+    ///
+    /// Returns `None` if there is no Path to the goal. The returned Path is the same as
+    /// [`find_path`](PathCache::find_path) would return, apart from Paths of equal cost, and
+    /// from the higher levels of the PathCache, which are not used here.
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] if `cache` wasn't updated with `grid`, see
+    /// [`PathCache::find_path`].
+    pub fn find_path<N: Neighborhood + Sync, G: CostGrid>(
+        &mut self,
+        cache: &PathCache<N>,
+        grid: &G,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        cache.check_grid(grid)?;
+        self.expanded = 0;
+        let (start, goal) = (self.start, self.goal);
+        let mut get_cost = cost_grid::cost_fn(grid);
+        if start == goal || get_cost(start) < 0 {
+            return cache.find_path(start, goal, grid);
+        }
+        let moves = MoveCosts::new(cache.neighborhood.clone(), grid);
+        self.update(cache, &mut get_cost, &moves);
+
+        let (start_id, start_path) = match &self.start_node {
+            Some((id, path)) => (*id, path.clone()),
+            // start is in a cave within its Chunk
+            None => return cache.find_path(start, goal, grid),
+        };
+        let (goal_id, goal_path) = match &self.goal_node {
+            Some((id, path)) => (*id, path.clone()),
+            // goal can't be reached from start, or only from within the Chunk
+            None => return cache.find_path(start, goal, grid),
+        };
+
+        let graph = AgentGraph::new(&cache.nodes, &cache.annotations, Agent::DEFAULT);
+        self.compute(cache, &graph, start_id);
+
+        let path = match self.node_path(cache, &graph, start_id, goal_id) {
+            Some(path) => path,
+            // the Nodes next to start and goal don't have to be connected with one-way moves
+            None if cache.nodes.is_directed() => return cache.find_path(start, goal, grid),
+            None => return Ok(None),
+        };
+        // see PathQuery::step
+        let short = path.len() == 2 || (cache.config.a_star_fallback && path.len() <= 4);
+        if short && !cache.uses_link(&path) {
+            let path = match cache.grid_a_star_with(&moves, start, goal, get_cost) {
+                Some(path) => path,
+                None => return Ok(None),
+            };
+            let mut path = AbstractPath::from_known_path(cache.neighborhood.clone(), path);
+            path.set_grid(cache.generation, cache.moves);
+            cache.track_chunks(&mut path);
+            return Ok(Some(path));
+        }
+
+        let mut paths = NodeIDMap::default();
+        paths.insert(goal_id, path);
+        let path = cache
+            .resolve_paths(
+                start,
+                start_path,
+                &[(goal, goal_id, goal_path)],
+                &paths,
+                &Revalidated::default(),
+                Agent::DEFAULT,
+                get_cost,
+                &moves,
+            )
+            .remove(&goal);
+        Ok(path.map(|mut path| {
+            path.set_grid(cache.generation, cache.moves);
+            cache.track_chunks(&mut path);
+            path
+        }))
+    }
+
+    /// Catches up with the changes to the PathCache and the moves of the agent
+    fn update<N: Neighborhood + Sync>(
+        &mut self,
+        cache: &PathCache<N>,
+        mut get_cost: impl FnMut(Point) -> isize,
+        moves: &MoveCosts<N>,
+    ) {
+        if self.generations.len() != cache.chunk_generations.len() {
+            // the first search, or a different PathCache
+            self.generations = cache.chunk_generations.clone();
+            self.start_node = cache.find_nearest_node(self.start, &mut get_cost, None, moves);
+            self.start_moved = false;
+            self.goal_node = self.find_goal_node(cache, &mut get_cost, moves);
+            self.reset(cache);
+            return;
+        }
+
+        let changed = (0..self.generations.len())
+            .filter(|&i| self.generations[i] != cache.chunk_generations[i])
+            .to_vec();
+        self.generations.copy_from_slice(&cache.chunk_generations);
+        let is_changed = |pos: Point| changed.binary_search(&cache.get_chunk_index(pos)).is_ok();
+
+        if self.start_moved || is_changed(self.start) {
+            self.start_node = cache.find_nearest_node(self.start, &mut get_cost, None, moves);
+            self.start_moved = false;
+        }
+        if !changed.is_empty() {
+            let goal_node = self.find_goal_node(cache, &mut get_cost, moves);
+            let same_node = match (&self.goal_node, &goal_node) {
+                (Some((old, _)), Some((new, _))) => {
+                    let pos = cache.nodes[*new].pos;
+                    old == new && self.states.get(new).is_some_and(|state| state.pos == pos)
+                }
+                (None, None) => true,
+                _ => false,
+            };
+            self.goal_node = goal_node;
+            if !same_node {
+                // the search starts somewhere else
+                self.reset(cache);
+                return;
+            }
+        }
+        if let Some((id, _)) = self.start_node {
+            let pos = cache.nodes[id].pos;
+            self.km = self.km.saturating_add(self.heuristic(cache, pos));
+            self.last_start = pos;
+        }
+        if changed.is_empty() {
+            return;
+        }
+
+        // forget the Nodes in the changed Chunks, apart from the goal Node that the search
+        // starts from
+        let goal_id = self.goal_node.as_ref().map(|(id, _)| *id);
+        let exists = |id: NodeID, pos: Point| matches!(cache.nodes.slots().get(id as usize), Some(Some(node)) if node.pos == pos);
+        self.states.retain(|&id, state| {
+            exists(id, state.pos) && (!is_changed(state.pos) || Some(id) == goal_id)
+        });
+        let mut affected = NodeIDSet::default();
+        for &index in changed.iter() {
+            for &id in cache.chunks[index].nodes.iter() {
+                affected.insert(id);
+                cache.nodes.for_each_incoming(id, |other_id| {
+                    affected.insert(other_id);
+                });
+            }
+        }
+        // and the Nodes whose best edge led to a forgotten Node
+        let graph = AgentGraph::new(&cache.nodes, &cache.annotations, Agent::DEFAULT);
+        for (&id, state) in self.states.iter() {
+            if state.rhs != INFINITY
+                && Some(id) != goal_id
+                && !affected.contains(&id)
+                && !self.has_best_edge(&graph, id, state.rhs)
+            {
+                affected.insert(id);
+            }
+        }
+        for id in affected {
+            self.update_node(cache, &graph, id);
+        }
+    }
+
+    /// Finds the Node next to goal that the search starts from, among the ones reachable
+    /// from start
+    fn find_goal_node<N: Neighborhood + Sync>(
+        &self,
+        cache: &PathCache<N>,
+        get_cost: impl FnMut(Point) -> isize,
+        moves: &MoveCosts<N>,
+    ) -> Option<(NodeID, Option<Path<Point>>)> {
+        let component = cache.component_at(self.start)?;
+        if !cache.can_reach(component, self.goal) {
+            return None;
+        }
+        cache.find_nearest_node(self.goal, get_cost, Some(component), moves)
+    }
+
+    /// Forgets the previous searches
+    fn reset<N: Neighborhood + Sync>(&mut self, cache: &PathCache<N>) {
+        self.states.clear();
+        self.open.clear();
+        self.km = 0;
+        if let Some((id, _)) = self.start_node {
+            self.last_start = cache.nodes[id].pos;
+        }
+        if let Some((goal_id, _)) = self.goal_node {
+            let state = self.state(cache, goal_id);
+            state.rhs = 0;
+            let key = (self.heuristic(cache, cache.nodes[goal_id].pos), 0);
+            self.push(goal_id, key);
+        }
+    }
+
+    /// The heuristic from the start Node to `pos`
+    fn heuristic<N: Neighborhood + Sync>(&self, cache: &PathCache<N>, pos: Point) -> Cost {
+        if cache.links.is_empty() {
+            cache.neighborhood.heuristic(self.last_start, pos)
+        } else {
+            // links can be cheaper than any heuristic
+            0
+        }
+    }
+
+    fn g(&self, id: NodeID) -> Cost {
+        self.states.get(&id).map_or(INFINITY, |state| state.g)
+    }
+
+    fn rhs(&self, id: NodeID) -> Cost {
+        self.states.get(&id).map_or(INFINITY, |state| state.rhs)
+    }
+
+    fn key<N: Neighborhood + Sync>(&self, cache: &PathCache<N>, id: NodeID) -> Key {
+        let min = self.g(id).min(self.rhs(id));
+        let heuristic = self.heuristic(cache, cache.nodes[id].pos);
+        (min.saturating_add(heuristic).saturating_add(self.km), min)
+    }
+
+    fn state<N: Neighborhood + Sync>(
+        &mut self,
+        cache: &PathCache<N>,
+        id: NodeID,
+    ) -> &mut NodeState {
+        self.states.entry(id).or_insert_with(|| NodeState {
+            pos: cache.nodes[id].pos,
+            g: INFINITY,
+            rhs: INFINITY,
+            queued: None,
+        })
+    }
+
+    fn push(&mut self, id: NodeID, key: Key) {
+        if let Some(state) = self.states.get_mut(&id) {
+            state.queued = Some(key);
+        }
+        self.open.push(Reverse((key, id)));
+    }
+
+    /// The smallest valid entry of the open list
+    fn top(&mut self) -> Option<(Key, NodeID)> {
+        while let Some(&Reverse((key, id))) = self.open.peek() {
+            match self.states.get(&id) {
+                Some(state) if state.queued == Some(key) => return Some((key, id)),
+                _ => {
+                    self.open.pop();
+                }
+            }
+        }
+        None
+    }
+
+    /// If `id` has an edge that leads to the goal for `rhs`
+    fn has_best_edge(&self, graph: &AgentGraph, id: NodeID, rhs: Cost) -> bool {
+        let mut found = false;
+        graph.for_each_edge(id, |other_id, cost| {
+            found |= cost.saturating_add(self.g(other_id)) == rhs;
+        });
+        found
+    }
+
+    /// Recalculates the `rhs` of a Node and puts it into the open list if it is inconsistent
+    fn update_node<N: Neighborhood + Sync>(
+        &mut self,
+        cache: &PathCache<N>,
+        graph: &AgentGraph,
+        id: NodeID,
+    ) {
+        if matches!(self.goal_node, Some((goal_id, _)) if goal_id == id) {
+            return;
+        }
+        let mut rhs = INFINITY;
+        graph.for_each_edge(id, |other_id, cost| {
+            rhs = rhs.min(cost.saturating_add(self.g(other_id)));
+        });
+        let state = self.state(cache, id);
+        state.rhs = rhs;
+        state.queued = None;
+        if state.g != rhs {
+            let key = self.key(cache, id);
+            self.push(id, key);
+        }
+    }
+
+    /// Searches until the cost from the Node `start_id` is known
+    fn compute<N: Neighborhood + Sync>(
+        &mut self,
+        cache: &PathCache<N>,
+        graph: &AgentGraph,
+        start_id: NodeID,
+    ) {
+        let mut incoming = vec![];
+        while let Some((key, id)) = self.top() {
+            if key >= self.key(cache, start_id) && self.rhs(start_id) == self.g(start_id) {
+                break;
+            }
+            self.expanded += 1;
+            let new_key = self.key(cache, id);
+            if key < new_key {
+                self.push(id, new_key);
+                continue;
+            }
+            let state = self.state(cache, id);
+            state.queued = None;
+            if state.g > state.rhs {
+                state.g = state.rhs;
+            } else {
+                state.g = INFINITY;
+                self.update_node(cache, graph, id);
+            }
+            incoming.clear();
+            cache
+                .nodes
+                .for_each_incoming(id, |other_id| incoming.push(other_id));
+            for &other_id in incoming.iter() {
+                self.update_node(cache, graph, other_id);
+            }
+        }
+    }
+
+    /// Follows the best edges from `start_id` to `goal_id`
+    fn node_path<N: Neighborhood + Sync>(
+        &self,
+        cache: &PathCache<N>,
+        graph: &AgentGraph,
+        start_id: NodeID,
+        goal_id: NodeID,
+    ) -> Option<Path<NodeID>> {
+        let cost = self.g(start_id);
+        if cost == INFINITY {
+            return None;
+        }
+        let mut steps = vec![start_id];
+        let mut current = start_id;
+        while current != goal_id {
+            let mut best = None;
+            graph.for_each_edge(current, |other_id, cost| {
+                let total = cost.saturating_add(self.g(other_id));
+                // among equally good Nodes, those closer to the goal make for a shorter Path
+                // from the last Node to the goal
+                let distance = cache.neighborhood.heuristic(graph.pos(other_id), self.goal);
+                if best.is_none_or(|(_, best)| (total, distance) < best) {
+                    best = Some((other_id, (total, distance)));
+                }
+            });
+            current = match best {
+                Some((next, (total, _)))
+                    if total != INFINITY && steps.len() <= self.states.len() =>
+                {
+                    next
+                }
+                _ => panic!("Internal Error #1 in Replanner. Please report this"),
+            };
+            steps.push(current);
+        }
+        if steps.len() == 1 {
+            // see graph::AStarSearch
+            steps.push(goal_id);
+        }
+        Some(Path::new(steps, cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph, prelude::*};
+    use nanorand::{Rng, WyRand};
+
+    /// The cost between the Nodes of start and goal, from a search without any state
+    fn node_cost<N: Neighborhood + Sync>(cache: &PathCache<N>, replanner: &Replanner) -> Cost {
+        let (start_id, goal_id) = match (&replanner.start_node, &replanner.goal_node) {
+            (Some((start_id, _)), Some((goal_id, _))) => (*start_id, *goal_id),
+            _ => return INFINITY,
+        };
+        let graph = AgentGraph::new(&cache.nodes, &cache.annotations, Agent::DEFAULT);
+        graph::dijkstra_search(&graph, start_id, &[goal_id], true, 0)
+            .get(&goal_id)
+            .map_or(INFINITY, |path| path.cost())
+    }
+
+    fn check<N: Neighborhood + Sync>(neighborhood: N, seed: u64) {
+        let size = 32;
+        let mut rng = WyRand::new_seed(seed);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| [-1, 1, 1, 1, 2][rng.generate_range(0..5_usize)]);
+        }
+        let mut pathfinding = PathCache::new(
+            (size, size),
+            &|(x, y): (usize, usize)| grid[y][x],
+            neighborhood.clone(),
+            PathCacheConfig::with_chunk_size(8),
+        );
+
+        for _ in 0..6 {
+            let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+            if grid[start.1][start.0] < 0 || grid[goal.1][goal.0] < 0 {
+                continue;
+            }
+            let mut replanner = Replanner::new(start, goal);
+            let mut pos = start;
+            for _ in 0..20 {
+                // the agent discovers a few Tiles
+                let mut changed = vec![];
+                for _ in 0..rng.generate_range(0..4_usize) {
+                    let tile = (rng.generate_range(0..size), rng.generate_range(0..size));
+                    if tile != pos && tile != goal {
+                        grid[tile.1][tile.0] = [-1, 1, 3][rng.generate_range(0..3_usize)];
+                        changed.push(tile);
+                    }
+                }
+                pathfinding.tiles_changed(&changed, &|(x, y): (usize, usize)| grid[y][x]);
+                replanner.move_to(pos);
+
+                let cost_fn = |(x, y): Point| grid[y][x];
+                let path = replanner.find_path(&pathfinding, &cost_fn).unwrap();
+                let expected = pathfinding.find_path(pos, goal, &cost_fn).unwrap();
+                assert_eq!(path.is_some(), expected.is_some());
+                let mut path = match path {
+                    Some(path) => path,
+                    None => break,
+                };
+                if let (Some((start_id, _)), Some(_)) =
+                    (&replanner.start_node, &replanner.goal_node)
+                {
+                    let start_id = *start_id;
+                    assert_eq!(replanner.g(start_id), node_cost(&pathfinding, &replanner));
+                }
+
+                // the Path is walkable and costs as much as its steps
+                let tiles = path.clone().resolve(&cost_fn).unwrap();
+                assert_eq!(tiles.last(), Some(&goal));
+                let (mut prev, mut total) = (pos, 0);
+                let mut neighbors = vec![];
+                for &next in tiles.iter() {
+                    neighbors.clear();
+                    neighborhood.get_all_neighbors(prev, &mut neighbors);
+                    assert!(neighbors.contains(&next));
+                    assert!(cost_fn(next) >= 0);
+                    let (from_cost, to_cost) = (cost_fn(prev) as Cost, cost_fn(next) as Cost);
+                    total += neighborhood.step_cost(prev, next, from_cost, to_cost);
+                    prev = next;
+                }
+                assert_eq!(path.cost(), total);
+
+                pos = match path.safe_next(&cost_fn).unwrap() {
+                    Some(next) if next != goal => next,
+                    _ => break,
+                };
+            }
+        }
+    }
+
+    #[test]
+    fn random() {
+        for seed in 0..4 {
+            check(ManhattanNeighborhood::new(32, 32), seed);
+            check(MooreNeighborhood::new(32, 32), seed);
+        }
+    }
+
+    #[test]
+    fn incremental() {
+        let mut grid = [[1; 64]; 64];
+        let mut pathfinding = PathCache::new(
+            (64, 64),
+            &|(x, y): (usize, usize)| grid[y][x],
+            ManhattanNeighborhood::new(64, 64),
+            PathCacheConfig::with_chunk_size(8),
+        );
+        let mut replanner = Replanner::new((0, 0), (63, 63));
+        let path = replanner
+            .find_path(&pathfinding, &|(x, y): (usize, usize)| grid[y][x])
+            .unwrap();
+        assert_eq!(path.unwrap().cost(), 126);
+        let first = replanner.expanded_nodes();
+
+        // nothing changed
+        replanner
+            .find_path(&pathfinding, &|(x, y): (usize, usize)| grid[y][x])
+            .unwrap();
+        assert_eq!(replanner.expanded_nodes(), 0);
+
+        // a change far away from the start only touches a few Nodes
+        grid[60][60] = 5;
+        pathfinding.tiles_changed(&[(60, 60)], &|(x, y): (usize, usize)| grid[y][x]);
+        let path = replanner
+            .find_path(&pathfinding, &|(x, y): (usize, usize)| grid[y][x])
+            .unwrap();
+        assert_eq!(path.unwrap().cost(), 126);
+        assert!(replanner.expanded_nodes() < first);
+    }
+}
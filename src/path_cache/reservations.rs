@@ -0,0 +1,506 @@
+use crate::{
+    grid::HeuristicElement,
+    neighbors::Neighborhood,
+    path::{AbstractPath, Cost, Path},
+    *,
+};
+
+use super::{moves::MoveCosts, PathCache};
+
+use std::collections::BinaryHeap;
+
+/// Tiles that agents reserved for certain time steps, so that several agents can move at the
+/// same time without running into each other.
+///
+/// This is used for cooperative pathfinding (also known as WHCA\*): every agent searches its
+/// Path with [`find_path_cooperative`](PathCache::find_path_cooperative), which plans around the
+/// Tiles that other agents reserved within the next [`window`](ReservationTable::window) time
+/// steps, and then [`reserve`](ReservationTable::reserve)s the beginning of its own Path. Every
+/// step of a Path takes one time step, and [`advance`](ReservationTable::advance) moves on to the
+/// next one once all agents made their move.
+///
+/// Beyond the window, Paths follow the Nodes of the PathCache and ignore the reservations, so
+/// agents should search again before they reach the end of the window.
+#[derive(Clone, Debug)]
+pub struct ReservationTable {
+    window: usize,
+    /// the current time step
+    time: usize,
+    /// the agent that reserved a Tile at a time step
+    tiles: hashbrown::HashMap<(Point, usize), usize>,
+    /// the reservations of every agent
+    agents: hashbrown::HashMap<usize, Vec<(Point, usize)>>,
+}
+
+impl ReservationTable {
+    /// Creates an empty ReservationTable for searches that plan `window` time steps ahead.
+    ///
+    /// Larger windows find better ways around other agents, but make every search slower.
+    pub fn new(window: usize) -> ReservationTable {
+        ReservationTable {
+            window,
+            time: 0,
+            tiles: Default::default(),
+            agents: Default::default(),
+        }
+    }
+
+    /// The number of time steps that searches plan ahead
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// The current time step
+    pub fn time(&self) -> usize {
+        self.time
+    }
+
+    /// Moves on to the next time step and forgets all reservations of the current one.
+    pub fn advance(&mut self) {
+        self.time += 1;
+        let time = self.time;
+        self.tiles.retain(|(_, t), _| *t >= time);
+        for reserved in self.agents.values_mut() {
+            reserved.retain(|(_, t)| *t >= time);
+        }
+        self.agents.retain(|_, reserved| !reserved.is_empty());
+    }
+
+    /// Reserves `tile` at the time step `time` for `agent`, e.g. for an agent that doesn't move.
+    ///
+    /// Returns `false` if another agent already reserved it, in which case that reservation is
+    /// kept.
+    pub fn reserve_tile(&mut self, agent: usize, tile: Point, time: usize) -> bool {
+        match self.tiles.entry((tile, time)) {
+            hashbrown::hash_map::Entry::Occupied(entry) => *entry.get() == agent,
+            hashbrown::hash_map::Entry::Vacant(entry) => {
+                entry.insert(agent);
+                self.agents.entry(agent).or_default().push((tile, time));
+                true
+            }
+        }
+    }
+
+    /// Reserves the Tiles that `path` visits within the window for `agent`, replacing all
+    /// previous reservations of `agent`.
+    ///
+    /// The position that `path` continues from is reserved for the current time step, and every
+    /// following step for one time step later. If `path` ends within the window, its end is
+    /// reserved for the rest of it, since the agent waits there. Unknown segments of `path` are
+    /// resolved on `grid`, like [`safe_next`](AbstractPath::safe_next) does.
+    ///
+    /// Returns `false` if another agent already reserved any of those Tiles, in which case those
+    /// reservations are kept.
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] under the same conditions as
+    /// [`safe_next`](AbstractPath::safe_next), without changing any reservations.
+    pub fn reserve<N: Neighborhood, G: CostGrid>(
+        &mut self,
+        agent: usize,
+        path: &AbstractPath<N>,
+        grid: &G,
+    ) -> Result<bool, GridMismatch> {
+        path.check_grid(grid)?;
+        self.release(agent);
+        let mut path = path.clone();
+        let mut pos = path.current_pos();
+        let mut free = self.reserve_tile(agent, pos, self.time);
+        for time in self.time + 1..=self.time + self.window {
+            if let Some(next) = path.next_on(grid) {
+                pos = next;
+            }
+            free &= self.reserve_tile(agent, pos, time);
+        }
+        Ok(free)
+    }
+
+    /// Removes all reservations of `agent`
+    pub fn release(&mut self, agent: usize) {
+        if let Some(reserved) = self.agents.remove(&agent) {
+            for key in reserved {
+                self.tiles.remove(&key);
+            }
+        }
+    }
+
+    /// Removes all reservations
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.agents.clear();
+    }
+
+    /// The agent that reserved `tile` at the time step `time`, if any
+    pub fn reserved_by(&self, tile: Point, time: usize) -> Option<usize> {
+        self.tiles.get(&(tile, time)).copied()
+    }
+
+    /// If the reservations of other agents keep `agent` from moving from `from` to `to` between
+    /// `time` and `time + 1`
+    fn blocks(&self, agent: usize, from: Point, to: Point, time: usize) -> bool {
+        let other = |tile, time| self.reserved_by(tile, time).filter(|a| *a != agent);
+        if other(to, time + 1).is_some() {
+            return true;
+        }
+        // two agents that swap their Tiles would run into each other
+        from != to && other(to, time).is_some_and(|a| other(from, time + 1) == Some(a))
+    }
+}
+
+impl<N: Neighborhood + Sync> PathCache<N> {
+    /// Calculates the Path from `start` to `goal` for `agent`, avoiding the Tiles that other
+    /// agents reserved in `reservations`.
+    ///
+    /// For the first [`window`](ReservationTable::window) steps, the search runs on the Grid and
+    /// in time: Every step of the Path takes one time step, and the Path only enters Tiles that
+    /// no other agent reserved for that time step. It also doesn't swap Tiles with another
+    /// agent, and can wait on a Tile, which shows up as the same Tile twice in a row. The rest of
+    /// the Path is found by [`find_path`](PathCache::find_path), which is also used to estimate
+    /// which end of the window is the closest to `goal`.
+    ///
+    /// Reservations of `agent` itself are ignored. Once the Path was found, it should be
+    /// [`reserve`](ReservationTable::reserve)d for `agent`, so that the agents searching after it
+    /// can avoid it.
+    ///
+    /// Returns `None` if there is no Path from `start` to `goal`, or if the reservations leave no
+    /// way out of `start` within the window.
+    ///
+    /// ## Errors
+    /// Returns a [`GridMismatch`] if the PathCache wasn't updated with `grid`, see
+    /// [`find_path`](PathCache::find_path).
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// // a corridor with a niche
+    /// let grid = [
+    ///     [0, 0, 0, 0, 0, 0, 0],
+    ///     [2, 2, 2, 2, 0, 2, 2],
+    /// ];
+    /// let (width, height) = (grid[0].len(), grid.len());
+    /// fn cost_fn(grid: &[[usize; 7]; 2]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    ///     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// }
+    /// let pathfinding = PathCache::new(
+    ///     (width, height),
+    ///     &cost_fn(&grid),
+    ///     ManhattanNeighborhood::new(width, height),
+    ///     PathCacheConfig::with_chunk_size(3),
+    /// );
+    /// let mut reservations = ReservationTable::new(10);
+    ///
+    /// // the first agent walks through the corridor
+    /// let (left, right) = ((0, 0), (6, 0));
+    /// let path = pathfinding
+    ///     .find_path_cooperative(left, right, 1, &reservations, &cost_fn(&grid)).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 6);
+    /// reservations.reserve(1, &path, &cost_fn(&grid)).unwrap();
+    ///
+    /// // the second agent steps aside and waits for it to pass
+    /// let path = pathfinding
+    ///     .find_path_cooperative(right, left, 2, &reservations, &cost_fn(&grid)).unwrap()
+    ///     .unwrap();
+    /// assert_eq!(path.cost(), 9);
+    /// assert!(path.collect::<Vec<_>>().contains(&(4, 1)));
+    /// ```
+    pub fn find_path_cooperative<G: CostGrid>(
+        &self,
+        start: Point,
+        goal: Point,
+        agent: usize,
+        reservations: &ReservationTable,
+        grid: &G,
+    ) -> Result<Option<AbstractPath<N>>, GridMismatch> {
+        let guide = match self.find_path(start, goal, grid)? {
+            Some(guide) => guide,
+            None => return Ok(None),
+        };
+        let mut path = match self.window_path(
+            (start, goal),
+            agent,
+            reservations,
+            guide,
+            &MoveCosts::new(self.neighborhood.clone(), grid),
+            cost_grid::cost_fn(grid),
+        ) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        if path.end() != goal {
+            // beyond the window
+            match self.find_path(path.end(), goal, grid)? {
+                Some(rest) => path.append(rest),
+                None => return Ok(None),
+            };
+        }
+        self.track_chunks(&mut path);
+        path.set_grid(self.generation, self.moves);
+        Ok(Some(path))
+    }
+
+    /// The search of [`find_path_cooperative`](PathCache::find_path_cooperative) within the
+    /// window, along the Path `guide` from `start` to `goal`
+    fn window_path(
+        &self,
+        (start, goal): (Point, Point),
+        agent: usize,
+        reservations: &ReservationTable,
+        guide: AbstractPath<N>,
+        neighborhood: &MoveCosts<N>,
+        mut get_cost: impl FnMut(Point) -> isize,
+    ) -> Option<AbstractPath<N>> {
+        let window = reservations.window;
+        if start == goal || window == 0 {
+            return Some(guide);
+        }
+        let obstacles = self.obstacles.with(None, start);
+        let mut applied = obstacles.cost_fn(&mut get_cost);
+
+        // the costs from the first Tiles of the hierarchical Path to goal, to estimate how far
+        // the Tiles at the end of the window are from goal
+        let mut guide_tiles = vec![(start, guide.cost())];
+        let mut remaining = guide.cost();
+        let mut guide = guide;
+        let mut neighbors = vec![];
+        while guide_tiles.len() <= 2 * window + 1 {
+            let (prev, prev_cost) = match guide_tiles.last() {
+                Some(&(prev, _)) => (prev, applied(prev) as Cost),
+                None => break,
+            };
+            let next = match guide.next_with(neighborhood, &mut applied) {
+                Some(next) => next,
+                None => break,
+            };
+            neighbors.clear();
+            neighborhood.get_all_neighbors(prev, &mut neighbors);
+            let step = if neighbors.contains(&next) {
+                let next_cost = applied(next) as Cost;
+                neighborhood.step_cost(prev, next, prev_cost, next_cost)
+            } else {
+                self.link_jumps(prev)
+                    .filter(|(to, _)| *to == next)
+                    .map(|(_, cost)| cost)
+                    .min()
+                    .unwrap_or(0)
+            };
+            remaining = remaining.saturating_sub(step);
+            guide_tiles.push((next, remaining));
+        }
+        let mut estimates = PointMap::default();
+        let mut estimate = |pos: Point| -> Cost {
+            if pos == goal {
+                return 0;
+            }
+            *estimates.entry(pos).or_insert_with(|| {
+                guide_tiles
+                    .iter()
+                    .map(|(tile, remaining)| remaining + neighborhood.heuristic(pos, *tile))
+                    .min()
+                    .unwrap_or(0)
+            })
+        };
+
+        // A* search over (Tile, time step) until the end of the window or goal
+        let mut visited = hashbrown::HashMap::new();
+        let mut next = BinaryHeap::new();
+        visited.insert((start, 0), (0, (start, 0)));
+        next.push(HeuristicElement((start, 0), 0, estimate(start)));
+        let mut moves = vec![];
+        let end = loop {
+            let HeuristicElement((pos, t), cost, _) = next.pop()?;
+            if cost > visited[&(pos, t)].0 {
+                continue;
+            }
+            if pos == goal || t == window {
+                break (pos, t);
+            }
+            let time = reservations.time + t;
+            let pos_cost = applied(pos) as Cost;
+
+            // waiting, stepping to a neighbor or jumping through a link
+            moves.clear();
+            moves.push((pos, pos_cost * neighborhood.cost_scale()));
+            neighbors.clear();
+            neighborhood.get_all_neighbors(pos, &mut neighbors);
+            for &other in neighbors.iter() {
+                let other_cost = applied(other);
+                if other_cost >= 0 && neighborhood.is_step_allowed(pos, other, &mut applied) {
+                    let step = neighborhood.step_cost(pos, other, pos_cost, other_cost as Cost);
+                    moves.push((other, step));
+                }
+            }
+            for (other, step) in self.link_jumps(pos) {
+                if applied(other) >= 0 {
+                    moves.push((other, step));
+                }
+            }
+
+            for &(other, step) in moves.iter() {
+                if reservations.blocks(agent, pos, other, time) {
+                    continue;
+                }
+                let key = (other, t + 1);
+                let other_cost = cost + step;
+                if visited
+                    .get(&key)
+                    .is_none_or(|(prev_cost, _)| other_cost < *prev_cost)
+                {
+                    visited.insert(key, (other_cost, (pos, t)));
+                    next.push(HeuristicElement(
+                        key,
+                        other_cost,
+                        other_cost + estimate(other),
+                    ));
+                }
+            }
+        };
+
+        drop(applied);
+
+        let mut steps = vec![end.0];
+        let mut current = end;
+        while current != (start, 0) {
+            current = visited[&current].1;
+            steps.push(current.0);
+        }
+        steps.reverse();
+
+        let mut path = AbstractPath::from_known_path(
+            self.neighborhood.clone(),
+            Path::new(steps, visited[&end].0),
+        );
+        path.set_search_options(self.config.search_options());
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{path::Cost, prelude::*, Point};
+    use nanorand::{Rng, WyRand};
+
+    fn check<N: Neighborhood + Sync>(neighborhood: N, seed: u64) {
+        let size = 24;
+        let mut rng = WyRand::new_seed(seed);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| [-1, 1, 1, 1, 2][rng.generate_range(0..5_usize)]);
+        }
+        let cost_fn = |(x, y): Point| grid[y][x];
+        let pathfinding = PathCache::new(
+            (size, size),
+            &cost_fn,
+            neighborhood.clone(),
+            PathCacheConfig::with_chunk_size(4),
+        );
+
+        let mut reservations = ReservationTable::new(8);
+        for _ in 0..3 {
+            // the Tiles every agent visits within the window
+            let mut planned: Vec<Vec<Point>> = vec![];
+            let mut used = vec![];
+            for agent in 0..12 {
+                let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+                let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+                if cost_fn(start) < 0 || cost_fn(goal) < 0 || used.contains(&start) {
+                    continue;
+                }
+                let time = reservations.time();
+                if reservations.reserved_by(start, time).is_some() {
+                    continue;
+                }
+                let plain = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+                let path = pathfinding
+                    .find_path_cooperative(start, goal, agent, &reservations, &cost_fn)
+                    .unwrap();
+                let path = match (plain, path) {
+                    (None, path) => {
+                        assert!(path.is_none());
+                        continue;
+                    }
+                    (Some(_), Some(path)) => path,
+                    // the other agents block the way
+                    (Some(_), None) => continue,
+                };
+                used.push(start);
+
+                // every step is a move or a wait, and the Path costs as much as its steps
+                let tiles = path.clone().collect::<Vec<_>>();
+                assert_eq!(tiles.last(), Some(&goal));
+                let (mut prev, mut total) = (start, 0);
+                let mut neighbors = vec![];
+                for &p in tiles.iter() {
+                    neighbors.clear();
+                    neighborhood.get_all_neighbors(prev, &mut neighbors);
+                    let prev_cost = cost_fn(prev) as Cost;
+                    if p == prev {
+                        total += prev_cost * neighborhood.cost_scale();
+                    } else {
+                        assert!(neighbors.contains(&p), "{:?} -> {:?}", prev, p);
+                        assert!(neighborhood.is_step_allowed(prev, p, cost_fn));
+                        total += neighborhood.step_cost(prev, p, prev_cost, cost_fn(p) as Cost);
+                    }
+                    prev = p;
+                }
+                assert_eq!(total, path.cost());
+
+                // no collisions with the agents that planned before
+                let window = std::iter::once(start)
+                    .chain(tiles.iter().copied())
+                    .take(reservations.window() + 1)
+                    .collect::<Vec<_>>();
+                for other in planned.iter() {
+                    let at = |t: usize| other[t.min(other.len() - 1)];
+                    for t in 1..window.len() {
+                        assert_ne!(window[t], at(t), "{} at {}", agent, t);
+                        assert!(window[t] != at(t - 1) || window[t - 1] != at(t));
+                    }
+                }
+                reservations.reserve(agent, &path, &cost_fn).unwrap();
+                planned.push(window);
+            }
+            for _ in 0..3 {
+                reservations.advance();
+            }
+            reservations.clear();
+        }
+    }
+
+    #[test]
+    fn random() {
+        check(ManhattanNeighborhood::new(24, 24), 8);
+        check(MooreNeighborhood::new_octile(24, 24), 9);
+    }
+
+    #[test]
+    fn reservations() {
+        let mut reservations = ReservationTable::new(4);
+        assert!(reservations.reserve_tile(1, (0, 0), 0));
+        assert!(reservations.reserve_tile(1, (0, 0), 1));
+        assert!(!reservations.reserve_tile(2, (0, 0), 1));
+        assert!(reservations.reserve_tile(2, (1, 0), 1));
+        assert_eq!(reservations.reserved_by((0, 0), 1), Some(1));
+
+        // swapping Tiles is blocked as well
+        assert!(reservations.reserve_tile(2, (0, 1), 2));
+        assert!(reservations.reserve_tile(2, (0, 2), 3));
+        assert!(reservations.blocks(3, (0, 1), (0, 0), 0));
+        assert!(reservations.blocks(3, (0, 2), (0, 1), 1));
+        assert!(reservations.blocks(3, (0, 1), (0, 2), 2));
+        assert!(!reservations.blocks(2, (0, 1), (0, 2), 2));
+        assert!(!reservations.blocks(3, (0, 1), (0, 2), 3));
+
+        reservations.advance();
+        assert_eq!(reservations.time(), 1);
+        assert_eq!(reservations.reserved_by((0, 0), 0), None);
+        assert_eq!(reservations.reserved_by((0, 0), 1), Some(1));
+
+        reservations.release(1);
+        assert_eq!(reservations.reserved_by((0, 0), 1), None);
+        assert_eq!(reservations.reserved_by((1, 0), 1), Some(2));
+        reservations.clear();
+        assert_eq!(reservations.reserved_by((1, 0), 1), None);
+    }
+}
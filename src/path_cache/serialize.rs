@@ -0,0 +1,779 @@
+use crate::{
+    graph::{Node, NodeList},
+    neighbors::Neighborhood,
+    path::{Path, PathSegment},
+    *,
+};
+
+use super::terrain::NUM_CLASSES;
+use super::{
+    chunk, Annotations, Chunk, Clearance, Components, Level, Link, PathCache, PathCacheConfig,
+    Terrain,
+};
+
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"HPAC";
+const VERSION: u32 = 9;
+
+/// 64-bit FNV-1a hash
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Identifies the Neighborhood (and with it usually the Grid size) a PathCache was created with,
+/// see [`Neighborhood::describe`]
+fn neighborhood_hash<N: Neighborhood>(neighborhood: &N) -> u64 {
+    let mut out = Writer(vec![]);
+    for val in neighborhood.describe() {
+        out.u64(val);
+    }
+    checksum(&out.0)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, val: u8) {
+        self.0.push(val);
+    }
+    fn bool(&mut self, val: bool) {
+        self.u8(val as u8);
+    }
+    fn u32(&mut self, val: u32) {
+        self.0.extend_from_slice(&val.to_le_bytes());
+    }
+    fn u64(&mut self, val: u64) {
+        self.0.extend_from_slice(&val.to_le_bytes());
+    }
+    fn usize(&mut self, val: usize) {
+        self.u64(val as u64);
+    }
+    fn point(&mut self, (x, y): Point) {
+        self.usize(x);
+        self.usize(y);
+    }
+    fn id_path(&mut self, path: &Path<NodeID>) {
+        self.usize(path.cost());
+        self.usize(path.len());
+        for id in path.iter() {
+            self.u32(*id);
+        }
+    }
+    fn segment(&mut self, segment: &PathSegment) {
+        match segment {
+            PathSegment::Known(path) => {
+                self.u8(0);
+                self.usize(path.cost());
+                self.usize(path.len());
+                for p in path.iter() {
+                    self.point(*p);
+                }
+            }
+            PathSegment::Unknown {
+                start,
+                end,
+                cost,
+                len,
+            } => {
+                self.u8(1);
+                self.point(*start);
+                self.point(*end);
+                self.usize(*cost);
+                self.usize(*len);
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const L: usize>(&mut self) -> io::Result<[u8; L]> {
+        if self.0.len() < L {
+            return Err(invalid_data("unexpected end of PathCache data"));
+        }
+        let (bytes, rest) = self.0.split_at(L);
+        self.0 = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+    fn bool(&mut self) -> io::Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool in PathCache data")),
+        }
+    }
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+    fn usize(&mut self) -> io::Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| invalid_data("number too large for this platform"))
+    }
+    /// reads a length and checks that at least `min_size` bytes per element are left
+    fn slice(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.0.len() < len {
+            return Err(invalid_data("unexpected end of PathCache data"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn len(&mut self, min_size: usize) -> io::Result<usize> {
+        let len = self.usize()?;
+        if len.saturating_mul(min_size) > self.0.len() {
+            return Err(invalid_data("invalid length in PathCache data"));
+        }
+        Ok(len)
+    }
+    fn point(&mut self) -> io::Result<Point> {
+        Ok((self.usize()?, self.usize()?))
+    }
+    fn id_path(&mut self) -> io::Result<Path<NodeID>> {
+        let cost = self.usize()?;
+        let len = self.len(4)?;
+        let steps = (0..len).map(|_| self.u32()).collect::<io::Result<_>>()?;
+        Ok(Path::new(steps, cost))
+    }
+    fn segment(&mut self) -> io::Result<PathSegment> {
+        match self.u8()? {
+            0 => {
+                let cost = self.usize()?;
+                let len = self.len(16)?;
+                let steps = (0..len).map(|_| self.point()).collect::<io::Result<_>>()?;
+                Ok(PathSegment::Known(Path::new(steps, cost)))
+            }
+            1 => Ok(PathSegment::Unknown {
+                start: self.point()?,
+                end: self.point()?,
+                cost: self.usize()?,
+                len: self.usize()?,
+            }),
+            _ => Err(invalid_data("invalid PathSegment in PathCache data")),
+        }
+    }
+}
+
+impl<N: Neighborhood + Sync> PathCache<N> {
+    /// Writes the PathCache to `writer` in a compact binary format.
+    ///
+    /// The PathCache can be restored with [`read_from`](PathCache::read_from), which is a lot
+    /// faster than creating it from scratch. This is useful for static Grids that are shipped
+    /// with an application.
+    ///
+    /// The format is versioned and contains a checksum of the data, as well as of the
+    /// [`Neighborhood`] that was used (which usually contains the size of the Grid).
+    /// Registered [`Obstacles`](super::Obstacles) are temporary and not written.
+    ///
+    /// ## Examples
+    /// Basic usage:
+    /// ```
+    /// # use hierarchical_pathfinding::prelude::*;
+    /// # let mut grid = [
+    /// #     [0, 2, 0, 0, 0],
+    /// #     [0, 2, 2, 2, 2],
+    /// #     [0, 1, 0, 0, 0],
+    /// #     [0, 1, 0, 2, 0],
+    /// #     [0, 0, 0, 2, 0],
+    /// # ];
+    /// # let (width, height) = (grid.len(), grid[0].len());
+    /// # fn cost_fn(grid: &[[usize; 5]; 5]) -> impl '_ + Sync + Fn((usize, usize)) -> isize {
+    /// #     move |(x, y)| [1, 10, -1][grid[y][x]]
+    /// # }
+    /// let pathfinding: PathCache<_> = // ...
+    /// # PathCache::new(
+    /// #     (width, height),
+    /// #     &cost_fn(&grid),
+    /// #     ManhattanNeighborhood::new(width, height),
+    /// #     PathCacheConfig::with_chunk_size(3),
+    /// # );
+    ///
+    /// let mut data = Vec::new();
+    /// pathfinding.write_to(&mut data).unwrap();
+    ///
+    /// // ... later
+    ///
+    /// let loaded = PathCache::read_from(
+    ///     &data[..],
+    ///     ManhattanNeighborhood::new(width, height),
+    /// ).unwrap();
+    ///
+    /// let path = loaded.find_path((0, 0), (4, 4), &cost_fn(&grid)).unwrap();
+    /// assert_eq!(path.unwrap().cost(), 12);
+    ///
+    /// // a different Grid size is rejected
+    /// let wrong_size = PathCache::read_from(
+    ///     &data[..],
+    ///     ManhattanNeighborhood::new(width + 1, height),
+    /// );
+    /// assert!(wrong_size.is_err());
+    /// ```
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut out = Writer(Vec::new());
+
+        out.point((self.width, self.height));
+
+        out.usize(self.config.chunk_size);
+        out.bool(self.config.cache_paths);
+        out.bool(self.config.a_star_fallback);
+        out.bool(self.config.perfect_paths);
+        out.usize(self.config.levels);
+        out.usize(self.config.max_agent_size);
+        out.bool(self.config.jump_points);
+        out.bool(self.config.bidirectional);
+
+        out.bool(self.generation.is_some());
+        out.u64(self.generation.unwrap_or(0));
+        out.bool(self.moves);
+
+        let clearance = self.annotations.clearance.values();
+        out.usize(clearance.len());
+        out.0.extend_from_slice(clearance);
+
+        let terrain = self.annotations.terrain.values();
+        out.usize(terrain.len());
+        out.0.extend_from_slice(terrain);
+
+        out.point(self.num_chunks);
+        out.usize(self.chunks.len());
+        for chunk in self.chunks.iter() {
+            out.point(chunk.pos);
+            out.point(chunk.size);
+            for side in chunk.sides {
+                out.bool(side);
+            }
+            out.usize(chunk.nodes.len());
+            for id in chunk.nodes.iter() {
+                out.u32(*id);
+            }
+            out.u32(chunk.num_regions);
+            for region in chunk.regions.iter() {
+                out.u32(*region);
+            }
+        }
+
+        out.bool(self.nodes.is_directed());
+        let slots = self.nodes.slots();
+        out.usize(slots.len());
+        for slot in slots {
+            out.bool(slot.is_some());
+            if let Some(node) = slot {
+                out.point(node.pos);
+                out.usize(node.walk_cost);
+                out.usize(node.edges.len());
+                for (other_id, segment) in node.edges.iter() {
+                    out.u32(*other_id);
+                    out.segment(segment);
+                }
+                out.usize(node.wide_edges.len());
+                for (other_id, paths) in node.wide_edges.iter() {
+                    out.u32(*other_id);
+                    out.usize(paths.len());
+                    for (clearance, segment) in paths.iter() {
+                        out.usize(*clearance);
+                        out.segment(segment);
+                    }
+                }
+                out.usize(node.terrain_edges.len());
+                for (other_id, paths) in node.terrain_edges.iter() {
+                    out.u32(*other_id);
+                    out.usize(paths.len());
+                    for (required, segment) in paths.iter() {
+                        out.u32(*required);
+                        out.segment(segment);
+                    }
+                }
+            }
+        }
+
+        out.usize(self.levels.len());
+        for level in self.levels.iter() {
+            out.usize(level.chunk_size);
+            out.point(level.num_chunks);
+            for ids in level.chunk_nodes.iter() {
+                out.usize(ids.len());
+                for id in ids.iter() {
+                    out.u32(*id);
+                }
+            }
+            out.usize(level.edges.len());
+            for (id, edges) in level.edges.iter() {
+                out.u32(*id);
+                out.usize(edges.len());
+                for (other_id, path) in edges.iter() {
+                    out.u32(*other_id);
+                    out.id_path(path);
+                }
+            }
+        }
+
+        out.usize(self.links.len());
+        for link in self.links.iter() {
+            out.point(link.from);
+            out.point(link.to);
+            out.usize(link.cost);
+            out.bool(link.bidirectional);
+        }
+
+        let body = out.0;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&neighborhood_hash(&self.neighborhood).to_le_bytes())?;
+        writer.write_all(&(body.len() as u64).to_le_bytes())?;
+        writer.write_all(&checksum(&body).to_le_bytes())?;
+        writer.write_all(&body)
+    }
+
+    /// Restores a PathCache that was written with [`write_to`](PathCache::write_to).
+    ///
+    /// `neighborhood` has to be the same as the one that was used to create the PathCache.
+    ///
+    /// ## Errors
+    /// Returns an error of kind [`InvalidData`](std::io::ErrorKind::InvalidData) if the data is
+    /// not a PathCache, was written by an incompatible version, is corrupted, or was created
+    /// with a different Neighborhood or Grid size. Any errors of `reader` are passed on.
+    ///
+    /// See [`write_to`](PathCache::write_to) for an example.
+    pub fn read_from(mut reader: impl Read, neighborhood: N) -> io::Result<PathCache<N>> {
+        let mut header = [0; 32];
+        reader.read_exact(&mut header)?;
+        let mut input = Reader(&header);
+
+        if &input.bytes::<4>()? != MAGIC {
+            return Err(invalid_data("not a PathCache"));
+        }
+        if input.u32()? != VERSION {
+            return Err(invalid_data("unsupported PathCache version"));
+        }
+        if input.u64()? != neighborhood_hash(&neighborhood) {
+            return Err(invalid_data(
+                "PathCache was created with a different Neighborhood or Grid size",
+            ));
+        }
+        let len = input.usize()?;
+        let expected_checksum = input.u64()?;
+
+        let mut body = vec![];
+        reader.take(len as u64).read_to_end(&mut body)?;
+        if body.len() != len || checksum(&body) != expected_checksum {
+            return Err(invalid_data("PathCache data is corrupted"));
+        }
+        let mut input = Reader(&body);
+
+        let (width, height) = input.point()?;
+
+        let config = PathCacheConfig {
+            chunk_size: input.usize()?,
+            cache_paths: input.bool()?,
+            a_star_fallback: input.bool()?,
+            perfect_paths: input.bool()?,
+            levels: input.usize()?,
+            max_agent_size: input.usize()?,
+            jump_points: input.bool()?,
+            bidirectional: input.bool()?,
+        };
+
+        let has_generation = input.bool()?;
+        let generation = input.u64()?;
+        let generation = has_generation.then_some(generation);
+        let moves = input.bool()?;
+
+        let num = input.len(1)?;
+        let values = input.slice(num)?.to_vec();
+        let clearance = Clearance::from_values((width, height), config.max_agent_size, values);
+
+        let num = input.len(1)?;
+        let values = input.slice(num)?.to_vec();
+        let terrain = Terrain::from_values(width, values);
+
+        let num_chunks = input.point()?;
+        let num = input.len(36)?;
+        let mut chunks = Vec::with_capacity(num);
+        for _ in 0..num {
+            let pos = input.point()?;
+            let size = input.point()?;
+            let mut sides = [false; 4];
+            for side in sides.iter_mut() {
+                *side = input.bool()?;
+            }
+            let num_nodes = input.len(4)?;
+            let nodes = (0..num_nodes)
+                .map(|_| input.u32())
+                .collect::<io::Result<_>>()?;
+            let num_regions = input.u32()?;
+            if size.0.saturating_mul(size.1).saturating_mul(4) > input.0.len() {
+                return Err(invalid_data("invalid Chunk size in PathCache data"));
+            }
+            let regions = (0..size.0 * size.1)
+                .map(|_| input.u32())
+                .collect::<io::Result<Vec<_>>>()?;
+            if regions
+                .iter()
+                .any(|r| *r >= num_regions && *r != chunk::SOLID)
+            {
+                return Err(invalid_data("inconsistent PathCache data"));
+            }
+            chunks.push(Chunk {
+                pos,
+                size,
+                nodes,
+                sides,
+                regions,
+                num_regions,
+            });
+        }
+
+        let directed = input.bool()?;
+        let num = input.len(1)?;
+        let mut slots = Vec::with_capacity(num);
+        for id in 0..num {
+            if !input.bool()? {
+                slots.push(None);
+                continue;
+            }
+            let mut node = Node::new(id as NodeID, input.point()?, input.usize()?);
+            let num_edges = input.len(5)?;
+            for _ in 0..num_edges {
+                let other_id = input.u32()?;
+                node.edges.insert(other_id, input.segment()?);
+            }
+            let num_wide = input.len(12)?;
+            for _ in 0..num_wide {
+                let other_id = input.u32()?;
+                let num_paths = input.len(9)?;
+                let paths = (0..num_paths)
+                    .map(|_| Ok((input.usize()?, input.segment()?)))
+                    .collect::<io::Result<_>>()?;
+                node.wide_edges.insert(other_id, paths);
+            }
+            let num_terrain = input.len(12)?;
+            for _ in 0..num_terrain {
+                let other_id = input.u32()?;
+                let num_paths = input.len(5)?;
+                let paths = (0..num_paths)
+                    .map(|_| Ok((input.u32()?, input.segment()?)))
+                    .collect::<io::Result<_>>()?;
+                node.terrain_edges.insert(other_id, paths);
+            }
+            slots.push(Some(node));
+        }
+        let nodes = NodeList::from_slots(slots, directed);
+
+        let num = input.len(24)?;
+        let mut levels = Vec::with_capacity(num);
+        for _ in 0..num {
+            let mut level = Level::new(input.usize()?, (width, height));
+            if input.point()? != level.num_chunks {
+                return Err(invalid_data("inconsistent PathCache data"));
+            }
+            for ids in level.chunk_nodes.iter_mut() {
+                let num_ids = input.len(4)?;
+                *ids = (0..num_ids)
+                    .map(|_| input.u32())
+                    .collect::<io::Result<_>>()?;
+            }
+            let num_nodes = input.len(12)?;
+            for _ in 0..num_nodes {
+                let id = input.u32()?;
+                let num_edges = input.len(20)?;
+                let mut edges = NodeIDMap::default();
+                for _ in 0..num_edges {
+                    let other_id = input.u32()?;
+                    edges.insert(other_id, input.id_path()?);
+                }
+                level.edges.insert(id, edges);
+            }
+            levels.push(level);
+        }
+
+        let num_links = input.len(41)?;
+        let links = (0..num_links)
+            .map(|_| {
+                Ok(Link {
+                    from: input.point()?,
+                    to: input.point()?,
+                    cost: input.usize()?,
+                    bidirectional: input.bool()?,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        // make sure that all referenced Nodes exist, so that the PathCache can't panic later
+        let exists = |id: &NodeID| nodes.slots().get(*id as usize).is_some_and(Option::is_some);
+        let cs = config.chunk_size;
+        let consistent = input.0.is_empty()
+            && cs > 0
+            && clearance.values().len()
+                == if clearance.max() > 1 {
+                    width * height
+                } else {
+                    0
+                }
+            && clearance
+                .values()
+                .iter()
+                .all(|c| *c as usize <= clearance.max())
+            && (terrain.is_empty() || terrain.values().len() == width * height)
+            && terrain.values().iter().all(|c| *c < NUM_CLASSES)
+            && num_chunks == (width.div_ceil(cs), height.div_ceil(cs))
+            && chunks.len() == num_chunks.0 * num_chunks.1
+            && chunks.iter().enumerate().all(|(i, chunk)| {
+                let pos = ((i % num_chunks.0) * cs, (i / num_chunks.0) * cs);
+                chunk.pos == pos
+                    && chunk.size == ((width - pos.0).min(cs), (height - pos.1).min(cs))
+            })
+            && chunks.iter().all(|chunk| chunk.nodes.iter().all(exists))
+            && nodes.values().all(|node| {
+                node.edges.keys().all(exists)
+                    && node.wide_edges.keys().all(|id| node.edges.contains_key(id))
+                    && node
+                        .terrain_edges
+                        .keys()
+                        .all(|id| node.edges.contains_key(id))
+            })
+            && levels.iter().all(|level| {
+                level.chunk_nodes.iter().flatten().all(exists)
+                    && level.edges.iter().all(|(id, edges)| {
+                        exists(id)
+                            && edges
+                                .iter()
+                                .all(|(other, path)| exists(other) && path.iter().all(exists))
+                    })
+            })
+            && links.iter().all(|link| {
+                [link.from, link.to]
+                    .iter()
+                    .all(|p| p.0 < width && p.1 < height)
+            });
+        if !consistent {
+            return Err(invalid_data("inconsistent PathCache data"));
+        }
+        // the incoming edges of the levels aren't stored, only after the check are the NodeIDs
+        // known to be small enough to index them
+        for level in levels.iter_mut() {
+            level.index_edges();
+        }
+
+        let mut cache = PathCache {
+            width,
+            height,
+            chunk_generations: vec![0; chunks.len()],
+            chunks,
+            num_chunks,
+            nodes,
+            levels,
+            components: Components::default(),
+            annotations: Annotations { clearance, terrain },
+            neighborhood,
+            config,
+            generation,
+            moves,
+            links,
+            obstacles: Default::default(),
+        };
+        let portals = cache.portals();
+        cache.components = Components::new(
+            &cache.chunks,
+            cache.chunk_index_fn(),
+            &cache.neighborhood,
+            &portals,
+        );
+        Ok(cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn round_trip() {
+        use nanorand::{Rng, WyRand};
+
+        let size = 40;
+        let mut rng = WyRand::new_seed(3);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| rng.generate_range(-2_isize..6));
+        }
+        let cost_fn = |(x, y): (usize, usize)| grid[y][x];
+        let mut classes = vec![vec![0_u8; size]; size];
+        for row in classes.iter_mut() {
+            row.fill_with(|| rng.generate_range(0_u8..2));
+        }
+        let tile_fn = |(x, y): (usize, usize)| (grid[y][x], classes[y][x]);
+
+        for cache_paths in [true, false] {
+            let config = PathCacheConfig {
+                chunk_size: 4,
+                cache_paths,
+                levels: 2,
+                max_agent_size: 2,
+                jump_points: cache_paths,
+                bidirectional: !cache_paths,
+                ..Default::default()
+            };
+            let neighborhood = ManhattanNeighborhood::new(size, size);
+            let mut pathfinding =
+                PathCache::new_with_terrain((size, size), tile_fn, neighborhood, config);
+            pathfinding.generation = cache_paths.then_some(7);
+
+            let mut data = vec![];
+            pathfinding.write_to(&mut data).unwrap();
+            let loaded = PathCache::read_from(&data[..], neighborhood).unwrap();
+            assert_eq!(loaded.config(), pathfinding.config());
+            assert_eq!(loaded.grid_generation(), pathfinding.grid_generation());
+
+            let graph = |cache: &PathCache<ManhattanNeighborhood>| {
+                let mut nodes = cache
+                    .inspect_nodes()
+                    .map(|node| {
+                        let mut edges = node
+                            .connected()
+                            .map(|(other, cost)| (other.pos(), cost))
+                            .collect::<Vec<_>>();
+                        edges.sort_unstable();
+                        (node.pos(), edges)
+                    })
+                    .collect::<Vec<_>>();
+                nodes.sort_unstable();
+                nodes
+            };
+            assert_eq!(graph(&pathfinding), graph(&loaded));
+
+            for _ in 0..30 {
+                let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+                let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+                if cost_fn(goal) < 0 {
+                    continue;
+                }
+                let expected = pathfinding.find_path(start, goal, &cost_fn).unwrap();
+                let actual = loaded.find_path(start, goal, &cost_fn).unwrap();
+                assert_eq!(expected.is_some(), actual.is_some());
+                if let Some(path) = actual {
+                    assert_eq!(path.resolve(&cost_fn).unwrap().last(), Some(&goal));
+                }
+                let expected = pathfinding
+                    .find_path_with_size(start, goal, 2, &cost_fn)
+                    .unwrap();
+                let actual = loaded
+                    .find_path_with_size(start, goal, 2, &cost_fn)
+                    .unwrap();
+                assert_eq!(expected.is_some(), actual.is_some());
+                let expected = pathfinding
+                    .find_path_with_capabilities(start, goal, 0b01, &cost_fn)
+                    .unwrap();
+                let actual = loaded
+                    .find_path_with_capabilities(start, goal, 0b01, &cost_fn)
+                    .unwrap();
+                assert_eq!(expected.is_some(), actual.is_some());
+            }
+
+            // corrupted data
+            let mut corrupted = data.clone();
+            let last = corrupted.len() - 1;
+            corrupted[last] ^= 1;
+            assert!(PathCache::read_from(&corrupted[..], neighborhood).is_err());
+
+            // truncated data
+            assert!(PathCache::read_from(&data[..data.len() - 1], neighborhood).is_err());
+
+            // different Grid size
+            let other = ManhattanNeighborhood::new(size, size + 1);
+            assert!(PathCache::read_from(&data[..], other).is_err());
+
+            // same Grid size, but a different kind of Neighborhood
+            let other = ManhattanNeighborhood::new_wrapping(size, size);
+            assert!(PathCache::read_from(&data[..], other).is_err());
+        }
+    }
+
+    #[test]
+    fn neighborhood_hash() {
+        use super::neighborhood_hash;
+        use crate::neighbors::{DiagonalRule, HexLayout};
+
+        // the hashes are part of the format and must not change between builds
+        assert_eq!(
+            neighborhood_hash(&ManhattanNeighborhood::new(16, 8)),
+            0xeeed_5274_bcd2_5dbd
+        );
+        let hashes = [
+            neighborhood_hash(&ManhattanNeighborhood::new(16, 8)),
+            neighborhood_hash(&ManhattanNeighborhood::new(8, 16)),
+            neighborhood_hash(&ManhattanNeighborhood::new_wrapping(16, 8)),
+            neighborhood_hash(&MooreNeighborhood::new(16, 8)),
+            neighborhood_hash(&MooreNeighborhood::new_octile(16, 8)),
+            neighborhood_hash(
+                &MooreNeighborhood::new(16, 8).with_diagonal_rule(DiagonalRule::NoCorners),
+            ),
+            neighborhood_hash(&HexNeighborhood::new(16, 8, HexLayout::OddR)),
+            neighborhood_hash(&HexNeighborhood::new(16, 8, HexLayout::Axial)),
+        ];
+        for (i, a) in hashes.iter().enumerate() {
+            assert!(!hashes[i + 1..].contains(a), "{}", i);
+        }
+    }
+
+    #[test]
+    fn directed() {
+        use nanorand::{Rng, WyRand};
+
+        let size = 24;
+        let mut rng = WyRand::new_seed(4);
+        let mut grid = vec![vec![0_isize; size]; size];
+        for row in grid.iter_mut() {
+            row.fill_with(|| rng.generate_range(-1_isize..4));
+        }
+        let cost_fn = |(x, y): (usize, usize)| grid[y][x];
+        // odd rows can only be walked to the right
+        let move_fn = |from: (usize, usize), to: (usize, usize)| {
+            if from.1 % 2 == 1 && to.0 < from.0 {
+                -1
+            } else {
+                cost_fn(from)
+            }
+        };
+
+        let move_grid = WithMoves(cost_fn, move_fn);
+
+        let neighborhood = ManhattanNeighborhood::new(size, size);
+        let config = PathCacheConfig::with_chunk_size(4);
+        let pathfinding = PathCache::new((size, size), &move_grid, neighborhood, config);
+
+        let mut data = vec![];
+        pathfinding.write_to(&mut data).unwrap();
+        let loaded = PathCache::read_from(&data[..], neighborhood).unwrap();
+
+        for _ in 0..30 {
+            let start = (rng.generate_range(0..size), rng.generate_range(0..size));
+            let goal = (rng.generate_range(0..size), rng.generate_range(0..size));
+            if cost_fn(goal) < 0 {
+                continue;
+            }
+            let expected = pathfinding.find_path(start, goal, &move_grid).unwrap();
+            let actual = loaded.find_path(start, goal, &move_grid).unwrap();
+            assert_eq!(expected.map(|p| p.cost()), actual.map(|p| p.cost()));
+        }
+        // the loaded PathCache still needs the move costs
+        assert_eq!(
+            loaded.find_path((0, 0), (1, 0), &cost_fn).unwrap_err(),
+            GridMismatch::Moves { expected: true }
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use crate::*;
+
+/// The terrain class of every Tile on the Grid.
+///
+/// Agents can only enter Tiles whose class is part of their capabilities, where bit `i` of
+/// the capabilities stands for class `i`.
+#[derive(Clone, Debug, Default)]
+pub struct Terrain {
+    width: usize,
+    /// empty if every Tile has class `0`
+    values: Vec<u8>,
+}
+
+/// The number of different terrain classes
+pub const NUM_CLASSES: u8 = 32;
+
+impl Terrain {
+    pub fn new((width, height): (usize, usize), mut get_class: impl FnMut(Point) -> u8) -> Terrain {
+        let mut values = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                values.push(Self::check(get_class((x, y))));
+            }
+        }
+        if values.iter().all(|c| *c == 0) {
+            values = vec![];
+        }
+        Terrain { width, values }
+    }
+
+    /// Creates a Terrain from previously stored values
+    pub fn from_values(width: usize, values: Vec<u8>) -> Terrain {
+        Terrain { width, values }
+    }
+
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// `true` if every Tile has class `0`
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[track_caller]
+    fn check(class: u8) -> u8 {
+        assert!(
+            class < NUM_CLASSES,
+            "terrain class {} is out of range, it has to be less than {}",
+            class,
+            NUM_CLASSES
+        );
+        class
+    }
+
+    /// The class of a Tile
+    pub fn get(&self, (x, y): Point) -> u8 {
+        if self.values.is_empty() {
+            0
+        } else {
+            self.values[y * self.width + x]
+        }
+    }
+
+    /// The class of a Tile as a capability bit
+    pub fn mask(&self, point: Point) -> u32 {
+        1 << self.get(point)
+    }
+
+    /// Sets the class of the changed `tiles`
+    pub fn update(
+        &mut self,
+        (width, height): (usize, usize),
+        tiles: &[Point],
+        mut get_class: impl FnMut(Point) -> u8,
+    ) {
+        for &(x, y) in tiles {
+            let class = Self::check(get_class((x, y)));
+            if self.values.is_empty() {
+                if class == 0 {
+                    continue;
+                }
+                self.width = width;
+                self.values = vec![0; width * height];
+            }
+            self.values[y * width + x] = class;
+        }
+    }
+}
@@ -78,6 +78,16 @@ pub fn get_in_dir(pos: Point, dir: Dir, base: Point, (w, h): (usize, usize)) ->
     }
 }
 
+/// Like [`get_in_dir`] on the whole Grid, but steps over the left and right edge to the other
+/// side, as if the Grid wrapped around horizontally
+pub fn get_in_dir_wrapping(pos: Point, dir: Dir, (w, h): (usize, usize)) -> Option<Point> {
+    match dir {
+        LEFT if pos.0 == 0 => Some((w - 1, pos.1)),
+        RIGHT if pos.0 == w - 1 => Some((0, pos.1)),
+        _ => get_in_dir(pos, dir, (0, 0), (w, h)),
+    }
+}
+
 pub fn jump_in_dir(
     pos: Point,
     dir: Dir,
@@ -115,4 +125,12 @@ mod tests {
         assert_eq!(jump_in_dir(pos, DOWN, 2, (0, 0), (5, 5)), None);
         assert_eq!(jump_in_dir(pos, LEFT, 2, (0, 0), (5, 5)), None);
     }
+
+    #[test]
+    fn wrapping_test() {
+        assert_eq!(get_in_dir_wrapping((0, 3), LEFT, (5, 5)), Some((4, 3)));
+        assert_eq!(get_in_dir_wrapping((4, 3), RIGHT, (5, 5)), Some((0, 3)));
+        assert_eq!(get_in_dir_wrapping((4, 3), LEFT, (5, 5)), Some((3, 3)));
+        assert_eq!(get_in_dir_wrapping((4, 4), DOWN, (5, 5)), None);
+    }
 }